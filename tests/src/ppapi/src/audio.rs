@@ -1,25 +1,346 @@
 
+use libc;
+
+use std::cell::Cell;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use super::ModuleInterface;
+use super::instance::Instance;
 use super::interface::*;
-use super::sys::*;
+use super::prelude::*;
+use super::timeline::TimelineEvent;
+use super::resource::{ResState, ResourceRc, get_resource, get_resource_arc,
+                      take_resource_id};
+use super::sys::{self, PP_Bool, PP_TRUE, PP_FALSE, PP_TimeDelta,
+                 PP_AudioSampleRate, PPB_Audio_1_0, PPB_Audio_1_1,
+                 PPB_AudioConfig_1_1, PPB_Audio_Callback, PPB_Audio_Callback_1_0};
+
+pub type Audio = Resource<AudioState>;
+pub type AudioConfig = Resource<AudioConfigState>;
+
+#[derive(Debug)]
+pub struct AudioConfigState {
+    id: PP_Resource,
+    instance: Instance,
+
+    sample_rate: PP_AudioSampleRate,
+    sample_frame_count: u32,
+}
+impl AudioConfigState {
+    pub fn create(i: &Instance, sample_rate: PP_AudioSampleRate,
+                  sample_frame_count: u32) -> Code<AudioConfig> {
+        match sample_rate {
+            sys::PP_AUDIOSAMPLERATE_44100 |
+            sys::PP_AUDIOSAMPLERATE_48000 => {},
+            _ => { return Err(Error::BadArgument); },
+        }
+        if sample_frame_count < sys::PP_AUDIOMINSAMPLEFRAMECOUNT ||
+            sample_frame_count > sys::PP_AUDIOMAXSAMPLEFRAMECOUNT
+        {
+            return Err(Error::BadArgument);
+        }
+
+        let inner = AudioConfigState {
+            id: take_resource_id(),
+            instance: i.clone(),
+            sample_rate: sample_rate,
+            sample_frame_count: sample_frame_count,
+        };
+        Ok(Resource::create(i, Arc::new(inner)))
+    }
+
+    pub fn sample_rate(&self) -> PP_AudioSampleRate { self.sample_rate }
+    pub fn sample_frame_count(&self) -> u32 { self.sample_frame_count }
+    /// Stereo, 16 bits per sample.
+    pub fn buffer_size(&self) -> usize { self.sample_frame_count as usize * 2 * 2 }
+    /// The time it takes the output device to play one buffer.
+    pub fn buffer_duration(&self) -> Duration {
+        let nanos = self.sample_frame_count as u64 * 1_000_000_000 /
+            self.sample_rate as u64;
+        Duration::new(nanos / 1_000_000_000, (nanos % 1_000_000_000) as u32)
+    }
+}
+impl ResourceState for AudioConfigState {
+    fn into_resstate(this: Arc<Self>) -> ResState {
+        ResState::AudioConfig(this)
+    }
+    fn state_from_resstate(rs: &Arc<ResourceRc>) -> Code<&Arc<Self>> {
+        match rs.state() {
+            &ResState::AudioConfig(ref c) => Ok(c),
+            _ => Err(Error::BadArgument),
+        }
+    }
+    fn resource_id(this: &Arc<Self>) -> PP_Resource { this.id }
+    fn resource_instance(this: &Arc<Self>) -> Instance { this.instance.clone() }
+}
+
+/// A simulated output device. The browser keeps a few buffers queued ahead of
+/// the one the module is currently filling, so the samples written during a
+/// callback aren't heard until those have been played.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OutputBufferModel {
+    /// Buffers already queued in the device when the callback is run.
+    pub queued_buffers: u32,
+    /// Any extra delay added by the (pretend) hardware.
+    pub hardware_latency: PP_TimeDelta,
+}
+impl OutputBufferModel {
+    /// The latency passed to `PPB_Audio_Callback` (ie version 1.1).
+    pub fn latency(&self, config: &AudioConfigState) -> PP_TimeDelta {
+        let queued_frames = self.queued_buffers as f64 *
+            config.sample_frame_count() as f64;
+        queued_frames / config.sample_rate() as f64 + self.hardware_latency
+    }
+}
+impl Default for OutputBufferModel {
+    fn default() -> OutputBufferModel {
+        OutputBufferModel {
+            queued_buffers: 2,
+            hardware_latency: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum AudioCallback {
+    /// `PPB_Audio;1.0`: no latency argument.
+    V1_0(PPB_Audio_Callback_1_0),
+    V1_1(PPB_Audio_Callback),
+}
+
+#[derive(Debug)]
+pub struct AudioState {
+    id: PP_Resource,
+    instance: Instance,
+
+    config: AudioConfig,
+    model: OutputBufferModel,
+    callback: AudioCallback,
+    user_data: *mut libc::c_void,
+
+    /// Bumped on every start/stop; a playback thread exits once its
+    /// generation is stale.
+    generation: AtomicUsize,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+unsafe impl Send for AudioState { }
+unsafe impl Sync for AudioState { }
+
+impl AudioState {
+    pub fn create(i: &Instance, config: AudioConfig,
+                  model: OutputBufferModel,
+                  callback: AudioCallback,
+                  user_data: *mut libc::c_void) -> Code<Audio> {
+        if config.instance() != *i {
+            return Err(Error::BadArgument);
+        }
+
+        let inner = AudioState {
+            id: take_resource_id(),
+            instance: i.clone(),
+            config: config,
+            model: model,
+            callback: callback,
+            user_data: user_data,
+            generation: AtomicUsize::new(0),
+            thread: Mutex::new(None),
+        };
+        Ok(Resource::create(i, Arc::new(inner)))
+    }
+
+    pub fn config(&self) -> &AudioConfig { &self.config }
+    pub fn model(&self) -> &OutputBufferModel { &self.model }
+    pub fn latency(&self) -> PP_TimeDelta { self.model.latency(&self.config) }
 
+    pub fn playing(&self) -> bool {
+        self.thread.lock().unwrap().is_some()
+    }
+
+    /// Run the module's callback once, as the audio thread would.
+    pub fn fire(&self, buffer: &mut [u8]) {
+        let ptr = buffer.as_mut_ptr() as *mut libc::c_void;
+        let len = buffer.len() as u32;
+        let latency = self.latency();
+        self.instance.record_timeline_event(TimelineEvent::AudioCallback {
+            audio: self.id,
+            sample_rate: self.config.sample_rate(),
+            frames: self.config.sample_frame_count(),
+            latency: latency,
+        });
+        match self.callback {
+            AudioCallback::V1_0(f) => f(ptr, len, self.user_data),
+            AudioCallback::V1_1(f) => f(ptr, len, latency, self.user_data),
+        }
+    }
+
+    pub fn start(this: &Arc<AudioState>) -> Code<()> {
+        let mut thread = try!(this.thread.lock());
+        if thread.is_some() { return Ok(()); }
+
+        let generation = this.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let state = this.clone();
+        *thread = Some(thread::spawn(move || {
+            AUDIO_THREAD.with(|t| t.set(true) );
+
+            let period = state.config.buffer_duration();
+            let mut buffer = vec![0u8; state.config.buffer_size()];
+            while state.generation.load(Ordering::SeqCst) == generation {
+                state.fire(&mut buffer[..]);
+                thread::sleep(period);
+            }
+        }));
+
+        Ok(())
+    }
+    /// Like Chrome, this waits for an in-progress callback to finish, unless
+    /// `join` is false (ie when called from the instance thread).
+    pub fn stop(&self, join: bool) -> Code<()> {
+        let thread = try!(self.thread.lock()).take();
+        self.generation.fetch_add(1, Ordering::SeqCst);
+
+        if let Some(thread) = thread {
+            if join && !AUDIO_THREAD.with(|t| t.get() ) {
+                try!(thread.join().map_err(|_| Error::Failed ));
+            }
+        }
+
+        Ok(())
+    }
+}
+impl ResourceState for AudioState {
+    fn into_resstate(this: Arc<Self>) -> ResState {
+        ResState::Audio(this)
+    }
+    fn state_from_resstate(rs: &Arc<ResourceRc>) -> Code<&Arc<Self>> {
+        match rs.state() {
+            &ResState::Audio(ref a) => Ok(a),
+            _ => Err(Error::BadArgument),
+        }
+    }
+    fn resource_id(this: &Arc<Self>) -> PP_Resource { this.id }
+    fn resource_instance(this: &Arc<Self>) -> Instance { this.instance.clone() }
+}
+
+thread_local!(static AUDIO_THREAD: Cell<bool> = Cell::new(false));
+
+static AUDIO_INTERFACE_1_0: PPB_Audio_1_0 = PPB_Audio_1_0 {
+    create: ppb_audio_create_1_0,
+    is_audio: ppb_audio_is,
+    get_config: ppb_audio_get_config,
+    start_playback: ppb_audio_start_playback,
+    stop_playback: ppb_audio_stop_playback,
+};
 static AUDIO_INTERFACE: PPB_Audio_1_1 = PPB_Audio_1_1 {
-    create: ret_default_stub::<_>,
-    is_audio: ret_false_stub,
-    get_config: ret_default_stub::<_>,
-    start_playback: ret_false_stub,
-    stop_playback: ret_false_stub,
+    create: ppb_audio_create,
+    is_audio: ppb_audio_is,
+    get_config: ppb_audio_get_config,
+    start_playback: ppb_audio_start_playback,
+    stop_playback: ppb_audio_stop_playback,
 };
 
 static AUDIO_CONFIG_INTERFACE: PPB_AudioConfig_1_1 = PPB_AudioConfig_1_1 {
-    CreateStereo16Bit: ret_default_stub::<_>,
-    RecommendSampleFrameCount: ret_default_stub::<_>,
-    IsAudioConfig: ret_false_stub,
-    GetSampleRate: ret_default_stub::<_>,
-    GetSampleFrameCount: ret_default_stub::<_>,
-    RecommendSampleRate: ret_default_stub::<_>,
+    CreateStereo16Bit: ppb_audio_config_create,
+    RecommendSampleFrameCount: ppb_audio_config_recommend_frame_count,
+    IsAudioConfig: ppb_audio_config_is,
+    GetSampleRate: ppb_audio_config_get_sample_rate,
+    GetSampleFrameCount: ppb_audio_config_get_frame_count,
+    RecommendSampleRate: ppb_audio_config_recommend_sample_rate,
 };
 
 pub static INTERFACES: Interfaces = &[
+    ("PPB_Audio;1.0", interface_ptr(&AUDIO_INTERFACE_1_0)),
     ("PPB_Audio;1.1", interface_ptr(&AUDIO_INTERFACE)),
     ("PPB_AudioConfig;1.1", interface_ptr(&AUDIO_CONFIG_INTERFACE)),
 ];
+
+fn create(instance: PP_Instance, config: PP_Resource,
+          callback: AudioCallback, user_data: *mut libc::c_void) -> PP_Resource {
+    let i = ModuleInterface::get_instance_interface(instance);
+    if i.is_err() { return 0; }
+    let i = i.unwrap();
+
+    i.create_audio(config, callback, user_data)
+        .map(|audio| audio.move_into_id() )
+        .unwrap_or(0)
+}
+extern "C" fn ppb_audio_create_1_0(instance: PP_Instance, config: PP_Resource,
+                                   callback: PPB_Audio_Callback_1_0,
+                                   user_data: *mut libc::c_void) -> PP_Resource {
+    create(instance, config, AudioCallback::V1_0(callback), user_data)
+}
+extern "C" fn ppb_audio_create(instance: PP_Instance, config: PP_Resource,
+                               callback: PPB_Audio_Callback,
+                               user_data: *mut libc::c_void) -> PP_Resource {
+    create(instance, config, AudioCallback::V1_1(callback), user_data)
+}
+extern "C" fn ppb_audio_is(res: PP_Resource) -> PP_Bool {
+    match unsafe { get_resource_arc(res) } {
+        Some(rc) => match rc.state() {
+            &ResState::Audio(_) => PP_TRUE,
+            _ => PP_FALSE,
+        },
+        None => PP_FALSE,
+    }
+}
+extern "C" fn ppb_audio_get_config(audio: PP_Resource) -> PP_Resource {
+    get_resource::<AudioState>(audio)
+        .map(|audio| audio.config().clone().move_into_id() )
+        .unwrap_or(0)
+}
+extern "C" fn ppb_audio_start_playback(audio: PP_Resource) -> PP_Bool {
+    let started = get_resource::<AudioState>(audio)
+        .and_then(|audio| {
+            let state = try!(AudioState::state_from_resstate(audio.get_rc())).clone();
+            AudioState::start(&state)
+        });
+    if started.is_ok() { PP_TRUE } else { PP_FALSE }
+}
+extern "C" fn ppb_audio_stop_playback(audio: PP_Resource) -> PP_Bool {
+    let stopped = get_resource::<AudioState>(audio)
+        .and_then(|audio| audio.stop(true) );
+    if stopped.is_ok() { PP_TRUE } else { PP_FALSE }
+}
+
+extern "C" fn ppb_audio_config_create(instance: PP_Instance,
+                                      sample_rate: PP_AudioSampleRate,
+                                      sample_frame_count: u32) -> PP_Resource {
+    let i = ModuleInterface::get_instance_interface(instance);
+    if i.is_err() { return 0; }
+    let i = i.unwrap();
+
+    AudioConfigState::create(&i, sample_rate, sample_frame_count)
+        .map(|config| config.move_into_id() )
+        .unwrap_or(0)
+}
+extern "C" fn ppb_audio_config_recommend_frame_count(_instance: PP_Instance,
+                                                     _sample_rate: PP_AudioSampleRate,
+                                                     requested: u32) -> u32 {
+    use std::cmp::{max, min};
+    min(max(requested, sys::PP_AUDIOMINSAMPLEFRAMECOUNT),
+        sys::PP_AUDIOMAXSAMPLEFRAMECOUNT)
+}
+extern "C" fn ppb_audio_config_is(res: PP_Resource) -> PP_Bool {
+    match unsafe { get_resource_arc(res) } {
+        Some(rc) => match rc.state() {
+            &ResState::AudioConfig(_) => PP_TRUE,
+            _ => PP_FALSE,
+        },
+        None => PP_FALSE,
+    }
+}
+extern "C" fn ppb_audio_config_get_sample_rate(config: PP_Resource) -> PP_AudioSampleRate {
+    get_resource::<AudioConfigState>(config)
+        .map(|config| config.sample_rate() )
+        .unwrap_or(sys::PP_AUDIOSAMPLERATE_NONE)
+}
+extern "C" fn ppb_audio_config_get_frame_count(config: PP_Resource) -> u32 {
+    get_resource::<AudioConfigState>(config)
+        .map(|config| config.sample_frame_count() )
+        .unwrap_or(0)
+}
+extern "C" fn ppb_audio_config_recommend_sample_rate(_instance: PP_Instance) -> PP_AudioSampleRate {
+    sys::PP_AUDIOSAMPLERATE_48000
+}
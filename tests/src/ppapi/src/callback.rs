@@ -2,15 +2,20 @@
 use libc;
 
 use std::cell::{RefCell};
+use std::collections::VecDeque;
 use std::sync::atomic::{Ordering, AtomicBool};
 use std::sync::mpsc::{Sender, Receiver, channel};
 use std::sync::{Mutex, Arc};
+use std::time::{Duration, Instant};
 
+use super::completion::PendingCompletion;
 use super::sys;
 use super::prelude::*;
+use super::scheduler::Scheduler;
 use super::resource::{ResourceRc, ResState, take_resource_id,
                       get_resource};
 use super::instance::Instance;
+use super::support::global_singleton_default;
 use super::interface::*;
 
 pub type MessageLoop = Resource<MessageLoopState>;
@@ -25,6 +30,8 @@ pub struct MessageLoopState {
     attached: AtomicBool,
     shutdown: AtomicBool,
     main:     bool,
+    /// Delayed work, waiting to be due. Only the loop's thread touches it.
+    timers:   Mutex<Timers<Work>>,
 
     /// Only the message thread accesses this, so doesn't *need* a mutex, but Rust.
     message_handler_data: Mutex<Option<(&'static sys::PPP_MessageHandler_0_2,
@@ -35,6 +42,12 @@ impl MessageLoopState {
     pub fn create(i: Instance, main: bool) -> Code<MessageLoop> {
         let id = take_resource_id();
         let (tx, rx) = channel();
+        if !main {
+            // Delayed work may have come due. The module thread does this
+            // for the main loop.
+            let wake_tx = tx.clone();
+            i.clock().on_change(Box::new(move || wake_tx.send(MlMsg::Wake).is_ok() ));
+        }
         let inner = MessageLoopState {
             id: id,
             instance: i.clone(),
@@ -43,6 +56,7 @@ impl MessageLoopState {
             attached: AtomicBool::new(false),
             shutdown: AtomicBool::new(false),
             main: main,
+            timers: Mutex::new(Default::default()),
             message_handler_data: Mutex::new(None),
         };
         Ok(Resource::create(&i, Arc::new(inner)))
@@ -73,7 +87,12 @@ impl MessageLoopState {
         let _ = rx.recv();
     }
 
+    pub fn is_main(&self) -> bool { self.main }
+
     pub fn run(&self) -> Code<()> {
+        // The module thread runs the main loop, between module messages.
+        if self.main { return Err(Error::InProgress); }
+
         ATTACHED.with(|attached| {
             let rx = {
                 let b = attached.borrow();
@@ -91,106 +110,216 @@ impl MessageLoopState {
             }
             let rx = rx.unwrap();
 
-            while let Ok(msg) = rx.recv() {
-                match msg {
-                    MlMsg::Shutdown {
-                        pause,
-                    } => {
-                        self.shutdown.store(!pause, Ordering::SeqCst);
-                        if !pause {
-                            attached.borrow_mut().take();
-                        } else {
-                            let b = attached.borrow();
-                            let mut lock = try!(b.as_ref().unwrap().rx.lock());
-                            *lock = Some(rx);
-                        }
-                        return Ok(());
+            let clock = self.instance.clock();
+            loop {
+                let msg = match clock.recv_until(&rx, self.next_due()) {
+                    Ok(Some(msg)) => msg,
+                    Ok(None) => {
+                        try!(self.run_timers(clock.now()));
+                        continue;
                     },
-                    MlMsg::Post {
-                        f, user, result,
-                    } => {
-                        let result = result.into_code();
-                        f(user, result);
+                    Err(_) => {
+                        return Ok(());
                     },
-                    MlMsg::Message {
-                        ret, msg,
-                    } => {
-                        let handler = self.message_handler_data.lock()
-                            .ok()
-                            .and_then(|mut hnd_l| hnd_l.take() );
-                        if handler.is_none() { continue; }
-                        let handler = handler.unwrap();
-
-                        let arg: sys::PP_Var = msg.into();
-                        if let Some(ret) = ret {
-                            let mut ret_v: sys::PP_Var = Default::default();
-
-                            let hbm = handler.0.HandleBlockingMessage
-                                .expect("PPP_MessageHandler missing HandleBlockingMessage");
-                            hbm(self.instance.id(), handler.1,
-                                &arg, &mut ret_v);
-
-                            if let Ok(ret_v) = Var::from(ret_v) {
-                                let _ = ret.send(ret_v);
-                            } else {
-                                // XXX
-                            }
-                        } else {
-                            let hm = handler.0.HandleMessage
-                                .expect("PPP_MessageHandler missing HandleMessage");
-
-                            hm(self.instance.id(), handler.1, &arg);
-                        }
+                };
+
+                if let Some(pause) = try!(self.handle(msg)) {
+                    // Like Chrome, quit once idle: what's ready still runs,
+                    // but delayed work that isn't due waits for the next
+                    // `Run`.
+                    try!(self.run_until_idle(&rx));
+
+                    self.shutdown.store(!pause, Ordering::SeqCst);
+                    if !pause {
+                        attached.borrow_mut().take();
+                        self.instance.scheduler().detach(self.id);
+                        self.leak_leftovers(&rx);
+                    } else {
+                        let b = attached.borrow();
+                        let mut lock = try!(b.as_ref().unwrap().rx.lock());
+                        *lock = Some(rx);
+                    }
+                    return Ok(());
+                }
+            }
+        })
+    }
 
-                        // Deref:
-                        let _arg = Var::from(arg);
+    /// Main loop only: runs everything posted so far and the delayed work
+    /// that's due, without blocking. The module thread calls this when it's
+    /// woken for main thread work, and when `next_due` passes.
+    pub fn run_pending(&self) -> Code<()> {
+        if !self.main { return Err(Error::WrongThread); }
 
-                        let mut hl = self.message_handler_data.lock().unwrap();
-                        if hl.is_some() {
-                            if let Some(dtor) = handler.0.Destroy {
-                                (dtor)(self.instance.id(), handler.1);
-                            }
-                        } else {
-                            *hl = Some(handler);
-                        }
-                    },
-                    MlMsg::RegisterMessageHandler {
-                        user, handler,
-                    } => {
-                        {
-                            let mut lock = self.message_handler_data.lock().unwrap();
-                            if let Some(prev) = lock.take() {
-                                if let Some(dtor) = prev.0.Destroy {
-                                    dtor(self.instance.id(), prev.1);
-                                }
-                            }
-
-                            *lock = Some((handler, user));
-                        }
-                    },
-                    MlMsg::UnregisterMessageHandler {
-                        ret,
-                    } => {
-                        {
-                            let mut lock = self.message_handler_data.lock().unwrap();
-                            if let Some(prev) = lock.take() {
-                                if let Some(dtor) = prev.0.Destroy {
-                                    dtor(self.instance.id(), prev.1);
-                                }
-                            }
+        let rx = match try!(self.rx.lock()).take() {
+            Some(rx) => rx,
+            None => { return Err(Error::InProgress); },
+        };
+        let ret = self.run_until_idle(&rx);
+        *try!(self.rx.lock()) = Some(rx);
+        ret
+    }
+    /// The loop was destroyed; whatever it still holds will never run.
+    fn leak_leftovers(&self, rx: &Receiver<MlMsg>) {
+        let mut leftovers = self.timers.lock().unwrap().take_where(|_| true );
+        leftovers.extend(rx.try_iter().filter_map(|msg| match msg {
+            MlMsg::Post { work, .. } => Some(work),
+            _ => None,
+        }));
+        for work in leftovers.into_iter() {
+            leaked_callbacks().report(LeakedCallback {
+                instance: self.instance.id(),
+                message_loop: self.id,
+                result: work.result,
+            });
+        }
+    }
+    /// When the earliest delayed work is due, on the module's clock.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.timers.lock().unwrap().next_due()
+    }
+
+    /// Delayed work that isn't due yet goes to the timers; anything else is
+    /// run. Returns `pause` for quits, which the caller handles.
+    fn handle(&self, msg: MlMsg) -> Code<Option<bool>> {
+        match msg {
+            MlMsg::Shutdown {
+                pause,
+            } => Ok(Some(pause)),
+            MlMsg::Wake => Ok(None),
+            MlMsg::Post {
+                work, due,
+            } => {
+                if due > self.instance.clock().now() {
+                    try!(self.timers.lock()).push(due, work);
+                    self.instance.scheduler().unqueued(self.id);
+                } else {
+                    // Work runs in the order it came due.
+                    try!(self.run_timers(due));
+                    let _turn = self.instance.scheduler().turn(self.id, true);
+                    work.run();
+                }
+                Ok(None)
+            },
+            msg => {
+                self.dispatch(msg);
+                Ok(None)
+            },
+        }
+    }
+    /// Run the delayed work due by `until`.
+    fn run_timers(&self, until: Instant) -> Code<()> {
+        loop {
+            // Don't hold the lock while the work runs; it may post more.
+            let work = try!(self.timers.lock()).pop_due(until);
+            match work {
+                Some(work) => {
+                    let _turn = self.instance.scheduler().turn(self.id, false);
+                    work.run();
+                },
+                None => { return Ok(()); },
+            }
+        }
+    }
+    /// Run until there's nothing ready: no messages, and no delayed work
+    /// that's due. Further quits are dropped; the loop is quitting already.
+    fn run_until_idle(&self, rx: &Receiver<MlMsg>) -> Code<()> {
+        loop {
+            let msg = match rx.try_recv() {
+                Ok(msg) => msg,
+                Err(_) => {
+                    let now = self.instance.clock().now();
+                    if !self.next_due().map(|due| due <= now ).unwrap_or(false) {
+                        return Ok(());
+                    }
+                    try!(self.run_timers(now));
+                    continue;
+                },
+            };
+            try!(self.handle(msg));
+        }
+    }
+
+    fn dispatch(&self, msg: MlMsg) {
+        match msg {
+            MlMsg::Shutdown { .. } |
+            MlMsg::Wake |
+            MlMsg::Post { .. } => unreachable!(),
+            MlMsg::Message {
+                ret, msg,
+            } => {
+                let handler = self.message_handler_data.lock()
+                    .ok()
+                    .and_then(|mut hnd_l| hnd_l.take() );
+                if handler.is_none() { return; }
+                let handler = handler.unwrap();
+
+                let arg: sys::PP_Var = msg.into();
+                if let Some(ret) = ret {
+                    let mut ret_v: sys::PP_Var = Default::default();
+
+                    let hbm = handler.0.HandleBlockingMessage
+                        .expect("PPP_MessageHandler missing HandleBlockingMessage");
+                    hbm(self.instance.id(), handler.1,
+                        &arg, &mut ret_v);
+
+                    if let Ok(ret_v) = Var::from(ret_v) {
+                        let _ = ret.send(ret_v);
+                    } else {
+                        // XXX
+                    }
+                } else {
+                    let hm = handler.0.HandleMessage
+                        .expect("PPP_MessageHandler missing HandleMessage");
+
+                    hm(self.instance.id(), handler.1, &arg);
+                }
+
+                // Deref:
+                let _arg = Var::from(arg);
+
+                let mut hl = self.message_handler_data.lock().unwrap();
+                if hl.is_some() {
+                    if let Some(dtor) = handler.0.Destroy {
+                        (dtor)(self.instance.id(), handler.1);
+                    }
+                } else {
+                    *hl = Some(handler);
+                }
+            },
+            MlMsg::RegisterMessageHandler {
+                user, handler,
+            } => {
+                {
+                    let mut lock = self.message_handler_data.lock().unwrap();
+                    if let Some(prev) = lock.take() {
+                        if let Some(dtor) = prev.0.Destroy {
+                            dtor(self.instance.id(), prev.1);
                         }
+                    }
 
-                        let _ = ret.send(());
+                    *lock = Some((handler, user));
+                }
+            },
+            MlMsg::UnregisterMessageHandler {
+                ret,
+            } => {
+                {
+                    let mut lock = self.message_handler_data.lock().unwrap();
+                    if let Some(prev) = lock.take() {
+                        if let Some(dtor) = prev.0.Destroy {
+                            dtor(self.instance.id(), prev.1);
+                        }
                     }
                 }
-            }
 
-            Ok(())
-        })
+                let _ = ret.send(());
+            }
+        }
     }
 
     pub fn attach_to_current_thread(this: MessageLoop) -> Code<()> {
-        if this.attached.load(Ordering::Relaxed) {
+        if this.main || this.attached.load(Ordering::Relaxed) {
             return Err(Error::InProgress);
         }
 
@@ -200,6 +329,11 @@ impl MessageLoopState {
                 Err(Error::InProgress)
             } else {
                 this.attached.store(true, Ordering::Relaxed);
+                this.instance.scheduler().attach(this.id);
+                ATTACHED_THREAD.with(|thread| {
+                    *thread.borrow_mut() = AttachedThread(Some((this.instance.scheduler().clone(),
+                                                                this.id)));
+                });
                 *b = Some(this);
                 Ok(())
             }
@@ -208,22 +342,39 @@ impl MessageLoopState {
 
     pub fn post(&self, f: sys::PP_CompletionCallback_Func,
                 user: *mut libc::c_void) -> Code<()> {
-        self.post_result(f, user, Ok(()))
+        self.post_result(f, user, sys::PP_OK)
     }
     pub fn post_result(&self, f: sys::PP_CompletionCallback_Func,
                        user: *mut libc::c_void,
-                       result: Code<()>) -> Code<()> {
-        let msg = MlMsg::Post {
+                       result: sys::PP_Code) -> Code<()> {
+        self.post_delayed(Work {
             f: f,
             user: user,
             result: result,
+        }, Duration::new(0, 0))
+    }
+    /// Run `work` once `delay` has passed, after any work that came due
+    /// before it.
+    pub fn post_delayed(&self, work: Work, delay: Duration) -> Code<()> {
+        if self.shutdown.load(Ordering::SeqCst) {
+            return Err(Error::Failed);
+        }
+        let due = self.instance.clock().now() + delay + self.instance.scheduler().chaos_delay();
+        let msg = MlMsg::Post {
+            work: work,
+            due: due,
         };
         let tx = self.tx.clone();
+        let scheduler = self.instance.scheduler();
+        scheduler.posted(self.id);
         if let Err(_) = tx.send(msg) {
-            Err(Error::Failed)
-        } else {
-            Ok(())
+            scheduler.unqueued(self.id);
+            return Err(Error::Failed);
+        }
+        if self.main {
+            super::global_module().wake_main_thread();
         }
+        Ok(())
     }
     pub fn post_quit(&self, shutdown: bool) -> Code<()> {
         if self.main {
@@ -237,6 +388,11 @@ impl MessageLoopState {
             .map_err(|_| Error::Failed )
     }
 }
+impl Drop for MessageLoopState {
+    fn drop(&mut self) {
+        self.instance.scheduler().detach(self.id);
+    }
+}
 unsafe impl Send for MessageLoopState { }
 unsafe impl Sync for MessageLoopState { }
 impl ResourceState for MessageLoopState {
@@ -265,6 +421,29 @@ impl ResourceState for MessageLoopState {
 
 thread_local!(static ATTACHED: RefCell<Option<MessageLoop>> = Default::default());
 
+/// Tells the scheduler when the thread a loop is attached to exits, so it
+/// doesn't wait on the loop any more.
+#[derive(Default)]
+struct AttachedThread(Option<(Arc<Scheduler>, PP_Resource)>);
+impl Drop for AttachedThread {
+    fn drop(&mut self) {
+        if let Some((scheduler, id)) = self.0.take() {
+            scheduler.detach(id);
+        }
+    }
+}
+thread_local!(static ATTACHED_THREAD: RefCell<AttachedThread> = Default::default());
+
+/// Whether this is the module's main thread, ie the module thread.
+pub fn on_main_thread() -> bool {
+    ATTACHED.with(|attached| {
+        attached.borrow()
+            .as_ref()
+            .map(|ml| ml.main )
+            .unwrap_or(false)
+    })
+}
+
 pub fn current_message_loop() -> Code<MessageLoop> {
     ATTACHED.with(|attached| {
         if let Some(msg_loop) = attached.borrow().clone() {
@@ -275,6 +454,34 @@ pub fn current_message_loop() -> Code<MessageLoop> {
     })
 }
 
+/// Which of Chrome's rules, from `EnterBase::SetStateForCallbackError`, a
+/// callback used on a thread with `current` loop breaks. Chrome doesn't tie
+/// loops to instances, but here another instance's loop is torn down with
+/// it, stranding the callback, so that's the wrong thread too.
+fn thread_error(blocking: bool, current: &Code<MessageLoop>,
+                instance: &Instance) -> Option<Error> {
+    match *current {
+        _ if blocking && on_main_thread() => Some(Error::BlocksMainThread),
+        _ if blocking => None,
+        Err(err) => Some(err),
+        Ok(ref msg_loop) if msg_loop.main || msg_loop.instance.id() == instance.id() => None,
+        Ok(_) => Some(Error::WrongThread),
+    }
+}
+
+/// How Chrome's threading rules for completion callbacks are applied to a
+/// module's calls. Per instance.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ThreadChecks {
+    /// Calls that break them fail, as in Chrome.
+    Enforce,
+    /// Calls that break them are logged, then run anyway.
+    Warn,
+    Off,
+}
+impl Default for ThreadChecks {
+    fn default() -> ThreadChecks { ThreadChecks::Enforce }
+}
 
 pub enum Callback {
     Async {
@@ -282,29 +489,47 @@ pub enum Callback {
         f: sys::PP_CompletionCallback_Func,
         user: *mut libc::c_void,
         message_loop: MessageLoop,
+        /// `PP_COMPLETIONCALLBACK_FLAG_OPTIONAL`: if the operation finishes
+        /// right away, its result is returned instead and this isn't called.
+        optional: bool,
     },
     Sync,
 }
 impl Callback {
-    pub fn from_ffi(ffi: sys::PP_CompletionCallback) -> Code<Callback> {
+    /// The callback a module passed to a call on `instance`'s resources,
+    /// held to Chrome's threading rules as strictly as the instance's
+    /// `ThreadChecks` say.
+    pub fn from_ffi(ffi: sys::PP_CompletionCallback, instance: &Instance) -> Code<Callback> {
         use std::intrinsics::transmute;
-        if ffi.flags as u32 & sys::PP_COMPLETIONCALLBACK_FLAG_OPTIONAL != 0 {
-            // TODO?
-            return Err(Error::NotSupported);
-        }
-
         let fp: *const () = unsafe { transmute(ffi.func) };
+        let blocking = fp.is_null();
+
+        let current = current_message_loop();
+        if let Some(err) = thread_error(blocking, &current, instance) {
+            match instance.thread_checks() {
+                ThreadChecks::Enforce => { return Err(err); },
+                ThreadChecks::Warn => {
+                    warn!("completion callback used against Chrome's threading rules: {:?}", err);
+                },
+                ThreadChecks::Off => { },
+            }
+        }
 
-        if fp.is_null() {
-            Ok(Callback::Sync)
-        } else {
-            let msg_loop = try!(current_message_loop());
-            Ok(Callback::Async {
-                f: ffi.func,
-                user: ffi.user_data,
-                message_loop: msg_loop,
-            })
+        if blocking {
+            return Ok(Callback::Sync);
         }
+        // A thread without a loop can't run the callback itself, so it's
+        // run on the main thread instead.
+        let msg_loop = match current {
+            Ok(msg_loop) => msg_loop,
+            Err(_) => try!(get(super::global_module().main_message_loop())),
+        };
+        Ok(Callback::Async {
+            f: ffi.func,
+            user: ffi.user_data,
+            message_loop: msg_loop,
+            optional: ffi.flags as u32 & sys::PP_COMPLETIONCALLBACK_FLAG_OPTIONAL != 0,
+        })
     }
 
     pub fn blocking(&self) -> bool {
@@ -313,24 +538,32 @@ impl Callback {
             _ => false,
         }
     }
-
-    pub fn trigger<U>(self, result: Code<U>) -> Code<U> {
+    pub fn optional(&self) -> bool {
         match self {
-            Callback::Sync => result,
-            Callback::Async {
-                f, user, message_loop: ml,
-            } => {
-                match result {
-                    Ok(_) => {
-                        let _ = ml.post_result(f, user, Ok(()));
-                        return result;
-                    },
-                    Err(err) => {
-                        let _ = ml.post_result(f, user, Err(err));
-                        return result;
-                    },
-                }
+            &Callback::Async { optional, .. } => optional,
+            _ => false,
+        }
+    }
+    /// Runs `operation`, on `resource`. Blocking and optional callbacks run
+    /// it now and get its result directly; for any other, it's handed to
+    /// `instance` with the callback (see `Completion`) and the caller told
+    /// the completion is pending. It runs on the callback's loop, just
+    /// before the callback, so nothing it does is seen until then.
+    pub fn complete<U, F>(self, instance: &Instance, resource: PP_Resource,
+                          operation: F) -> Code<U>
+        where F: FnOnce() -> Code<U> + 'static,
+              Code<U>: ResultCode,
+    {
+        match self {
+            Callback::Async { f, user, message_loop, optional: false, } => {
+                let mut operation = Some(operation);
+                let operation = Box::new(move || (operation.take().unwrap())().into_code() );
+                let pending = PendingCompletion::new(resource, message_loop, f, user,
+                                                     operation);
+                instance.complete(pending);
+                Err(Error::CompletionPending)
             },
+            _ => operation(),
         }
     }
 }
@@ -339,14 +572,92 @@ impl Default for Callback {
     fn default() -> Callback { Callback::Sync }
 }
 
+/// A completion callback that will never run, because its loop was
+/// destroyed first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LeakedCallback {
+    pub instance: PP_Instance,
+    pub message_loop: PP_Resource,
+    /// What it would have been called with. `PP_OK` for an operation's
+    /// callback if the operation hadn't run yet either.
+    pub result: i32,
+}
+#[derive(Default)]
+pub struct LeakedCallbacks(Mutex<Vec<LeakedCallback>>);
+impl LeakedCallbacks {
+    pub fn report(&self, leak: LeakedCallback) {
+        error!("instance {}: a callback with result {} was left on destroyed message loop {}",
+               leak.instance, leak.result, leak.message_loop);
+        self.0.lock().unwrap().push(leak);
+    }
+    /// Those reported so far for `instance`, which are forgotten.
+    pub fn take_instance(&self, instance: PP_Instance) -> Vec<LeakedCallback> {
+        let mut lock = self.0.lock().unwrap();
+        let (taken, left) = lock.drain(..).partition(|leak| leak.instance == instance );
+        *lock = left;
+        taken
+    }
+}
+pub fn leaked_callbacks() -> &'static LeakedCallbacks { global_singleton_default() }
+
+/// A completion callback to run on a loop, with its result.
+#[derive(Clone, Copy, Debug)]
+pub struct Work {
+    pub f: sys::PP_CompletionCallback_Func,
+    pub user: *mut libc::c_void,
+    pub result: i32,
+}
+impl Work {
+    pub fn run(self) { (self.f)(self.user, self.result) }
+}
+
+/// Delayed work, in the order it's due. Work due at the same time stays in
+/// the order it was posted.
+#[derive(Debug)]
+pub struct Timers<T>(VecDeque<(Instant, T)>);
+impl<T> Default for Timers<T> {
+    fn default() -> Timers<T> { Timers(VecDeque::new()) }
+}
+impl<T> Timers<T> {
+    pub fn push(&mut self, due: Instant, work: T) {
+        let pos = self.0
+            .iter()
+            .rposition(|&(d, _)| d <= due )
+            .map(|p| p + 1 )
+            .unwrap_or(0);
+        self.0.insert(pos, (due, work));
+    }
+    pub fn next_due(&self) -> Option<Instant> {
+        self.0.front().map(|&(due, _)| due )
+    }
+    pub fn pop_due(&mut self, now: Instant) -> Option<T> {
+        if self.next_due().map(|due| due <= now ).unwrap_or(false) {
+            self.0.pop_front().map(|(_, work)| work )
+        } else {
+            None
+        }
+    }
+    /// Removes the work `f` picks, whenever it's due.
+    pub fn take_where<F>(&mut self, mut f: F) -> Vec<T>
+        where F: FnMut(&T) -> bool,
+    {
+        let (taken, left) = self.0.drain(..).partition(|&(_, ref work)| f(work) );
+        self.0 = left;
+        taken.into_iter().map(|(_, work)| work ).collect()
+    }
+    pub fn len(&self) -> usize { self.0.len() }
+}
+
 enum MlMsg {
     Shutdown {
         pause: bool,
     },
+    /// The clock changed; only wakes the loop, to check its timers.
+    Wake,
     Post {
-        f: sys::PP_CompletionCallback_Func,
-        user: *mut libc::c_void,
-        result: Code<()>,
+        work: Work,
+        /// When it was posted, for work without a delay.
+        due: Instant,
     },
     RegisterMessageHandler {
         user: *mut libc::c_void,
@@ -363,13 +674,23 @@ enum MlMsg {
 
 unsafe impl Send for MlMsg { }
 
-pub fn main_thread_init() {
-    unimplemented!();
+/// Attach the module's main loop to the module thread. Done once, before the
+/// module thread handles anything.
+pub fn main_thread_init(main_loop: MessageLoop) -> Code<()> {
+    assert!(main_loop.main);
+    ATTACHED.with(|attached| {
+        let mut b = attached.borrow_mut();
+        if b.is_some() { return Err(Error::InProgress); }
+        main_loop.attached.store(true, Ordering::Relaxed);
+        main_loop.instance.scheduler().attach(main_loop.id);
+        *b = Some(main_loop);
+        Ok(())
+    })
 }
 
 static ML_INTERFACE: sys::PPB_MessageLoop_1_0 = sys::PPB_MessageLoop_1_0 {
     Create: Some(ppb_ml_create),
-    GetForMainThread: Some(ret_default_stub),
+    GetForMainThread: Some(ppb_ml_get_for_main_thread),
     GetCurrent: Some(ppb_ml_get_current),
     AttachToCurrentThread: Some(ppb_ml_attach_to_current_thread),
     Run: Some(ppb_ml_run),
@@ -391,6 +712,12 @@ extern "C" fn ppb_ml_create(instance: PP_Instance) -> PP_Resource {
     if ml.is_err() { return 0; }
     ml.unwrap().move_into_id()
 }
+extern "C" fn ppb_ml_get_for_main_thread() -> PP_Resource {
+    let id = super::global_module().main_message_loop();
+    // Like `GetCurrent`, the caller gets a reference.
+    super::resource::up_ref_resource(id);
+    id
+}
 extern "C" fn ppb_ml_get_current() -> PP_Resource {
     let ml = current_message_loop();
     if ml.is_err() { return 0; }
@@ -413,17 +740,22 @@ extern "C" fn ppb_ml_run(ml: PP_Resource) -> libc::int32_t {
 extern "C" fn ppb_ml_post_work(ml: PP_Resource,
                                callback: sys::PP_CompletionCallback,
                                delay_ms: libc::int64_t) -> libc::int32_t {
-    let cb = Callback::from_ffi(callback);
-    if let Err(e) = cb { return e.into(); }
-    let cb = cb.unwrap();
-
-    if cb.blocking() { return Error::BadArgument.into(); }
-
-    if delay_ms != 0 { return Error::NotSupported.into(); }
-
+    use std::intrinsics::transmute;
+
+    // The work runs on `ml`, so unlike other callbacks, the caller doesn't
+    // need a loop of its own.
+    let fp: *const () = unsafe { transmute(callback.func) };
+    if fp.is_null() { return Error::BadArgument.into(); }
+
+    let work = Work {
+        f: callback.func,
+        user: callback.user_data,
+        result: sys::PP_OK,
+    };
+    let delay = Duration::from_millis(::std::cmp::max(delay_ms, 0) as u64);
     get(ml)
         .and_then(|ml| {
-            ml.post(callback.func, callback.user_data)
+            ml.post_delayed(work, delay)
         })
         .into_code()
 }
@@ -0,0 +1,192 @@
+//! Reading back Graphics3D color buffers after `SwapBuffers`, for golden
+//! image tests of the video outputs.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use super::raster::Framebuffer;
+use super::sys::{PP_Resource, PP_TimeTicks};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ImageFormat {
+    /// Binary `P6`; alpha is dropped.
+    Ppm,
+    /// 8-bit RGBA, uncompressed.
+    Png,
+}
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Png => "png",
+        }
+    }
+    pub fn write<W: Write>(&self, out: W, width: usize, height: usize,
+                           rgba: &[u8]) -> io::Result<()> {
+        match *self {
+            ImageFormat::Ppm => write_ppm(out, width, height, rgba),
+            ImageFormat::Png => write_png(out, width, height, rgba),
+        }
+    }
+}
+
+/// What to do with each presented frame. Every frame is hashed regardless.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FrameSink {
+    Hash,
+    /// Keep the pixels in `Frame::pixels`.
+    Keep,
+    /// Write each frame to `dir` as `<context>-<index>.<ext>`. `dir` must
+    /// exist.
+    Save {
+        dir: PathBuf,
+        format: ImageFormat,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub context: PP_Resource,
+    /// Counts the context's swaps, from 0.
+    pub index: usize,
+    pub ts: PP_TimeTicks,
+    pub width: usize,
+    pub height: usize,
+    /// `fnv1a` of the RGBA rows, top row first.
+    pub hash: u64,
+    pub pixels: Option<Vec<u8>>,
+    /// Where the frame was saved to; `None` if saving failed.
+    pub path: Option<PathBuf>,
+}
+impl Frame {
+    pub fn capture(context: PP_Resource, index: usize, ts: PP_TimeTicks,
+                   fb: &Framebuffer, sink: &FrameSink) -> Frame {
+        let rgba = fb.top_down();
+        let mut frame = Frame {
+            context: context,
+            index: index,
+            ts: ts,
+            width: fb.width,
+            height: fb.height,
+            hash: fnv1a(&rgba[..]),
+            pixels: None,
+            path: None,
+        };
+
+        match *sink {
+            FrameSink::Hash => {},
+            FrameSink::Keep => {
+                frame.pixels = Some(rgba);
+            },
+            FrameSink::Save { ref dir, format, } => {
+                let path = dir.join(format!("{}-{}.{}", context, index,
+                                            format.extension()));
+                let written = File::create(&path)
+                    .and_then(|f| format.write(BufWriter::new(f), fb.width,
+                                               fb.height, &rgba[..]) );
+                match written {
+                    Ok(()) => { frame.path = Some(path); },
+                    Err(err) => {
+                        error!("couldn't save frame to `{}`: {}", path.display(), err);
+                    },
+                }
+            },
+        }
+
+        frame
+    }
+
+    pub fn hash_hex(&self) -> String { format!("{:016x}", self.hash) }
+}
+
+/// 64-bit FNV-1a. Unlike `std`'s hashers it's stable, so hashes can be
+/// checked in.
+pub fn fnv1a(data: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for &b in data.iter() {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+pub fn write_ppm<W: Write>(mut out: W, width: usize, height: usize,
+                           rgba: &[u8]) -> io::Result<()> {
+    try!(write!(out, "P6\n{} {}\n255\n", width, height));
+    let rgb: Vec<u8> = rgba
+        .chunks(4)
+        .flat_map(|px| px[..3].iter().cloned() )
+        .collect();
+    try!(out.write_all(&rgb[..]));
+    out.flush()
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data.iter() {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data.iter() {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+fn be32(v: u32) -> [u8; 4] {
+    [(v >> 24) as u8, (v >> 16) as u8, (v >> 8) as u8, v as u8]
+}
+fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    try!(out.write_all(&be32(data.len() as u32)));
+    let mut body = kind.to_vec();
+    body.extend_from_slice(data);
+    try!(out.write_all(&body[..]));
+    out.write_all(&be32(crc32(&body[..])))
+}
+
+/// The image data is stored with deflate's uncompressed blocks; the files are
+/// bigger, but no compressor is needed.
+pub fn write_png<W: Write>(mut out: W, width: usize, height: usize,
+                           rgba: &[u8]) -> io::Result<()> {
+    try!(out.write_all(b"\x89PNG\r\n\x1a\n"));
+
+    let mut ihdr = Vec::new();
+    ihdr.extend_from_slice(&be32(width as u32));
+    ihdr.extend_from_slice(&be32(height as u32));
+    // 8 bits, RGBA, deflate, no filtering, no interlacing.
+    ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+    try!(write_chunk(&mut out, b"IHDR", &ihdr[..]));
+
+    // Each row starts with its filter type, 0 (none).
+    let mut raw = Vec::with_capacity(height * (width * 4 + 1));
+    let stride = width * 4;
+    for y in 0..height {
+        raw.push(0);
+        raw.extend_from_slice(&rgba[y * stride..(y + 1) * stride]);
+    }
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        zlib.push(last as u8);
+        zlib.extend_from_slice(&[len as u8, (len >> 8) as u8,
+                                 !len as u8, (!len >> 8) as u8]);
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&be32(adler32(&raw[..])));
+    try!(write_chunk(&mut out, b"IDAT", &zlib[..]));
+
+    try!(write_chunk(&mut out, b"IEND", &[]));
+    out.flush()
+}
@@ -0,0 +1,166 @@
+//! The module's clock. `PPB_Core`'s time functions, delayed message loop
+//! work, the instance's timers and throttled URL loads all read it instead of
+//! the system clocks, so tests can stop it or speed it up rather than sleep
+//! through timeouts.
+
+use std::sync::{Condvar, Mutex};
+use std::sync::mpsc::{Receiver, RecvError, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClockMode {
+    /// Follows the system clocks.
+    Real,
+    /// Runs this many times faster than real time.
+    Scaled(f64),
+    /// Stands still. Only `Clock::advance` moves it.
+    Frozen,
+}
+impl Default for ClockMode {
+    fn default() -> ClockMode { ClockMode::Real }
+}
+
+/// Called after every change to the clock; returns false once it's no
+/// longer needed.
+pub type Waker = Box<Fn() -> bool + Send>;
+
+#[derive(Debug)]
+struct ClockState {
+    mode: ClockMode,
+    /// Where the clock was, in real and in virtual time, at the last change.
+    real: Instant,
+    virt: Instant,
+}
+impl ClockState {
+    fn now(&self) -> Instant {
+        match self.mode {
+            ClockMode::Real => self.virt + (Instant::now() - self.real),
+            ClockMode::Scaled(rate) => {
+                self.virt + scale(Instant::now() - self.real, rate)
+            },
+            ClockMode::Frozen => self.virt,
+        }
+    }
+    /// Restarts the mode from now.
+    fn rebase(&mut self) {
+        self.virt = self.now();
+        self.real = Instant::now();
+    }
+}
+
+pub struct Clock {
+    state: Mutex<ClockState>,
+    changed: Condvar,
+    wakers: Mutex<Vec<Waker>>,
+
+    started: Instant,
+    /// The wall time when the clock started.
+    started_wall: Duration,
+}
+impl Clock {
+    pub fn new() -> Clock {
+        let now = Instant::now();
+        Clock {
+            state: Mutex::new(ClockState {
+                mode: Default::default(),
+                real: now,
+                virt: now,
+            }),
+            changed: Condvar::new(),
+            wakers: Mutex::new(Vec::new()),
+            started: now,
+            started_wall: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap(),
+        }
+    }
+
+    pub fn now(&self) -> Instant { self.state.lock().unwrap().now() }
+    /// Module ticks.
+    pub fn elapsed(&self) -> Duration { self.now() - self.started }
+    /// Wall time, which moves with the clock.
+    pub fn since_epoch(&self) -> Duration { self.started_wall + self.elapsed() }
+
+    pub fn mode(&self) -> ClockMode { self.state.lock().unwrap().mode }
+    /// The clock carries on from where it is; it never jumps back.
+    pub fn set_mode(&self, mode: ClockMode) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.rebase();
+            state.mode = mode;
+        }
+        self.wake();
+    }
+    /// Moves the clock forward by `by`, in any mode. Whatever comes due runs
+    /// as if the time had passed.
+    pub fn advance(&self, by: Duration) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.rebase();
+            state.virt += by;
+        }
+        self.wake();
+    }
+
+    /// How long, in real time, until the clock reaches `due`. `None` if it
+    /// won't get there by itself.
+    pub fn timeout_until(&self, due: Instant) -> Option<Duration> {
+        let state = self.state.lock().unwrap();
+        let now = state.now();
+        if due <= now {
+            return Some(Duration::new(0, 0));
+        }
+        match state.mode {
+            ClockMode::Real => Some(due - now),
+            ClockMode::Scaled(rate) if rate > 0.0 => Some(scale(due - now, 1.0 / rate)),
+            ClockMode::Scaled(_) | ClockMode::Frozen => None,
+        }
+    }
+    /// Blocks until the clock reaches `due`.
+    pub fn wait_until(&self, due: Instant) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let now = state.now();
+            if due <= now { return; }
+            state = match state.mode {
+                ClockMode::Real => self.changed.wait_timeout(state, due - now).unwrap().0,
+                ClockMode::Scaled(rate) if rate > 0.0 => {
+                    self.changed.wait_timeout(state, scale(due - now, 1.0 / rate)).unwrap().0
+                },
+                ClockMode::Scaled(_) | ClockMode::Frozen => self.changed.wait(state).unwrap(),
+            };
+        }
+    }
+    /// Waits for a message on `rx`, or for the clock to reach `due`, in which
+    /// case it's `Ok(None)`. Message loops use this, and register a waker so
+    /// they can wait again when the clock changes.
+    pub fn recv_until<T>(&self, rx: &Receiver<T>,
+                         due: Option<Instant>) -> Result<Option<T>, RecvError> {
+        match due.and_then(|due| self.timeout_until(due) ) {
+            Some(timeout) => match rx.recv_timeout(timeout) {
+                Ok(msg) => Ok(Some(msg)),
+                Err(RecvTimeoutError::Timeout) => Ok(None),
+                Err(RecvTimeoutError::Disconnected) => Err(RecvError),
+            },
+            None => rx.recv().map(Some),
+        }
+    }
+
+    pub fn on_change(&self, waker: Waker) {
+        self.wakers.lock().unwrap().push(waker);
+    }
+    fn wake(&self) {
+        self.changed.notify_all();
+        self.wakers.lock().unwrap().retain(|waker| waker() );
+    }
+}
+impl ::std::fmt::Debug for Clock {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "Clock({:?})", self.mode())
+    }
+}
+
+fn scale(d: Duration, by: f64) -> Duration {
+    let secs = (d.as_secs() as f64 + d.subsec_nanos() as f64 / 1_000_000_000f64) * by;
+    Duration::new(secs as u64, (secs.fract() * 1_000_000_000f64) as u32)
+}
@@ -0,0 +1,216 @@
+//! When asynchronous operations and their callbacks are run. An operation
+//! runs on its callback's loop just before the callback, so what it does
+//! (filling a buffer, creating a resource) isn't seen any earlier. By default
+//! both are posted right away; the other policies hold them back, as a
+//! browser's IPC would, so modules see `PP_OK_COMPLETIONPENDING` results
+//! arrive late and out of order.
+
+use libc;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use super::callback::{LeakedCallback, MessageLoop, Timers, Work, leaked_callbacks};
+use super::prelude::*;
+use super::sys;
+
+/// Per instance.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Completion {
+    /// Post operations as soon as they're started.
+    Immediate,
+    /// Post each operation this long after it's started.
+    Delayed(Duration),
+    /// Post each operation after a random delay of up to `max_delay`, so
+    /// they may run in a different order than they were started in.
+    Random {
+        seed: u64,
+        max_delay: Duration,
+    },
+    /// Hold operations until the test releases them; see
+    /// `Instance::release_completions`.
+    Manual,
+}
+impl Default for Completion {
+    fn default() -> Completion { Completion::Immediate }
+}
+
+/// xorshift64*. Enough to pick delays and orders reproducibly from a seed.
+#[derive(Clone, Debug)]
+pub struct Rng(u64);
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // Zero is xorshift's only fixed point.
+        Rng(if seed == 0 { 0x9e37_79b9_7f4a_7c15 } else { seed })
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+    /// In `0..n`; `n` must not be 0.
+    pub fn below(&mut self, n: u64) -> u64 { self.next_u64() % n }
+    /// In `0..=max`.
+    pub fn duration(&mut self, max: Duration) -> Duration {
+        let max_us = max.as_secs() * 1_000_000 + (max.subsec_nanos() / 1000) as u64;
+        Duration::from_micros(self.below(max_us + 1))
+    }
+}
+
+/// Runs once, returning the operation's result.
+pub type Operation = Box<FnMut() -> sys::PP_Code>;
+
+/// An operation and its callback, waiting to be posted to the loop the
+/// operation was started on.
+pub struct PendingCompletion {
+    /// What the operation is on.
+    resource: PP_Resource,
+    message_loop: MessageLoop,
+    operation: Operation,
+    f: sys::PP_CompletionCallback_Func,
+    user: *mut libc::c_void,
+}
+unsafe impl Send for PendingCompletion { }
+impl PendingCompletion {
+    pub fn new(resource: PP_Resource, message_loop: MessageLoop,
+               f: sys::PP_CompletionCallback_Func, user: *mut libc::c_void,
+               operation: Operation) -> PendingCompletion {
+        PendingCompletion {
+            resource: resource,
+            message_loop: message_loop,
+            operation: operation,
+            f: f,
+            user: user,
+        }
+    }
+    pub fn resource(&self) -> PP_Resource { self.resource }
+
+    /// If the loop is gone, the callback can't ever run, so it's reported as
+    /// leaked.
+    pub fn post(self) { self.post_with(sys::PP_OK) }
+    /// The operation is dropped without running; the callback gets
+    /// `PP_ERROR_ABORTED`.
+    pub fn abort(self) { self.post_with(sys::PP_ERROR_ABORTED) }
+    /// `run_operation` only runs the operation if `result` is `PP_OK`.
+    fn post_with(self, result: sys::PP_Code) {
+        let instance = self.message_loop.instance().id();
+        let message_loop = self.message_loop.clone();
+        let deferred = Box::into_raw(Box::new(Deferred {
+            operation: self.operation,
+            f: self.f,
+            user: self.user,
+        }));
+        let work = Work {
+            f: run_operation,
+            user: deferred as *mut _,
+            result: result,
+        };
+        if let Err(_) = message_loop.post_delayed(work, Duration::new(0, 0)) {
+            drop(unsafe { Box::from_raw(deferred) });
+            leaked_callbacks().report(LeakedCallback {
+                instance: instance,
+                message_loop: message_loop.id(),
+                result: result,
+            });
+        }
+    }
+}
+impl ::std::fmt::Debug for PendingCompletion {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "PendingCompletion({})", self.resource)
+    }
+}
+
+struct Deferred {
+    operation: Operation,
+    f: sys::PP_CompletionCallback_Func,
+    user: *mut libc::c_void,
+}
+extern "C" fn run_operation(user: *mut libc::c_void, result: sys::PP_Code) {
+    let mut deferred: Box<Deferred> = unsafe { Box::from_raw(user as *mut Deferred) };
+    let result = if result == sys::PP_OK { (deferred.operation)() } else { result };
+    (deferred.f)(deferred.user, result);
+}
+
+/// The instance's side: applies the policy to each completion handed to it,
+/// and keeps those it holds back until they're due, released, or aborted.
+#[derive(Debug, Default)]
+pub struct Completions {
+    policy: Completion,
+    rng: Option<Rng>,
+    /// For `Manual`, oldest first.
+    held: VecDeque<PendingCompletion>,
+    /// For `Delayed` and `Random`.
+    timed: Timers<PendingCompletion>,
+}
+impl Completions {
+    /// Anything held is run through the new policy; timed completions keep
+    /// their due time.
+    pub fn set_policy(&mut self, policy: Completion, now: Instant) {
+        self.policy = policy;
+        self.rng = match policy {
+            Completion::Random { seed, .. } => Some(Rng::new(seed)),
+            _ => None,
+        };
+        let held: Vec<_> = self.held.drain(..).collect();
+        for pending in held.into_iter() {
+            self.schedule(pending, now);
+        }
+    }
+
+    /// `now` is on the module's clock, as are due times.
+    pub fn schedule(&mut self, pending: PendingCompletion, now: Instant) {
+        let delay = match self.policy {
+            Completion::Immediate => {
+                pending.post();
+                return;
+            },
+            Completion::Manual => {
+                self.held.push_back(pending);
+                return;
+            },
+            Completion::Delayed(delay) => delay,
+            Completion::Random { max_delay, .. } => {
+                self.rng.as_mut().unwrap().duration(max_delay)
+            },
+        };
+        self.timed.push(now + delay, pending);
+    }
+    /// When the next timed completion is due.
+    pub fn next_due(&self) -> Option<Instant> { self.timed.next_due() }
+    pub fn post_due(&mut self, now: Instant) {
+        while let Some(pending) = self.timed.pop_due(now) {
+            pending.post();
+        }
+    }
+
+    pub fn held(&self) -> usize { self.held.len() }
+    /// Posts up to `count` held completions, oldest first. Returns how many
+    /// were posted.
+    pub fn release(&mut self, count: usize) -> usize {
+        let count = ::std::cmp::min(count, self.held.len());
+        for pending in self.held.drain(..count) {
+            pending.post();
+        }
+        count
+    }
+
+    /// Aborts everything waiting for `resource`, or for any resource if
+    /// `None`. Returns how many were aborted.
+    pub fn abort(&mut self, resource: Option<PP_Resource>) -> usize {
+        let matches = |pending: &PendingCompletion| {
+            resource.map(|res| res == pending.resource ).unwrap_or(true)
+        };
+        let (mut aborted, held): (VecDeque<_>, VecDeque<_>) = self.held
+            .drain(..)
+            .partition(|pending| matches(pending) );
+        self.held = held;
+        aborted.extend(self.timed.take_where(|pending| matches(pending) ));
+
+        let count = aborted.len();
+        for pending in aborted.into_iter() {
+            pending.abort();
+        }
+        count
+    }
+}
@@ -59,10 +59,10 @@ extern "C" fn read(file_io: PP_Resource, offset: libc::int64_t,
                    bytes_to_read: libc::int32_t,
                    callback: PP_CompletionCallback) -> libc::int32_t {
     use std::slice::from_raw_parts_mut;
-    let buffer = unsafe {
+    // Made in the operation, which may run later.
+    ppb_f!(R(file_io), callback, offset as usize, unsafe {
         from_raw_parts_mut(buffer as *mut u8, bytes_to_read as usize)
-    };
-    ppb_f!(R(file_io), callback, offset as usize, buffer => read_file_io)
+    } => read_file_io)
 }
 extern "C" fn write(file_io: PP_Resource, offset: libc::int64_t,
                     buffer: *const ::libc::c_char,
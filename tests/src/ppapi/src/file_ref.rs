@@ -3,6 +3,7 @@ use libc;
 
 use super::callback::Callback;
 use super::interface::*;
+use super::result::ResultCode;
 use super::sys::*;
 use super::resource::{get_resource_instance, get_resource_arc, ResState};
 
@@ -112,26 +113,23 @@ extern "C" fn rename(file_ref: PP_Resource,
 extern "C" fn query(file_ref: PP_Resource,
                     info: *mut PP_FileInfo,
                     callback: PP_CompletionCallback) -> libc::int32_t {
-    let dest_info = unsafe { info.as_mut() };
-    if dest_info.is_none() {
+    if info.is_null() {
         return PP_ERROR_BADARGUMENT;
     }
-    let dest_info = dest_info.unwrap();
 
     if let Some(i) = get_resource_instance(file_ref) {
 
-        let callback = match Callback::from_ffi(callback) {
+        let callback = match Callback::from_ffi(callback, &i) {
             Ok(cb) => cb,
             Err(c) => { return c.into(); },
         };
 
-        match i.query_file_ref(file_ref, callback) {
-            Ok(info) => {
-                *dest_info = info;
-                return PP_OK;
-            },
-            Err(c) => { return c.into(); },
-        }
+        let instance = i.clone();
+        let query = move || {
+            instance.query_file_ref(file_ref, Callback::Sync)
+                .map(|queried| unsafe { *info = queried; } )
+        };
+        return callback.complete(&i, file_ref, query).into_code();
     } else {
         return PP_ERROR_BADARGUMENT;
     }
@@ -149,45 +147,42 @@ extern "C" fn read_dir_entries(file_ref: PP_Resource,
 
     if let Some(i) = get_resource_instance(file_ref) {
 
-        let callback = match Callback::from_ffi(callback) {
+        let callback = match Callback::from_ffi(callback, &i) {
             Ok(cb) => cb,
             Err(c) => { return c.into(); },
         };
 
-        match i.read_dir_entries_file_ref(file_ref, callback) {
-            Ok(entries) => {
-                let entry_size = size_of::<PP_DirectoryEntry>();
-                let entry_count = entries.len();
-
-                let alloc_f = output.alloc.unwrap();
-                let dest = alloc_f(output.user_data,
-                                   entry_count as u32,
-                                   entry_size as u32);
+        let instance = i.clone();
+        let read = move || instance.read_dir_entries_file_ref(file_ref, Callback::Sync).map(|entries| {
+            let entry_size = size_of::<PP_DirectoryEntry>();
+            let entry_count = entries.len();
 
-                // Errors must not happen from here on.
-                let dest = unsafe {
-                    from_raw_parts_mut(dest as *mut PP_DirectoryEntry, entry_count)
-                };
+            let alloc_f = output.alloc.unwrap();
+            let dest = alloc_f(output.user_data,
+                               entry_count as u32,
+                               entry_size as u32);
 
-                for idx in 0..entry_count {
-                    let fr = &entries[idx];
-                    let info = &mut dest[idx];
+            // Errors must not happen from here on.
+            let dest = unsafe {
+                from_raw_parts_mut(dest as *mut PP_DirectoryEntry, entry_count)
+            };
 
-                    fr.get_rc().up_ref(); // they are expected to down ref the file refs when done.
+            for idx in 0..entry_count {
+                let fr = &entries[idx];
+                let info = &mut dest[idx];
 
-                    info.file_ref = fr.id();
-                    info.file_type = {
-                        let r = fr.read_inner(|inner| Ok(inner.file_type()) );
+                fr.get_rc().up_ref(); // they are expected to down ref the file refs when done.
 
-                        // We can't dealloc, so we must gracefully handle all errors.
-                        r.unwrap_or(PP_FILETYPE_OTHER)
-                    };
-                }
+                info.file_ref = fr.id();
+                info.file_type = {
+                    let r = fr.read_inner(|inner| Ok(inner.file_type()) );
 
-                PP_OK
-            },
-            Err(c) => { return c.into(); },
-        }
+                    // We can't dealloc, so we must gracefully handle all errors.
+                    r.unwrap_or(PP_FILETYPE_OTHER)
+                };
+            }
+        });
+        return callback.complete(&i, file_ref, read).into_code();
     } else {
         return PP_ERROR_BADARGUMENT;
     }
@@ -946,11 +946,12 @@ impl FileSystemState {
                     let src = match inner.data {
                         FileRefType::File {
                             ref data,
-                        } => &data[offset..],
+                        } => &data[std::cmp::min(offset, data.len())..],
                         _ => { return Err(Error::NotAFile); },
                     };
-                    buffer.copy_from_slice(src);
-                    src.len()
+                    let len = std::cmp::min(src.len(), buffer.len());
+                    buffer[..len].copy_from_slice(&src[..len]);
+                    len
                 };
                 inner.last_access_time = istate.seconds_elapsed();
                 Ok(src_len)
@@ -970,11 +971,17 @@ impl FileSystemState {
                     let dest = match lock.data {
                         FileRefType::File {
                             ref mut data,
-                        } => &mut data[offset..],
+                        } => {
+                            // Writes past the end grow the file.
+                            if data.len() < offset + buffer.len() {
+                                data.resize(offset + buffer.len(), 0);
+                            }
+                            &mut data[offset..offset + buffer.len()]
+                        },
                         _ => { return Err(Error::NotAFile); },
                     };
                     dest.copy_from_slice(buffer);
-                    std::cmp::min(dest.len(), buffer.len())
+                    dest.len()
                 };
                 lock.last_modified_time = istate.seconds_elapsed();
                 Ok(dest_len)
@@ -0,0 +1,1871 @@
+//! `PPB_OpenGLES2;1.0` backed by a CPU rasterizer, plus the `gl*` entry points
+//! and `gl*PPAPI` helpers the modules normally get from `ppapi_gles2`.
+//!
+//! This isn't a GLSL compiler. Shaders are only parsed for their declarations;
+//! at link time a program is recognized by the interface `vout_display_opengl`
+//! gives its shaders (see `Shading`) and then run natively. Programs that
+//! don't look like one of those fail to link, with a log saying why.
+
+use libc::c_char;
+
+use std::cell::Cell;
+use std::cmp::{max, min};
+use std::collections::{HashMap, HashSet};
+use std::ffi::CStr;
+use std::ptr;
+use std::sync::MutexGuard;
+
+use super::graphics::Graphics3DState;
+use super::interface::*;
+use super::prelude::*;
+use super::raster::{self, Blend, Framebuffer, Rect, Texture, Vec4, Vertex};
+use super::resource::get_resource;
+use super::sys::*;
+
+pub const MAX_TEXTURE_SIZE: GLint = 4096;
+pub const MAX_TEXTURE_UNITS: usize = 8;
+pub const MAX_VERTEX_ATTRIBS: usize = 16;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GlslType {
+    Float,
+    Vec2,
+    Vec3,
+    Vec4,
+    Int,
+    Bool,
+    Mat2,
+    Mat3,
+    Mat4,
+    Sampler2D,
+}
+impl GlslType {
+    fn parse(s: &str) -> Option<GlslType> {
+        let ty = match s {
+            "float" => GlslType::Float,
+            "vec2" => GlslType::Vec2,
+            "vec3" => GlslType::Vec3,
+            "vec4" => GlslType::Vec4,
+            "int" => GlslType::Int,
+            "bool" => GlslType::Bool,
+            "mat2" => GlslType::Mat2,
+            "mat3" => GlslType::Mat3,
+            "mat4" => GlslType::Mat4,
+            "sampler2D" => GlslType::Sampler2D,
+            _ => { return None; },
+        };
+        Some(ty)
+    }
+    pub fn components(&self) -> usize {
+        match *self {
+            GlslType::Float | GlslType::Int |
+            GlslType::Bool | GlslType::Sampler2D => 1,
+            GlslType::Vec2 => 2,
+            GlslType::Vec3 => 3,
+            GlslType::Vec4 | GlslType::Mat2 => 4,
+            GlslType::Mat3 => 9,
+            GlslType::Mat4 => 16,
+        }
+    }
+    fn integral(&self) -> bool {
+        match *self {
+            GlslType::Int | GlslType::Bool | GlslType::Sampler2D => true,
+            _ => false,
+        }
+    }
+    fn matrix(&self) -> bool {
+        match *self {
+            GlslType::Mat2 | GlslType::Mat3 | GlslType::Mat4 => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Qualifier {
+    Uniform,
+    Attribute,
+    Varying,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Decl {
+    pub qualifier: Qualifier,
+    pub ty: GlslType,
+    pub name: String,
+    /// 1 for non-arrays.
+    pub len: usize,
+}
+
+/// Pull the global `uniform`/`attribute`/`varying` declarations out of a
+/// shader.
+pub fn parse_decls(source: &str) -> Result<Vec<Decl>, String> {
+    // Drop comments and preprocessor lines.
+    let mut stripped = String::new();
+    let mut in_block = false;
+    for line in source.lines() {
+        let mut line = line;
+        if !in_block && line.trim_left().starts_with('#') { continue; }
+        loop {
+            if in_block {
+                match line.find("*/") {
+                    Some(end) => {
+                        line = &line[end + 2..];
+                        in_block = false;
+                    },
+                    None => { line = ""; break; },
+                }
+            } else {
+                let block = line.find("/*");
+                let eol = line.find("//");
+                match (block, eol) {
+                    (Some(b), Some(e)) if e < b => { stripped.push_str(&line[..e]); line = ""; break; },
+                    (Some(b), _) => {
+                        stripped.push_str(&line[..b]);
+                        stripped.push(' ');
+                        line = &line[b + 2..];
+                        in_block = true;
+                    },
+                    (None, Some(e)) => { stripped.push_str(&line[..e]); line = ""; break; },
+                    (None, None) => break,
+                }
+            }
+        }
+        stripped.push_str(line);
+        stripped.push('\n');
+    }
+
+    let mut decls = Vec::new();
+    for stmt in stripped.split(|c| c == ';' || c == '{' || c == '}' ) {
+        let mut words = stmt.split_whitespace();
+        let qualifier = match words.next() {
+            Some("uniform") => Qualifier::Uniform,
+            Some("attribute") => Qualifier::Attribute,
+            Some("varying") => Qualifier::Varying,
+            _ => { continue; },
+        };
+        let mut ty = words.next();
+        if let Some("lowp") | Some("mediump") | Some("highp") = ty {
+            ty = words.next();
+        }
+        let ty = match ty.and_then(GlslType::parse) {
+            Some(ty) => ty,
+            None => {
+                return Err(format!("unsupported type in `{}`", stmt.trim()));
+            },
+        };
+
+        let names: String = words.collect();
+        for name in names.split(',') {
+            let (name, len) = match name.find('[') {
+                Some(open) => {
+                    let len = name[open + 1..].trim_right_matches(']').parse::<usize>();
+                    match len {
+                        Ok(len) if len > 0 => (&name[..open], len),
+                        _ => { return Err(format!("bad array size in `{}`", stmt.trim())); },
+                    }
+                },
+                None => (name, 1),
+            };
+            if name.is_empty() {
+                return Err(format!("missing name in `{}`", stmt.trim()));
+            }
+            decls.push(Decl {
+                qualifier: qualifier,
+                ty: ty,
+                name: name.to_string(),
+                len: len,
+            });
+        }
+    }
+
+    Ok(decls)
+}
+
+#[derive(Clone, Debug)]
+pub struct Shader {
+    pub kind: GLenum,
+    pub source: String,
+    pub compiled: bool,
+    pub log: String,
+    decls: Vec<Decl>,
+}
+
+/// What a linked program's fragment shader does, as recognized from its
+/// declarations. Samplers are `(uniform, varying)` index pairs.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shading {
+    /// VLC's YUV to RGB conversion:
+    /// `x * Coefficient[0] + Coefficient[3] + y * Coefficient[1] + z * Coefficient[2]`
+    /// where x/y/z are `Texture0`..`Texture2`; `y` and `z` swap when the
+    /// shader reads `z` from `Texture1`.
+    Yuv {
+        samplers: [(usize, usize); 3],
+        coefficient: usize,
+        swap_uv: bool,
+    },
+    /// `texture2D(Texture0, TexCoord0) * FillColor`.
+    Modulate {
+        sampler: (usize, usize),
+        color: usize,
+    },
+    /// `texture2D(Texture0, TexCoord0)`.
+    Texture {
+        sampler: (usize, usize),
+    },
+    /// A flat `uniform vec4`.
+    Color {
+        color: usize,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct Uniform {
+    pub name: String,
+    pub element: usize,
+    pub ty: GlslType,
+    pub value: [f32; 16],
+    pub set: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Program {
+    shaders: Vec<GLuint>,
+    bound_attribs: HashMap<String, GLuint>,
+    deleted: bool,
+
+    pub linked: bool,
+    pub log: String,
+    /// Locations are indices; array elements get one each.
+    pub uniforms: Vec<Uniform>,
+    pub attributes: Vec<(String, GLuint)>,
+    pub varyings: Vec<String>,
+
+    position: Option<GLuint>,
+    /// The attribute feeding each varying.
+    varying_sources: Vec<Option<GLuint>>,
+    /// `mat4` uniforms applied to the position, in declaration order.
+    matrices: Vec<usize>,
+    pub shading: Option<Shading>,
+}
+impl Program {
+    fn uniform_location(&self, name: &str) -> Option<usize> {
+        let (base, element) = match name.find('[') {
+            Some(open) => {
+                match name[open + 1..].trim_right_matches(']').parse::<usize>() {
+                    Ok(e) => (&name[..open], e),
+                    Err(_) => { return None; },
+                }
+            },
+            None => (name, 0),
+        };
+        self.uniforms
+            .iter()
+            .position(|u| u.name == base && u.element == element )
+    }
+    fn uniform_named(&self, name: &str) -> Option<usize> {
+        self.uniform_location(name)
+    }
+    fn varying_named(&self, name: &str) -> Option<usize> {
+        self.varyings.iter().position(|v| v == name )
+    }
+    fn attribute_named(&self, name: &str) -> Option<GLuint> {
+        self.attributes
+            .iter()
+            .find(|a| a.0 == name )
+            .map(|a| a.1 )
+    }
+
+    fn link(&mut self, vertex: &Shader, fragment: &Shader) -> Result<(), String> {
+        self.uniforms.clear();
+        self.attributes.clear();
+        self.varyings.clear();
+
+        let mut used_locations: HashSet<GLuint> = HashSet::new();
+        let mut unbound = Vec::new();
+        for decl in vertex.decls.iter().chain(fragment.decls.iter()) {
+            match decl.qualifier {
+                Qualifier::Uniform => {
+                    if self.uniforms.iter().any(|u| u.name == decl.name ) {
+                        continue;
+                    }
+                    for element in 0..decl.len {
+                        let mut value = [0.0f32; 16];
+                        if decl.ty.matrix() {
+                            let n = (decl.ty.components() as f32).sqrt() as usize;
+                            for i in 0..n { value[i * n + i] = 1.0; }
+                        }
+                        self.uniforms.push(Uniform {
+                            name: decl.name.clone(),
+                            element: element,
+                            ty: decl.ty,
+                            value: value,
+                            set: false,
+                        });
+                    }
+                },
+                Qualifier::Attribute => {
+                    match self.bound_attribs.get(&decl.name) {
+                        Some(&loc) => {
+                            used_locations.insert(loc);
+                            self.attributes.push((decl.name.clone(), loc));
+                        },
+                        None => unbound.push(decl.name.clone()),
+                    }
+                },
+                Qualifier::Varying => {
+                    if !self.varyings.contains(&decl.name) {
+                        self.varyings.push(decl.name.clone());
+                    }
+                },
+            }
+        }
+        let mut next = 0;
+        for name in unbound.into_iter() {
+            while used_locations.contains(&next) { next += 1; }
+            if next as usize >= MAX_VERTEX_ATTRIBS {
+                return Err("too many attributes".to_string());
+            }
+            used_locations.insert(next);
+            self.attributes.push((name, next));
+        }
+
+        // The vertex stage: `gl_Position = <matrices> * VertexPosition` and
+        // `TexCoordN = MultiTexCoordN`.
+        self.position = self.attribute_named("VertexPosition")
+            .or_else(|| {
+                self.attributes
+                    .iter()
+                    .find(|a| a.0.contains("Position") || a.0.contains("Vertex") )
+                    .map(|a| a.1 )
+            })
+            .or_else(|| self.attributes.first().map(|a| a.1 ) );
+        let sources: Vec<_> = self.varyings
+            .iter()
+            .map(|v| {
+                self.attributes
+                    .iter()
+                    .find(|a| a.0.ends_with(&v[..]) )
+                    .map(|a| a.1 )
+            })
+            .collect();
+        self.varying_sources = sources;
+        self.matrices = vertex.decls
+            .iter()
+            .filter(|d| d.qualifier == Qualifier::Uniform && d.ty == GlslType::Mat4 )
+            .filter_map(|d| self.uniform_named(&d.name) )
+            .collect();
+
+        self.shading = Some(try!(self.recognize(fragment)));
+        Ok(())
+    }
+
+    fn sampler(&self, n: usize) -> Option<(usize, usize)> {
+        let sampler = self.uniform_named(&format!("Texture{}", n));
+        let varying = self.varying_named(&format!("TexCoord{}", n))
+            .or_else(|| if self.varyings.is_empty() { None } else { Some(0) } );
+        match (sampler, varying) {
+            (Some(s), Some(v)) => Some((s, v)),
+            _ => None,
+        }
+    }
+    fn recognize(&self, fragment: &Shader) -> Result<Shading, String> {
+        let squashed: String = fragment.source
+            .chars()
+            .filter(|c| !c.is_whitespace() )
+            .collect();
+
+        if let Some(coefficient) = self.uniform_named("Coefficient") {
+            let samplers = (self.sampler(0), self.sampler(1), self.sampler(2));
+            if let (Some(x), Some(y), Some(z)) = samplers {
+                return Ok(Shading::Yuv {
+                    samplers: [x, y, z],
+                    coefficient: coefficient,
+                    swap_uv: squashed.contains("z=texture2D(Texture1"),
+                });
+            } else {
+                return Err("YUV shader needs Texture0..2 and TexCoord0..2".to_string());
+            }
+        }
+
+        let color = self.uniform_named("FillColor")
+            .or_else(|| {
+                fragment.decls
+                    .iter()
+                    .find(|d| d.qualifier == Qualifier::Uniform && d.ty == GlslType::Vec4 )
+                    .and_then(|d| self.uniform_named(&d.name) )
+            });
+        match (self.sampler(0), color) {
+            (Some(sampler), Some(color)) => Ok(Shading::Modulate {
+                sampler: sampler,
+                color: color,
+            }),
+            (Some(sampler), None) => Ok(Shading::Texture { sampler: sampler, }),
+            (None, Some(color)) => Ok(Shading::Color { color: color, }),
+            (None, None) => Err("unrecognized fragment shader".to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct AttribArray {
+    enabled: bool,
+    size: usize,
+    ty: GLenum,
+    normalized: bool,
+    stride: usize,
+    /// An offset into `buffer` if it isn't 0.
+    pointer: *const u8,
+    buffer: GLuint,
+}
+impl Default for AttribArray {
+    fn default() -> AttribArray {
+        AttribArray {
+            enabled: false,
+            size: 4,
+            ty: GL_FLOAT,
+            normalized: false,
+            stride: 0,
+            pointer: ptr::null(),
+            buffer: 0,
+        }
+    }
+}
+fn type_size(ty: GLenum) -> Option<usize> {
+    match ty {
+        GL_BYTE | GL_UNSIGNED_BYTE => Some(1),
+        GL_SHORT | GL_UNSIGNED_SHORT => Some(2),
+        GL_FLOAT => Some(4),
+        _ => None,
+    }
+}
+
+/// The GL state of one Graphics3D context.
+#[derive(Debug)]
+pub struct GlContext {
+    error: GLenum,
+    framebuffer: Framebuffer,
+
+    viewport: Rect,
+    scissor: Rect,
+    clear_color: Vec4,
+    enabled: HashSet<GLenum>,
+    blend: Blend,
+    unpack_alignment: usize,
+    pack_alignment: usize,
+
+    next_name: GLuint,
+    active_texture: usize,
+    texture_units: [GLuint; MAX_TEXTURE_UNITS],
+    /// Name 0 is the default texture.
+    textures: HashMap<GLuint, Texture>,
+    buffers: HashMap<GLuint, Vec<u8>>,
+    array_buffer: GLuint,
+    element_array_buffer: GLuint,
+    shaders: HashMap<GLuint, Shader>,
+    programs: HashMap<GLuint, Program>,
+    current_program: GLuint,
+    attribs: [AttribArray; MAX_VERTEX_ATTRIBS],
+}
+unsafe impl Send for GlContext { }
+
+impl GlContext {
+    pub fn new(width: usize, height: usize) -> GlContext {
+        let mut enabled = HashSet::new();
+        enabled.insert(GL_DITHER);
+        let mut textures = HashMap::new();
+        textures.insert(0, Default::default());
+
+        GlContext {
+            error: GL_NO_ERROR,
+            framebuffer: Framebuffer::new(width, height),
+            viewport: Rect::new(0, 0, width as i32, height as i32),
+            scissor: Rect::new(0, 0, width as i32, height as i32),
+            clear_color: [0.0; 4],
+            enabled: enabled,
+            blend: Default::default(),
+            unpack_alignment: 4,
+            pack_alignment: 4,
+            next_name: 1,
+            active_texture: 0,
+            texture_units: [0; MAX_TEXTURE_UNITS],
+            textures: textures,
+            buffers: HashMap::new(),
+            array_buffer: 0,
+            element_array_buffer: 0,
+            shaders: HashMap::new(),
+            programs: HashMap::new(),
+            current_program: 0,
+            attribs: [Default::default(); MAX_VERTEX_ATTRIBS],
+        }
+    }
+
+    /// The color buffer's contents are undefined after a resize; this mock
+    /// clears them.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.framebuffer = Framebuffer::new(width, height);
+    }
+    pub fn framebuffer(&self) -> &Framebuffer { &self.framebuffer }
+    pub fn program(&self, program: GLuint) -> Option<&Program> {
+        self.programs.get(&program)
+    }
+    pub fn texture(&self, texture: GLuint) -> Option<&Texture> {
+        self.textures.get(&texture)
+    }
+
+    fn set_error(&mut self, error: GLenum) {
+        if self.error == GL_NO_ERROR {
+            self.error = error;
+        }
+    }
+    fn gen_name(&mut self) -> GLuint {
+        let name = self.next_name;
+        self.next_name += 1;
+        name
+    }
+
+    fn active_texture(&mut self, texture: GLenum) {
+        let unit = texture.wrapping_sub(GL_TEXTURE0) as usize;
+        if unit >= MAX_TEXTURE_UNITS {
+            return self.set_error(GL_INVALID_ENUM);
+        }
+        self.active_texture = unit;
+    }
+    fn attach_shader(&mut self, program: GLuint, shader: GLuint) {
+        if !self.shaders.contains_key(&shader) {
+            return self.set_error(GL_INVALID_VALUE);
+        }
+        let attached = match self.programs.get_mut(&program) {
+            Some(p) => {
+                if p.shaders.contains(&shader) {
+                    true
+                } else {
+                    p.shaders.push(shader);
+                    false
+                }
+            },
+            None => { return self.set_error(GL_INVALID_VALUE); },
+        };
+        if attached { self.set_error(GL_INVALID_OPERATION); }
+    }
+    fn bind_attrib_location(&mut self, program: GLuint, index: GLuint,
+                            name: *const GLchar) {
+        if index as usize >= MAX_VERTEX_ATTRIBS || name.is_null() {
+            return self.set_error(GL_INVALID_VALUE);
+        }
+        let name = unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned();
+        match self.programs.get_mut(&program) {
+            Some(p) => { p.bound_attribs.insert(name, index); },
+            None => self.set_error(GL_INVALID_VALUE),
+        }
+    }
+    fn bind_buffer(&mut self, target: GLenum, buffer: GLuint) {
+        if buffer != 0 && !self.buffers.contains_key(&buffer) {
+            self.buffers.insert(buffer, Vec::new());
+        }
+        match target {
+            GL_ARRAY_BUFFER => self.array_buffer = buffer,
+            GL_ELEMENT_ARRAY_BUFFER => self.element_array_buffer = buffer,
+            _ => self.set_error(GL_INVALID_ENUM),
+        }
+    }
+    fn bind_texture(&mut self, target: GLenum, texture: GLuint) {
+        if target != GL_TEXTURE_2D {
+            return self.set_error(GL_INVALID_ENUM);
+        }
+        if !self.textures.contains_key(&texture) {
+            self.textures.insert(texture, Default::default());
+        }
+        self.texture_units[self.active_texture] = texture;
+    }
+    fn blend_func(&mut self, sfactor: GLenum, dfactor: GLenum) {
+        self.blend_func_separate(sfactor, dfactor, sfactor, dfactor)
+    }
+    fn blend_func_separate(&mut self, src_rgb: GLenum, dst_rgb: GLenum,
+                           src_alpha: GLenum, dst_alpha: GLenum) {
+        let factors = [src_rgb, dst_rgb, src_alpha, dst_alpha];
+        if !factors.iter().all(|&f| raster::valid_blend_factor(f) ) {
+            return self.set_error(GL_INVALID_ENUM);
+        }
+        self.blend.src_rgb = src_rgb;
+        self.blend.dst_rgb = dst_rgb;
+        self.blend.src_alpha = src_alpha;
+        self.blend.dst_alpha = dst_alpha;
+    }
+
+    fn bound_buffer(&mut self, target: GLenum) -> Option<GLuint> {
+        let buffer = match target {
+            GL_ARRAY_BUFFER => self.array_buffer,
+            GL_ELEMENT_ARRAY_BUFFER => self.element_array_buffer,
+            _ => {
+                self.set_error(GL_INVALID_ENUM);
+                return None;
+            },
+        };
+        if buffer == 0 {
+            self.set_error(GL_INVALID_OPERATION);
+            return None;
+        }
+        Some(buffer)
+    }
+    fn buffer_data(&mut self, target: GLenum, size: GLsizeiptr,
+                   data: *const GLvoid, usage: GLenum) {
+        let buffer = match self.bound_buffer(target) {
+            Some(b) => b,
+            None => { return; },
+        };
+        match usage {
+            GL_STREAM_DRAW | GL_STATIC_DRAW | GL_DYNAMIC_DRAW => {},
+            _ => { return self.set_error(GL_INVALID_ENUM); },
+        }
+        if size < 0 {
+            return self.set_error(GL_INVALID_VALUE);
+        }
+
+        let contents = if data.is_null() {
+            vec![0u8; size as usize]
+        } else {
+            unsafe {
+                ::std::slice::from_raw_parts(data as *const u8, size as usize)
+            }.to_vec()
+        };
+        self.buffers.insert(buffer, contents);
+    }
+    fn buffer_sub_data(&mut self, target: GLenum, offset: GLintptr,
+                       size: GLsizeiptr, data: *const GLvoid) {
+        let buffer = match self.bound_buffer(target) {
+            Some(b) => b,
+            None => { return; },
+        };
+        let len = self.buffers[&buffer].len() as GLintptr;
+        if offset < 0 || size < 0 || offset + size > len {
+            return self.set_error(GL_INVALID_VALUE);
+        }
+        if data.is_null() { return; }
+
+        let src = unsafe {
+            ::std::slice::from_raw_parts(data as *const u8, size as usize)
+        };
+        let dst = self.buffers.get_mut(&buffer).unwrap();
+        let offset = offset as usize;
+        dst[offset..offset + size as usize].copy_from_slice(src);
+    }
+
+    fn scissor_area(&self) -> Rect {
+        if self.enabled.contains(&GL_SCISSOR_TEST) {
+            self.scissor
+        } else {
+            self.framebuffer.bounds()
+        }
+    }
+    fn clear(&mut self, mask: GLbitfield) {
+        let valid = GL_COLOR_BUFFER_BIT | GL_DEPTH_BUFFER_BIT | GL_STENCIL_BUFFER_BIT;
+        if mask & !valid != 0 {
+            return self.set_error(GL_INVALID_VALUE);
+        }
+        // There's no depth or stencil buffer.
+        if mask & GL_COLOR_BUFFER_BIT != 0 {
+            let area = self.scissor_area();
+            self.framebuffer.clear(self.clear_color, area);
+        }
+    }
+    fn clear_color(&mut self, red: GLclampf, green: GLclampf, blue: GLclampf,
+                   alpha: GLclampf) {
+        fn clamp(v: f32) -> f32 { if v < 0.0 { 0.0 } else if v > 1.0 { 1.0 } else { v } }
+        self.clear_color = [clamp(red), clamp(green), clamp(blue), clamp(alpha)];
+    }
+
+    fn compile_shader(&mut self, shader: GLuint) {
+        match self.shaders.get_mut(&shader) {
+            Some(s) => {
+                match parse_decls(&s.source) {
+                    Ok(decls) if s.source.contains("main") => {
+                        s.decls = decls;
+                        s.compiled = true;
+                        s.log.clear();
+                    },
+                    Ok(_) => {
+                        s.compiled = false;
+                        s.log = "ERROR: missing main()".to_string();
+                    },
+                    Err(err) => {
+                        s.compiled = false;
+                        s.log = format!("ERROR: {}", err);
+                    },
+                }
+            },
+            None => self.set_error(GL_INVALID_VALUE),
+        }
+    }
+    fn create_program(&mut self) -> GLuint {
+        let name = self.gen_name();
+        self.programs.insert(name, Default::default());
+        name
+    }
+    fn create_shader(&mut self, type_: GLenum) -> GLuint {
+        match type_ {
+            GL_VERTEX_SHADER | GL_FRAGMENT_SHADER => {},
+            _ => {
+                self.set_error(GL_INVALID_ENUM);
+                return 0;
+            },
+        }
+        let name = self.gen_name();
+        self.shaders.insert(name, Shader {
+            kind: type_,
+            source: String::new(),
+            compiled: false,
+            log: String::new(),
+            decls: Vec::new(),
+        });
+        name
+    }
+    fn delete_buffers(&mut self, n: GLsizei, buffers: *const GLuint) {
+        if n < 0 { return self.set_error(GL_INVALID_VALUE); }
+        if buffers.is_null() { return; }
+        let names = unsafe { ::std::slice::from_raw_parts(buffers, n as usize) };
+        for &name in names.iter() {
+            if name == 0 { continue; }
+            self.buffers.remove(&name);
+            if self.array_buffer == name { self.array_buffer = 0; }
+            if self.element_array_buffer == name { self.element_array_buffer = 0; }
+        }
+    }
+    fn delete_program(&mut self, program: GLuint) {
+        if program == 0 { return; }
+        if program == self.current_program {
+            // Deleted once it's no longer in use.
+            match self.programs.get_mut(&program) {
+                Some(p) => { p.deleted = true; },
+                None => self.set_error(GL_INVALID_VALUE),
+            }
+        } else if self.programs.remove(&program).is_none() {
+            self.set_error(GL_INVALID_VALUE);
+        }
+    }
+    /// Linked programs keep what they need, so shaders can go right away.
+    fn delete_shader(&mut self, shader: GLuint) {
+        if shader == 0 { return; }
+        if self.shaders.remove(&shader).is_none() {
+            self.set_error(GL_INVALID_VALUE);
+        }
+    }
+    fn delete_textures(&mut self, n: GLsizei, textures: *const GLuint) {
+        if n < 0 { return self.set_error(GL_INVALID_VALUE); }
+        if textures.is_null() { return; }
+        let names = unsafe { ::std::slice::from_raw_parts(textures, n as usize) };
+        for &name in names.iter() {
+            if name == 0 { continue; }
+            self.textures.remove(&name);
+            for unit in self.texture_units.iter_mut() {
+                if *unit == name { *unit = 0; }
+            }
+        }
+    }
+    fn detach_shader(&mut self, program: GLuint, shader: GLuint) {
+        let detached = match self.programs.get_mut(&program) {
+            Some(p) => {
+                let before = p.shaders.len();
+                p.shaders.retain(|&s| s != shader );
+                before != p.shaders.len()
+            },
+            None => { return self.set_error(GL_INVALID_VALUE); },
+        };
+        if !detached { self.set_error(GL_INVALID_OPERATION); }
+    }
+
+    fn valid_cap(cap: GLenum) -> bool {
+        match cap {
+            GL_BLEND | GL_CULL_FACE | GL_DEPTH_TEST | GL_DITHER |
+            GL_POLYGON_OFFSET_FILL | GL_SAMPLE_ALPHA_TO_COVERAGE |
+            GL_SAMPLE_COVERAGE | GL_SCISSOR_TEST | GL_STENCIL_TEST => true,
+            _ => false,
+        }
+    }
+    fn disable(&mut self, cap: GLenum) {
+        if !GlContext::valid_cap(cap) { return self.set_error(GL_INVALID_ENUM); }
+        self.enabled.remove(&cap);
+        self.blend.enabled = self.enabled.contains(&GL_BLEND);
+    }
+    fn enable(&mut self, cap: GLenum) {
+        if !GlContext::valid_cap(cap) { return self.set_error(GL_INVALID_ENUM); }
+        self.enabled.insert(cap);
+        self.blend.enabled = self.enabled.contains(&GL_BLEND);
+    }
+    fn is_enabled(&mut self, cap: GLenum) -> GLboolean {
+        if !GlContext::valid_cap(cap) {
+            self.set_error(GL_INVALID_ENUM);
+            return GL_FALSE;
+        }
+        if self.enabled.contains(&cap) { GL_TRUE } else { GL_FALSE }
+    }
+    fn disable_vertex_attrib_array(&mut self, index: GLuint) {
+        match self.attribs.get_mut(index as usize) {
+            Some(a) => { a.enabled = false; },
+            None => self.set_error(GL_INVALID_VALUE),
+        }
+    }
+    fn enable_vertex_attrib_array(&mut self, index: GLuint) {
+        match self.attribs.get_mut(index as usize) {
+            Some(a) => { a.enabled = true; },
+            None => self.set_error(GL_INVALID_VALUE),
+        }
+    }
+
+    fn fetch_attrib(&self, location: GLuint, index: usize) -> Option<Vec4> {
+        let array = self.attribs[location as usize];
+        if !array.enabled {
+            // The generic attribute isn't settable here, so it's always the
+            // default.
+            return Some([0.0, 0.0, 0.0, 1.0]);
+        }
+
+        let size = type_size(array.ty).unwrap();
+        let stride = if array.stride == 0 { size * array.size } else { array.stride };
+        let start = index * stride;
+        let end = start + size * array.size;
+        let bytes = if array.buffer != 0 {
+            let buffer = match self.buffers.get(&array.buffer) {
+                Some(b) => b,
+                None => { return None; },
+            };
+            let offset = array.pointer as usize;
+            if offset + end > buffer.len() { return None; }
+            buffer[offset + start..offset + end].to_vec()
+        } else {
+            if array.pointer.is_null() { return None; }
+            unsafe {
+                ::std::slice::from_raw_parts(array.pointer.offset(start as isize),
+                                             end - start)
+            }.to_vec()
+        };
+
+        let mut out = [0.0, 0.0, 0.0, 1.0];
+        for c in 0..array.size {
+            let b = &bytes[c * size..(c + 1) * size];
+            let (v, max) = match array.ty {
+                GL_BYTE => (b[0] as i8 as f32, 127.0),
+                GL_UNSIGNED_BYTE => (b[0] as f32, 255.0),
+                GL_SHORT => ((b[0] as u16 | (b[1] as u16) << 8) as i16 as f32, 32767.0),
+                GL_UNSIGNED_SHORT => ((b[0] as u16 | (b[1] as u16) << 8) as f32, 65535.0),
+                GL_FLOAT => {
+                    let bits = b[0] as u32 | (b[1] as u32) << 8 |
+                        (b[2] as u32) << 16 | (b[3] as u32) << 24;
+                    (f32::from_bits(bits), 1.0)
+                },
+                _ => unreachable!(),
+            };
+            out[c] = if array.normalized && array.ty != GL_FLOAT {
+                (v / max).max(-1.0)
+            } else {
+                v
+            };
+        }
+        Some(out)
+    }
+    /// How many vertices the buffers behind `program`'s enabled arrays hold.
+    /// Client side arrays are the module's to size, so don't limit it.
+    fn vertex_limit(&self, program: &Program) -> usize {
+        let locations = program.position
+            .iter()
+            .chain(program.varying_sources.iter().filter_map(|s| s.as_ref() ));
+        let mut limit = usize::max_value();
+        for &location in locations {
+            let array = self.attribs[location as usize];
+            if !array.enabled || array.buffer == 0 { continue; }
+
+            let len = self.buffers.get(&array.buffer).map(|b| b.len() ).unwrap_or(0);
+            let size = type_size(array.ty).unwrap() * array.size;
+            let stride = if array.stride == 0 { size } else { array.stride };
+            let offset = array.pointer as usize;
+            let count = if offset + size > len { 0 } else { (len - offset - size) / stride + 1 };
+            limit = min(limit, count);
+        }
+        limit
+    }
+    fn shade_vertex(&self, program: &Program, index: usize) -> Option<Vertex> {
+        let mut position = match program.position {
+            Some(loc) => match self.fetch_attrib(loc, index) {
+                Some(v) => v,
+                None => { return None; },
+            },
+            None => [0.0, 0.0, 0.0, 1.0],
+        };
+        for &m in program.matrices.iter().rev() {
+            let u = &program.uniforms[m];
+            if !u.set { continue; }
+            let mut out = [0.0f32; 4];
+            for row in 0..4 {
+                for col in 0..4 {
+                    out[row] += u.value[col * 4 + row] * position[col];
+                }
+            }
+            position = out;
+        }
+
+        let mut varyings = Vec::with_capacity(program.varying_sources.len());
+        for source in program.varying_sources.iter() {
+            let v = match *source {
+                Some(loc) => match self.fetch_attrib(loc, index) {
+                    Some(v) => v,
+                    None => { return None; },
+                },
+                None => [0.0; 4],
+            };
+            varyings.push(v);
+        }
+
+        Some(Vertex {
+            position: position,
+            varyings: varyings,
+        })
+    }
+
+    fn draw(&mut self, mode: GLenum, indices: Vec<usize>) {
+        let program = match self.programs.get(&self.current_program) {
+            Some(p) if p.linked => p.clone(),
+            _ => { return self.set_error(GL_INVALID_OPERATION); },
+        };
+
+        let mut vertices = Vec::with_capacity(indices.len());
+        for &index in indices.iter() {
+            match self.shade_vertex(&program, index) {
+                Some(v) => vertices.push(v),
+                None => { return self.set_error(GL_INVALID_OPERATION); },
+            }
+        }
+
+        let mut triangles = Vec::new();
+        match mode {
+            GL_TRIANGLES => {
+                for i in 0..vertices.len() / 3 {
+                    triangles.push([i * 3, i * 3 + 1, i * 3 + 2]);
+                }
+            },
+            GL_TRIANGLE_STRIP => {
+                for i in 2..vertices.len() {
+                    triangles.push([i - 2, i - 1, i]);
+                }
+            },
+            GL_TRIANGLE_FAN => {
+                for i in 2..vertices.len() {
+                    triangles.push([0, i - 1, i]);
+                }
+            },
+            // Not rasterized.
+            GL_POINTS | GL_LINES | GL_LINE_LOOP | GL_LINE_STRIP => {},
+            _ => { return self.set_error(GL_INVALID_ENUM); },
+        }
+
+        let area = self.scissor_area();
+        let viewport = self.viewport;
+        let blend = self.blend;
+        let textures = &self.textures;
+        let units = &self.texture_units;
+        let samplers: Vec<Option<&Texture>> = program.uniforms
+            .iter()
+            .map(|u| {
+                if u.ty != GlslType::Sampler2D { return None; }
+                units
+                    .get(u.value[0] as usize)
+                    .and_then(|name| textures.get(name) )
+            })
+            .collect();
+        let shading = program.shading.clone().unwrap();
+        let uniforms = &program.uniforms;
+        let sample = |s: (usize, usize), varyings: &[Vec4]| -> Vec4 {
+            match samplers[s.0] {
+                Some(t) => t.sample(varyings[s.1][0], varyings[s.1][1]),
+                None => [0.0, 0.0, 0.0, 1.0],
+            }
+        };
+        let vec4 = |u: usize| -> Vec4 {
+            let v = &uniforms[u].value;
+            [v[0], v[1], v[2], v[3]]
+        };
+        let shade = |varyings: &[Vec4]| -> Vec4 {
+            match shading {
+                Shading::Yuv { samplers, coefficient, swap_uv, } => {
+                    let x = sample(samplers[0], varyings);
+                    let (mut y, mut z) = (sample(samplers[1], varyings),
+                                          sample(samplers[2], varyings));
+                    if swap_uv { ::std::mem::swap(&mut y, &mut z); }
+                    let c: Vec<Vec4> = (0..4).map(|i| vec4(coefficient + i) ).collect();
+                    let mut out = [0.0f32; 4];
+                    for i in 0..4 {
+                        out[i] = x[i] * c[0][i] + c[3][i] + y[i] * c[1][i] + z[i] * c[2][i];
+                    }
+                    out
+                },
+                Shading::Modulate { sampler, color, } => {
+                    let t = sample(sampler, varyings);
+                    let c = vec4(color);
+                    [t[0] * c[0], t[1] * c[1], t[2] * c[2], t[3] * c[3]]
+                },
+                Shading::Texture { sampler, } => sample(sampler, varyings),
+                Shading::Color { color, } => vec4(color),
+            }
+        };
+
+        let fb = &mut self.framebuffer;
+        for tri in triangles.into_iter() {
+            let tri = [&vertices[tri[0]], &vertices[tri[1]], &vertices[tri[2]]];
+            raster::draw_triangle(fb, viewport, area, &blend, tri, &shade);
+        }
+    }
+    fn draw_arrays(&mut self, mode: GLenum, first: GLint, count: GLsizei) {
+        if first < 0 || count < 0 {
+            return self.set_error(GL_INVALID_VALUE);
+        }
+        let end = match first.checked_add(count) {
+            Some(end) => end as usize,
+            None => { return self.set_error(GL_INVALID_VALUE); },
+        };
+        let limit = match self.programs.get(&self.current_program) {
+            Some(p) if p.linked => self.vertex_limit(p),
+            _ => { return self.set_error(GL_INVALID_OPERATION); },
+        };
+        if count > 0 && end > limit {
+            return self.set_error(GL_INVALID_OPERATION);
+        }
+        let indices = (first as usize..end).collect();
+        self.draw(mode, indices)
+    }
+    fn draw_elements(&mut self, mode: GLenum, count: GLsizei, type_: GLenum,
+                     indices: *const GLvoid) {
+        if count < 0 { return self.set_error(GL_INVALID_VALUE); }
+        let size = match type_ {
+            GL_UNSIGNED_BYTE => 1,
+            GL_UNSIGNED_SHORT => 2,
+            _ => { return self.set_error(GL_INVALID_ENUM); },
+        };
+        let len = match (count as usize).checked_mul(size) {
+            Some(len) => len,
+            None => { return self.set_error(GL_INVALID_OPERATION); },
+        };
+        let bytes = if self.element_array_buffer != 0 {
+            let buffer = &self.buffers[&self.element_array_buffer];
+            let offset = indices as usize;
+            let end = match offset.checked_add(len) {
+                Some(end) if end <= buffer.len() => end,
+                _ => { return self.set_error(GL_INVALID_OPERATION); },
+            };
+            buffer[offset..end].to_vec()
+        } else {
+            if indices.is_null() { return self.set_error(GL_INVALID_OPERATION); }
+            unsafe { ::std::slice::from_raw_parts(indices as *const u8, len) }.to_vec()
+        };
+        let indices = bytes
+            .chunks(size)
+            .map(|b| if size == 1 { b[0] as usize } else { b[0] as usize | (b[1] as usize) << 8 } )
+            .collect();
+        self.draw(mode, indices)
+    }
+    fn finish(&mut self) { }
+    fn flush(&mut self) { }
+
+    fn gen_buffers(&mut self, n: GLsizei, buffers: *mut GLuint) {
+        if n < 0 { return self.set_error(GL_INVALID_VALUE); }
+        for i in 0..n as isize {
+            let name = self.gen_name();
+            unsafe { *buffers.offset(i) = name; }
+        }
+    }
+    fn gen_textures(&mut self, n: GLsizei, textures: *mut GLuint) {
+        if n < 0 { return self.set_error(GL_INVALID_VALUE); }
+        for i in 0..n as isize {
+            let name = self.gen_name();
+            unsafe { *textures.offset(i) = name; }
+        }
+    }
+    fn get_attrib_location(&mut self, program: GLuint, name: *const GLchar) -> GLint {
+        if name.is_null() { return -1; }
+        let name = unsafe { CStr::from_ptr(name) }.to_string_lossy();
+        match self.programs.get(&program) {
+            Some(p) if p.linked => {
+                p.attribute_named(&name).map(|l| l as GLint ).unwrap_or(-1)
+            },
+            Some(_) => {
+                self.set_error(GL_INVALID_OPERATION);
+                -1
+            },
+            None => {
+                self.set_error(GL_INVALID_VALUE);
+                -1
+            },
+        }
+    }
+    fn get_error(&mut self) -> GLenum {
+        let error = self.error;
+        self.error = GL_NO_ERROR;
+        error
+    }
+    fn get_integerv(&mut self, pname: GLenum, params: *mut GLint) {
+        let values = match pname {
+            GL_VIEWPORT => {
+                let v = self.viewport;
+                vec![v.x, v.y, v.width, v.height]
+            },
+            GL_SCISSOR_BOX => {
+                let s = self.scissor;
+                vec![s.x, s.y, s.width, s.height]
+            },
+            GL_MAX_VIEWPORT_DIMS => vec![MAX_TEXTURE_SIZE, MAX_TEXTURE_SIZE],
+            GL_MAX_TEXTURE_SIZE => vec![MAX_TEXTURE_SIZE],
+            GL_MAX_TEXTURE_IMAGE_UNITS |
+            GL_MAX_COMBINED_TEXTURE_IMAGE_UNITS => vec![MAX_TEXTURE_UNITS as GLint],
+            GL_MAX_VERTEX_ATTRIBS => vec![MAX_VERTEX_ATTRIBS as GLint],
+            GL_UNPACK_ALIGNMENT => vec![self.unpack_alignment as GLint],
+            GL_PACK_ALIGNMENT => vec![self.pack_alignment as GLint],
+            GL_ACTIVE_TEXTURE => vec![(GL_TEXTURE0 as usize + self.active_texture) as GLint],
+            GL_TEXTURE_BINDING_2D => vec![self.texture_units[self.active_texture] as GLint],
+            GL_CURRENT_PROGRAM => vec![self.current_program as GLint],
+            GL_ARRAY_BUFFER_BINDING => vec![self.array_buffer as GLint],
+            _ => { return self.set_error(GL_INVALID_ENUM); },
+        };
+        if params.is_null() { return; }
+        for (i, v) in values.into_iter().enumerate() {
+            unsafe { *params.offset(i as isize) = v; }
+        }
+    }
+
+    fn copy_log(log: &str, bufsize: GLsizei, length: *mut GLsizei, infolog: *mut GLchar) {
+        let n = if bufsize > 0 { ::std::cmp::min(log.len(), bufsize as usize - 1) } else { 0 };
+        if !infolog.is_null() && bufsize > 0 {
+            unsafe {
+                ptr::copy_nonoverlapping(log.as_ptr() as *const GLchar, infolog, n);
+                *infolog.offset(n as isize) = 0;
+            }
+        }
+        if !length.is_null() {
+            unsafe { *length = n as GLsizei; }
+        }
+    }
+    fn log_length(log: &str) -> GLint {
+        if log.is_empty() { 0 } else { log.len() as GLint + 1 }
+    }
+    fn get_programiv(&mut self, program: GLuint, pname: GLenum, params: *mut GLint) {
+        let v = match self.programs.get(&program) {
+            Some(p) => match pname {
+                GL_DELETE_STATUS => p.deleted as GLint,
+                GL_LINK_STATUS | GL_VALIDATE_STATUS => p.linked as GLint,
+                GL_INFO_LOG_LENGTH => GlContext::log_length(&p.log),
+                GL_ATTACHED_SHADERS => p.shaders.len() as GLint,
+                GL_ACTIVE_UNIFORMS => {
+                    p.uniforms.iter().filter(|u| u.element == 0 ).count() as GLint
+                },
+                GL_ACTIVE_ATTRIBUTES => p.attributes.len() as GLint,
+                _ => { return self.set_error(GL_INVALID_ENUM); },
+            },
+            None => { return self.set_error(GL_INVALID_VALUE); },
+        };
+        if !params.is_null() { unsafe { *params = v; } }
+    }
+    fn get_program_info_log(&mut self, program: GLuint, bufsize: GLsizei,
+                            length: *mut GLsizei, infolog: *mut GLchar) {
+        match self.programs.get(&program) {
+            Some(p) => GlContext::copy_log(&p.log, bufsize, length, infolog),
+            None => self.set_error(GL_INVALID_VALUE),
+        }
+    }
+    fn get_shaderiv(&mut self, shader: GLuint, pname: GLenum, params: *mut GLint) {
+        let v = match self.shaders.get(&shader) {
+            Some(s) => match pname {
+                GL_SHADER_TYPE => s.kind as GLint,
+                GL_DELETE_STATUS => 0,
+                GL_COMPILE_STATUS => s.compiled as GLint,
+                GL_INFO_LOG_LENGTH => GlContext::log_length(&s.log),
+                GL_SHADER_SOURCE_LENGTH => GlContext::log_length(&s.source),
+                _ => { return self.set_error(GL_INVALID_ENUM); },
+            },
+            None => { return self.set_error(GL_INVALID_VALUE); },
+        };
+        if !params.is_null() { unsafe { *params = v; } }
+    }
+    fn get_shader_info_log(&mut self, shader: GLuint, bufsize: GLsizei,
+                           length: *mut GLsizei, infolog: *mut GLchar) {
+        match self.shaders.get(&shader) {
+            Some(s) => GlContext::copy_log(&s.log, bufsize, length, infolog),
+            None => self.set_error(GL_INVALID_VALUE),
+        }
+    }
+    fn get_uniform_location(&mut self, program: GLuint, name: *const GLchar) -> GLint {
+        if name.is_null() { return -1; }
+        let name = unsafe { CStr::from_ptr(name) }.to_string_lossy();
+        match self.programs.get(&program) {
+            Some(p) if p.linked => {
+                p.uniform_location(&name).map(|l| l as GLint ).unwrap_or(-1)
+            },
+            Some(_) => {
+                self.set_error(GL_INVALID_OPERATION);
+                -1
+            },
+            None => {
+                self.set_error(GL_INVALID_VALUE);
+                -1
+            },
+        }
+    }
+    fn hint(&mut self, target: GLenum, mode: GLenum) {
+        match (target, mode) {
+            (GL_GENERATE_MIPMAP_HINT, GL_DONT_CARE) |
+            (GL_GENERATE_MIPMAP_HINT, GL_FASTEST) |
+            (GL_GENERATE_MIPMAP_HINT, GL_NICEST) => {},
+            _ => self.set_error(GL_INVALID_ENUM),
+        }
+    }
+    fn is_program(&mut self, program: GLuint) -> GLboolean {
+        if self.programs.contains_key(&program) { GL_TRUE } else { GL_FALSE }
+    }
+    fn is_shader(&mut self, shader: GLuint) -> GLboolean {
+        if self.shaders.contains_key(&shader) { GL_TRUE } else { GL_FALSE }
+    }
+    fn is_texture(&mut self, texture: GLuint) -> GLboolean {
+        if texture != 0 && self.textures.contains_key(&texture) { GL_TRUE } else { GL_FALSE }
+    }
+    fn link_program(&mut self, program: GLuint) {
+        let shaders = match self.programs.get(&program) {
+            Some(p) => p.shaders.clone(),
+            None => { return self.set_error(GL_INVALID_VALUE); },
+        };
+        let find = |kind: GLenum| {
+            shaders
+                .iter()
+                .filter_map(|s| self.shaders.get(s) )
+                .find(|s| s.kind == kind )
+                .cloned()
+        };
+        let vertex = find(GL_VERTEX_SHADER);
+        let fragment = find(GL_FRAGMENT_SHADER);
+
+        let p = self.programs.get_mut(&program).unwrap();
+        let linked = match (vertex, fragment) {
+            (Some(ref v), Some(ref f)) if v.compiled && f.compiled => p.link(v, f),
+            (Some(_), Some(_)) => Err("attached shaders aren't compiled".to_string()),
+            _ => Err("needs a vertex and a fragment shader".to_string()),
+        };
+        match linked {
+            Ok(()) => {
+                p.linked = true;
+                p.log.clear();
+            },
+            Err(err) => {
+                p.linked = false;
+                p.shading = None;
+                p.log = format!("ERROR: {}", err);
+            },
+        }
+    }
+    fn pixel_storei(&mut self, pname: GLenum, param: GLint) {
+        match param {
+            1 | 2 | 4 | 8 => {},
+            _ => { return self.set_error(GL_INVALID_VALUE); },
+        }
+        match pname {
+            GL_UNPACK_ALIGNMENT => self.unpack_alignment = param as usize,
+            GL_PACK_ALIGNMENT => self.pack_alignment = param as usize,
+            _ => self.set_error(GL_INVALID_ENUM),
+        }
+    }
+    fn read_pixels(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei,
+                   format: GLenum, type_: GLenum, pixels: *mut GLvoid) {
+        if width < 0 || height < 0 {
+            return self.set_error(GL_INVALID_VALUE);
+        }
+        if format != GL_RGBA || type_ != GL_UNSIGNED_BYTE {
+            return self.set_error(GL_INVALID_OPERATION);
+        }
+        if pixels.is_null() { return; }
+
+        let stride = raster::row_stride(width as usize, 4, self.pack_alignment);
+        let fb = &self.framebuffer;
+        // Outside the framebuffer is undefined; leave it alone.
+        let (x, y) = (x as i64, y as i64);
+        let cols = max(0, -x)..min(width as i64, fb.width as i64 - x);
+        let rows = max(0, -y)..min(height as i64, fb.height as i64 - y);
+        for row in rows {
+            for col in cols.clone() {
+                let px = fb.pixel((x + col) as usize, (y + row) as usize);
+                let offset = row as usize * stride + col as usize * 4;
+                unsafe {
+                    let dst = (pixels as *mut u8).offset(offset as isize);
+                    ptr::copy_nonoverlapping(px.as_ptr(), dst, 4);
+                }
+            }
+        }
+    }
+    fn scissor(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        if width < 0 || height < 0 { return self.set_error(GL_INVALID_VALUE); }
+        self.scissor = Rect::new(x, y, width, height);
+    }
+    fn shader_source(&mut self, shader: GLuint, count: GLsizei,
+                     str: *const *const GLchar, length: *const GLint) {
+        if count < 0 || str.is_null() { return self.set_error(GL_INVALID_VALUE); }
+
+        let mut source = String::new();
+        for i in 0..count as isize {
+            unsafe {
+                let s = *str.offset(i);
+                if s.is_null() { continue; }
+                let len = if length.is_null() { -1 } else { *length.offset(i) };
+                let bytes = if len < 0 {
+                    CStr::from_ptr(s).to_bytes()
+                } else {
+                    ::std::slice::from_raw_parts(s as *const u8, len as usize)
+                };
+                source.push_str(&String::from_utf8_lossy(bytes));
+            }
+        }
+        match self.shaders.get_mut(&shader) {
+            Some(s) => { s.source = source; },
+            None => self.set_error(GL_INVALID_VALUE),
+        }
+    }
+
+    fn bound_texture(&mut self, target: GLenum) -> Option<GLuint> {
+        if target != GL_TEXTURE_2D {
+            self.set_error(GL_INVALID_ENUM);
+            return None;
+        }
+        Some(self.texture_units[self.active_texture])
+    }
+    fn tex_image_2d(&mut self, target: GLenum, level: GLint, internalformat: GLint,
+                    width: GLsizei, height: GLsizei, border: GLint, format: GLenum,
+                    type_: GLenum, pixels: *const GLvoid) {
+        let texture = match self.bound_texture(target) {
+            Some(t) => t,
+            None => { return; },
+        };
+        if raster::format_components(format).is_none() || type_ != GL_UNSIGNED_BYTE {
+            return self.set_error(GL_INVALID_ENUM);
+        }
+        if level < 0 || width < 0 || height < 0 || border != 0 ||
+            width > MAX_TEXTURE_SIZE || height > MAX_TEXTURE_SIZE
+        {
+            return self.set_error(GL_INVALID_VALUE);
+        }
+        if internalformat as GLenum != format {
+            return self.set_error(GL_INVALID_OPERATION);
+        }
+
+        let alignment = self.unpack_alignment;
+        let t = self.textures.get_mut(&texture).unwrap();
+        if level > 0 {
+            t.mipmapped = true;
+            return;
+        }
+        unsafe {
+            t.image(width as usize, height as usize, format, alignment,
+                    pixels as *const u8);
+        }
+    }
+    fn tex_parameter(&mut self, target: GLenum, pname: GLenum, param: GLenum) {
+        let texture = match self.bound_texture(target) {
+            Some(t) => t,
+            None => { return; },
+        };
+        let valid = match pname {
+            GL_TEXTURE_MIN_FILTER => match param {
+                GL_NEAREST | GL_LINEAR | GL_NEAREST_MIPMAP_NEAREST |
+                GL_LINEAR_MIPMAP_NEAREST | GL_NEAREST_MIPMAP_LINEAR |
+                GL_LINEAR_MIPMAP_LINEAR => true,
+                _ => false,
+            },
+            GL_TEXTURE_MAG_FILTER => param == GL_NEAREST || param == GL_LINEAR,
+            GL_TEXTURE_WRAP_S | GL_TEXTURE_WRAP_T => match param {
+                GL_REPEAT | GL_CLAMP_TO_EDGE | GL_MIRRORED_REPEAT => true,
+                _ => false,
+            },
+            _ => false,
+        };
+        if !valid { return self.set_error(GL_INVALID_ENUM); }
+
+        let t = self.textures.get_mut(&texture).unwrap();
+        match pname {
+            GL_TEXTURE_MIN_FILTER => t.min_filter = param,
+            GL_TEXTURE_MAG_FILTER => t.mag_filter = param,
+            GL_TEXTURE_WRAP_S => t.wrap_s = param,
+            GL_TEXTURE_WRAP_T => t.wrap_t = param,
+            _ => unreachable!(),
+        }
+    }
+    fn tex_parameterf(&mut self, target: GLenum, pname: GLenum, param: GLfloat) {
+        self.tex_parameter(target, pname, param as GLenum)
+    }
+    fn tex_parameteri(&mut self, target: GLenum, pname: GLenum, param: GLint) {
+        self.tex_parameter(target, pname, param as GLenum)
+    }
+    fn tex_sub_image_2d(&mut self, target: GLenum, level: GLint, xoffset: GLint,
+                        yoffset: GLint, width: GLsizei, height: GLsizei,
+                        format: GLenum, type_: GLenum, pixels: *const GLvoid) {
+        let texture = match self.bound_texture(target) {
+            Some(t) => t,
+            None => { return; },
+        };
+        if raster::format_components(format).is_none() || type_ != GL_UNSIGNED_BYTE {
+            return self.set_error(GL_INVALID_ENUM);
+        }
+        if level > 0 { return; }
+
+        let (tw, th, tformat) = {
+            let t = &self.textures[&texture];
+            (t.width as GLint, t.height as GLint, t.format)
+        };
+        if level < 0 || xoffset < 0 || yoffset < 0 || width < 0 || height < 0 ||
+            xoffset + width > tw || yoffset + height > th
+        {
+            return self.set_error(GL_INVALID_VALUE);
+        }
+        if format != tformat {
+            return self.set_error(GL_INVALID_OPERATION);
+        }
+        if pixels.is_null() { return; }
+
+        let alignment = self.unpack_alignment;
+        let t = self.textures.get_mut(&texture).unwrap();
+        unsafe {
+            t.sub_image(xoffset as usize, yoffset as usize, width as usize,
+                        height as usize, alignment, pixels as *const u8);
+        }
+    }
+
+    fn set_uniform(&mut self, location: GLint, count: GLsizei, components: usize,
+                   integral: bool, values: &[f32]) {
+        if count < 0 { return self.set_error(GL_INVALID_VALUE); }
+        let current = self.current_program;
+        let error = match self.programs.get_mut(&current) {
+            Some(p) if p.linked => {
+                if location == -1 { return; }
+                let location = location as usize;
+                if location >= p.uniforms.len() {
+                    Some(GL_INVALID_OPERATION)
+                } else {
+                    let (name, ty, element) = {
+                        let u = &p.uniforms[location];
+                        (u.name.clone(), u.ty, u.element)
+                    };
+                    let type_ok = ty.components() == components &&
+                        (ty.integral() == integral || ty == GlslType::Bool) &&
+                        (ty != GlslType::Sampler2D || integral);
+                    let array = p.uniforms
+                        .get(location + 1)
+                        .map(|u| u.name == name )
+                        .unwrap_or(false) || element > 0;
+                    if !type_ok || (count > 1 && !array) {
+                        Some(GL_INVALID_OPERATION)
+                    } else {
+                        for i in 0..count as usize {
+                            match p.uniforms.get_mut(location + i) {
+                                Some(u) if u.name == name => {
+                                    let v = &values[i * components..(i + 1) * components];
+                                    u.value[..components].copy_from_slice(v);
+                                    u.set = true;
+                                },
+                                _ => break,
+                            }
+                        }
+                        None
+                    }
+                }
+            },
+            _ => Some(GL_INVALID_OPERATION),
+        };
+        if let Some(error) = error { self.set_error(error); }
+    }
+    fn uniform_fv(&mut self, location: GLint, count: GLsizei, components: usize,
+                  v: *const GLfloat) {
+        if count < 0 || v.is_null() { return self.set_error(GL_INVALID_VALUE); }
+        let values = unsafe {
+            ::std::slice::from_raw_parts(v, count as usize * components)
+        }.to_vec();
+        self.set_uniform(location, count, components, false, &values[..])
+    }
+    fn uniform1f(&mut self, location: GLint, x: GLfloat) {
+        self.set_uniform(location, 1, 1, false, &[x])
+    }
+    fn uniform1fv(&mut self, location: GLint, count: GLsizei, v: *const GLfloat) {
+        self.uniform_fv(location, count, 1, v)
+    }
+    fn uniform1i(&mut self, location: GLint, x: GLint) {
+        self.set_uniform(location, 1, 1, true, &[x as f32])
+    }
+    fn uniform1iv(&mut self, location: GLint, count: GLsizei, v: *const GLint) {
+        if count < 0 || v.is_null() { return self.set_error(GL_INVALID_VALUE); }
+        let values: Vec<f32> = unsafe { ::std::slice::from_raw_parts(v, count as usize) }
+            .iter()
+            .map(|&i| i as f32 )
+            .collect();
+        self.set_uniform(location, count, 1, true, &values[..])
+    }
+    fn uniform2f(&mut self, location: GLint, x: GLfloat, y: GLfloat) {
+        self.set_uniform(location, 1, 2, false, &[x, y])
+    }
+    fn uniform2fv(&mut self, location: GLint, count: GLsizei, v: *const GLfloat) {
+        self.uniform_fv(location, count, 2, v)
+    }
+    fn uniform3f(&mut self, location: GLint, x: GLfloat, y: GLfloat, z: GLfloat) {
+        self.set_uniform(location, 1, 3, false, &[x, y, z])
+    }
+    fn uniform3fv(&mut self, location: GLint, count: GLsizei, v: *const GLfloat) {
+        self.uniform_fv(location, count, 3, v)
+    }
+    fn uniform4f(&mut self, location: GLint, x: GLfloat, y: GLfloat, z: GLfloat,
+                 w: GLfloat) {
+        self.set_uniform(location, 1, 4, false, &[x, y, z, w])
+    }
+    fn uniform4fv(&mut self, location: GLint, count: GLsizei, v: *const GLfloat) {
+        self.uniform_fv(location, count, 4, v)
+    }
+    fn uniform_matrix(&mut self, location: GLint, count: GLsizei, n: usize,
+                      transpose: GLboolean, value: *const GLfloat) {
+        // GLES2 doesn't allow transposing.
+        if transpose != GL_FALSE { return self.set_error(GL_INVALID_VALUE); }
+        self.uniform_fv(location, count, n * n, value)
+    }
+    fn uniform_matrix2fv(&mut self, location: GLint, count: GLsizei,
+                         transpose: GLboolean, value: *const GLfloat) {
+        self.uniform_matrix(location, count, 2, transpose, value)
+    }
+    fn uniform_matrix3fv(&mut self, location: GLint, count: GLsizei,
+                         transpose: GLboolean, value: *const GLfloat) {
+        self.uniform_matrix(location, count, 3, transpose, value)
+    }
+    fn uniform_matrix4fv(&mut self, location: GLint, count: GLsizei,
+                         transpose: GLboolean, value: *const GLfloat) {
+        self.uniform_matrix(location, count, 4, transpose, value)
+    }
+    fn use_program(&mut self, program: GLuint) {
+        match self.programs.get(&program) {
+            Some(p) if !p.linked => { return self.set_error(GL_INVALID_OPERATION); },
+            None if program != 0 => { return self.set_error(GL_INVALID_VALUE); },
+            _ => {},
+        }
+
+        let previous = self.current_program;
+        self.current_program = program;
+        if previous != program &&
+            self.programs.get(&previous).map(|p| p.deleted ).unwrap_or(false)
+        {
+            self.programs.remove(&previous);
+        }
+    }
+    fn vertex_attrib_pointer(&mut self, indx: GLuint, size: GLint, type_: GLenum,
+                             normalized: GLboolean, stride: GLsizei,
+                             ptr: *const GLvoid) {
+        if indx as usize >= MAX_VERTEX_ATTRIBS || size < 1 || size > 4 || stride < 0 {
+            return self.set_error(GL_INVALID_VALUE);
+        }
+        if type_size(type_).is_none() {
+            return self.set_error(GL_INVALID_ENUM);
+        }
+        let a = &mut self.attribs[indx as usize];
+        a.size = size as usize;
+        a.ty = type_;
+        a.normalized = normalized != GL_FALSE;
+        a.stride = stride as usize;
+        a.pointer = ptr as *const u8;
+        a.buffer = self.array_buffer;
+    }
+    fn viewport(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        if width < 0 || height < 0 { return self.set_error(GL_INVALID_VALUE); }
+        self.viewport = Rect::new(x, y, width, height);
+    }
+}
+
+fn gl_context(context: PP_Resource) -> Code<Resource<Graphics3DState>> {
+    get_resource(context)
+}
+fn with_gl<F, R>(context: PP_Resource, f: F) -> R
+    where F: FnOnce(&mut MutexGuard<GlContext>) -> R,
+          R: Default,
+{
+    match gl_context(context) {
+        Ok(ref g3d) if g3d.is_lost() => Default::default(),
+        Ok(g3d) => {
+            let mut gl = g3d.gl();
+            f(&mut gl)
+        },
+        // Like the real library, calls on a bad or lost context are dropped.
+        Err(_) => Default::default(),
+    }
+}
+
+/// Defines both the `PPB_OpenGLES2` function (taking the context first) and
+/// the `gl*` export using the thread's current context.
+macro_rules! gl_fns {
+    ($($export:ident => $name:ident($($arg:ident: $ty:ty),*) $(-> $ret:ty)*;)*) => {
+        $(
+            extern "C" fn $name(context: PP_Resource $(, $arg: $ty)*) $(-> $ret)* {
+                with_gl(context, |gl| gl.$name($($arg),*) )
+            }
+            #[no_mangle] #[allow(non_snake_case)]
+            pub extern "C" fn $export($($arg: $ty),*) $(-> $ret)* {
+                $name(glGetCurrentContextPPAPI() $(, $arg)*)
+            }
+        )*
+    }
+}
+
+gl_fns! {
+    glActiveTexture => active_texture(texture: GLenum);
+    glAttachShader => attach_shader(program: GLuint, shader: GLuint);
+    glBindAttribLocation => bind_attrib_location(program: GLuint, index: GLuint, name: *const GLchar);
+    glBindBuffer => bind_buffer(target: GLenum, buffer: GLuint);
+    glBindTexture => bind_texture(target: GLenum, texture: GLuint);
+    glBlendFunc => blend_func(sfactor: GLenum, dfactor: GLenum);
+    glBlendFuncSeparate => blend_func_separate(src_rgb: GLenum, dst_rgb: GLenum, src_alpha: GLenum, dst_alpha: GLenum);
+    glBufferData => buffer_data(target: GLenum, size: GLsizeiptr, data: *const GLvoid, usage: GLenum);
+    glBufferSubData => buffer_sub_data(target: GLenum, offset: GLintptr, size: GLsizeiptr, data: *const GLvoid);
+    glClear => clear(mask: GLbitfield);
+    glClearColor => clear_color(red: GLclampf, green: GLclampf, blue: GLclampf, alpha: GLclampf);
+    glCompileShader => compile_shader(shader: GLuint);
+    glCreateProgram => create_program() -> GLuint;
+    glCreateShader => create_shader(type_: GLenum) -> GLuint;
+    glDeleteBuffers => delete_buffers(n: GLsizei, buffers: *const GLuint);
+    glDeleteProgram => delete_program(program: GLuint);
+    glDeleteShader => delete_shader(shader: GLuint);
+    glDeleteTextures => delete_textures(n: GLsizei, textures: *const GLuint);
+    glDetachShader => detach_shader(program: GLuint, shader: GLuint);
+    glDisable => disable(cap: GLenum);
+    glDisableVertexAttribArray => disable_vertex_attrib_array(index: GLuint);
+    glDrawArrays => draw_arrays(mode: GLenum, first: GLint, count: GLsizei);
+    glDrawElements => draw_elements(mode: GLenum, count: GLsizei, type_: GLenum, indices: *const GLvoid);
+    glEnable => enable(cap: GLenum);
+    glEnableVertexAttribArray => enable_vertex_attrib_array(index: GLuint);
+    glFinish => finish();
+    glFlush => flush();
+    glGenBuffers => gen_buffers(n: GLsizei, buffers: *mut GLuint);
+    glGenTextures => gen_textures(n: GLsizei, textures: *mut GLuint);
+    glGetAttribLocation => get_attrib_location(program: GLuint, name: *const GLchar) -> GLint;
+    glGetError => get_error() -> GLenum;
+    glGetIntegerv => get_integerv(pname: GLenum, params: *mut GLint);
+    glGetProgramiv => get_programiv(program: GLuint, pname: GLenum, params: *mut GLint);
+    glGetProgramInfoLog => get_program_info_log(program: GLuint, bufsize: GLsizei, length: *mut GLsizei, infolog: *mut GLchar);
+    glGetShaderiv => get_shaderiv(shader: GLuint, pname: GLenum, params: *mut GLint);
+    glGetShaderInfoLog => get_shader_info_log(shader: GLuint, bufsize: GLsizei, length: *mut GLsizei, infolog: *mut GLchar);
+    glGetUniformLocation => get_uniform_location(program: GLuint, name: *const GLchar) -> GLint;
+    glHint => hint(target: GLenum, mode: GLenum);
+    glIsEnabled => is_enabled(cap: GLenum) -> GLboolean;
+    glIsProgram => is_program(program: GLuint) -> GLboolean;
+    glIsShader => is_shader(shader: GLuint) -> GLboolean;
+    glIsTexture => is_texture(texture: GLuint) -> GLboolean;
+    glLinkProgram => link_program(program: GLuint);
+    glPixelStorei => pixel_storei(pname: GLenum, param: GLint);
+    glReadPixels => read_pixels(x: GLint, y: GLint, width: GLsizei, height: GLsizei, format: GLenum, type_: GLenum, pixels: *mut GLvoid);
+    glScissor => scissor(x: GLint, y: GLint, width: GLsizei, height: GLsizei);
+    glShaderSource => shader_source(shader: GLuint, count: GLsizei, str: *const *const GLchar, length: *const GLint);
+    glTexImage2D => tex_image_2d(target: GLenum, level: GLint, internalformat: GLint, width: GLsizei, height: GLsizei, border: GLint, format: GLenum, type_: GLenum, pixels: *const GLvoid);
+    glTexParameterf => tex_parameterf(target: GLenum, pname: GLenum, param: GLfloat);
+    glTexParameteri => tex_parameteri(target: GLenum, pname: GLenum, param: GLint);
+    glTexSubImage2D => tex_sub_image_2d(target: GLenum, level: GLint, xoffset: GLint, yoffset: GLint, width: GLsizei, height: GLsizei, format: GLenum, type_: GLenum, pixels: *const GLvoid);
+    glUniform1f => uniform1f(location: GLint, x: GLfloat);
+    glUniform1fv => uniform1fv(location: GLint, count: GLsizei, v: *const GLfloat);
+    glUniform1i => uniform1i(location: GLint, x: GLint);
+    glUniform1iv => uniform1iv(location: GLint, count: GLsizei, v: *const GLint);
+    glUniform2f => uniform2f(location: GLint, x: GLfloat, y: GLfloat);
+    glUniform2fv => uniform2fv(location: GLint, count: GLsizei, v: *const GLfloat);
+    glUniform3f => uniform3f(location: GLint, x: GLfloat, y: GLfloat, z: GLfloat);
+    glUniform3fv => uniform3fv(location: GLint, count: GLsizei, v: *const GLfloat);
+    glUniform4f => uniform4f(location: GLint, x: GLfloat, y: GLfloat, z: GLfloat, w: GLfloat);
+    glUniform4fv => uniform4fv(location: GLint, count: GLsizei, v: *const GLfloat);
+    glUniformMatrix2fv => uniform_matrix2fv(location: GLint, count: GLsizei, transpose: GLboolean, value: *const GLfloat);
+    glUniformMatrix3fv => uniform_matrix3fv(location: GLint, count: GLsizei, transpose: GLboolean, value: *const GLfloat);
+    glUniformMatrix4fv => uniform_matrix4fv(location: GLint, count: GLsizei, transpose: GLboolean, value: *const GLfloat);
+    glUseProgram => use_program(program: GLuint);
+    glVertexAttribPointer => vertex_attrib_pointer(indx: GLuint, size: GLint, type_: GLenum, normalized: GLboolean, stride: GLsizei, ptr: *const GLvoid);
+    glViewport => viewport(x: GLint, y: GLint, width: GLsizei, height: GLsizei);
+}
+
+extern "C" fn get_string(context: PP_Resource, name: GLenum) -> *const GLubyte {
+    let s: &'static [u8] = match name {
+        GL_VENDOR => b"vlc-nacl tests\0",
+        GL_RENDERER => b"ppapi mock software rasterizer\0",
+        GL_VERSION => b"OpenGL ES 2.0 (ppapi mock)\0",
+        GL_SHADING_LANGUAGE_VERSION => b"OpenGL ES GLSL ES 1.0 (ppapi mock)\0",
+        GL_EXTENSIONS => b"\0",
+        _ => {
+            with_gl(context, |gl| gl.set_error(GL_INVALID_ENUM) );
+            return ptr::null();
+        },
+    };
+    if gl_context(context).is_err() { return ptr::null(); }
+    s.as_ptr()
+}
+#[no_mangle] #[allow(non_snake_case)]
+pub extern "C" fn glGetString(name: GLenum) -> *const GLubyte {
+    get_string(glGetCurrentContextPPAPI(), name)
+}
+
+static OPENGLES2: PPB_OpenGLES2 = PPB_OpenGLES2 {
+    ActiveTexture: Some(active_texture),
+    AttachShader: Some(attach_shader),
+    BindAttribLocation: Some(bind_attrib_location),
+    BindBuffer: Some(bind_buffer),
+    BindFramebuffer: Some(ret_default_stub::<()>),
+    BindRenderbuffer: Some(ret_default_stub::<()>),
+    BindTexture: Some(bind_texture),
+    BlendColor: Some(ret_default_stub::<()>),
+    BlendEquation: Some(ret_default_stub::<()>),
+    BlendEquationSeparate: Some(ret_default_stub::<()>),
+    BlendFunc: Some(blend_func),
+    BlendFuncSeparate: Some(blend_func_separate),
+    BufferData: Some(buffer_data),
+    BufferSubData: Some(buffer_sub_data),
+    CheckFramebufferStatus: Some(ret_default_stub::<()>),
+    Clear: Some(clear),
+    ClearColor: Some(clear_color),
+    ClearDepthf: Some(ret_default_stub::<()>),
+    ClearStencil: Some(ret_default_stub::<()>),
+    ColorMask: Some(ret_default_stub::<()>),
+    CompileShader: Some(compile_shader),
+    CompressedTexImage2D: Some(ret_default_stub::<()>),
+    CompressedTexSubImage2D: Some(ret_default_stub::<()>),
+    CopyTexImage2D: Some(ret_default_stub::<()>),
+    CopyTexSubImage2D: Some(ret_default_stub::<()>),
+    CreateProgram: Some(create_program),
+    CreateShader: Some(create_shader),
+    CullFace: Some(ret_default_stub::<()>),
+    DeleteBuffers: Some(delete_buffers),
+    DeleteFramebuffers: Some(ret_default_stub::<()>),
+    DeleteProgram: Some(delete_program),
+    DeleteRenderbuffers: Some(ret_default_stub::<()>),
+    DeleteShader: Some(delete_shader),
+    DeleteTextures: Some(delete_textures),
+    DepthFunc: Some(ret_default_stub::<()>),
+    DepthMask: Some(ret_default_stub::<()>),
+    DepthRangef: Some(ret_default_stub::<()>),
+    DetachShader: Some(detach_shader),
+    Disable: Some(disable),
+    DisableVertexAttribArray: Some(disable_vertex_attrib_array),
+    DrawArrays: Some(draw_arrays),
+    DrawElements: Some(draw_elements),
+    Enable: Some(enable),
+    EnableVertexAttribArray: Some(enable_vertex_attrib_array),
+    Finish: Some(finish),
+    Flush: Some(flush),
+    FramebufferRenderbuffer: Some(ret_default_stub::<()>),
+    FramebufferTexture2D: Some(ret_default_stub::<()>),
+    FrontFace: Some(ret_default_stub::<()>),
+    GenBuffers: Some(gen_buffers),
+    GenerateMipmap: Some(ret_default_stub::<()>),
+    GenFramebuffers: Some(ret_default_stub::<()>),
+    GenRenderbuffers: Some(ret_default_stub::<()>),
+    GenTextures: Some(gen_textures),
+    GetActiveAttrib: Some(ret_default_stub::<()>),
+    GetActiveUniform: Some(ret_default_stub::<()>),
+    GetAttachedShaders: Some(ret_default_stub::<()>),
+    GetAttribLocation: Some(get_attrib_location),
+    GetBooleanv: Some(ret_default_stub::<()>),
+    GetBufferParameteriv: Some(ret_default_stub::<()>),
+    GetError: Some(get_error),
+    GetFloatv: Some(ret_default_stub::<()>),
+    GetFramebufferAttachmentParameteriv: Some(ret_default_stub::<()>),
+    GetIntegerv: Some(get_integerv),
+    GetProgramiv: Some(get_programiv),
+    GetProgramInfoLog: Some(get_program_info_log),
+    GetRenderbufferParameteriv: Some(ret_default_stub::<()>),
+    GetShaderiv: Some(get_shaderiv),
+    GetShaderInfoLog: Some(get_shader_info_log),
+    GetShaderPrecisionFormat: Some(ret_default_stub::<()>),
+    GetShaderSource: Some(ret_default_stub::<()>),
+    GetString: Some(get_string),
+    GetTexParameterfv: Some(ret_default_stub::<()>),
+    GetTexParameteriv: Some(ret_default_stub::<()>),
+    GetUniformfv: Some(ret_default_stub::<()>),
+    GetUniformiv: Some(ret_default_stub::<()>),
+    GetUniformLocation: Some(get_uniform_location),
+    GetVertexAttribfv: Some(ret_default_stub::<()>),
+    GetVertexAttribiv: Some(ret_default_stub::<()>),
+    GetVertexAttribPointerv: Some(ret_default_stub::<()>),
+    Hint: Some(hint),
+    IsBuffer: Some(ret_default_stub::<()>),
+    IsEnabled: Some(is_enabled),
+    IsFramebuffer: Some(ret_default_stub::<()>),
+    IsProgram: Some(is_program),
+    IsRenderbuffer: Some(ret_default_stub::<()>),
+    IsShader: Some(is_shader),
+    IsTexture: Some(is_texture),
+    LineWidth: Some(ret_default_stub::<()>),
+    LinkProgram: Some(link_program),
+    PixelStorei: Some(pixel_storei),
+    PolygonOffset: Some(ret_default_stub::<()>),
+    ReadPixels: Some(read_pixels),
+    ReleaseShaderCompiler: Some(ret_default_stub::<()>),
+    RenderbufferStorage: Some(ret_default_stub::<()>),
+    SampleCoverage: Some(ret_default_stub::<()>),
+    Scissor: Some(scissor),
+    ShaderBinary: Some(ret_default_stub::<()>),
+    ShaderSource: Some(shader_source),
+    StencilFunc: Some(ret_default_stub::<()>),
+    StencilFuncSeparate: Some(ret_default_stub::<()>),
+    StencilMask: Some(ret_default_stub::<()>),
+    StencilMaskSeparate: Some(ret_default_stub::<()>),
+    StencilOp: Some(ret_default_stub::<()>),
+    StencilOpSeparate: Some(ret_default_stub::<()>),
+    TexImage2D: Some(tex_image_2d),
+    TexParameterf: Some(tex_parameterf),
+    TexParameterfv: Some(ret_default_stub::<()>),
+    TexParameteri: Some(tex_parameteri),
+    TexParameteriv: Some(ret_default_stub::<()>),
+    TexSubImage2D: Some(tex_sub_image_2d),
+    Uniform1f: Some(uniform1f),
+    Uniform1fv: Some(uniform1fv),
+    Uniform1i: Some(uniform1i),
+    Uniform1iv: Some(uniform1iv),
+    Uniform2f: Some(uniform2f),
+    Uniform2fv: Some(uniform2fv),
+    Uniform2i: Some(ret_default_stub::<()>),
+    Uniform2iv: Some(ret_default_stub::<()>),
+    Uniform3f: Some(uniform3f),
+    Uniform3fv: Some(uniform3fv),
+    Uniform3i: Some(ret_default_stub::<()>),
+    Uniform3iv: Some(ret_default_stub::<()>),
+    Uniform4f: Some(uniform4f),
+    Uniform4fv: Some(uniform4fv),
+    Uniform4i: Some(ret_default_stub::<()>),
+    Uniform4iv: Some(ret_default_stub::<()>),
+    UniformMatrix2fv: Some(uniform_matrix2fv),
+    UniformMatrix3fv: Some(uniform_matrix3fv),
+    UniformMatrix4fv: Some(uniform_matrix4fv),
+    UseProgram: Some(use_program),
+    ValidateProgram: Some(ret_default_stub::<()>),
+    VertexAttrib1f: Some(ret_default_stub::<()>),
+    VertexAttrib1fv: Some(ret_default_stub::<()>),
+    VertexAttrib2f: Some(ret_default_stub::<()>),
+    VertexAttrib2fv: Some(ret_default_stub::<()>),
+    VertexAttrib3f: Some(ret_default_stub::<()>),
+    VertexAttrib3fv: Some(ret_default_stub::<()>),
+    VertexAttrib4f: Some(ret_default_stub::<()>),
+    VertexAttrib4fv: Some(ret_default_stub::<()>),
+    VertexAttribPointer: Some(vertex_attrib_pointer),
+    Viewport: Some(viewport),
+};
+
+pub static INTERFACES: Interfaces = &[
+    ("PPB_OpenGLES2;1.0", interface_ptr(&OPENGLES2)),
+];
+
+thread_local!(static CURRENT_CONTEXT: Cell<PP_Resource> = Cell::new(0));
+
+#[no_mangle] #[allow(non_snake_case)]
+pub extern "C" fn glInitializePPAPI(get_interface: GetInterface) -> PP_Bool {
+    let iface = get_interface(b"PPB_OpenGLES2;1.0\0".as_ptr() as *const c_char);
+    if iface.is_null() { PP_FALSE } else { PP_TRUE }
+}
+#[no_mangle] #[allow(non_snake_case)]
+pub extern "C" fn glTerminatePPAPI() -> PP_Bool {
+    PP_TRUE
+}
+/// Like `ppapi_gles2`, the current context is per thread.
+#[no_mangle] #[allow(non_snake_case)]
+pub extern "C" fn glSetCurrentContextPPAPI(context: PP_Resource) {
+    CURRENT_CONTEXT.with(|c| c.set(context) );
+}
+#[no_mangle] #[allow(non_snake_case)]
+pub extern "C" fn glGetCurrentContextPPAPI() -> PP_Resource {
+    CURRENT_CONTEXT.with(|c| c.get() )
+}
+#[no_mangle] #[allow(non_snake_case)]
+pub extern "C" fn glGetInterfacePPAPI() -> *const PPB_OpenGLES2 {
+    &OPENGLES2
+}
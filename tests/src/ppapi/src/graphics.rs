@@ -1,23 +1,588 @@
+use libc::{self, int32_t};
 
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::mpsc::{Sender, channel};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use super::ModuleInterface;
+use super::callback::{Callback, MessageLoop};
+use super::capture::Frame;
+use super::gles2::GlContext;
+use super::instance::Instance;
 use super::interface::*;
+use super::prelude::*;
+use super::resource::{ResState, ResourceRc, get_resource, get_resource_arc,
+                      take_resource_id};
 use super::sys::*;
+use super::timeline::TimelineEvent;
+use super::view::Visibility;
+
+pub type Graphics3D = Resource<Graphics3DState>;
+
+/// What `GetAttribMaxValue` reports for the surface size; Chrome's GPU process
+/// reports the driver's `GL_MAX_RENDERBUFFER_SIZE`.
+pub const MAX_SURFACE_SIZE: int32_t = 16384;
+
+pub fn attrib_max_value(attrib: int32_t) -> Code<int32_t> {
+    match attrib {
+        PP_GRAPHICS3DATTRIB_ALPHA_SIZE |
+        PP_GRAPHICS3DATTRIB_BLUE_SIZE |
+        PP_GRAPHICS3DATTRIB_GREEN_SIZE |
+        PP_GRAPHICS3DATTRIB_RED_SIZE |
+        PP_GRAPHICS3DATTRIB_STENCIL_SIZE => Ok(8),
+        PP_GRAPHICS3DATTRIB_DEPTH_SIZE => Ok(24),
+        PP_GRAPHICS3DATTRIB_SAMPLES => Ok(4),
+        PP_GRAPHICS3DATTRIB_SAMPLE_BUFFERS => Ok(1),
+        PP_GRAPHICS3DATTRIB_WIDTH |
+        PP_GRAPHICS3DATTRIB_HEIGHT => Ok(MAX_SURFACE_SIZE),
+        _ => Err(Error::BadArgument),
+    }
+}
+
+/// Collect the key/value pairs of a `PP_GRAPHICS3DATTRIB_NONE` terminated
+/// attribute list. A null list is empty.
+unsafe fn attrib_pairs(list: *const int32_t) -> Vec<(isize, int32_t)> {
+    let mut pairs = Vec::new();
+    if list.is_null() { return pairs; }
+
+    let mut idx = 0isize;
+    loop {
+        let attrib = *list.offset(idx);
+        if attrib == PP_GRAPHICS3DATTRIB_NONE { break; }
+        pairs.push((idx, attrib));
+        idx += 2;
+    }
+    pairs
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Graphics3DAttribs {
+    pub alpha_size: int32_t,
+    pub blue_size: int32_t,
+    pub green_size: int32_t,
+    pub red_size: int32_t,
+    pub depth_size: int32_t,
+    pub stencil_size: int32_t,
+    pub samples: int32_t,
+    pub sample_buffers: int32_t,
+    pub width: int32_t,
+    pub height: int32_t,
+    pub swap_behavior: int32_t,
+    pub gpu_preference: int32_t,
+}
+impl Default for Graphics3DAttribs {
+    fn default() -> Graphics3DAttribs {
+        Graphics3DAttribs {
+            alpha_size: 8,
+            blue_size: 8,
+            green_size: 8,
+            red_size: 8,
+            depth_size: 0,
+            stencil_size: 0,
+            samples: 0,
+            sample_buffers: 0,
+            width: 0,
+            height: 0,
+            swap_behavior: PP_GRAPHICS3DATTRIB_BUFFER_DESTROYED,
+            gpu_preference: PP_GRAPHICS3DATTRIB_GPU_PREFERENCE_PERFORMANCE,
+        }
+    }
+}
+impl Graphics3DAttribs {
+    /// Parse the list given to `Create`. Unknown attributes and out of range
+    /// values are errors, like in Chrome.
+    pub unsafe fn parse(list: *const int32_t) -> Code<Graphics3DAttribs> {
+        let mut attribs: Graphics3DAttribs = Default::default();
+        for (idx, attrib) in attrib_pairs(list) {
+            try!(attribs.set(attrib, *list.offset(idx + 1)));
+        }
+        Ok(attribs)
+    }
+
+    pub fn get(&self, attrib: int32_t) -> Code<int32_t> {
+        let v = match attrib {
+            PP_GRAPHICS3DATTRIB_ALPHA_SIZE => self.alpha_size,
+            PP_GRAPHICS3DATTRIB_BLUE_SIZE => self.blue_size,
+            PP_GRAPHICS3DATTRIB_GREEN_SIZE => self.green_size,
+            PP_GRAPHICS3DATTRIB_RED_SIZE => self.red_size,
+            PP_GRAPHICS3DATTRIB_DEPTH_SIZE => self.depth_size,
+            PP_GRAPHICS3DATTRIB_STENCIL_SIZE => self.stencil_size,
+            PP_GRAPHICS3DATTRIB_SAMPLES => self.samples,
+            PP_GRAPHICS3DATTRIB_SAMPLE_BUFFERS => self.sample_buffers,
+            PP_GRAPHICS3DATTRIB_WIDTH => self.width,
+            PP_GRAPHICS3DATTRIB_HEIGHT => self.height,
+            PP_GRAPHICS3DATTRIB_SWAP_BEHAVIOR => self.swap_behavior,
+            PP_GRAPHICS3DATTRIB_GPU_PREFERENCE => self.gpu_preference,
+            _ => { return Err(Error::BadArgument); },
+        };
+        Ok(v)
+    }
+    pub fn set(&mut self, attrib: int32_t, value: int32_t) -> Code<()> {
+        match attrib {
+            PP_GRAPHICS3DATTRIB_SWAP_BEHAVIOR => {
+                match value {
+                    PP_GRAPHICS3DATTRIB_BUFFER_PRESERVED |
+                    PP_GRAPHICS3DATTRIB_BUFFER_DESTROYED => {},
+                    _ => { return Err(Error::BadArgument); },
+                }
+                self.swap_behavior = value;
+                return Ok(());
+            },
+            PP_GRAPHICS3DATTRIB_GPU_PREFERENCE => {
+                match value {
+                    PP_GRAPHICS3DATTRIB_GPU_PREFERENCE_LOW_POWER |
+                    PP_GRAPHICS3DATTRIB_GPU_PREFERENCE_PERFORMANCE => {},
+                    _ => { return Err(Error::BadArgument); },
+                }
+                self.gpu_preference = value;
+                return Ok(());
+            },
+            _ => {},
+        }
+
+        let max = try!(attrib_max_value(attrib));
+        if value < 0 || value > max {
+            return Err(Error::BadArgument);
+        }
+        let field = match attrib {
+            PP_GRAPHICS3DATTRIB_ALPHA_SIZE => &mut self.alpha_size,
+            PP_GRAPHICS3DATTRIB_BLUE_SIZE => &mut self.blue_size,
+            PP_GRAPHICS3DATTRIB_GREEN_SIZE => &mut self.green_size,
+            PP_GRAPHICS3DATTRIB_RED_SIZE => &mut self.red_size,
+            PP_GRAPHICS3DATTRIB_DEPTH_SIZE => &mut self.depth_size,
+            PP_GRAPHICS3DATTRIB_STENCIL_SIZE => &mut self.stencil_size,
+            PP_GRAPHICS3DATTRIB_SAMPLES => &mut self.samples,
+            PP_GRAPHICS3DATTRIB_SAMPLE_BUFFERS => &mut self.sample_buffers,
+            PP_GRAPHICS3DATTRIB_WIDTH => &mut self.width,
+            PP_GRAPHICS3DATTRIB_HEIGHT => &mut self.height,
+            _ => unreachable!(),
+        };
+        *field = value;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Graphics3DState {
+    id: PP_Resource,
+    instance: Instance,
+
+    share_context: Option<Graphics3D>,
+    attribs: Mutex<Graphics3DAttribs>,
+
+    /// Set from `SwapBuffers` until its callback has been run.
+    swap_pending: AtomicBool,
+    frames: AtomicUsize,
+    /// Set by `lose`; never cleared, the module has to make a new context.
+    lost: AtomicBool,
+
+    gl: Mutex<GlContext>,
+}
+
+impl Graphics3DState {
+    pub fn create(i: &Instance, share_context: Option<Graphics3D>,
+                  attribs: Graphics3DAttribs) -> Code<Graphics3D> {
+        if let Some(ref share) = share_context {
+            if share.instance() != *i {
+                return Err(Error::BadArgument);
+            }
+        }
+
+        let gl = GlContext::new(attribs.width as usize, attribs.height as usize);
+        let inner = Graphics3DState {
+            id: take_resource_id(),
+            instance: i.clone(),
+            share_context: share_context,
+            attribs: Mutex::new(attribs),
+            swap_pending: AtomicBool::new(false),
+            frames: AtomicUsize::new(0),
+            lost: AtomicBool::new(false),
+            gl: Mutex::new(gl),
+        };
+        Ok(Resource::create(i, Arc::new(inner)))
+    }
+
+    pub fn share_context(&self) -> Option<&Graphics3D> { self.share_context.as_ref() }
+    pub fn attribs(&self) -> Graphics3DAttribs { *self.attribs.lock().unwrap() }
+    /// The surface size, as last set by `Create` or `ResizeBuffers`.
+    pub fn size(&self) -> (int32_t, int32_t) {
+        let attribs = self.attribs();
+        (attribs.width, attribs.height)
+    }
+    /// The number of frames presented so far.
+    pub fn frames(&self) -> usize { self.frames.load(Ordering::SeqCst) }
+    pub fn swap_pending(&self) -> bool { self.swap_pending.load(Ordering::SeqCst) }
+    pub fn is_lost(&self) -> bool { self.lost.load(Ordering::SeqCst) }
+    /// Simulate a GPU process crash or reset. Returns false if the context was
+    /// already lost.
+    pub fn lose(&self) -> bool { !self.lost.swap(true, Ordering::SeqCst) }
+    /// The context's `PPB_OpenGLES2` state.
+    pub fn gl(&self) -> MutexGuard<GlContext> { self.gl.lock().unwrap() }
+
+    /// Only `PP_GRAPHICS3DATTRIB_SWAP_BEHAVIOR` may be changed after creation.
+    /// Nothing is changed unless the whole list is valid.
+    pub unsafe fn set_attribs(&self, list: *const int32_t) -> Code<()> {
+        let mut attribs = try!(self.attribs.lock());
+        let mut new = *attribs;
+        for (idx, attrib) in attrib_pairs(list) {
+            if attrib != PP_GRAPHICS3DATTRIB_SWAP_BEHAVIOR {
+                return Err(Error::BadArgument);
+            }
+            try!(new.set(attrib, *list.offset(idx + 1)));
+        }
+        *attribs = new;
+        Ok(())
+    }
+    pub unsafe fn get_attribs(&self, list: *mut int32_t) -> Code<()> {
+        let attribs = self.attribs();
+        let pairs = attrib_pairs(list as *const _);
+        // Check everything first so the list isn't half filled on error.
+        for &(_, attrib) in pairs.iter() {
+            try!(attribs.get(attrib));
+        }
+        for (idx, attrib) in pairs {
+            *list.offset(idx + 1) = attribs.get(attrib).unwrap();
+        }
+        Ok(())
+    }
+
+    pub fn resize_buffers(&self, width: int32_t, height: int32_t) -> Code<()> {
+        if width < 0 || height < 0 ||
+            width > MAX_SURFACE_SIZE || height > MAX_SURFACE_SIZE
+        {
+            return Err(Error::BadArgument);
+        }
+
+        let mut attribs = try!(self.attribs.lock());
+        attribs.width = width;
+        attribs.height = height;
+        self.gl().resize(width as usize, height as usize);
+        Ok(())
+    }
+
+    fn present(&self) {
+        let index = self.frames.fetch_add(1, Ordering::SeqCst);
+        self.instance.capture_frame(|sink| {
+            let ts = super::global_module().seconds_elapsed();
+            Frame::capture(self.id, index, ts, self.gl().framebuffer(), sink)
+        });
+        self.instance.record_timeline_event(TimelineEvent::SwapBuffers {
+            context: self.id,
+        });
+    }
+
+    /// Like Chrome, only one swap can be in flight. The instance decides when
+    /// the swap completes (see `SwapThrottling`); non-blocking callbacks are
+    /// then run on the caller's message loop. Optional callbacks are skipped
+    /// if the instance completes the swap right away.
+    pub fn swap_buffers(this: &Arc<Graphics3DState>, callback: Callback) -> Code<()> {
+        if this.is_lost() {
+            return Err(Error::ContextLost);
+        }
+        if this.swap_pending.swap(true, Ordering::SeqCst) {
+            return Err(Error::InProgress);
+        }
+
+        this.present();
+
+        match callback {
+            Callback::Sync => {
+                let (tx, rx) = channel();
+                this.instance.swap_ack(SwapAck {
+                    context: this.clone(),
+                    to: AckTo::Blocked(tx),
+                });
+                // Errors if the instance went away with the ack.
+                let result = rx.recv();
+                this.swap_pending.store(false, Ordering::SeqCst);
+                match result {
+                    Ok(PP_ERROR_CONTEXT_LOST) => Err(Error::ContextLost),
+                    _ => Ok(()),
+                }
+            },
+            Callback::Async {
+                f, user, message_loop, optional: false,
+            } => {
+                this.instance.swap_ack(SwapAck {
+                    context: this.clone(),
+                    to: AckTo::Loop {
+                        message_loop: message_loop,
+                        f: f,
+                        user: user,
+                    },
+                });
+                Err(Error::CompletionPending)
+            },
+            Callback::Async {
+                f, user, message_loop, optional: true,
+            } => {
+                let (tx, rx) = channel();
+                this.instance.swap_ack(SwapAck {
+                    context: this.clone(),
+                    to: AckTo::Optional {
+                        completed_now: tx,
+                        message_loop: message_loop,
+                        f: f,
+                        user: user,
+                    },
+                });
+                match rx.recv() {
+                    Ok(None) => Err(Error::CompletionPending),
+                    result => {
+                        this.swap_pending.store(false, Ordering::SeqCst);
+                        match result {
+                            Ok(Some(PP_ERROR_CONTEXT_LOST)) => Err(Error::ContextLost),
+                            _ => Ok(()),
+                        }
+                    },
+                }
+            },
+        }
+    }
+}
+impl ResourceState for Graphics3DState {
+    fn into_resstate(this: Arc<Self>) -> ResState {
+        ResState::Graphics3D(this)
+    }
+    fn state_from_resstate(rs: &Arc<ResourceRc>) -> Code<&Arc<Self>> {
+        match rs.state() {
+            &ResState::Graphics3D(ref g) => Ok(g),
+            _ => Err(Error::BadArgument),
+        }
+    }
+    fn resource_id(this: &Arc<Self>) -> PP_Resource { this.id }
+    fn resource_instance(this: &Arc<Self>) -> Instance { this.instance.clone() }
+}
+
+/// How long swap completions are put off while the instance can't be seen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Throttle {
+    /// Complete swaps right away.
+    Unthrottled,
+    /// Complete at most one swap per interval.
+    Interval(Duration),
+    /// Hold completions until the instance is visible again.
+    Hold,
+}
+
+/// Per instance. The default is Chrome's: a hidden page isn't composited, so
+/// swaps aren't acked until it's shown again, but an element that is merely
+/// scrolled out of view is still drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SwapThrottling {
+    pub offscreen: Throttle,
+    pub page_hidden: Throttle,
+}
+impl Default for SwapThrottling {
+    fn default() -> SwapThrottling {
+        SwapThrottling {
+            offscreen: Throttle::Unthrottled,
+            page_hidden: Throttle::Hold,
+        }
+    }
+}
+impl SwapThrottling {
+    pub fn throttle(&self, visibility: Visibility) -> Throttle {
+        match visibility {
+            Visibility::Visible => Throttle::Unthrottled,
+            Visibility::Offscreen => self.offscreen,
+            Visibility::PageHidden => self.page_hidden,
+        }
+    }
+}
+
+enum AckTo {
+    Loop {
+        message_loop: MessageLoop,
+        f: PP_CompletionCallback_Func,
+        user: *mut libc::c_void,
+    },
+    /// A blocking `SwapBuffers`, waiting for the result.
+    Blocked(Sender<int32_t>),
+    /// A `SwapBuffers` with an optional callback, waiting to hear whether
+    /// the swap completed right away, and with what. If not, it becomes
+    /// `Loop`.
+    Optional {
+        completed_now: Sender<Option<int32_t>>,
+        message_loop: MessageLoop,
+        f: PP_CompletionCallback_Func,
+        user: *mut libc::c_void,
+    },
+}
+
+/// A presented swap waiting for the instance to complete it.
+pub struct SwapAck {
+    context: Arc<Graphics3DState>,
+    to: AckTo,
+}
+unsafe impl Send for SwapAck { }
+impl SwapAck {
+    pub fn context(&self) -> PP_Resource { self.context.id }
+    pub fn context_lost(&self) -> bool { self.context.is_lost() }
+    /// The swap won't complete right away; optional callbacks will be called
+    /// when it does.
+    pub fn defer(self) -> SwapAck {
+        let to = match self.to {
+            AckTo::Optional { completed_now, message_loop, f, user, } => {
+                let _ = completed_now.send(None);
+                AckTo::Loop {
+                    message_loop: message_loop,
+                    f: f,
+                    user: user,
+                }
+            },
+            to => to,
+        };
+        SwapAck {
+            context: self.context,
+            to: to,
+        }
+    }
+    /// With `PP_ERROR_CONTEXT_LOST` if the context was lost since the swap.
+    pub fn complete(self) { self.finish(PP_OK) }
+    /// The instance went away first. Blocking swaps return as usual.
+    pub fn abort(self) { self.finish(PP_ERROR_ABORTED) }
+    fn finish(self, result: int32_t) {
+        let result = if result == PP_OK && self.context.is_lost() {
+            PP_ERROR_CONTEXT_LOST
+        } else {
+            result
+        };
+        match self.to {
+            AckTo::Loop { message_loop, f, user, } => {
+                let pending = Box::new(PendingSwap {
+                    context: self.context.clone(),
+                    f: f,
+                    user: user,
+                });
+                let pending = Box::into_raw(pending);
+                if let Err(_) = message_loop.post_result(swap_complete, pending as *mut _,
+                                                         result) {
+                    drop(unsafe { Box::from_raw(pending) });
+                    self.context.swap_pending.store(false, Ordering::SeqCst);
+                }
+            },
+            AckTo::Blocked(tx) => {
+                let _ = tx.send(result);
+            },
+            AckTo::Optional { completed_now, .. } => {
+                let _ = completed_now.send(Some(result));
+            },
+        }
+    }
+}
+impl ::std::fmt::Debug for SwapAck {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "SwapAck({})", self.context.id)
+    }
+}
+
+struct PendingSwap {
+    context: Arc<Graphics3DState>,
+    f: PP_CompletionCallback_Func,
+    user: *mut libc::c_void,
+}
+extern "C" fn swap_complete(user: *mut libc::c_void, result: int32_t) {
+    let pending: Box<PendingSwap> = unsafe { Box::from_raw(user as *mut PendingSwap) };
+    pending.context.swap_pending.store(false, Ordering::SeqCst);
+    (pending.f)(pending.user, result);
+}
 
 static GRAPHICS_3D: PPB_Graphics3D_1_0 = PPB_Graphics3D_1_0 {
-    GetAttribMaxValue: Some(ret_default_stub::<_>),
-    Create: Some(ret_default_stub::<_>),
-    IsGraphics3D: Some(ret_default_stub::<_>),
-    GetAttribs: Some(ret_not_supported_stub),
-    SetAttribs: Some(ret_not_supported_stub),
-    GetError: Some(ret_not_supported_stub),
-    ResizeBuffers: Some(ret_not_supported_stub),
-    SwapBuffers: Some(ret_not_supported_stub),
+    GetAttribMaxValue: Some(get_attrib_max_value),
+    Create: Some(create),
+    IsGraphics3D: Some(is_graphics_3d),
+    GetAttribs: Some(get_attribs),
+    SetAttribs: Some(set_attribs),
+    GetError: Some(get_error),
+    ResizeBuffers: Some(resize_buffers),
+    SwapBuffers: Some(swap_buffers),
 };
 
 pub static INTERFACES: Interfaces = &[
     ("PPB_Graphics3D;1.0", interface_ptr(&GRAPHICS_3D)),
 ];
 
-#[no_mangle] #[allow(non_snake_case)]
-pub extern "C" fn glInitializePPAPI(_: GetInterface) -> PP_Bool {
-    PP_TRUE
+fn get(context: PP_Resource) -> Code<Graphics3D> { get_resource(context) }
+
+extern "C" fn get_attrib_max_value(instance: PP_Resource, attribute: int32_t,
+                                   value: *mut int32_t) -> int32_t {
+    if value.is_null() { return PP_ERROR_BADARGUMENT; }
+    if ModuleInterface::get_instance_interface(instance).is_err() {
+        return PP_ERROR_BADRESOURCE;
+    }
+
+    attrib_max_value(attribute)
+        .map(|max| unsafe { *value = max; } )
+        .into_code()
+}
+extern "C" fn create(instance: PP_Instance, share_context: PP_Resource,
+                     attrib_list: *const int32_t) -> PP_Resource {
+    let i = ModuleInterface::get_instance_interface(instance);
+    if i.is_err() { return 0; }
+    let i = i.unwrap();
+
+    let share_context = if share_context != 0 {
+        match get(share_context) {
+            Ok(share) => Some(share),
+            Err(_) => { return 0; },
+        }
+    } else {
+        None
+    };
+
+    unsafe { Graphics3DAttribs::parse(attrib_list) }
+        .and_then(|attribs| Graphics3DState::create(&i, share_context, attribs) )
+        .map(|context| context.move_into_id() )
+        .unwrap_or(0)
+}
+extern "C" fn is_graphics_3d(res: PP_Resource) -> PP_Bool {
+    match unsafe { get_resource_arc(res) } {
+        Some(rc) => match rc.state() {
+            &ResState::Graphics3D(_) => PP_TRUE,
+            _ => PP_FALSE,
+        },
+        None => PP_FALSE,
+    }
+}
+extern "C" fn get_attribs(context: PP_Resource, attrib_list: *mut int32_t) -> int32_t {
+    get(context)
+        .map_err(|_| Error::BadResource )
+        .and_then(|context| unsafe { context.get_attribs(attrib_list) } )
+        .into_code()
+}
+extern "C" fn set_attribs(context: PP_Resource, attrib_list: *const int32_t) -> int32_t {
+    get(context)
+        .map_err(|_| Error::BadResource )
+        .and_then(|context| unsafe { context.set_attribs(attrib_list) } )
+        .into_code()
+}
+extern "C" fn get_error(context: PP_Resource) -> int32_t {
+    get(context)
+        .map_err(|_| Error::BadResource )
+        .and_then(|context| {
+            // Modules poll this to know when to recreate the context.
+            if context.is_lost() { Err(Error::ContextLost) }
+            else { Ok(()) }
+        })
+        .into_code()
+}
+extern "C" fn resize_buffers(context: PP_Resource, width: int32_t,
+                             height: int32_t) -> int32_t {
+    get(context)
+        .map_err(|_| Error::BadResource )
+        .and_then(|context| context.resize_buffers(width, height) )
+        .into_code()
+}
+extern "C" fn swap_buffers(context: PP_Resource,
+                           callback: PP_CompletionCallback) -> int32_t {
+    get(context)
+        .map_err(|_| Error::BadResource )
+        .and_then(|context| {
+            let state = try!(Graphics3DState::state_from_resstate(context.get_rc())).clone();
+            let callback = try!(Callback::from_ffi(callback, &state.instance));
+            Graphics3DState::swap_buffers(&state, callback)
+        })
+        .into_code()
 }
@@ -0,0 +1,146 @@
+//! `PPB_ImageData`, just enough for modules to build custom cursors. The
+//! pixels live in memory owned by the resource; `Map` hands out a pointer to
+//! them which stays valid until the resource is released.
+
+use libc;
+use std::sync::{Arc, Mutex};
+
+use super::ModuleInterface;
+use super::instance::Instance;
+use super::interface::*;
+use super::prelude::*;
+use super::resource::{ResState, ResourceRc, get_resource, get_resource_arc,
+                      take_resource_id};
+use super::sys::{self, PP_Bool, PP_ImageDataDesc, PP_ImageDataFormat, PP_Size,
+                 PP_TRUE, PP_FALSE};
+
+pub type ImageData = Resource<ImageDataState>;
+
+/// What Chrome uses on little-endian platforms.
+pub const NATIVE_FORMAT: PP_ImageDataFormat = sys::PP_IMAGEDATAFORMAT_BGRA_PREMUL;
+
+#[derive(Debug)]
+pub struct ImageDataState {
+    id: PP_Resource,
+    instance: Instance,
+
+    format: PP_ImageDataFormat,
+    size: PP_Size,
+    pixels: Mutex<Vec<u8>>,
+}
+impl ImageDataState {
+    pub fn create(i: &Instance, format: PP_ImageDataFormat, size: PP_Size) -> Code<ImageData> {
+        match format {
+            sys::PP_IMAGEDATAFORMAT_BGRA_PREMUL |
+            sys::PP_IMAGEDATAFORMAT_RGBA_PREMUL => {},
+            _ => { return Err(Error::BadArgument); },
+        }
+        if size.width <= 0 || size.height <= 0 {
+            return Err(Error::BadArgument);
+        }
+
+        let len = size.width as usize * size.height as usize * 4;
+        let inner = ImageDataState {
+            id: take_resource_id(),
+            instance: i.clone(),
+            format: format,
+            size: size,
+            pixels: Mutex::new(vec![0; len]),
+        };
+        Ok(Resource::create(i, Arc::new(inner)))
+    }
+
+    pub fn desc(&self) -> PP_ImageDataDesc {
+        PP_ImageDataDesc {
+            format: self.format,
+            size: self.size,
+            stride: self.size.width * 4,
+        }
+    }
+    pub fn format(&self) -> PP_ImageDataFormat { self.format }
+    pub fn size(&self) -> PP_Size { self.size }
+    /// A copy of the current pixels.
+    pub fn pixels(&self) -> Vec<u8> { self.pixels.lock().unwrap().clone() }
+
+    /// The buffer is never resized, so the pointer outlives the lock.
+    fn map(&self) -> *mut u8 { self.pixels.lock().unwrap().as_mut_ptr() }
+}
+impl ResourceState for ImageDataState {
+    fn into_resstate(this: Arc<Self>) -> ResState {
+        ResState::ImageData(this)
+    }
+    fn state_from_resstate(rs: &Arc<ResourceRc>) -> Code<&Arc<Self>> {
+        match rs.state() {
+            &ResState::ImageData(ref d) => Ok(d),
+            _ => Err(Error::BadArgument),
+        }
+    }
+    fn resource_id(this: &Arc<Self>) -> PP_Resource { this.id }
+    fn resource_instance(this: &Arc<Self>) -> Instance { this.instance.clone() }
+}
+
+extern "C" fn get_native_image_data_format() -> PP_ImageDataFormat { NATIVE_FORMAT }
+extern "C" fn is_image_data_format_supported(format: PP_ImageDataFormat) -> PP_Bool {
+    match format {
+        sys::PP_IMAGEDATAFORMAT_BGRA_PREMUL |
+        sys::PP_IMAGEDATAFORMAT_RGBA_PREMUL => PP_TRUE,
+        _ => PP_FALSE,
+    }
+}
+extern "C" fn create(instance: PP_Instance, format: PP_ImageDataFormat,
+                     size: *const PP_Size, _init_to_zero: PP_Bool) -> PP_Resource {
+    // Always zeroed.
+    let size = match unsafe { size.as_ref() } {
+        Some(&size) => size,
+        None => { return 0; },
+    };
+    ModuleInterface::get_instance_interface(instance)
+        .and_then(|i| ImageDataState::create(&i, format, size) )
+        .map(|image| image.move_into_id() )
+        .unwrap_or(0)
+}
+extern "C" fn is_image_data(res: PP_Resource) -> PP_Bool {
+    match unsafe { get_resource_arc(res) } {
+        Some(rc) => match rc.state() {
+            &ResState::ImageData(_) => PP_TRUE,
+            _ => PP_FALSE,
+        },
+        None => PP_FALSE,
+    }
+}
+extern "C" fn describe(image: PP_Resource, desc: *mut PP_ImageDataDesc) -> PP_Bool {
+    let desc = match unsafe { desc.as_mut() } {
+        Some(desc) => desc,
+        None => { return PP_FALSE; },
+    };
+    match get_resource::<ImageDataState>(image) {
+        Ok(image) => {
+            *desc = image.desc();
+            PP_TRUE
+        },
+        Err(_) => {
+            *desc = Default::default();
+            PP_FALSE
+        },
+    }
+}
+extern "C" fn map(image: PP_Resource) -> *mut libc::c_void {
+    get_resource::<ImageDataState>(image)
+        .map(|image| image.map() as *mut libc::c_void )
+        .unwrap_or(0 as *mut _)
+}
+extern "C" fn unmap(_image: PP_Resource) { }
+
+static IMAGE_DATA_INTERFACE: sys::PPB_ImageData_1_0 = sys::PPB_ImageData_1_0 {
+    GetNativeImageDataFormat: Some(get_native_image_data_format),
+    IsImageDataFormatSupported: Some(is_image_data_format_supported),
+    Create: Some(create),
+    IsImageData: Some(is_image_data),
+    Describe: Some(describe),
+    Map: Some(map),
+    Unmap: Some(unmap),
+};
+
+pub static INTERFACES: Interfaces = &[
+    ("PPB_ImageData;1.0", interface_ptr(&IMAGE_DATA_INTERFACE)),
+];
@@ -0,0 +1,463 @@
+//! `PPB_InputEvent` and the mouse, wheel and keyboard event resources. Tests
+//! inject `Event`s with `Instance::send_input_event`, which are passed to
+//! `PPP_InputEvent::HandleInputEvent` like Chrome would.
+
+use libc::{int32_t, uint32_t};
+use std::sync::Arc;
+
+use super::ModuleInterface;
+use super::instance::Instance;
+use super::interface::*;
+use super::prelude::*;
+use super::resource::{ResState, ResourceRc, get_resource, get_resource_arc,
+                      take_resource_id};
+use super::sys::{self, PP_Bool, PP_FloatPoint, PP_InputEvent_Class,
+                 PP_InputEvent_MouseButton, PP_InputEvent_Type, PP_Point,
+                 PP_TimeTicks, PP_Var, PP_TRUE, PP_FALSE};
+
+pub type InputEvent = Resource<InputEventState>;
+
+/// Every class `RequestInputEvents` accepts; touch and IME events aren't
+/// supported.
+pub const SUPPORTED_CLASSES: PP_InputEvent_Class =
+    sys::PP_INPUTEVENT_CLASS_MOUSE |
+    sys::PP_INPUTEVENT_CLASS_KEYBOARD |
+    sys::PP_INPUTEVENT_CLASS_WHEEL;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EventData {
+    Mouse {
+        button: PP_InputEvent_MouseButton,
+        /// In DIPs, relative to the instance.
+        position: PP_Point,
+        click_count: i32,
+        movement: PP_Point,
+    },
+    Wheel {
+        delta: PP_FloatPoint,
+        ticks: PP_FloatPoint,
+        scroll_by_page: bool,
+    },
+    Keyboard {
+        key_code: u32,
+        /// Only `CHAR` events have text.
+        text: Option<String>,
+        /// The DOM `code`, eg `"KeyA"`.
+        code: Option<String>,
+    },
+}
+
+/// An input event without its time stamp, which is taken when it's
+/// dispatched.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    pub kind: PP_InputEvent_Type,
+    pub modifiers: u32,
+    pub data: EventData,
+}
+impl Event {
+    /// A single click for `MOUSEDOWN` and `MOUSEUP`, none otherwise.
+    pub fn mouse(kind: PP_InputEvent_Type, button: PP_InputEvent_MouseButton,
+                 x: i32, y: i32) -> Event {
+        let click_count = match kind {
+            sys::PP_INPUTEVENT_TYPE_MOUSEDOWN | sys::PP_INPUTEVENT_TYPE_MOUSEUP => 1,
+            _ => 0,
+        };
+        Event {
+            kind: kind,
+            modifiers: 0,
+            data: EventData::Mouse {
+                button: button,
+                position: PP_Point { x: x, y: y, },
+                click_count: click_count,
+                movement: Default::default(),
+            },
+        }
+    }
+    /// A pixel scroll of `(dx, dy)`. Chrome counts 100 pixels per wheel tick.
+    pub fn wheel(dx: f32, dy: f32) -> Event {
+        Event {
+            kind: sys::PP_INPUTEVENT_TYPE_WHEEL,
+            modifiers: 0,
+            data: EventData::Wheel {
+                delta: PP_FloatPoint { x: dx, y: dy, },
+                ticks: PP_FloatPoint { x: dx / 100.0, y: dy / 100.0, },
+                scroll_by_page: false,
+            },
+        }
+    }
+    pub fn key(kind: PP_InputEvent_Type, key_code: u32) -> Event {
+        Event {
+            kind: kind,
+            modifiers: 0,
+            data: EventData::Keyboard {
+                key_code: key_code,
+                text: None,
+                code: None,
+            },
+        }
+    }
+    /// What Chrome sends for a key press: `KEYDOWN`, then `CHAR` if the key
+    /// has `text`, then `KEYUP`.
+    pub fn key_press(key_code: u32, text: Option<&str>) -> Vec<Event> {
+        let mut events = vec![Event::key(sys::PP_INPUTEVENT_TYPE_KEYDOWN, key_code)];
+        if let Some(text) = text {
+            let mut c = Event::key(sys::PP_INPUTEVENT_TYPE_CHAR, key_code);
+            if let EventData::Keyboard { text: ref mut t, .. } = c.data {
+                *t = Some(text.to_string());
+            }
+            events.push(c);
+        }
+        events.push(Event::key(sys::PP_INPUTEVENT_TYPE_KEYUP, key_code));
+        events
+    }
+    pub fn with_modifiers(mut self, modifiers: u32) -> Event {
+        self.modifiers = modifiers;
+        self
+    }
+
+    pub fn class(&self) -> PP_InputEvent_Class {
+        match self.data {
+            EventData::Mouse { .. } => sys::PP_INPUTEVENT_CLASS_MOUSE,
+            EventData::Wheel { .. } => sys::PP_INPUTEVENT_CLASS_WHEEL,
+            EventData::Keyboard { .. } => sys::PP_INPUTEVENT_CLASS_KEYBOARD,
+        }
+    }
+}
+
+/// How an instance receives a class of events.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Delivery {
+    /// The module isn't sent these; the page gets them.
+    NotRequested,
+    /// Sent to the module, and always treated as handled.
+    Requested,
+    /// Sent to the module, which decides whether the page gets them.
+    Filtered,
+}
+
+/// An instance's `RequestInputEvents`/`RequestFilteringInputEvents` state.
+/// Requesting a class one way drops the other request for it, as in Chrome.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct InputEventRequests {
+    pub requested: PP_InputEvent_Class,
+    pub filtering: PP_InputEvent_Class,
+}
+impl InputEventRequests {
+    pub fn request(&mut self, classes: PP_InputEvent_Class, filtering: bool) {
+        if filtering {
+            self.filtering |= classes;
+            self.requested &= !classes;
+        } else {
+            self.requested |= classes;
+            self.filtering &= !classes;
+        }
+    }
+    pub fn clear(&mut self, classes: PP_InputEvent_Class) {
+        self.requested &= !classes;
+        self.filtering &= !classes;
+    }
+    pub fn delivery(&self, class: PP_InputEvent_Class) -> Delivery {
+        if self.filtering & class != 0 {
+            Delivery::Filtered
+        } else if self.requested & class != 0 {
+            Delivery::Requested
+        } else {
+            Delivery::NotRequested
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct InputEventState {
+    id: PP_Resource,
+    instance: Instance,
+
+    time_stamp: PP_TimeTicks,
+    event: Event,
+}
+impl InputEventState {
+    pub fn create(i: &Instance, time_stamp: PP_TimeTicks, event: Event) -> InputEvent {
+        let inner = InputEventState {
+            id: take_resource_id(),
+            instance: i.clone(),
+            time_stamp: time_stamp,
+            event: event,
+        };
+        Resource::create(i, Arc::new(inner))
+    }
+
+    pub fn time_stamp(&self) -> PP_TimeTicks { self.time_stamp }
+    pub fn event(&self) -> &Event { &self.event }
+}
+impl ResourceState for InputEventState {
+    fn into_resstate(this: Arc<Self>) -> ResState {
+        ResState::InputEvent(this)
+    }
+    fn state_from_resstate(rs: &Arc<ResourceRc>) -> Code<&Arc<Self>> {
+        match rs.state() {
+            &ResState::InputEvent(ref e) => Ok(e),
+            _ => Err(Error::BadArgument),
+        }
+    }
+    fn resource_id(this: &Arc<Self>) -> PP_Resource { this.id }
+    fn resource_instance(this: &Arc<Self>) -> Instance { this.instance.clone() }
+}
+
+fn to_bool(b: bool) -> PP_Bool { if b { PP_TRUE } else { PP_FALSE } }
+fn get(event: PP_Resource) -> Code<InputEvent> { get_resource(event) }
+fn get_data(event: PP_Resource) -> Option<EventData> {
+    get(event).ok().map(|event| event.event().data.clone() )
+}
+fn create(instance: PP_Instance, time_stamp: PP_TimeTicks, event: Event) -> PP_Resource {
+    ModuleInterface::get_instance_interface(instance)
+        .map(|i| InputEventState::create(&i, time_stamp, event).move_into_id() )
+        .unwrap_or(0)
+}
+fn var_to_string(var: PP_Var) -> Option<String> {
+    match Var::from(var) {
+        Ok(Var::String(s)) => Some(s.to_string()),
+        _ => None,
+    }
+}
+fn string_to_var(s: Option<String>) -> PP_Var {
+    s.map(|s| StringVar::new(s).into() )
+        .unwrap_or(Var::Undefined)
+        .into()
+}
+
+extern "C" fn request_input_events(instance: PP_Instance, classes: uint32_t) -> int32_t {
+    ModuleInterface::get_instance_interface(instance)
+        .and_then(|i| i.request_input_events(classes, false) )
+        .into_code()
+}
+extern "C" fn request_filtering_input_events(instance: PP_Instance,
+                                             classes: uint32_t) -> int32_t {
+    ModuleInterface::get_instance_interface(instance)
+        .and_then(|i| i.request_input_events(classes, true) )
+        .into_code()
+}
+extern "C" fn clear_input_event_request(instance: PP_Instance, classes: uint32_t) {
+    if let Ok(i) = ModuleInterface::get_instance_interface(instance) {
+        i.clear_input_event_request(classes);
+    }
+}
+extern "C" fn is_input_event(res: PP_Resource) -> PP_Bool {
+    match unsafe { get_resource_arc(res) } {
+        Some(rc) => match rc.state() {
+            &ResState::InputEvent(_) => PP_TRUE,
+            _ => PP_FALSE,
+        },
+        None => PP_FALSE,
+    }
+}
+extern "C" fn get_type(event: PP_Resource) -> PP_InputEvent_Type {
+    get(event)
+        .map(|event| event.event().kind )
+        .unwrap_or(sys::PP_INPUTEVENT_TYPE_UNDEFINED)
+}
+extern "C" fn get_time_stamp(event: PP_Resource) -> PP_TimeTicks {
+    get(event)
+        .map(|event| event.time_stamp() )
+        .unwrap_or(0.0)
+}
+extern "C" fn get_modifiers(event: PP_Resource) -> uint32_t {
+    get(event)
+        .map(|event| event.event().modifiers )
+        .unwrap_or(0)
+}
+
+static INPUT_EVENT_INTERFACE: sys::PPB_InputEvent_1_0 = sys::PPB_InputEvent_1_0 {
+    RequestInputEvents: Some(request_input_events),
+    RequestFilteringInputEvents: Some(request_filtering_input_events),
+    ClearInputEventRequest: Some(clear_input_event_request),
+    IsInputEvent: Some(is_input_event),
+    GetType: Some(get_type),
+    GetTimeStamp: Some(get_time_stamp),
+    GetModifiers: Some(get_modifiers),
+};
+
+extern "C" fn mouse_create(instance: PP_Instance, kind: PP_InputEvent_Type,
+                           time_stamp: PP_TimeTicks, modifiers: uint32_t,
+                           button: PP_InputEvent_MouseButton,
+                           position: *const PP_Point, click_count: int32_t,
+                           movement: *const PP_Point) -> PP_Resource {
+    match kind {
+        sys::PP_INPUTEVENT_TYPE_MOUSEDOWN |
+        sys::PP_INPUTEVENT_TYPE_MOUSEUP |
+        sys::PP_INPUTEVENT_TYPE_MOUSEMOVE |
+        sys::PP_INPUTEVENT_TYPE_MOUSEENTER |
+        sys::PP_INPUTEVENT_TYPE_MOUSELEAVE |
+        sys::PP_INPUTEVENT_TYPE_CONTEXTMENU => {},
+        _ => { return 0; },
+    }
+    let position = match unsafe { position.as_ref() } {
+        Some(&position) => position,
+        None => { return 0; },
+    };
+    let movement = unsafe { movement.as_ref() }
+        .cloned()
+        .unwrap_or_default();
+
+    create(instance, time_stamp, Event {
+        kind: kind,
+        modifiers: modifiers,
+        data: EventData::Mouse {
+            button: button,
+            position: position,
+            click_count: click_count,
+            movement: movement,
+        },
+    })
+}
+extern "C" fn is_mouse_input_event(res: PP_Resource) -> PP_Bool {
+    match get_data(res) {
+        Some(EventData::Mouse { .. }) => PP_TRUE,
+        _ => PP_FALSE,
+    }
+}
+extern "C" fn mouse_get_button(event: PP_Resource) -> PP_InputEvent_MouseButton {
+    match get_data(event) {
+        Some(EventData::Mouse { button, .. }) => button,
+        _ => sys::PP_INPUTEVENT_MOUSEBUTTON_NONE,
+    }
+}
+extern "C" fn mouse_get_position(event: PP_Resource) -> PP_Point {
+    match get_data(event) {
+        Some(EventData::Mouse { position, .. }) => position,
+        _ => Default::default(),
+    }
+}
+extern "C" fn mouse_get_click_count(event: PP_Resource) -> int32_t {
+    match get_data(event) {
+        Some(EventData::Mouse { click_count, .. }) => click_count,
+        _ => 0,
+    }
+}
+extern "C" fn mouse_get_movement(event: PP_Resource) -> PP_Point {
+    match get_data(event) {
+        Some(EventData::Mouse { movement, .. }) => movement,
+        _ => Default::default(),
+    }
+}
+
+static MOUSE_INPUT_EVENT_INTERFACE: sys::PPB_MouseInputEvent_1_1 = sys::PPB_MouseInputEvent_1_1 {
+    Create: Some(mouse_create),
+    IsMouseInputEvent: Some(is_mouse_input_event),
+    GetButton: Some(mouse_get_button),
+    GetPosition: Some(mouse_get_position),
+    GetClickCount: Some(mouse_get_click_count),
+    GetMovement: Some(mouse_get_movement),
+};
+
+extern "C" fn wheel_create(instance: PP_Instance, time_stamp: PP_TimeTicks,
+                           modifiers: uint32_t, delta: *const PP_FloatPoint,
+                           ticks: *const PP_FloatPoint,
+                           scroll_by_page: PP_Bool) -> PP_Resource {
+    let (delta, ticks) = match unsafe { (delta.as_ref(), ticks.as_ref()) } {
+        (Some(&delta), Some(&ticks)) => (delta, ticks),
+        _ => { return 0; },
+    };
+    create(instance, time_stamp, Event {
+        kind: sys::PP_INPUTEVENT_TYPE_WHEEL,
+        modifiers: modifiers,
+        data: EventData::Wheel {
+            delta: delta,
+            ticks: ticks,
+            scroll_by_page: scroll_by_page != PP_FALSE,
+        },
+    })
+}
+extern "C" fn is_wheel_input_event(res: PP_Resource) -> PP_Bool {
+    match get_data(res) {
+        Some(EventData::Wheel { .. }) => PP_TRUE,
+        _ => PP_FALSE,
+    }
+}
+extern "C" fn wheel_get_delta(event: PP_Resource) -> PP_FloatPoint {
+    match get_data(event) {
+        Some(EventData::Wheel { delta, .. }) => delta,
+        _ => Default::default(),
+    }
+}
+extern "C" fn wheel_get_ticks(event: PP_Resource) -> PP_FloatPoint {
+    match get_data(event) {
+        Some(EventData::Wheel { ticks, .. }) => ticks,
+        _ => Default::default(),
+    }
+}
+extern "C" fn wheel_get_scroll_by_page(event: PP_Resource) -> PP_Bool {
+    match get_data(event) {
+        Some(EventData::Wheel { scroll_by_page, .. }) => to_bool(scroll_by_page),
+        _ => PP_FALSE,
+    }
+}
+
+static WHEEL_INPUT_EVENT_INTERFACE: sys::PPB_WheelInputEvent_1_0 = sys::PPB_WheelInputEvent_1_0 {
+    Create: Some(wheel_create),
+    IsWheelInputEvent: Some(is_wheel_input_event),
+    GetDelta: Some(wheel_get_delta),
+    GetTicks: Some(wheel_get_ticks),
+    GetScrollByPage: Some(wheel_get_scroll_by_page),
+};
+
+extern "C" fn keyboard_create(instance: PP_Instance, kind: PP_InputEvent_Type,
+                              time_stamp: PP_TimeTicks, modifiers: uint32_t,
+                              key_code: uint32_t, text: PP_Var,
+                              code: PP_Var) -> PP_Resource {
+    match kind {
+        sys::PP_INPUTEVENT_TYPE_RAWKEYDOWN |
+        sys::PP_INPUTEVENT_TYPE_KEYDOWN |
+        sys::PP_INPUTEVENT_TYPE_KEYUP |
+        sys::PP_INPUTEVENT_TYPE_CHAR => {},
+        _ => { return 0; },
+    }
+    create(instance, time_stamp, Event {
+        kind: kind,
+        modifiers: modifiers,
+        data: EventData::Keyboard {
+            key_code: key_code,
+            text: var_to_string(text),
+            code: var_to_string(code),
+        },
+    })
+}
+extern "C" fn is_keyboard_input_event(res: PP_Resource) -> PP_Bool {
+    match get_data(res) {
+        Some(EventData::Keyboard { .. }) => PP_TRUE,
+        _ => PP_FALSE,
+    }
+}
+extern "C" fn keyboard_get_key_code(event: PP_Resource) -> uint32_t {
+    match get_data(event) {
+        Some(EventData::Keyboard { key_code, .. }) => key_code,
+        _ => 0,
+    }
+}
+extern "C" fn keyboard_get_character_text(event: PP_Resource) -> PP_Var {
+    match get_data(event) {
+        Some(EventData::Keyboard { text, .. }) => string_to_var(text),
+        _ => Var::Undefined.into(),
+    }
+}
+extern "C" fn keyboard_get_code(event: PP_Resource) -> PP_Var {
+    match get_data(event) {
+        Some(EventData::Keyboard { code, .. }) => string_to_var(code),
+        _ => Var::Undefined.into(),
+    }
+}
+
+static KEYBOARD_INPUT_EVENT_INTERFACE: sys::PPB_KeyboardInputEvent_1_2 = sys::PPB_KeyboardInputEvent_1_2 {
+    Create: Some(keyboard_create),
+    IsKeyboardInputEvent: Some(is_keyboard_input_event),
+    GetKeyCode: Some(keyboard_get_key_code),
+    GetCharacterText: Some(keyboard_get_character_text),
+    GetCode: Some(keyboard_get_code),
+};
+
+pub static INTERFACES: Interfaces = &[
+    ("PPB_InputEvent;1.0", interface_ptr(&INPUT_EVENT_INTERFACE)),
+    ("PPB_MouseInputEvent;1.1", interface_ptr(&MOUSE_INPUT_EVENT_INTERFACE)),
+    ("PPB_WheelInputEvent;1.0", interface_ptr(&WHEEL_INPUT_EVENT_INTERFACE)),
+    ("PPB_KeyboardInputEvent;1.2", interface_ptr(&KEYBOARD_INPUT_EVENT_INTERFACE)),
+];
@@ -1,21 +1,33 @@
 
 use libc::{self};
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::hash::{Hash, Hasher};
 use std::path::{PathBuf};
-use std::sync::{Arc};
-use std::sync::mpsc::{Sender, Receiver, channel};
-use std::thread::JoinHandle;
-
-use super::callback::{Callback, MessageLoop};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{Sender, SendError, Receiver, channel};
+use std::thread::{JoinHandle, sleep};
+use std::time::{Duration, Instant};
+
+use super::audio::{Audio, AudioCallback, AudioConfig, AudioState,
+                   OutputBufferModel};
+use super::callback::{Callback, MessageLoop, ThreadChecks};
+use super::capture::{Frame, FrameSink};
+use super::clock::Clock;
+use super::scheduler::Scheduler;
+use super::completion::{Completion, Completions, PendingCompletion};
+use super::graphics::{Graphics3D, SwapAck, SwapThrottling, Throttle};
+use super::input_event::{Delivery, Event, InputEventRequests, SUPPORTED_CLASSES};
+use super::mouse::{Cursor, CursorChange};
 use super::sys::{self, PP_FileInfo, PP_Time, PP_TimeTicks};
-use super::resource::ResourceRc;
+use super::timeline::{Timeline, TimelineEntry, TimelineEvent};
+use super::resource::{ResourceRc, ResState};
 use super::filesystem_manager::{FileIo, FileRef, FileSystem,
                                 FileRefResource, FileIoResource};
 use super::prelude::*;
 use super::interface::*;
 use super::var::VarRc;
+use super::view::ViewData;
 
 /// Do not use from the instance state thread. Nb: Instance can't be shared
 /// between threads because Sender<> can't. We forcably get around this issue so
@@ -26,15 +38,60 @@ pub struct Instance {
     instance_id: PP_Instance,
     module_id: super::ModuleHandle,
     tx: Sender<Message>,
+    full_frame: bool,
+    /// Checked on the module's threads, so it can't wait on the instance
+    /// thread.
+    thread_checks: Arc<Mutex<ThreadChecks>>,
+    /// `Some` while capturing. Read on every swap, so it's here rather than
+    /// a round trip to the instance thread.
+    frame_sink: Arc<Mutex<Option<FrameSink>>>,
+    /// The module's.
+    clock: Arc<Clock>,
+    scheduler: Arc<Scheduler>,
 }
 impl Instance {
+    /// Owns module-wide resources, like the main thread's message loop. It
+    /// isn't a real instance: its id is 0, and nothing receives what's sent
+    /// to it.
+    #[doc(hidden)]
+    pub fn module_owner(module: super::ModuleHandle, clock: Arc<Clock>,
+                        scheduler: Arc<Scheduler>) -> Instance {
+        let (tx, _) = channel();
+        Instance {
+            instance_id: 0,
+            module_id: module,
+            tx: tx,
+            full_frame: false,
+            thread_checks: Default::default(),
+            frame_sink: Default::default(),
+            clock: clock,
+            scheduler: scheduler,
+        }
+    }
+
     pub fn id(&self) -> PP_Instance { self.instance_id }
+    /// Whether the instance was created to handle a document load, as when
+    /// navigating straight to a media file.
+    pub fn is_full_frame(&self) -> bool { self.full_frame }
+    pub fn clock(&self) -> &Arc<Clock> { &self.clock }
+    pub fn scheduler(&self) -> &Arc<Scheduler> { &self.scheduler }
+
+    /// Every message is promised to the scheduler, so no loop gets a turn
+    /// until it's been handled; see `Scheduler::promise`.
+    fn send(&self, msg: Message) -> Result<(), SendError<Message>> {
+        self.scheduler.promise(self.instance_id);
+        let sent = self.tx.send(msg);
+        if sent.is_err() {
+            self.scheduler.keep(self.instance_id);
+        }
+        sent
+    }
 
     pub fn ping(&self) -> Code<()> {
         let (tx, rx) = channel();
 
         let msg = Message::Ping(tx);
-        if let Err(_) = self.tx.send(msg) {
+        if let Err(_) = self.send(msg) {
             return Err(Error::BadInstance);
         }
         if let Err(_) = rx.recv() {
@@ -46,7 +103,7 @@ impl Instance {
 
     #[doc(hidden)]
     pub fn stop(&self) {
-        let _ = self.tx.send(Message::Stop);
+        let _ = self.send(Message::Stop);
     }
 
     /// Do not call any other function after calling this.
@@ -55,7 +112,7 @@ impl Instance {
         let msg = Message::Destroy {
             ret: tx,
         };
-        if let Err(_) = self.tx.send(msg) {
+        if let Err(_) = self.send(msg) {
             return Err(Error::BadInstance);
         }
 
@@ -64,15 +121,15 @@ impl Instance {
 
     pub fn resource_ctor(&self, res: Arc<ResourceRc>) {
         let msg = Message::ResourceCtor(res);
-        let _ = self.tx.send(msg);
+        let _ = self.send(msg);
     }
     pub fn resource_dtor(&self, res: Arc<ResourceRc>) {
         let msg = Message::ResourceDtor(res);
-        let _ = self.tx.send(msg);
+        let _ = self.send(msg);
     }
     pub fn track_var(&self, id: PP_VarId) {
         let msg = Message::TrackVar(id);
-        let _ = self.tx.send(msg);
+        let _ = self.send(msg);
     }
 
     pub fn get_live_vars(&self) -> Code<Vec<VarRc>> {
@@ -81,7 +138,7 @@ impl Instance {
             ret: tx,
         };
 
-        if let Some(vars) = self.tx.send(msg)
+        if let Some(vars) = self.send(msg)
             .ok()
             .and_then(|_| {
                 rx.recv().ok()
@@ -108,7 +165,7 @@ impl Instance {
         let (tx, rx) = channel();
         let msg = Message::CreateFileSystem(tx);
 
-        if let Some(fs) = self.tx.send(msg)
+        if let Some(fs) = self.send(msg)
             .ok()
             .and_then(|_| {
                 rx.recv().ok()
@@ -125,7 +182,7 @@ impl Instance {
         let (tx, rx) = channel();
         let msg = Message::OpenFileSystem(tx);
 
-        if let Some(result) = self.tx.send(msg)
+        if let Some(result) = self.send(msg)
             .ok()
             .and_then(|_| {
                 rx.recv().ok()
@@ -142,7 +199,7 @@ impl Instance {
         let (tx, rx) = channel();
         let msg = Message::CreateFileRef(tx, fs, path);
 
-        if let Some(fr) = self.tx.send(msg)
+        if let Some(fr) = self.send(msg)
             .ok()
             .and_then(|_| {
                 rx.recv().ok()
@@ -160,7 +217,7 @@ impl Instance {
             file_ref: fr,
         };
 
-        if let Some(fr) = self.tx.send(msg)
+        if let Some(fr) = self.send(msg)
             .ok()
             .and_then(|_| {
                 rx.recv().ok()
@@ -178,7 +235,7 @@ impl Instance {
             file_ref: fr,
         };
 
-        if let Some(fr) = self.tx.send(msg)
+        if let Some(fr) = self.send(msg)
             .ok()
             .and_then(|_| {
                 rx.recv().ok()
@@ -196,7 +253,7 @@ impl Instance {
             file_ref: fr,
         };
 
-        if let Some(fr) = self.tx.send(msg)
+        if let Some(fr) = self.send(msg)
             .ok()
             .and_then(|_| {
                 rx.recv().ok()
@@ -218,7 +275,7 @@ impl Instance {
             flags: flags,
         };
 
-        if let Some(fr) = self.tx.send(msg)
+        if let Some(fr) = self.send(msg)
             .ok()
             .and_then(|_| {
                 rx.recv().ok()
@@ -242,7 +299,7 @@ impl Instance {
             last_modified_time: last_modified_time,
         };
 
-        if let Some(fr) = self.tx.send(msg)
+        if let Some(fr) = self.send(msg)
             .ok()
             .and_then(|_| {
                 rx.recv().ok()
@@ -263,7 +320,7 @@ impl Instance {
             file_ref: fr,
         };
 
-        if let Some(fr) = self.tx.send(msg)
+        if let Some(fr) = self.send(msg)
             .ok()
             .and_then(|_| {
                 rx.recv().ok()
@@ -285,7 +342,7 @@ impl Instance {
             new_file_ref: new_fr,
         };
 
-        if let Some(fr) = self.tx.send(msg)
+        if let Some(fr) = self.send(msg)
             .ok()
             .and_then(|_| {
                 rx.recv().ok()
@@ -305,7 +362,7 @@ impl Instance {
             file_ref: fr,
         };
 
-        if let Some(fr) = self.tx.send(msg)
+        if let Some(fr) = self.send(msg)
             .ok()
             .and_then(|_| {
                 rx.recv().ok()
@@ -326,7 +383,7 @@ impl Instance {
             file_ref: fr,
         };
 
-        if let Some(fr) = self.tx.send(msg)
+        if let Some(fr) = self.send(msg)
             .ok()
             .and_then(|_| {
                 rx.recv().ok()
@@ -343,7 +400,7 @@ impl Instance {
         let msg = Message::CreateFileIo {
             ret: tx,
         };
-        self.tx.send(msg).unwrap();
+        self.send(msg).unwrap();
         rx.recv().unwrap()
     }
     pub fn open_file_io(&self, res: PP_Resource, file_ref: PP_Resource,
@@ -357,7 +414,7 @@ impl Instance {
             file_ref: file_ref,
             flags: flags,
         };
-        if let Err(_) = self.tx.send(msg) {
+        if let Err(_) = self.send(msg) {
             return Err(Error::BadInstance);
         }
         rx.recv().unwrap()
@@ -374,7 +431,7 @@ impl Instance {
             ret: tx,
             io: res,
         };
-        if let Err(_) = self.tx.send(msg) {
+        if let Err(_) = self.send(msg) {
             return Err(Error::BadInstance);
         }
         let ret = rx.recv();
@@ -406,7 +463,7 @@ impl Instance {
             offset: offset,
             buffer: buffer,
         };
-        if let Err(_) = self.tx.send(msg) {
+        if let Err(_) = self.send(msg) {
             return Err(Error::BadInstance);
         }
         rx.recv().unwrap()
@@ -423,7 +480,7 @@ impl Instance {
             offset: offset,
             buffer: buffer,
         };
-        if let Err(_) = self.tx.send(msg) {
+        if let Err(_) = self.send(msg) {
             return Err(Error::BadInstance);
         }
         rx.recv().unwrap()
@@ -438,7 +495,7 @@ impl Instance {
             io: res,
             new_length: len,
         };
-        if let Err(_) = self.tx.send(msg) {
+        if let Err(_) = self.send(msg) {
             return Err(Error::BadInstance);
         }
         try!(rx.recv().unwrap());
@@ -452,7 +509,7 @@ impl Instance {
             ret: tx,
             io: res,
         };
-        if let Err(_) = self.tx.send(msg) {
+        if let Err(_) = self.send(msg) {
             return Err(Error::BadInstance);
         }
         rx.recv().unwrap()
@@ -463,15 +520,276 @@ impl Instance {
             ret: tx,
             io: res,
         };
-        if let Err(_) = self.tx.send(msg) {
+        if let Err(_) = self.send(msg) {
             return;
         }
         rx.recv().unwrap();
     }
 
+    pub fn create_audio(&self, config: PP_Resource, callback: AudioCallback,
+                        user_data: *mut libc::c_void) -> Code<Audio> {
+        let (tx, rx) = channel();
+        let msg = Message::CreateAudio {
+            ret: tx,
+            config: config,
+            callback: callback,
+            user_data: user_data,
+        };
+
+        if let Some(audio) = self.send(msg)
+            .ok()
+            .and_then(|_| {
+                rx.recv().ok()
+            })
+        {
+            Ok(try!(audio))
+        } else {
+            Err(Error::BadInstance)
+        }
+    }
+    /// Sets the output device model used for audio resources created after
+    /// this call.
+    pub fn set_audio_output_model(&self, model: OutputBufferModel) {
+        let msg = Message::SetAudioOutputModel(model);
+        let _ = self.send(msg);
+    }
+
+    /// Start recording audio callbacks and frame presentations. Events from
+    /// before this call are dropped.
+    pub fn start_timeline(&self) {
+        let _ = self.send(Message::StartTimeline);
+    }
+    /// Stop recording and return everything recorded since `start_timeline`.
+    pub fn take_timeline(&self) -> Code<Timeline> {
+        let (tx, rx) = channel();
+        if let Err(_) = self.send(Message::TakeTimeline(tx)) {
+            return Err(Error::BadInstance);
+        }
+        rx.recv().map_err(|_| Error::BadInstance )
+    }
+    /// Timestamped here, so the instance thread's backlog doesn't skew it.
+    pub fn record_timeline_event(&self, event: TimelineEvent) {
+        let entry = TimelineEntry {
+            ts: super::global_module().seconds_elapsed(),
+            event: event,
+        };
+        let _ = self.send(Message::RecordTimeline(entry));
+    }
+
+    /// Read back Graphics3D color buffers as they're presented. Frames from
+    /// before this call are dropped.
+    pub fn start_frame_capture(&self, sink: FrameSink) {
+        // Sent under the lock; `capture_frame` sends under it too, so frames
+        // captured with an earlier sink arrive before this and are dropped.
+        let mut frame_sink = self.frame_sink.lock().unwrap();
+        *frame_sink = Some(sink);
+        let _ = self.send(Message::StartFrameCapture);
+    }
+    /// Stop capturing and return the frames captured, in presentation order.
+    pub fn take_frames(&self) -> Code<Vec<Frame>> {
+        let (tx, rx) = channel();
+        {
+            let mut frame_sink = self.frame_sink.lock().unwrap();
+            *frame_sink = None;
+            if let Err(_) = self.send(Message::TakeFrames(tx)) {
+                return Err(Error::BadInstance);
+            }
+        }
+        rx.recv().map_err(|_| Error::BadInstance )
+    }
+    /// Record the frame `capture` reads back, if capturing. The sink stays
+    /// locked until the frame is sent, so it can't land in a later capture.
+    pub fn capture_frame<F>(&self, capture: F)
+        where F: FnOnce(&FrameSink) -> Frame,
+    {
+        let frame_sink = self.frame_sink.lock().unwrap();
+        if let Some(ref sink) = *frame_sink {
+            let _ = self.send(Message::RecordFrame(capture(sink)));
+        }
+    }
+
+    /// Give the module a new view with `PPP_Instance::DidChangeView`. Returns
+    /// once the module's handler has returned.
+    /// Don't call from the module thread.
+    pub fn set_view(&self, view: ViewData) -> Code<()> {
+        super::global_module().change_view(self.instance_id, view)
+    }
+    /// Calls `PPP_Instance::DidChangeFocus`; returns once it has returned.
+    /// Don't call from the module thread.
+    pub fn set_focus(&self, has_focus: bool) -> Code<()> {
+        super::global_module().change_focus(self.instance_id, has_focus)
+    }
+    /// Called from the module thread, before the module sees the view.
+    pub fn view_changed(&self, view: ViewData) {
+        let _ = self.send(Message::ViewChanged(view));
+    }
+
+    /// How swap completions are throttled while this instance isn't visible.
+    /// Swaps already waiting are rescheduled.
+    pub fn set_swap_throttling(&self, throttling: SwapThrottling) {
+        let _ = self.send(Message::SetSwapThrottling(throttling));
+    }
+    /// How the module's calls are held to Chrome's threading rules for
+    /// completion callbacks.
+    pub fn set_thread_checks(&self, checks: ThreadChecks) {
+        *self.thread_checks.lock().unwrap() = checks;
+    }
+    pub fn thread_checks(&self) -> ThreadChecks {
+        *self.thread_checks.lock().unwrap()
+    }
+
+    /// When the callbacks of asynchronous operations are run. Callbacks
+    /// already held are run through the new policy.
+    pub fn set_completion(&self, completion: Completion) {
+        let _ = self.send(Message::SetCompletion(completion));
+    }
+    /// Hand a finished operation's callback to the instance to be posted
+    /// when its `Completion` allows.
+    pub fn complete(&self, pending: PendingCompletion) {
+        if let Err(SendError(Message::Complete(pending))) = self.send(Message::Complete(pending)) {
+            // No instance left to hold it back.
+            let _ = pending.post();
+        }
+    }
+    /// Run the callbacks of `resource`'s operations that are still waiting
+    /// on the `Completion` with `PP_ERROR_ABORTED`, as when it's closed.
+    pub fn abort_completions(&self, resource: PP_Resource) {
+        let _ = self.send(Message::AbortCompletions(resource));
+    }
+    /// How many callbacks `Completion::Manual` is holding.
+    pub fn held_completions(&self) -> Code<usize> {
+        let (tx, rx) = channel();
+        try!(self.send(Message::GetHeldCompletions(tx))
+             .map_err(|_| Error::BadInstance ));
+        rx.recv().map_err(|_| Error::BadInstance )
+    }
+    /// Post up to `count` of the held callbacks, oldest first. Returns how
+    /// many were posted.
+    pub fn release_completions(&self, count: usize) -> Code<usize> {
+        let (tx, rx) = channel();
+        let msg = Message::ReleaseCompletions {
+            ret: tx,
+            count: count,
+        };
+        try!(self.send(msg).map_err(|_| Error::BadInstance ));
+        rx.recv().map_err(|_| Error::BadInstance )
+    }
+
+    /// Hand a presented swap to the instance to be completed once the view
+    /// allows it.
+    pub fn swap_ack(&self, ack: SwapAck) {
+        if let Err(SendError(Message::SwapAck(ack))) = self.send(Message::SwapAck(ack)) {
+            // Nothing outlives the instance; see `Message::Destroy`.
+            ack.abort();
+        }
+    }
+
+    /// Mark all of this instance's Graphics3D contexts lost, then call the
+    /// module's `PPP_Graphics3D::Graphics3DContextLost` if any weren't lost
+    /// already. Returns the number of contexts newly lost.
+    /// Don't call from the module thread.
+    pub fn lose_graphics_3d_contexts(&self) -> Code<usize> {
+        let (tx, rx) = channel();
+        if let Err(_) = self.send(Message::LoseGraphics3DContexts(tx)) {
+            return Err(Error::BadInstance);
+        }
+        let lost = try!(rx.recv().map_err(|_| Error::BadInstance ));
+        if lost > 0 {
+            try!(super::global_module().graphics_3d_context_lost(self.instance_id));
+        }
+        Ok(lost)
+    }
+
+    /// `PPB_Instance::BindGraphics`: `device` becomes what's shown for this
+    /// instance, or with 0 nothing is. Only this instance's own Graphics3D
+    /// contexts can be bound (there's no Graphics2D). The instance keeps a
+    /// reference to the bound context, as in Chrome.
+    pub fn bind_graphics(&self, device: PP_Resource) -> Code<()> {
+        let (tx, rx) = channel();
+        let msg = Message::BindGraphics {
+            ret: tx,
+            device: device,
+        };
+        if let Err(_) = self.send(msg) {
+            return Err(Error::BadInstance);
+        }
+        rx.recv().map_err(|_| Error::BadInstance )
+            .and_then(|r| r )
+    }
+    pub fn bound_graphics(&self) -> Code<Option<PP_Resource>> {
+        let (tx, rx) = channel();
+        if let Err(_) = self.send(Message::GetBoundGraphics(tx)) {
+            return Err(Error::BadInstance);
+        }
+        rx.recv().map_err(|_| Error::BadInstance )
+    }
+
+    /// `PPB_InputEvent::RequestInputEvents`, or with `filtering`
+    /// `RequestFilteringInputEvents`. Fails with `NotSupported` for classes
+    /// other than mouse, keyboard and wheel, leaving the requests as they were.
+    pub fn request_input_events(&self, classes: u32, filtering: bool) -> Code<()> {
+        if classes & !SUPPORTED_CLASSES != 0 { return Err(Error::NotSupported); }
+        let msg = Message::RequestInputEvents {
+            classes: classes,
+            filtering: filtering,
+        };
+        self.send(msg).map_err(|_| Error::BadInstance )
+    }
+    pub fn clear_input_event_request(&self, classes: u32) {
+        let _ = self.send(Message::ClearInputEventRequest(classes));
+    }
+    pub fn input_event_requests(&self) -> Code<InputEventRequests> {
+        let (tx, rx) = channel();
+        if let Err(_) = self.send(Message::GetInputEventRequests(tx)) {
+            return Err(Error::BadInstance);
+        }
+        rx.recv().map_err(|_| Error::BadInstance )
+    }
+    /// Inject `event` like Chrome would: it reaches
+    /// `PPP_InputEvent::HandleInputEvent` only if its class was requested.
+    /// Returns whether the event was consumed, ie whether the page wouldn't
+    /// see it; requested (non-filtered) events always are.
+    /// Don't call from the module thread.
+    pub fn send_input_event(&self, event: Event) -> Code<bool> {
+        let requests = try!(self.input_event_requests());
+        match requests.delivery(event.class()) {
+            Delivery::NotRequested => Ok(false),
+            Delivery::Requested => {
+                try!(super::global_module().handle_input_event(self.instance_id, event));
+                Ok(true)
+            },
+            Delivery::Filtered => {
+                super::global_module().handle_input_event(self.instance_id, event)
+            },
+        }
+    }
+
+    /// `PPB_MouseCursor::SetCursor`, after the arguments have been checked.
+    pub fn set_cursor(&self, cursor: Cursor) {
+        let _ = self.send(Message::SetCursor(cursor));
+    }
+    /// The cursor over this instance; the pointer until the module sets one.
+    pub fn cursor(&self) -> Code<Cursor> {
+        self.cursor_history()
+            .map(|history| {
+                history.into_iter()
+                    .last()
+                    .map(|change| change.cursor )
+                    .unwrap_or_default()
+            })
+    }
+    /// Every cursor the module has set, oldest first.
+    pub fn cursor_history(&self) -> Code<Vec<CursorChange>> {
+        let (tx, rx) = channel();
+        if let Err(_) = self.send(Message::GetCursorHistory(tx)) {
+            return Err(Error::BadInstance);
+        }
+        rx.recv().map_err(|_| Error::BadInstance )
+    }
+
     pub fn post_message(&self, msg: Var) {
         let msg = Message::PostMessage(msg);
-        let _ = self.tx.send(msg);
+        let _ = self.send(msg);
     }
 
     pub fn register_message_handler(&self, user: *mut libc::c_void,
@@ -485,7 +803,7 @@ impl Instance {
             ml: ml,
         };
 
-        if let Some(fr) = self.tx.send(msg)
+        if let Some(fr) = self.send(msg)
             .ok()
             .and_then(|_| {
                 rx.recv().ok()
@@ -500,7 +818,7 @@ impl Instance {
         let (tx, rx) = channel();
         let msg = Message::UnregisterMessageHandler(tx);
 
-        let _ = self.tx.send(msg)
+        let _ = self.send(msg)
             .ok()
             .and_then(|_| {
                 rx.recv().ok()
@@ -522,6 +840,8 @@ impl Eq for Instance { }
 
 enum Message {
     Ping(Sender<()>),
+    /// Only wakes the instance thread, to check its timers.
+    ClockChanged,
     Destroy {
         /// This isn't used by the instance thread; it's passed on to the module thread.
         ret: Sender<Code<()>>,
@@ -536,6 +856,50 @@ enum Message {
         ret: Sender<Code<Vec<PP_VarId>>>,
     },
 
+    CreateAudio {
+        ret: Sender<Code<Audio>>,
+        config: PP_Resource,
+        callback: AudioCallback,
+        user_data: *mut libc::c_void,
+    },
+    SetAudioOutputModel(OutputBufferModel),
+
+    StartTimeline,
+    TakeTimeline(Sender<Timeline>),
+    RecordTimeline(TimelineEntry),
+
+    StartFrameCapture,
+    TakeFrames(Sender<Vec<Frame>>),
+    RecordFrame(Frame),
+
+    LoseGraphics3DContexts(Sender<usize>),
+    BindGraphics {
+        ret: Sender<Code<()>>,
+        device: PP_Resource,
+    },
+    GetBoundGraphics(Sender<Option<PP_Resource>>),
+    RequestInputEvents {
+        classes: u32,
+        filtering: bool,
+    },
+    ClearInputEventRequest(u32),
+    GetInputEventRequests(Sender<InputEventRequests>),
+    SetCursor(Cursor),
+    GetCursorHistory(Sender<Vec<CursorChange>>),
+
+    ViewChanged(ViewData),
+    SetSwapThrottling(SwapThrottling),
+    SwapAck(SwapAck),
+
+    SetCompletion(Completion),
+    Complete(PendingCompletion),
+    AbortCompletions(PP_Resource),
+    GetHeldCompletions(Sender<usize>),
+    ReleaseCompletions {
+        ret: Sender<usize>,
+        count: usize,
+    },
+
     PostMessage(Var),
     RegisterMessageHandler {
         ret: Sender<Code<()>>,
@@ -645,11 +1009,35 @@ pub struct InstanceState {
     temp_fs_man: FileSystem,
 
     message_handler: Option<MessageLoop>,
+
+    audio_model: OutputBufferModel,
+
+    /// `Some` while recording.
+    timeline: Option<Timeline>,
+    /// `Some` while capturing frames.
+    capture: Option<Vec<Frame>>,
+
+    /// The last view given to the module.
+    view: ViewData,
+    swap_throttling: SwapThrottling,
+    /// Swaps held until the instance is visible.
+    held_swaps: Vec<SwapAck>,
+    /// Swaps to complete at a set time, in order.
+    timed_swaps: VecDeque<(Instant, SwapAck)>,
+    /// When the last paced swap was (or will be) completed.
+    last_swap_due: Option<Instant>,
+    completions: Completions,
+
+    bound_graphics: Option<Graphics3D>,
+
+    input_event_requests: InputEventRequests,
+    cursor_history: Vec<CursorChange>,
 }
 
 impl InstanceState {
     #[doc(hidden)]
-    pub fn new(id: PP_Instance, parent: super::ModuleInterface) -> (JoinHandle<()>, Instance) {
+    pub fn new(id: PP_Instance, parent: super::ModuleInterface,
+               full_frame: bool) -> (JoinHandle<()>, Instance) {
         use super::filesystem_manager::FileSystemState;
         use std::thread::*;
         let (tx, rx) = channel();
@@ -657,7 +1045,15 @@ impl InstanceState {
             instance_id: id,
             module_id:   parent.id(),
             tx:          tx,
+            full_frame:  full_frame,
+            thread_checks: Default::default(),
+            frame_sink: Default::default(),
+            clock: parent.clock().clone(),
+            scheduler: parent.scheduler().clone(),
         };
+        // Timed swaps and completions may have come due.
+        let wake = this.clone();
+        parent.clock().on_change(Box::new(move || wake.send(Message::ClockChanged).is_ok() ));
 
         let mut state = InstanceState {
             parent: parent,
@@ -668,13 +1064,29 @@ impl InstanceState {
             temp_fs_man: FileSystemState::new(&this),
             message_handler: None,
             post_msg_dest: None,
+            audio_model: Default::default(),
+            timeline: None,
+            capture: None,
+            view: Default::default(),
+            swap_throttling: Default::default(),
+            held_swaps: Vec::new(),
+            timed_swaps: VecDeque::new(),
+            last_swap_due: None,
+            completions: Default::default(),
+            bound_graphics: None,
+            input_event_requests: Default::default(),
+            cursor_history: Vec::new(),
         };
 
         state.resources.insert(state.temp_fs_man.id(), state.temp_fs_man.get_rc().clone());
 
         let join = spawn(move || {
             let mut state = state;
+            let scheduler = state.this.scheduler.clone();
             state.run();
+            // Nothing more can be sent once the receiver's dropped.
+            drop(state);
+            scheduler.forget(id);
         });
 
         (join, this)
@@ -694,31 +1106,117 @@ impl InstanceState {
     {
         if let Some(res) = self.resources.get(&id) {
             let res = res.clone();
-            let res = try!(<T as ResourceState>::from_resstate(res));
-            f(res, try!(previous))
+            // The `Resource` gives this ref back when dropped.
+            res.up_ref();
+            match <T as ResourceState>::from_resstate(res.clone()) {
+                Ok(res) => f(res, try!(previous)),
+                Err(err) => {
+                    res.down_ref();
+                    Err(err)
+                },
+            }
         } else {
             Err(Error::BadArgument)
         }
     }
 
+    fn schedule_swap(&mut self, ack: SwapAck) {
+        // Nothing to wait for; it fails now.
+        if ack.context_lost() {
+            return ack.complete();
+        }
+        match self.swap_throttling.throttle(self.view.visibility()) {
+            Throttle::Unthrottled => ack.complete(),
+            Throttle::Hold => self.held_swaps.push(ack.defer()),
+            Throttle::Interval(interval) => {
+                let now = self.this.clock.now();
+                let due = self.last_swap_due
+                    .map(|last| last + interval )
+                    .unwrap_or(now);
+                let due = if due < now { now } else { due };
+                self.last_swap_due = Some(due);
+                self.timed_swaps.push_back((due, ack.defer()));
+            },
+        }
+    }
+    /// The view or the throttling changed; run everything waiting through
+    /// `schedule_swap` again.
+    fn reschedule_swaps(&mut self) {
+        let mut waiting: Vec<SwapAck> = self.timed_swaps
+            .drain(..)
+            .map(|(_, ack)| ack )
+            .collect();
+        waiting.extend(self.held_swaps.drain(..));
+        for ack in waiting.into_iter() {
+            self.schedule_swap(ack);
+        }
+    }
+    fn abort_swaps(&mut self) {
+        let waiting: Vec<SwapAck> = self.timed_swaps
+            .drain(..)
+            .map(|(_, ack)| ack )
+            .chain(self.held_swaps.drain(..))
+            .collect();
+        for ack in waiting.into_iter() {
+            ack.abort();
+        }
+    }
+    fn complete_timed_swaps(&mut self) {
+        let now = self.this.clock.now();
+        while self.timed_swaps.front().map(|&(due, _)| due <= now ).unwrap_or(false) {
+            let (_, ack) = self.timed_swaps.pop_front().unwrap();
+            ack.complete();
+        }
+    }
+
     fn run(&mut self) {
         use self::Message::*;
         super::var::set_var_instance(self.this.clone());
 
+        /// Keeps the promise `Instance::send` made for a message once it's
+        /// been handled, however the handler leaves.
+        struct Handled<'a>(&'a Scheduler, PP_Instance);
+        impl<'a> Drop for Handled<'a> {
+            fn drop(&mut self) { self.0.keep(self.1); }
+        }
+
+        let scheduler = self.this.scheduler.clone();
+        let id = self.this.id();
+
         loop {
-            let msg = match self.rx.recv() {
-                Ok(msg) => msg,
+            let swaps_due = self.timed_swaps.front().map(|&(due, _)| due );
+            let next_due = match (swaps_due, self.completions.next_due()) {
+                (Some(a), Some(b)) => Some(::std::cmp::min(a, b)),
+                (a, b) => a.or(b),
+            };
+            let msg = match self.this.clock.recv_until(&self.rx, next_due) {
+                Ok(Some(msg)) => {
+                    let delay = self.this.scheduler.chaos_delay();
+                    if delay > Duration::new(0, 0) {
+                        sleep(delay);
+                    }
+                    msg
+                },
+                Ok(None) => {
+                    self.complete_timed_swaps();
+                    self.completions.post_due(self.this.clock.now());
+                    continue;
+                },
                 Err(_) => {
                     return;
                 },
             };
+            let _handled = Handled(&*scheduler, id);
 
             match msg {
                 Ping(ret) => {
                     let _ = ret.send(());
                 },
+                ClockChanged => { },
                 Destroy { ret, } => {
-                    // TODO XXX XXX
+                    // Like Chrome, nothing pending outlives the instance.
+                    self.completions.abort(None);
+                    self.abort_swaps();
 
                     self.parent.destroy_instance(self.this.id(), ret);
                 },
@@ -729,6 +1227,12 @@ impl InstanceState {
                     self.resources.insert(res.id(), res);
                 },
                 Message::ResourceDtor(res) => {
+                    self.completions.abort(Some(res.id()));
+                    if let &ResState::Audio(ref audio) = res.state() {
+                        // Don't wait; the callback might be blocked on us.
+                        let _ = audio.stop(false);
+                    }
+
                     let id = res.id();
                     if id == self.temp_fs_man.id() {
                         self.temp_fs_man.close();
@@ -748,6 +1252,130 @@ impl InstanceState {
                     let _ = ret.send(Ok(vars));
                 },
 
+                CreateAudio {
+                    ret, config, callback, user_data,
+                } => {
+                    let model = self.audio_model;
+                    let this = self.this.clone();
+                    let ret_v = self
+                        .with_typed_resource(Ok(()), config,
+                                             |config: AudioConfig, _| {
+                                                 AudioState::create(&this, config, model,
+                                                                    callback, user_data)
+                                             });
+                    let _ = ret.send(ret_v);
+                },
+                SetAudioOutputModel(model) => {
+                    self.audio_model = model;
+                },
+
+                StartTimeline => {
+                    self.timeline = Some(Timeline::new());
+                },
+                TakeTimeline(ret) => {
+                    let _ = ret.send(self.timeline.take().unwrap_or_default());
+                },
+                RecordTimeline(entry) => {
+                    if let Some(ref mut timeline) = self.timeline {
+                        timeline.push(entry);
+                    }
+                },
+
+                StartFrameCapture => {
+                    self.capture = Some(Vec::new());
+                },
+                TakeFrames(ret) => {
+                    let _ = ret.send(self.capture.take().unwrap_or_default());
+                },
+                RecordFrame(frame) => {
+                    if let Some(ref mut frames) = self.capture {
+                        frames.push(frame);
+                    }
+                },
+
+                ViewChanged(view) => {
+                    let was = self.view.visibility();
+                    self.view = view;
+                    if was != view.visibility() {
+                        self.reschedule_swaps();
+                    }
+                },
+                SetSwapThrottling(throttling) => {
+                    self.swap_throttling = throttling;
+                    self.reschedule_swaps();
+                },
+                Message::SwapAck(ack) => {
+                    self.schedule_swap(ack);
+                },
+
+                SetCompletion(completion) => {
+                    self.completions.set_policy(completion, self.this.clock.now());
+                },
+                Complete(pending) => {
+                    self.completions.schedule(pending, self.this.clock.now());
+                },
+                AbortCompletions(resource) => {
+                    self.completions.abort(Some(resource));
+                },
+                GetHeldCompletions(ret) => {
+                    let _ = ret.send(self.completions.held());
+                },
+                ReleaseCompletions {
+                    ret, count,
+                } => {
+                    let _ = ret.send(self.completions.release(count));
+                },
+
+                LoseGraphics3DContexts(ret) => {
+                    let lost = self.resources
+                        .values()
+                        .filter(|res| match res.state() {
+                            &ResState::Graphics3D(ref g3d) => g3d.lose(),
+                            _ => false,
+                        })
+                        .count();
+                    if lost > 0 {
+                        self.reschedule_swaps();
+                    }
+                    let _ = ret.send(lost);
+                },
+                BindGraphics {
+                    ret, device,
+                } => {
+                    let ret_v = if device == 0 {
+                        Ok(None)
+                    } else {
+                        self.with_typed_resource(Ok(()), device,
+                                                 |g3d: Graphics3D, _| Ok(Some(g3d)) )
+                    };
+                    let ret_v = ret_v.map(|bound| { self.bound_graphics = bound; });
+                    let _ = ret.send(ret_v);
+                },
+                GetBoundGraphics(ret) => {
+                    let _ = ret.send(self.bound_graphics.as_ref().map(|g| g.id() ));
+                },
+                RequestInputEvents {
+                    classes, filtering,
+                } => {
+                    self.input_event_requests.request(classes, filtering);
+                },
+                ClearInputEventRequest(classes) => {
+                    self.input_event_requests.clear(classes);
+                },
+                GetInputEventRequests(ret) => {
+                    let _ = ret.send(self.input_event_requests);
+                },
+                SetCursor(cursor) => {
+                    let change = CursorChange {
+                        ts: self.seconds_elapsed(),
+                        cursor: cursor,
+                    };
+                    self.cursor_history.push(change);
+                },
+                GetCursorHistory(ret) => {
+                    let _ = ret.send(self.cursor_history.clone());
+                },
+
                 Message::PostMessage(msg) => {
                     if let Some(tx) = self.post_msg_dest.take() {
                         if tx.send(msg).is_ok() {
@@ -974,6 +1602,7 @@ impl InstanceState {
                 CloseFileIo {
                     ret, io,
                 } => {
+                    self.completions.abort(Some(io));
                     let ret_v = self
                         .with_typed_resource(Ok(()), io,
                                              |io: FileIo, _| {
@@ -987,9 +1616,21 @@ impl InstanceState {
     }
 }
 
+extern "C" fn bind_graphics(instance: PP_Instance, device: PP_Resource) -> sys::PP_Bool {
+    let bound = super::ModuleInterface::get_instance_interface(instance)
+        .and_then(|i| i.bind_graphics(device) );
+    if bound.is_ok() { sys::PP_TRUE } else { sys::PP_FALSE }
+}
+extern "C" fn is_full_frame(instance: PP_Instance) -> sys::PP_Bool {
+    match super::ModuleInterface::get_instance_interface(instance) {
+        Ok(ref i) if i.is_full_frame() => sys::PP_TRUE,
+        _ => sys::PP_FALSE,
+    }
+}
+
 static INSTANCE_INTERFACE: sys::PPB_Instance_1_0 = sys::PPB_Instance_1_0 {
-    BindGraphics: Some(ret_false_stub),
-    IsFullFrame: Some(ret_false_stub),
+    BindGraphics: Some(bind_graphics),
+    IsFullFrame: Some(is_full_frame),
 };
 
 pub static INTERFACES: Interfaces = &[
@@ -17,10 +17,10 @@ use self::sys::*;
 
 use std::collections::HashMap;
 use std::ffi::CString;
-use std::sync::{RwLock};
+use std::sync::{Arc, RwLock};
 use std::sync::atomic::{AtomicPtr};
-use std::sync::mpsc::{Sender, Receiver, channel};
-use std::time::{Duration, Instant, SystemTime};
+use std::sync::mpsc::{Sender, SendError, Receiver, channel};
+use std::time::Duration;
 use std::thread::JoinHandle;
 
 pub use self::result::{Code, Error};
@@ -33,20 +33,22 @@ pub mod ppapi { pub use super::*; }
 macro_rules! ppb_f {
     (R($res:expr), $callback:expr $(,$arg:expr)* => $fn_name:ident) => ({
         use ::result::ResultCode;
-        let callback = match ::ppapi::callback::Callback::from_ffi($callback) {
+        let instance = match ::ppapi::resource::get_resource_instance($res) {
+            Some(instance) => instance,
+            None => { return ::ppapi::sys::PP_ERROR_BADRESOURCE; },
+        };
+        let callback = match ::ppapi::callback::Callback::from_ffi($callback, &instance) {
             Ok(cb) => cb,
             Err(code) => {
                 let err: ::ppapi::result::Code<()> = Err(code);
                 return err.into_code();
             },
         };
-        let ret = if let Some(instance) = ::ppapi::resource::get_resource_instance($res) {
-            instance.$fn_name($res, $($arg,)* callback)
-        } else {
-            Err(::ppapi::result::Error::BadResource)
-        };
-
-        ret.into_code()
+        // These all finish before returning, so are run as blocking calls
+        // whenever the callback says.
+        let i = instance.clone();
+        let operation = move || i.$fn_name($res, $($arg,)* ::ppapi::callback::Callback::Sync);
+        callback.complete(&instance, $res, operation).into_code()
     })
 }
 
@@ -61,15 +63,24 @@ pub mod console;
 pub mod file_io;
 pub mod file_ref;
 pub mod resource;
+pub mod scheduler;
 pub mod instance;
 pub mod result;
 pub mod callback;
+pub mod capture;
+pub mod clock;
+pub mod completion;
 pub mod var;
 pub mod filesystem_manager;
 pub mod url_loader;
 pub mod graphics;
+pub mod gles2;
+pub mod image_data;
+pub mod input_event;
+pub mod raster;
 pub mod mouse;
 pub mod messaging;
+pub mod timeline;
 pub mod view;
 
 mod interface;
@@ -89,18 +100,71 @@ pub struct ModuleHandle(PP_Module);
 #[derive(Clone)]
 pub struct ModuleInterface {
     id: ModuleHandle,
-    start_ts: Instant,
+    clock: Arc<clock::Clock>,
+    scheduler: Arc<scheduler::Scheduler>,
     tx: Sender<Message>,
+    /// The message loop attached to the module thread, ie the main thread.
+    main_loop: PP_Resource,
+}
+
+/// What the module is told about a new instance after `DidCreate`, before
+/// `create_instance` returns.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Lifecycle {
+    /// Nothing; the test drives every call itself.
+    Raw,
+    /// Chrome's sequence: `DidChangeView` with the initial layout, then
+    /// `DidChangeFocus`, then for full-frame instances `HandleDocumentLoad`.
+    Chrome {
+        view: view::ViewData,
+        has_focus: bool,
+        /// Makes a full-frame instance for a navigation to this URL. Its
+        /// fixture must have been added to `url_loader::url_manager()`.
+        document: Option<String>,
+    },
+}
+impl Lifecycle {
+    /// The instance Chrome creates for a navigation straight to `url`: it
+    /// fills the tab and has focus.
+    pub fn full_frame(url: &str, view: view::ViewData) -> Lifecycle {
+        Lifecycle::Chrome {
+            view: view,
+            has_focus: true,
+            document: Some(url.to_string()),
+        }
+    }
+    fn document(&self) -> Option<&str> {
+        match *self {
+            Lifecycle::Chrome { document: Some(ref url), .. } => Some(&url[..]),
+            _ => None,
+        }
+    }
+}
+impl Default for Lifecycle {
+    /// Chrome's, with the view an instance has before layout and no focus.
+    fn default() -> Lifecycle {
+        Lifecycle::Chrome {
+            view: Default::default(),
+            has_focus: false,
+            document: None,
+        }
+    }
 }
 
 impl ModuleInterface {
+    /// Creates an instance with the default (Chrome's) lifecycle.
     pub fn create_instance(&self, args: Vec<(String, String)>) -> Code<Instance> {
+        self.create_instance_with(args, Default::default())
+    }
+    pub fn create_instance_with(&self, args: Vec<(String, String)>,
+                                lifecycle: Lifecycle) -> Code<Instance> {
         let (tx, rx) = channel();
         let msg = Message::CreateInstance {
             ret: tx,
             args: From::from(args),
+            lifecycle: lifecycle,
         };
-        self.tx.send(msg).unwrap();
+        self.dispatch(msg).unwrap();
         rx.recv()
             .unwrap()
             .map(|instance| {
@@ -111,27 +175,89 @@ impl ModuleInterface {
 
     /// Called only from the instance threads.
     fn destroy_instance(&self, id: PP_Instance, ret: Sender<Code<()>>) {
-        self.tx.send(Message::DestroyInstance {
+        self.dispatch(Message::DestroyInstance {
             id: id, ret: ret,
         }).unwrap();
     }
 
+    /// Runs `PPP_Graphics3D::Graphics3DContextLost` on the module thread, if
+    /// the module has it. Returns once it has been called.
+    pub fn graphics_3d_context_lost(&self, id: PP_Instance) -> Code<()> {
+        let (tx, rx) = channel();
+        try!(self.dispatch(Message::Graphics3DContextLost {
+            ret: tx, id: id,
+        }).map_err(|_| Error::Aborted ));
+        rx.recv().map_err(|_| Error::Aborted )
+    }
+
+    /// Creates a View resource from `view` and passes it to
+    /// `PPP_Instance::DidChangeView` on the module thread. The module has to
+    /// AddRef the view to keep it past the call, as in Chrome.
+    pub fn change_view(&self, id: PP_Instance, view: view::ViewData) -> Code<()> {
+        let (tx, rx) = channel();
+        try!(self.dispatch(Message::ChangeView {
+            ret: tx, id: id, view: view,
+        }).map_err(|_| Error::Aborted ));
+        rx.recv().map_err(|_| Error::Aborted )
+            .and_then(|r| r )
+    }
+
+    /// Passes focus changes to `PPP_Instance::DidChangeFocus` on the module
+    /// thread.
+    pub fn change_focus(&self, id: PP_Instance, has_focus: bool) -> Code<()> {
+        let (tx, rx) = channel();
+        try!(self.dispatch(Message::ChangeFocus {
+            ret: tx, id: id, has_focus: has_focus,
+        }).map_err(|_| Error::Aborted ));
+        rx.recv().map_err(|_| Error::Aborted )
+            .and_then(|r| r )
+    }
+
+    /// Passes `event` to `PPP_InputEvent::HandleInputEvent` on the module
+    /// thread, returning whether the module handled it. Modules that don't
+    /// export `PPP_InputEvent` handle nothing.
+    pub fn handle_input_event(&self, id: PP_Instance,
+                              event: input_event::Event) -> Code<bool> {
+        let (tx, rx) = channel();
+        try!(self.dispatch(Message::HandleInputEvent {
+            ret: tx, id: id, event: event,
+        }).map_err(|_| Error::Aborted ));
+        rx.recv().map_err(|_| Error::Aborted )
+            .and_then(|r| r )
+    }
+
+    /// Sends a PPP call to the module thread, which makes it main loop work
+    /// as far as the scheduler's concerned.
+    fn dispatch(&self, msg: Message) -> Result<(), SendError<Message>> {
+        self.scheduler.posted(self.main_loop);
+        let sent = self.tx.send(msg);
+        if sent.is_err() {
+            self.scheduler.unqueued(self.main_loop);
+        }
+        sent
+    }
+
     pub fn get_instance_interface(id: PP_Instance) -> Code<Instance> {
         ModuleInstances::get(id).ok_or(Error::BadInstance)
     }
 
     pub fn id(&self) -> ModuleHandle { self.id }
+    /// The main thread's message loop. It lives as long as the module.
+    pub fn main_message_loop(&self) -> PP_Resource { self.main_loop }
+    /// Have the module thread run what's been posted to the main loop.
+    #[doc(hidden)]
+    pub fn wake_main_thread(&self) {
+        let _ = self.tx.send(Message::MainThreadWork);
+    }
+    /// What the module's time functions, timers and throttled loads go by.
+    pub fn clock(&self) -> &Arc<clock::Clock> { &self.clock }
+    /// How its message loops take turns.
+    pub fn scheduler(&self) -> &Arc<scheduler::Scheduler> { &self.scheduler }
     pub fn seconds_elapsed(&self) -> PP_TimeTicks {
-        let elapsed = Instant::now().duration_since(self.start_ts);
-        duration_to_seconds(elapsed)
+        duration_to_seconds(self.clock.elapsed())
     }
     pub fn wall_time(&self) -> PP_Time {
-        use std::time::UNIX_EPOCH;
-        let elapsed = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap();
-
-        duration_to_seconds(elapsed)
+        duration_to_seconds(self.clock.since_epoch())
     }
 }
 
@@ -171,12 +297,22 @@ static INSTANCE_CALLBACKS: AtomicPtr<InstanceInterfaces> =
 #[derive(Clone, Copy)]
 struct InstanceInterfaces {
     instance: Option<&'static PPP_Instance_1_1>,
+    graphics_3d: Option<&'static PPP_Graphics3D_1_0>,
+    input_event: Option<&'static PPP_InputEvent_0_1>,
 }
 impl InstanceInterfaces {
     pub fn instance() -> &'static PPP_Instance_1_1 {
         get_ppp().instance
             .expect("missing instance interface!")
     }
+    /// Optional; modules not using Graphics3D don't export it.
+    pub fn graphics_3d() -> Option<&'static PPP_Graphics3D_1_0> {
+        get_ppp().graphics_3d
+    }
+    /// Optional too; only needed to receive input events.
+    pub fn input_event() -> Option<&'static PPP_InputEvent_0_1> {
+        get_ppp().input_event
+    }
 }
 impl Default for InstanceInterfaces {
     fn default() -> InstanceInterfaces {
@@ -194,8 +330,24 @@ impl Default for InstanceInterfaces {
             instance
         });
 
+        let iptr = unsafe { PPP_GetInterface("PPP_Graphics3D;1.0\0".as_ptr() as *const i8) };
+        let graphics_3d = unsafe { iptr.as_ref() };
+        let graphics_3d = graphics_3d.map(|iptr| {
+            let graphics_3d: &'static PPP_Graphics3D_1_0 = unsafe { transmute(iptr) };
+            graphics_3d
+        });
+
+        let iptr = unsafe { PPP_GetInterface("PPP_InputEvent;0.1\0".as_ptr() as *const i8) };
+        let input_event = unsafe { iptr.as_ref() };
+        let input_event = input_event.map(|iptr| {
+            let input_event: &'static PPP_InputEvent_0_1 = unsafe { transmute(iptr) };
+            input_event
+        });
+
         InstanceInterfaces {
             instance: instance,
+            graphics_3d: graphics_3d,
+            input_event: input_event,
         }
     }
 }
@@ -230,10 +382,51 @@ fn drop_instance(instance: PP_Instance) {
     (ppp.destroy)(instance);
 }
 
+/// Module thread only. The view resource lives only for the call.
+fn did_change_view(i: &Instance, view: view::ViewData) {
+    let ppp = InstanceInterfaces::instance();
+    i.view_changed(view);
+    let view = view::ViewState::create(i, view);
+    (ppp.change_view)(i.id(), view.id());
+}
+/// Module thread only. Like views, the loader is only kept if the module
+/// AddRefs it.
+fn handle_document_load(i: &Instance, url: &str) {
+    let ppp = InstanceInterfaces::instance();
+    match url_loader::UrlLoaderState::open_document(i, url) {
+        Ok(loader) => {
+            if (ppp.handle_document_load)(i.id(), loader.id()) == sys::PP_FALSE {
+                warn!("instance {} didn't handle the document load of `{}`", i.id(), url);
+            }
+        },
+        Err(err) => {
+            error!("couldn't open `{}` for instance {}: {:?}", url, i.id(), err);
+        },
+    }
+}
+/// Module thread only.
+fn did_change_focus(instance: PP_Instance, has_focus: bool) {
+    let ppp = InstanceInterfaces::instance();
+    let has_focus = if has_focus { sys::PP_TRUE } else { sys::PP_FALSE };
+    (ppp.change_focus)(instance, has_focus);
+}
+/// Module thread only. The event resource, like views, lives only for the
+/// call.
+fn handle_input_event(i: &Instance, time_stamp: sys::PP_TimeTicks,
+                      event: input_event::Event) -> bool {
+    let ppp = match InstanceInterfaces::input_event() {
+        Some(ppp) => ppp,
+        None => { return false; },
+    };
+    let event = input_event::InputEventState::create(i, time_stamp, event);
+    (ppp.HandleInputEvent)(i.id(), event.id()) != sys::PP_FALSE
+}
+
 struct ModuleState {
     this: ModuleInterface,
 
     rx: Receiver<Message>,
+    main_loop: callback::MessageLoop,
 }
 impl ModuleState {
     fn new(id: ModuleHandle) -> ModuleInterface {
@@ -241,18 +434,33 @@ impl ModuleState {
 
         let (tx, rx) = channel();
 
+        let clock = Arc::new(clock::Clock::new());
+        let scheduler = Arc::new(scheduler::Scheduler::new());
+        let owner = instance::Instance::module_owner(id, clock.clone(), scheduler.clone());
+        let main_loop = callback::MessageLoopState::create(owner, true)
+            .expect("couldn't create the main message loop");
+
+        // Delayed main loop work may have come due.
+        let wake_tx = tx.clone();
+        clock.on_change(Box::new(move || wake_tx.send(Message::MainThreadWork).is_ok() ));
+
         let this = ModuleInterface {
             id: id,
-            start_ts: Instant::now(),
+            clock: clock,
+            scheduler: scheduler,
             tx: tx,
+            main_loop: main_loop.id(),
         };
 
         let state = ModuleState {
             rx: rx,
             this: this.clone(),
+            main_loop: main_loop,
         };
 
         spawn(move || {
+            callback::main_thread_init(state.main_loop.clone())
+                .expect("module thread already has a message loop");
             state.run();
         });
 
@@ -266,16 +474,53 @@ impl ModuleState {
         ModuleInstances::remove(id)
     }
 
+    fn run_main_loop(&self) {
+        if let Err(err) = self.main_loop.run_pending() {
+            error!("main message loop failed: {:?}", err);
+        }
+    }
+
     fn run(self) {
-        while let Ok(msg) = self.rx.recv() {
+        loop {
+            let msg = match self.this.clock.recv_until(&self.rx, self.main_loop.next_due()) {
+                Ok(Some(msg)) => msg,
+                Ok(None) => {
+                    self.run_main_loop();
+                    continue;
+                },
+                Err(_) => {
+                    return;
+                },
+            };
+
+            // The main loop's work takes its own turns.
+            let _turn = match msg {
+                Message::MainThreadWork => None,
+                _ => Some(self.this.scheduler.turn(self.main_loop.id(), true)),
+            };
+
             match msg {
+                Message::MainThreadWork => {
+                    self.run_main_loop();
+                },
                 Message::CreateInstance {
-                    ret, args,
+                    ret, args, lifecycle,
                 } => {
                     let ppp = InstanceInterfaces::instance();
 
+                    // Chrome only picks the plugin for a navigation it could
+                    // load.
+                    let document = lifecycle.document()
+                        .map(|url| url::Url::parse(url).ok()
+                             .and_then(|url| url_loader::url_manager().get(&url) ) );
+                    if let Some(None) = document {
+                        let _ = ret.send(Err(Error::BadArgument));
+                        continue;
+                    }
+
                     let id = take_instance_id();
-                    let (join, instance) = self::instance::InstanceState::new(id, self.this.clone());
+                    let (join, instance) = self::instance::InstanceState::new(id, self.this.clone(),
+                                                                              document.is_some());
                     Self::insert_instance(id, join, instance.clone());
 
                     let success = (ppp.create)(id, args.len() as libc::uint32_t,
@@ -287,6 +532,16 @@ impl ModuleState {
                         }
                         Err(Error::Aborted)
                     } else {
+                        match lifecycle {
+                            Lifecycle::Raw => {},
+                            Lifecycle::Chrome { view, has_focus, ref document, } => {
+                                did_change_view(&instance, view);
+                                did_change_focus(id, has_focus);
+                                if let &Some(ref url) = document {
+                                    handle_document_load(&instance, url);
+                                }
+                            },
+                        }
                         Ok(instance)
                     };
 
@@ -312,6 +567,39 @@ impl ModuleState {
 
                     let _ = ret.send(ret_v);
                 },
+                Message::ChangeView {
+                    ret, id, view,
+                } => {
+                    let ret_v = ModuleInstances::get(id)
+                        .ok_or(Error::BadInstance)
+                        .map(|i| did_change_view(&i, view) );
+                    let _ = ret.send(ret_v);
+                },
+                Message::ChangeFocus {
+                    ret, id, has_focus,
+                } => {
+                    let ret_v = ModuleInstances::get(id)
+                        .ok_or(Error::BadInstance)
+                        .map(|_| did_change_focus(id, has_focus) );
+                    let _ = ret.send(ret_v);
+                },
+                Message::HandleInputEvent {
+                    ret, id, event,
+                } => {
+                    let time_stamp = self.this.seconds_elapsed();
+                    let ret_v = ModuleInstances::get(id)
+                        .ok_or(Error::BadInstance)
+                        .map(|i| handle_input_event(&i, time_stamp, event) );
+                    let _ = ret.send(ret_v);
+                },
+                Message::Graphics3DContextLost {
+                    ret, id,
+                } => {
+                    if let Some(ppp) = InstanceInterfaces::graphics_3d() {
+                        (ppp.Graphics3DContextLost)(id);
+                    }
+                    let _ = ret.send(());
+                },
             }
         }
     }
@@ -357,14 +645,36 @@ impl From<Vec<(String, String)>> for PreprocessedCArgs {
 unsafe impl Send for PreprocessedCArgs { }
 
 enum Message {
+    /// Something was posted to the main loop.
+    MainThreadWork,
     CreateInstance {
         ret: Sender<Code<Instance>>,
         args: PreprocessedCArgs,
+        lifecycle: Lifecycle,
     },
     DestroyInstance {
         ret: Sender<Code<()>>,
         id: sys::PP_Instance,
     },
+    ChangeView {
+        ret: Sender<Code<()>>,
+        id: sys::PP_Instance,
+        view: view::ViewData,
+    },
+    ChangeFocus {
+        ret: Sender<Code<()>>,
+        id: sys::PP_Instance,
+        has_focus: bool,
+    },
+    HandleInputEvent {
+        ret: Sender<Code<bool>>,
+        id: sys::PP_Instance,
+        event: input_event::Event,
+    },
+    Graphics3DContextLost {
+        ret: Sender<()>,
+        id: sys::PP_Instance,
+    },
 }
 
 /// Creates a new module by calling PPP_InitializeModule. Should really only be called once.
@@ -479,6 +789,12 @@ pub extern "C" fn get_interface(name: *const libc::c_char) -> *const libc::c_voi
                            name);
     let r = find_interface(r, graphics::INTERFACES,
                            name);
+    let r = find_interface(r, gles2::INTERFACES,
+                           name);
+    let r = find_interface(r, image_data::INTERFACES,
+                           name);
+    let r = find_interface(r, input_event::INTERFACES,
+                           name);
     let r = find_interface(r, mouse::INTERFACES,
                            name);
     let r = find_interface(r, messaging::INTERFACES,
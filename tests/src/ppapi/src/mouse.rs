@@ -1,9 +1,106 @@
+//! `PPB_MouseCursor`. There's no real cursor; each instance just remembers
+//! what it was last set to, and when, so tests can check eg that the vout
+//! hides it during playback.
 
+use super::ModuleInterface;
+use super::image_data::ImageDataState;
 use super::interface::*;
+use super::resource::get_resource;
 use super::sys::*;
 
+/// Custom cursors bigger than this are refused, as in Chrome.
+pub const MAX_CUSTOM_CURSOR_SIZE: i32 = 32;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Cursor {
+    Standard(PP_MouseCursor_Type),
+    /// The image is copied when the cursor is set, so later changes to the
+    /// ImageData don't show.
+    Custom {
+        size: PP_Size,
+        hot_spot: PP_Point,
+        pixels: Vec<u8>,
+    },
+}
+impl Cursor {
+    pub fn is_hidden(&self) -> bool {
+        self == &Cursor::Standard(PP_MOUSECURSOR_TYPE_NONE)
+    }
+}
+impl Default for Cursor {
+    fn default() -> Cursor { Cursor::Standard(PP_MOUSECURSOR_TYPE_POINTER) }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CursorChange {
+    /// Module ticks.
+    pub ts: PP_TimeTicks,
+    pub cursor: Cursor,
+}
+
+/// When the cursor was hidden, if it still is at the end of `history`.
+pub fn hidden_since(history: &[CursorChange]) -> Option<PP_TimeTicks> {
+    if !history.last().map(|c| c.cursor.is_hidden() ).unwrap_or(false) {
+        return None;
+    }
+    history.iter()
+        .rev()
+        .take_while(|c| c.cursor.is_hidden() )
+        .last()
+        .map(|c| c.ts )
+}
+
+/// Chrome's checks, from `PPB_Instance_Shared::ValidateSetCursorParams`.
+fn cursor_from_params(kind: PP_MouseCursor_Type, image: PP_Resource,
+                      hot_spot: *const PP_Point) -> Option<Cursor> {
+    if kind < PP_MOUSECURSOR_TYPE_CUSTOM || kind > PP_MOUSECURSOR_TYPE_GRABBING {
+        return None;
+    }
+    if kind != PP_MOUSECURSOR_TYPE_CUSTOM {
+        return Some(Cursor::Standard(kind));
+    }
+
+    let hot_spot = match unsafe { hot_spot.as_ref() } {
+        Some(&hot_spot) => hot_spot,
+        None => { return None; },
+    };
+    let image = match get_resource::<ImageDataState>(image) {
+        Ok(image) => image,
+        Err(_) => { return None; },
+    };
+    let size = image.size();
+    if size.width > MAX_CUSTOM_CURSOR_SIZE || size.height > MAX_CUSTOM_CURSOR_SIZE {
+        return None;
+    }
+    if hot_spot.x < 0 || hot_spot.x >= size.width ||
+        hot_spot.y < 0 || hot_spot.y >= size.height {
+        return None;
+    }
+
+    Some(Cursor::Custom {
+        size: size,
+        hot_spot: hot_spot,
+        pixels: image.pixels(),
+    })
+}
+
+extern "C" fn set_cursor(instance: PP_Instance, kind: PP_MouseCursor_Type,
+                         image: PP_Resource, hot_spot: *const PP_Point) -> PP_Bool {
+    let i = match ModuleInterface::get_instance_interface(instance) {
+        Ok(i) => i,
+        Err(_) => { return PP_FALSE; },
+    };
+    match cursor_from_params(kind, image, hot_spot) {
+        Some(cursor) => {
+            i.set_cursor(cursor);
+            PP_TRUE
+        },
+        None => PP_FALSE,
+    }
+}
+
 static MOUSE_CURSOR_INTERFACE: PPB_MouseCursor_1_0 = PPB_MouseCursor_1_0 {
-    SetCursor: Some(ret_false_stub),
+    SetCursor: Some(set_cursor),
 };
 pub static INTERFACES: Interfaces = &[
     ("PPB_MouseCursor;1.0", interface_ptr(&MOUSE_CURSOR_INTERFACE)),
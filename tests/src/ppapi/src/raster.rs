@@ -0,0 +1,405 @@
+//! The CPU side of the software GLES2 backend: textures, the color buffer and
+//! triangle setup. Knows nothing about GL objects or shaders; see `gles2`.
+
+use std::cmp::{max, min};
+
+use super::sys::*;
+
+pub type Vec4 = [f32; 4];
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+impl Rect {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Rect {
+        Rect { x: x, y: y, width: width, height: height, }
+    }
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        // In i64; the module sets the scissor and viewport to anything.
+        let x0 = max(self.x, other.x);
+        let y0 = max(self.y, other.y);
+        let x1 = min(self.x as i64 + self.width as i64, other.x as i64 + other.width as i64);
+        let y1 = min(self.y as i64 + self.height as i64, other.y as i64 + other.height as i64);
+        let clamp = |len: i64| min(max(len, 0), i32::max_value() as i64) as i32;
+        Rect::new(x0, y0, clamp(x1 - x0 as i64), clamp(y1 - y0 as i64))
+    }
+}
+
+/// Number of components in a client pixel of `format`.
+pub fn format_components(format: GLenum) -> Option<usize> {
+    match format {
+        GL_ALPHA | GL_LUMINANCE => Some(1),
+        GL_LUMINANCE_ALPHA => Some(2),
+        GL_RGB => Some(3),
+        GL_RGBA => Some(4),
+        _ => None,
+    }
+}
+/// Bytes between the starts of consecutive client rows.
+pub fn row_stride(width: usize, components: usize, alignment: usize) -> usize {
+    let len = width * components;
+    (len + alignment - 1) / alignment * alignment
+}
+
+/// Expand a client pixel to RGBA, as the GL does when sampling.
+fn expand(format: GLenum, px: &[u8]) -> [u8; 4] {
+    match format {
+        GL_ALPHA => [0, 0, 0, px[0]],
+        GL_LUMINANCE => [px[0], px[0], px[0], 255],
+        GL_LUMINANCE_ALPHA => [px[0], px[0], px[0], px[1]],
+        GL_RGB => [px[0], px[1], px[2], 255],
+        GL_RGBA => [px[0], px[1], px[2], px[3]],
+        _ => unreachable!(),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+    pub format: GLenum,
+    /// Row 0 is the first row uploaded, ie `t == 0`.
+    texels: Vec<[u8; 4]>,
+    /// Only level 0 is kept; this just remembers that more were given.
+    pub mipmapped: bool,
+
+    pub min_filter: GLenum,
+    pub mag_filter: GLenum,
+    pub wrap_s: GLenum,
+    pub wrap_t: GLenum,
+}
+impl Default for Texture {
+    fn default() -> Texture {
+        Texture {
+            width: 0,
+            height: 0,
+            format: GL_RGBA,
+            texels: Vec::new(),
+            mipmapped: false,
+            min_filter: GL_NEAREST_MIPMAP_LINEAR,
+            mag_filter: GL_LINEAR,
+            wrap_s: GL_REPEAT,
+            wrap_t: GL_REPEAT,
+        }
+    }
+}
+impl Texture {
+    /// Replace level 0 with `width`x`height` pixels of `format`. `pixels` may
+    /// be null, in which case the texture is black.
+    pub unsafe fn image(&mut self, width: usize, height: usize, format: GLenum,
+                        alignment: usize, pixels: *const u8) {
+        self.width = width;
+        self.height = height;
+        self.format = format;
+        self.texels = vec![expand(format, &[0, 0, 0, 0]); width * height];
+        if !pixels.is_null() {
+            self.sub_image(0, 0, width, height, alignment, pixels);
+        }
+    }
+    /// The caller has checked the bounds and the format.
+    pub unsafe fn sub_image(&mut self, x: usize, y: usize, width: usize,
+                            height: usize, alignment: usize, pixels: *const u8) {
+        use std::slice::from_raw_parts;
+
+        let components = format_components(self.format).unwrap();
+        let stride = row_stride(width, components, alignment);
+        for row in 0..height {
+            let src = from_raw_parts(pixels.offset((row * stride) as isize),
+                                     width * components);
+            let dst_start = (y + row) * self.width + x;
+            for (col, px) in src.chunks(components).enumerate() {
+                self.texels[dst_start + col] = expand(self.format, px);
+            }
+        }
+    }
+
+    fn power_of_two(&self) -> bool {
+        self.width.is_power_of_two() && self.height.is_power_of_two()
+    }
+    /// Sampling an incomplete texture returns opaque black.
+    pub fn complete(&self) -> bool {
+        if self.width == 0 || self.height == 0 { return false; }
+
+        let mipmap_filter = match self.min_filter {
+            GL_NEAREST | GL_LINEAR => false,
+            _ => true,
+        };
+        if mipmap_filter && !self.mipmapped &&
+            (self.width > 1 || self.height > 1)
+        {
+            return false;
+        }
+        // GLES2 limits on non power of two textures.
+        if !self.power_of_two() &&
+            (mipmap_filter || self.wrap_s != GL_CLAMP_TO_EDGE ||
+             self.wrap_t != GL_CLAMP_TO_EDGE)
+        {
+            return false;
+        }
+
+        true
+    }
+
+    fn wrap(mode: GLenum, i: i64, size: usize) -> usize {
+        let size = size as i64;
+        let i = match mode {
+            GL_REPEAT => ((i % size) + size) % size,
+            GL_MIRRORED_REPEAT => {
+                let period = size * 2;
+                let m = ((i % period) + period) % period;
+                if m >= size { period - 1 - m } else { m }
+            },
+            _ => max(0, min(i, size - 1)),
+        };
+        i as usize
+    }
+    fn texel(&self, i: i64, j: i64) -> Vec4 {
+        let i = Texture::wrap(self.wrap_s, i, self.width);
+        let j = Texture::wrap(self.wrap_t, j, self.height);
+        let t = self.texels[j * self.width + i];
+        [t[0] as f32 / 255.0, t[1] as f32 / 255.0,
+         t[2] as f32 / 255.0, t[3] as f32 / 255.0]
+    }
+
+    /// There are no derivatives to pick between the min and mag filters with,
+    /// so the mag filter is always used.
+    pub fn sample(&self, s: f32, t: f32) -> Vec4 {
+        if !self.complete() { return [0.0, 0.0, 0.0, 1.0]; }
+
+        let u = s * self.width as f32;
+        let v = t * self.height as f32;
+        if self.mag_filter == GL_NEAREST {
+            return self.texel(u.floor() as i64, v.floor() as i64);
+        }
+
+        let u = u - 0.5;
+        let v = v - 0.5;
+        let (i0, j0) = (u.floor() as i64, v.floor() as i64);
+        let (a, b) = (u - u.floor(), v - v.floor());
+        let t00 = self.texel(i0, j0);
+        let t10 = self.texel(i0 + 1, j0);
+        let t01 = self.texel(i0, j0 + 1);
+        let t11 = self.texel(i0 + 1, j0 + 1);
+        let mut out = [0.0f32; 4];
+        for c in 0..4 {
+            out[c] = (1.0 - a) * (1.0 - b) * t00[c] + a * (1.0 - b) * t10[c] +
+                (1.0 - a) * b * t01[c] + a * b * t11[c];
+        }
+        out
+    }
+}
+
+/// An RGBA8 color buffer. Row 0 is the bottom row, like `glReadPixels`.
+#[derive(Clone, Debug, Default)]
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        Framebuffer {
+            width: width,
+            height: height,
+            pixels: vec![0; width * height * 4],
+        }
+    }
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width as i32, self.height as i32)
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let i = (y * self.width + x) * 4;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]]
+    }
+    /// The pixels with the top row first, like an image file.
+    pub fn top_down(&self) -> Vec<u8> {
+        let stride = self.width * 4;
+        let mut out = Vec::with_capacity(self.pixels.len());
+        for y in (0..self.height).rev() {
+            out.extend_from_slice(&self.pixels[y * stride..(y + 1) * stride]);
+        }
+        out
+    }
+    fn put(&mut self, x: usize, y: usize, px: [u8; 4]) {
+        let i = (y * self.width + x) * 4;
+        self.pixels[i..i + 4].copy_from_slice(&px[..]);
+    }
+
+    pub fn clear(&mut self, color: Vec4, area: Rect) {
+        let px = to_rgba8(color);
+        let area = area.intersect(&self.bounds());
+        for y in area.y..area.y + area.height {
+            for x in area.x..area.x + area.width {
+                self.put(x as usize, y as usize, px);
+            }
+        }
+    }
+}
+
+pub fn to_rgba8(c: Vec4) -> [u8; 4] {
+    fn conv(v: f32) -> u8 {
+        let v = if v < 0.0 { 0.0 } else if v > 1.0 { 1.0 } else { v };
+        (v * 255.0 + 0.5).floor() as u8
+    }
+    [conv(c[0]), conv(c[1]), conv(c[2]), conv(c[3])]
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Blend {
+    pub enabled: bool,
+    pub src_rgb: GLenum,
+    pub dst_rgb: GLenum,
+    pub src_alpha: GLenum,
+    pub dst_alpha: GLenum,
+}
+impl Default for Blend {
+    fn default() -> Blend {
+        Blend {
+            enabled: false,
+            src_rgb: GL_ONE,
+            dst_rgb: GL_ZERO,
+            src_alpha: GL_ONE,
+            dst_alpha: GL_ZERO,
+        }
+    }
+}
+pub fn valid_blend_factor(factor: GLenum) -> bool {
+    match factor {
+        GL_ZERO | GL_ONE | GL_SRC_COLOR | GL_ONE_MINUS_SRC_COLOR |
+        GL_SRC_ALPHA | GL_ONE_MINUS_SRC_ALPHA | GL_DST_ALPHA |
+        GL_ONE_MINUS_DST_ALPHA | GL_DST_COLOR | GL_ONE_MINUS_DST_COLOR => true,
+        _ => false,
+    }
+}
+impl Blend {
+    fn factor(factor: GLenum, c: usize, src: &Vec4, dst: &Vec4) -> f32 {
+        match factor {
+            GL_ZERO => 0.0,
+            GL_ONE => 1.0,
+            GL_SRC_COLOR => src[c],
+            GL_ONE_MINUS_SRC_COLOR => 1.0 - src[c],
+            GL_SRC_ALPHA => src[3],
+            GL_ONE_MINUS_SRC_ALPHA => 1.0 - src[3],
+            GL_DST_ALPHA => dst[3],
+            GL_ONE_MINUS_DST_ALPHA => 1.0 - dst[3],
+            GL_DST_COLOR => dst[c],
+            GL_ONE_MINUS_DST_COLOR => 1.0 - dst[c],
+            _ => unreachable!(),
+        }
+    }
+    fn apply(&self, src: Vec4, dst: [u8; 4]) -> Vec4 {
+        if !self.enabled { return src; }
+
+        let mut src = src;
+        for c in src.iter_mut() {
+            *c = if *c < 0.0 { 0.0 } else if *c > 1.0 { 1.0 } else { *c };
+        }
+        let dst = [dst[0] as f32 / 255.0, dst[1] as f32 / 255.0,
+                   dst[2] as f32 / 255.0, dst[3] as f32 / 255.0];
+        let mut out = [0.0f32; 4];
+        for c in 0..4 {
+            let (sf, df) = if c == 3 {
+                (self.src_alpha, self.dst_alpha)
+            } else {
+                (self.src_rgb, self.dst_rgb)
+            };
+            out[c] = src[c] * Blend::factor(sf, c, &src, &dst) +
+                dst[c] * Blend::factor(df, c, &src, &dst);
+        }
+        out
+    }
+}
+
+/// A shaded vertex: clip space position and the varyings for the fragment
+/// stage.
+#[derive(Clone, Debug)]
+pub struct Vertex {
+    pub position: Vec4,
+    pub varyings: Vec<Vec4>,
+}
+
+fn edge(a: (f32, f32), b: (f32, f32), p: (f32, f32)) -> f32 {
+    (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
+/// Pixel centers exactly on an edge belong to the triangle only for "top" or
+/// "left" edges, so quads split into two triangles don't blend twice along
+/// the diagonal.
+fn top_left(a: (f32, f32), b: (f32, f32)) -> bool {
+    let dy = b.1 - a.1;
+    dy < 0.0 || (dy == 0.0 && b.0 - a.0 < 0.0)
+}
+
+/// Fill a triangle. `shade` gets the perspective correct varyings and returns
+/// the fragment color. There's no clipping: triangles with a vertex behind
+/// the eye (`w <= 0`) are dropped, and the rest are confined to `area` (the
+/// viewport, cut down by the scissor box).
+pub fn draw_triangle<F>(fb: &mut Framebuffer, viewport: Rect, area: Rect,
+                        blend: &Blend, tri: [&Vertex; 3], mut shade: F)
+    where F: FnMut(&[Vec4]) -> Vec4,
+{
+    if tri.iter().any(|v| !(v.position[3] > 0.0) ) { return; }
+
+    let to_window = |v: &Vertex| -> (f32, f32) {
+        let inv_w = 1.0 / v.position[3];
+        let x = v.position[0] * inv_w;
+        let y = v.position[1] * inv_w;
+        (viewport.x as f32 + (x + 1.0) * viewport.width as f32 / 2.0,
+         viewport.y as f32 + (y + 1.0) * viewport.height as f32 / 2.0)
+    };
+    let a = tri[0];
+    let (mut b, mut c) = (tri[1], tri[2]);
+    let pa = to_window(a);
+    let (mut pb, mut pc) = (to_window(b), to_window(c));
+    let mut area2 = edge(pa, pb, pc);
+    if area2 == 0.0 || area2.is_nan() { return; }
+    if area2 < 0.0 {
+        // Culling isn't supported, so just fix the winding.
+        ::std::mem::swap(&mut b, &mut c);
+        ::std::mem::swap(&mut pb, &mut pc);
+        area2 = -area2;
+    }
+
+    let area = area.intersect(&viewport).intersect(&fb.bounds());
+    let fmin = |v: f32, w: f32, x: f32| v.min(w).min(x);
+    let fmax = |v: f32, w: f32, x: f32| v.max(w).max(x);
+    let x0 = max(area.x, fmin(pa.0, pb.0, pc.0).floor() as i32);
+    let y0 = max(area.y, fmin(pa.1, pb.1, pc.1).floor() as i32);
+    let x1 = min(area.x + area.width, (fmax(pa.0, pb.0, pc.0).ceil() as i32).saturating_add(1));
+    let y1 = min(area.y + area.height, (fmax(pa.1, pb.1, pc.1).ceil() as i32).saturating_add(1));
+
+    let inv_w = [1.0 / a.position[3], 1.0 / b.position[3], 1.0 / c.position[3]];
+    let tl = [top_left(pb, pc), top_left(pc, pa), top_left(pa, pb)];
+    let count = a.varyings.len();
+    let mut varyings = vec![[0.0f32; 4]; count];
+
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let p = (x as f32 + 0.5, y as f32 + 0.5);
+            let w = [edge(pb, pc, p), edge(pc, pa, p), edge(pa, pb, p)];
+            if (0..3).any(|i| w[i] < 0.0 || (w[i] == 0.0 && !tl[i]) ) {
+                continue;
+            }
+
+            let l = [w[0] / area2 * inv_w[0], w[1] / area2 * inv_w[1],
+                     w[2] / area2 * inv_w[2]];
+            let sum = l[0] + l[1] + l[2];
+            for (i, out) in varyings.iter_mut().enumerate() {
+                for comp in 0..4 {
+                    out[comp] = (l[0] * a.varyings[i][comp] +
+                                 l[1] * b.varyings[i][comp] +
+                                 l[2] * c.varyings[i][comp]) / sum;
+                }
+            }
+
+            let color = shade(&varyings[..]);
+            let (x, y) = (x as usize, y as usize);
+            let color = blend.apply(color, fb.pixel(x, y));
+            fb.put(x, y, to_rgba8(color));
+        }
+    }
+}
@@ -10,7 +10,12 @@ use std::sync::atomic::{AtomicUsize, AtomicPtr, Ordering};
 use super::instance::Instance;
 use super::sys::*;
 use super::result::{Code, Error};
+use super::audio::{AudioState, AudioConfigState};
 use super::callback::MessageLoopState;
+use super::graphics::Graphics3DState;
+use super::image_data::ImageDataState;
+use super::input_event::InputEventState;
+use super::view::ViewState;
 use super::url_loader::{UrlLoaderState, UrlRequestInfoState, UrlResponseInfoState};
 use super::filesystem_manager::{FileRefState, FileIoState,
                                 FileSystemState};
@@ -49,6 +54,11 @@ impl RefCounted for ResourceRc {
 
 #[derive(Debug)]
 pub enum ResState {
+    Audio(Arc<AudioState>),
+    AudioConfig(Arc<AudioConfigState>),
+    Graphics3D(Arc<Graphics3DState>),
+    ImageData(Arc<ImageDataState>),
+    InputEvent(Arc<InputEventState>),
     MessageLoop(Arc<MessageLoopState>),
     UrlLoader(Arc<UrlLoaderState>),
     UrlRequestInfo(Arc<UrlRequestInfoState>),
@@ -56,11 +66,17 @@ pub enum ResState {
     FileIo(Arc<FileIoState>),
     FileRef(Arc<FileRefState>),
     FileSystem(Arc<FileSystemState>),
+    View(Arc<ViewState>),
 }
 impl ResState {
     pub fn id(&self) -> PP_Resource {
         use self::ResState::*;
         match self {
+            &Audio(ref v) => <AudioState as ResourceState>::resource_id(v),
+            &AudioConfig(ref v) => <AudioConfigState as ResourceState>::resource_id(v),
+            &Graphics3D(ref v) => <Graphics3DState as ResourceState>::resource_id(v),
+            &ImageData(ref v) => <ImageDataState as ResourceState>::resource_id(v),
+            &InputEvent(ref v) => <InputEventState as ResourceState>::resource_id(v),
             &MessageLoop(ref v) => <MessageLoopState as ResourceState>::resource_id(v),
             &UrlLoader(ref v) => <UrlLoaderState as ResourceState>::resource_id(v),
             &FileIo(ref v) => <FileIoState as ResourceState>::resource_id(v),
@@ -68,6 +84,7 @@ impl ResState {
             &FileSystem(ref v) => <FileSystemState as ResourceState>::resource_id(v),
             &UrlRequestInfo(ref v) => <UrlRequestInfoState as ResourceState>::resource_id(v),
             &UrlResponseInfo(ref v) => <UrlResponseInfoState as ResourceState>::resource_id(v),
+            &View(ref v) => <ViewState as ResourceState>::resource_id(v),
         }
     }
 
@@ -85,6 +102,11 @@ impl ResState {
     pub fn instance(&self) -> Instance {
         use self::ResState::*;
         match self {
+            &Audio(ref v) => <AudioState as ResourceState>::resource_instance(v),
+            &AudioConfig(ref v) => <AudioConfigState as ResourceState>::resource_instance(v),
+            &Graphics3D(ref v) => <Graphics3DState as ResourceState>::resource_instance(v),
+            &ImageData(ref v) => <ImageDataState as ResourceState>::resource_instance(v),
+            &InputEvent(ref v) => <InputEventState as ResourceState>::resource_instance(v),
             &MessageLoop(ref v) => <MessageLoopState as ResourceState>::resource_instance(v),
             &UrlLoader(ref v) => <UrlLoaderState as ResourceState>::resource_instance(v),
             &FileIo(ref v) => <FileIoState as ResourceState>::resource_instance(v),
@@ -92,6 +114,7 @@ impl ResState {
             &FileSystem(ref v) => <FileSystemState as ResourceState>::resource_instance(v),
             &UrlRequestInfo(ref v) => <UrlRequestInfoState as ResourceState>::resource_instance(v),
             &UrlResponseInfo(ref v) => <UrlResponseInfoState as ResourceState>::resource_instance(v),
+            &View(ref v) => <ViewState as ResourceState>::resource_instance(v),
         }
     }
 }
@@ -359,13 +382,27 @@ extern "C" fn get_module_ticks() -> PP_TimeTicks {
     module.seconds_elapsed()
 }
 
-extern "C" fn call_on_main_thread(_delay_in_milliseconds: libc::int32_t,
-                                  _callback: PP_CompletionCallback,
-                                  _result: libc::int32_t) {
-    unimplemented!()
+extern "C" fn call_on_main_thread(delay_in_milliseconds: libc::int32_t,
+                                  callback: PP_CompletionCallback,
+                                  result: libc::int32_t) {
+    use std::time::Duration;
+    use super::callback::{MessageLoopState, Work};
+
+    let module = super::global_module();
+    let work = Work {
+        f: callback.func,
+        user: callback.user_data,
+        result: result,
+    };
+    let delay = Duration::from_millis(::std::cmp::max(delay_in_milliseconds, 0) as u64);
+    let posted = get_resource::<MessageLoopState>(module.main_message_loop())
+        .and_then(|ml| ml.post_delayed(work, delay) );
+    if let Err(err) = posted {
+        error!("couldn't post to the main thread: {:?}", err);
+    }
 }
 extern "C" fn on_main_thread() -> PP_Bool {
-    unimplemented!()
+    if super::callback::on_main_thread() { PP_TRUE } else { PP_FALSE }
 }
 
 pub static INTERFACES: &'static [(&'static str, SyncInterfacePtr)] = &[
@@ -70,6 +70,7 @@ pub enum Error {
     ConnectionClosed,  // = ffi::PP_ERROR_CONNECTION_CLOSED,
     TimedOut,          // = ffi::PP_ERROR_TIMEDOUT,
     NoMessageLoop,     // = ffi::PP_ERROR_NO_MESSAGE_LOOP,
+    BlocksMainThread,  // = ffi::PP_ERROR_BLOCKS_MAIN_THREAD,
     ResourceFailed,    // = sys::PP_ERROR_RESOURCE_FAILED,
 
     /// See PP_ERROR_ABORTED.
@@ -106,6 +107,7 @@ impl Into<i32> for Error {
             Error::ConnectionClosed  => PP_ERROR_CONNECTION_CLOSED,
             Error::TimedOut          => PP_ERROR_TIMEDOUT,
             Error::NoMessageLoop     => PP_ERROR_NO_MESSAGE_LOOP,
+            Error::BlocksMainThread  => PP_ERROR_BLOCKS_MAIN_THREAD,
             Error::NoInterface       => PP_ERROR_NOINTERFACE,
             Error::Aborted           => PP_ERROR_ABORTED,
 
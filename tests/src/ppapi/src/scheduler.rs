@@ -0,0 +1,242 @@
+//! How the module's message loops take turns. By default each loop runs its
+//! work on its own thread as soon as it can, as in Chrome, so the order work
+//! on different loops runs in changes from run to run. `Scheduling::Seeded`
+//! runs one piece of work at a time across every loop, picking whose turn it
+//! is from the seed; a run that fails can then be replayed with its seed.
+//! The module thread's PPP calls take turns as the main loop's work.
+//!
+//! A turn is only given once every attached loop with work queued is waiting
+//! for one, and every instance thread has handled what was sent to it (and
+//! so posted the completions and swap acks it was going to), so which loops
+//! are in the draw doesn't depend on how any thread was scheduled. That
+//! means a thread must `Run` its loop before it waits on anything the other
+//! loops do, or they stall. Delayed work joins the draw when it comes due,
+//! which only happens at the same point in every run if the clock is frozen.
+//!
+//! `Scheduling::Chaos` goes the other way, to shake out races in modules'
+//! callback code: loops still run on their own, but posted work and the
+//! instance's messages are held back by random delays.
+
+use std::collections::BTreeMap;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+use super::completion::Rng;
+use super::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scheduling {
+    /// Every loop runs whenever it has work.
+    Threaded,
+    /// One loop at a time, picked in an order the seed decides.
+    Seeded(u64),
+    /// Every loop runs whenever it has work, but each piece of posted work
+    /// is delayed by up to `max_delay`, so work on a loop can run out of the
+    /// order it was posted in; each message to an instance is handled up to
+    /// `max_delay` late too. Replaying the seed only makes the same delays
+    /// likely, as threads still race for them.
+    Chaos {
+        seed: u64,
+        max_delay: Duration,
+    },
+}
+impl Default for Scheduling {
+    fn default() -> Scheduling { Scheduling::Threaded }
+}
+
+#[derive(Debug, Default)]
+struct LoopEntry {
+    /// To a thread. Only attached loops are waited for.
+    attached: bool,
+    /// Work posted that hasn't been received yet.
+    queued: usize,
+    waiting: bool,
+}
+
+#[derive(Debug, Default)]
+struct SchedulerState {
+    scheduling: Scheduling,
+    /// For `Seeded`.
+    rng: Option<Rng>,
+    /// For `Chaos`, with the most to delay by.
+    chaos: Option<(Rng, Duration)>,
+    /// Ordered, so the draw is the same for the same loops.
+    loops: BTreeMap<PP_Resource, LoopEntry>,
+    /// Messages sent to each instance thread that it hasn't handled yet.
+    promised: BTreeMap<PP_Instance, usize>,
+    running: Option<PP_Resource>,
+    /// Whose turn each was, since the scheduling was set.
+    turns: Vec<PP_Resource>,
+}
+impl SchedulerState {
+    fn entry(&mut self, id: PP_Resource) -> &mut LoopEntry {
+        self.loops.entry(id).or_insert_with(Default::default)
+    }
+    /// Gives the next turn, if nothing's running and every loop that will
+    /// want one is waiting.
+    fn pick(&mut self) {
+        if self.running.is_some() { return; }
+        let rng = match self.rng.as_mut() {
+            Some(rng) => rng,
+            None => { return; },
+        };
+        let settled = self.promised.is_empty() && self.loops
+            .values()
+            .all(|entry| !entry.attached || entry.waiting || entry.queued == 0 );
+        if !settled { return; }
+
+        let waiting: Vec<PP_Resource> = self.loops
+            .iter()
+            .filter(|&(_, entry)| entry.waiting )
+            .map(|(&id, _)| id )
+            .collect();
+        if waiting.is_empty() { return; }
+        let id = waiting[rng.below(waiting.len() as u64) as usize];
+        self.loops.get_mut(&id).unwrap().waiting = false;
+        self.running = Some(id);
+        self.turns.push(id);
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    state: Mutex<SchedulerState>,
+    changed: Condvar,
+}
+impl Scheduler {
+    pub fn new() -> Scheduler { Default::default() }
+
+    pub fn scheduling(&self) -> Scheduling { self.lock().scheduling }
+    /// Loops waiting for a turn are let go if this is `Threaded`.
+    pub fn set_scheduling(&self, scheduling: Scheduling) {
+        {
+            let mut state = self.lock();
+            state.scheduling = scheduling;
+            state.rng = match scheduling {
+                Scheduling::Seeded(seed) => Some(Rng::new(seed)),
+                _ => None,
+            };
+            state.chaos = match scheduling {
+                Scheduling::Chaos { seed, max_delay, } => Some((Rng::new(seed), max_delay)),
+                _ => None,
+            };
+            state.turns.clear();
+        }
+        self.changed.notify_all();
+    }
+    /// How much longer to hold something back for, under `Chaos`.
+    pub fn chaos_delay(&self) -> Duration {
+        match self.lock().chaos {
+            Some((ref mut rng, max_delay)) => rng.duration(max_delay),
+            None => Duration::new(0, 0),
+        }
+    }
+
+    /// The loop of each turn given since the scheduling was last set.
+    pub fn turns(&self) -> Vec<PP_Resource> { self.lock().turns.clone() }
+
+    pub fn attach(&self, id: PP_Resource) {
+        self.lock().entry(id).attached = true;
+        self.changed.notify_all();
+    }
+    /// The loop was destroyed, or its thread exited; nothing it had queued
+    /// will run.
+    pub fn detach(&self, id: PP_Resource) {
+        self.lock().loops.remove(&id);
+        self.changed.notify_all();
+    }
+    /// Call before sending the work, so the loop can't receive it first.
+    pub fn posted(&self, id: PP_Resource) {
+        self.lock().entry(id).queued += 1;
+    }
+    /// Posted work that won't take a turn when it's received: it failed to
+    /// send, or went to the loop's timers.
+    pub fn unqueued(&self, id: PP_Resource) {
+        {
+            let mut state = self.lock();
+            let entry = state.entry(id);
+            entry.queued = entry.queued.saturating_sub(1);
+        }
+        self.changed.notify_all();
+    }
+
+    /// Call before sending `instance`'s thread a message. No turn is given
+    /// until it's been handled, so whatever the instance posts for it is in
+    /// the draw.
+    pub fn promise(&self, instance: PP_Instance) {
+        *self.lock().promised.entry(instance).or_insert(0) += 1;
+    }
+    /// The message was handled, or couldn't be sent.
+    pub fn keep(&self, instance: PP_Instance) {
+        {
+            let mut state = self.lock();
+            let left = match state.promised.get_mut(&instance) {
+                Some(count) => {
+                    *count -= 1;
+                    *count
+                },
+                None => { return; },
+            };
+            if left == 0 {
+                state.promised.remove(&instance);
+            }
+        }
+        self.changed.notify_all();
+    }
+    /// The instance's thread is gone, with whatever it hadn't handled. Call
+    /// once it can't receive any more.
+    pub fn forget(&self, instance: PP_Instance) {
+        self.lock().promised.remove(&instance);
+        self.changed.notify_all();
+    }
+
+    /// Waits until loop `id` may run a piece of work, which it does until
+    /// the `Turn` is dropped. `queued` is for posted work just received, as
+    /// opposed to delayed work that came due.
+    pub fn turn<'a>(&'a self, id: PP_Resource, queued: bool) -> Turn<'a> {
+        let mut state = self.lock();
+        {
+            let entry = state.entry(id);
+            if queued {
+                entry.queued = entry.queued.saturating_sub(1);
+            }
+            entry.waiting = true;
+        }
+        loop {
+            if state.rng.is_none() {
+                state.entry(id).waiting = false;
+                return Turn { scheduler: self, id: None, };
+            }
+            if state.running == Some(id) {
+                return Turn { scheduler: self, id: Some(id), };
+            }
+            state.pick();
+            if state.running == Some(id) { continue; }
+            if state.running.is_some() {
+                // Someone else's turn; let them know.
+                self.changed.notify_all();
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    fn lock<'a>(&'a self) -> MutexGuard<'a, SchedulerState> { self.state.lock().unwrap() }
+}
+
+/// A loop's turn to run, until dropped.
+pub struct Turn<'a> {
+    scheduler: &'a Scheduler,
+    /// `None` if it didn't have to wait for it.
+    id: Option<PP_Resource>,
+}
+impl<'a> Drop for Turn<'a> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            let mut state = self.scheduler.lock();
+            if state.running == Some(id) {
+                state.running = None;
+            }
+        }
+        self.scheduler.changed.notify_all();
+    }
+}
@@ -171,12 +171,22 @@ impl ::std::default::Default for PPP_Instance_1_1 {
     fn default() -> Self { unsafe { ::std::mem::zeroed() } }
 }
 
+#[repr(C)]
+#[derive(Copy)]
+pub struct PPP_Graphics3D_1_0 {
+    pub Graphics3DContextLost: extern "C" fn(instance: PP_Instance),
+}
+impl ::std::clone::Clone for PPP_Graphics3D_1_0 {
+    fn clone(&self) -> Self { *self }
+}
+
 
 #[repr(C)]
 #[derive(Copy)]
 pub struct PPB_Instance_1_0 {
-    pub BindGraphics: Option<StubInterfaceFunc<PP_Bool>>,
-    pub IsFullFrame: Option<StubInterfaceFunc<PP_Bool>>,
+    pub BindGraphics: Option<extern "C" fn(instance: PP_Instance,
+                                           device: PP_Resource) -> PP_Bool>,
+    pub IsFullFrame: Option<extern "C" fn(instance: PP_Instance) -> PP_Bool>,
 }
 impl ::std::clone::Clone for PPB_Instance_1_0 {
     fn clone(&self) -> Self { *self }
@@ -329,38 +339,74 @@ impl ::std::default::Default for PPB_FileRef_1_2 {
     fn default() -> Self { unsafe { ::std::mem::zeroed() } }
 }
 
+pub type PP_TimeDelta = libc::c_double;
+
+pub const PP_AUDIOSAMPLERATE_NONE: ::libc::c_uint = 0;
+pub const PP_AUDIOSAMPLERATE_44100: ::libc::c_uint = 44100;
+pub const PP_AUDIOSAMPLERATE_48000: ::libc::c_uint = 48000;
+pub type PP_AudioSampleRate = ::libc::c_uint;
+
+pub const PP_AUDIOMINSAMPLEFRAMECOUNT: uint32_t = 64;
+pub const PP_AUDIOMAXSAMPLEFRAMECOUNT: uint32_t = 32768;
+
+pub type PPB_Audio_Callback_1_0 = extern "C" fn(sample_buffer: *mut ::libc::c_void,
+                                                buffer_size_in_bytes: uint32_t,
+                                                user_data: *mut ::libc::c_void);
+pub type PPB_Audio_Callback = extern "C" fn(sample_buffer: *mut ::libc::c_void,
+                                            buffer_size_in_bytes: uint32_t,
+                                            latency: PP_TimeDelta,
+                                            user_data: *mut ::libc::c_void);
+
+#[repr(C)]
+#[derive(Copy)]
+pub struct PPB_Audio_1_0 {
+    pub create: extern "C" fn(instance: PP_Instance,
+                              config: PP_Resource,
+                              audio_callback: PPB_Audio_Callback_1_0,
+                              user_data: *mut ::libc::c_void) -> PP_Resource,
+    pub is_audio: extern "C" fn(resource: PP_Resource) -> PP_Bool,
+    pub get_config: extern "C" fn(audio: PP_Resource) -> PP_Resource,
+    pub start_playback: extern "C" fn(audio: PP_Resource) -> PP_Bool,
+    pub stop_playback: extern "C" fn(audio: PP_Resource) -> PP_Bool,
+}
+impl ::std::clone::Clone for PPB_Audio_1_0 {
+    fn clone(&self) -> Self { *self }
+}
+
 #[repr(C)]
 #[derive(Copy)]
 pub struct PPB_Audio_1_1 {
-    pub create: StubInterfaceFunc<PP_Resource>,
-    pub is_audio: StubInterfaceFunc<PP_Bool>,
-    pub get_config: StubInterfaceFunc<PP_Resource>,
-    pub start_playback: StubInterfaceFunc<PP_Bool>,
-    pub stop_playback: StubInterfaceFunc<PP_Bool>,
+    pub create: extern "C" fn(instance: PP_Instance,
+                              config: PP_Resource,
+                              audio_callback: PPB_Audio_Callback,
+                              user_data: *mut ::libc::c_void) -> PP_Resource,
+    pub is_audio: extern "C" fn(resource: PP_Resource) -> PP_Bool,
+    pub get_config: extern "C" fn(audio: PP_Resource) -> PP_Resource,
+    pub start_playback: extern "C" fn(audio: PP_Resource) -> PP_Bool,
+    pub stop_playback: extern "C" fn(audio: PP_Resource) -> PP_Bool,
 }
 impl ::std::clone::Clone for PPB_Audio_1_1 {
     fn clone(&self) -> Self { *self }
 }
-impl ::std::default::Default for PPB_Audio_1_1 {
-    fn default() -> Self { unsafe { ::std::mem::zeroed() } }
-}
 
 #[repr(C)]
 #[derive(Copy)]
 pub struct PPB_AudioConfig_1_1 {
-    pub CreateStereo16Bit: StubInterfaceFunc<PP_Resource>,
-    pub RecommendSampleFrameCount: StubInterfaceFunc<uint32_t>,
-    pub IsAudioConfig: StubInterfaceFunc<PP_Bool>,
-    pub GetSampleRate: StubInterfaceFunc<c_uint>,
-    pub GetSampleFrameCount: StubInterfaceFunc<uint32_t>,
-    pub RecommendSampleRate: StubInterfaceFunc<c_uint>,
+    pub CreateStereo16Bit: extern "C" fn(instance: PP_Instance,
+                                         sample_rate: PP_AudioSampleRate,
+                                         sample_frame_count: uint32_t) -> PP_Resource,
+    pub RecommendSampleFrameCount: extern "C" fn(instance: PP_Instance,
+                                                 sample_rate: PP_AudioSampleRate,
+                                                 requested_sample_frame_count: uint32_t)
+                                                 -> uint32_t,
+    pub IsAudioConfig: extern "C" fn(resource: PP_Resource) -> PP_Bool,
+    pub GetSampleRate: extern "C" fn(config: PP_Resource) -> PP_AudioSampleRate,
+    pub GetSampleFrameCount: extern "C" fn(config: PP_Resource) -> uint32_t,
+    pub RecommendSampleRate: extern "C" fn(instance: PP_Instance) -> PP_AudioSampleRate,
 }
 impl ::std::clone::Clone for PPB_AudioConfig_1_1 {
     fn clone(&self) -> Self { *self }
 }
-impl ::std::default::Default for PPB_AudioConfig_1_1 {
-    fn default() -> Self { unsafe { ::std::mem::zeroed() } }
-}
 
 pub type Enum_Unnamed7 = ::libc::c_uint;
 pub const PP_LOGLEVEL_TIP: ::libc::c_uint = 0;
@@ -494,17 +540,44 @@ impl ::std::clone::Clone for PPB_MessageLoop_1_0 {
 impl ::std::default::Default for PPB_MessageLoop_1_0 {
     fn default() -> Self { unsafe { ::std::mem::zeroed() } }
 }
+pub const PP_GRAPHICS3DATTRIB_ALPHA_SIZE: int32_t = 0x3021;
+pub const PP_GRAPHICS3DATTRIB_BLUE_SIZE: int32_t = 0x3022;
+pub const PP_GRAPHICS3DATTRIB_GREEN_SIZE: int32_t = 0x3023;
+pub const PP_GRAPHICS3DATTRIB_RED_SIZE: int32_t = 0x3024;
+pub const PP_GRAPHICS3DATTRIB_DEPTH_SIZE: int32_t = 0x3025;
+pub const PP_GRAPHICS3DATTRIB_STENCIL_SIZE: int32_t = 0x3026;
+pub const PP_GRAPHICS3DATTRIB_SAMPLES: int32_t = 0x3031;
+pub const PP_GRAPHICS3DATTRIB_SAMPLE_BUFFERS: int32_t = 0x3032;
+pub const PP_GRAPHICS3DATTRIB_NONE: int32_t = 0x3038;
+pub const PP_GRAPHICS3DATTRIB_HEIGHT: int32_t = 0x3056;
+pub const PP_GRAPHICS3DATTRIB_WIDTH: int32_t = 0x3057;
+pub const PP_GRAPHICS3DATTRIB_SWAP_BEHAVIOR: int32_t = 0x3093;
+pub const PP_GRAPHICS3DATTRIB_BUFFER_PRESERVED: int32_t = 0x3094;
+pub const PP_GRAPHICS3DATTRIB_BUFFER_DESTROYED: int32_t = 0x3095;
+pub const PP_GRAPHICS3DATTRIB_GPU_PREFERENCE: int32_t = 0x11000;
+pub const PP_GRAPHICS3DATTRIB_GPU_PREFERENCE_LOW_POWER: int32_t = 0x11001;
+pub const PP_GRAPHICS3DATTRIB_GPU_PREFERENCE_PERFORMANCE: int32_t = 0x11002;
+
 #[repr(C)]
 #[derive(Copy)]
 pub struct PPB_Graphics3D_1_0 {
-    pub GetAttribMaxValue: Option<StubInterfaceFunc<int32_t>>,
-    pub Create: Option<StubInterfaceFunc<PP_Resource>>,
-    pub IsGraphics3D: Option<StubInterfaceFunc<PP_Bool>>,
-    pub GetAttribs: Option<StubInterfaceFunc<int32_t>>,
-    pub SetAttribs: Option<StubInterfaceFunc<int32_t>>,
-    pub GetError: Option<StubInterfaceFunc<int32_t>>,
-    pub ResizeBuffers: Option<StubInterfaceFunc<int32_t>>,
-    pub SwapBuffers: Option<StubInterfaceFunc<int32_t>>,
+    pub GetAttribMaxValue: Option<extern "C" fn(instance: PP_Resource,
+                                                attribute: int32_t,
+                                                value: *mut int32_t) -> int32_t>,
+    pub Create: Option<extern "C" fn(instance: PP_Instance,
+                                     share_context: PP_Resource,
+                                     attrib_list: *const int32_t) -> PP_Resource>,
+    pub IsGraphics3D: Option<extern "C" fn(resource: PP_Resource) -> PP_Bool>,
+    pub GetAttribs: Option<extern "C" fn(context: PP_Resource,
+                                         attrib_list: *mut int32_t) -> int32_t>,
+    pub SetAttribs: Option<extern "C" fn(context: PP_Resource,
+                                         attrib_list: *const int32_t) -> int32_t>,
+    pub GetError: Option<extern "C" fn(context: PP_Resource) -> int32_t>,
+    pub ResizeBuffers: Option<extern "C" fn(context: PP_Resource,
+                                            width: int32_t,
+                                            height: int32_t) -> int32_t>,
+    pub SwapBuffers: Option<extern "C" fn(context: PP_Resource,
+                                          callback: PP_CompletionCallback) -> int32_t>,
 }
 impl ::std::clone::Clone for PPB_Graphics3D_1_0 {
     fn clone(&self) -> Self { *self }
@@ -513,10 +586,345 @@ impl ::std::default::Default for PPB_Graphics3D_1_0 {
     fn default() -> Self { unsafe { ::std::mem::zeroed() } }
 }
 
+pub type GLenum = c_uint;
+pub type GLboolean = c_uchar;
+pub type GLbitfield = c_uint;
+pub type GLbyte = c_schar;
+pub type GLshort = c_short;
+pub type GLint = c_int;
+pub type GLsizei = c_int;
+pub type GLubyte = c_uchar;
+pub type GLushort = c_ushort;
+pub type GLuint = c_uint;
+pub type GLfloat = c_float;
+pub type GLclampf = c_float;
+pub type GLvoid = c_void;
+pub type GLchar = c_char;
+pub type GLintptr = c_long;
+pub type GLsizeiptr = c_long;
+
+pub const GL_FALSE: GLboolean = 0;
+pub const GL_TRUE: GLboolean = 1;
+
+pub const GL_NO_ERROR: GLenum = 0;
+pub const GL_INVALID_ENUM: GLenum = 0x0500;
+pub const GL_INVALID_VALUE: GLenum = 0x0501;
+pub const GL_INVALID_OPERATION: GLenum = 0x0502;
+pub const GL_OUT_OF_MEMORY: GLenum = 0x0505;
+
+pub const GL_DEPTH_BUFFER_BIT: GLbitfield = 0x00000100;
+pub const GL_STENCIL_BUFFER_BIT: GLbitfield = 0x00000400;
+pub const GL_COLOR_BUFFER_BIT: GLbitfield = 0x00004000;
+
+pub const GL_POINTS: GLenum = 0x0000;
+pub const GL_LINES: GLenum = 0x0001;
+pub const GL_LINE_LOOP: GLenum = 0x0002;
+pub const GL_LINE_STRIP: GLenum = 0x0003;
+pub const GL_TRIANGLES: GLenum = 0x0004;
+pub const GL_TRIANGLE_STRIP: GLenum = 0x0005;
+pub const GL_TRIANGLE_FAN: GLenum = 0x0006;
+
+pub const GL_ZERO: GLenum = 0;
+pub const GL_ONE: GLenum = 1;
+pub const GL_SRC_COLOR: GLenum = 0x0300;
+pub const GL_ONE_MINUS_SRC_COLOR: GLenum = 0x0301;
+pub const GL_SRC_ALPHA: GLenum = 0x0302;
+pub const GL_ONE_MINUS_SRC_ALPHA: GLenum = 0x0303;
+pub const GL_DST_ALPHA: GLenum = 0x0304;
+pub const GL_ONE_MINUS_DST_ALPHA: GLenum = 0x0305;
+pub const GL_DST_COLOR: GLenum = 0x0306;
+pub const GL_ONE_MINUS_DST_COLOR: GLenum = 0x0307;
+
+pub const GL_ARRAY_BUFFER: GLenum = 0x8892;
+pub const GL_ELEMENT_ARRAY_BUFFER: GLenum = 0x8893;
+pub const GL_STREAM_DRAW: GLenum = 0x88E0;
+pub const GL_STATIC_DRAW: GLenum = 0x88E4;
+pub const GL_DYNAMIC_DRAW: GLenum = 0x88E8;
+
+pub const GL_CULL_FACE: GLenum = 0x0B44;
+pub const GL_DITHER: GLenum = 0x0BD0;
+pub const GL_STENCIL_TEST: GLenum = 0x0B90;
+pub const GL_DEPTH_TEST: GLenum = 0x0B71;
+pub const GL_SCISSOR_TEST: GLenum = 0x0C11;
+pub const GL_BLEND: GLenum = 0x0BE2;
+pub const GL_POLYGON_OFFSET_FILL: GLenum = 0x8037;
+pub const GL_SAMPLE_ALPHA_TO_COVERAGE: GLenum = 0x809E;
+pub const GL_SAMPLE_COVERAGE: GLenum = 0x80A0;
+
+pub const GL_VIEWPORT: GLenum = 0x0BA2;
+pub const GL_SCISSOR_BOX: GLenum = 0x0C10;
+pub const GL_UNPACK_ALIGNMENT: GLenum = 0x0CF5;
+pub const GL_PACK_ALIGNMENT: GLenum = 0x0D05;
+pub const GL_MAX_TEXTURE_SIZE: GLenum = 0x0D33;
+pub const GL_MAX_VIEWPORT_DIMS: GLenum = 0x0D3A;
+pub const GL_MAX_VERTEX_ATTRIBS: GLenum = 0x8869;
+pub const GL_MAX_TEXTURE_IMAGE_UNITS: GLenum = 0x8872;
+pub const GL_MAX_COMBINED_TEXTURE_IMAGE_UNITS: GLenum = 0x8B4D;
+pub const GL_TEXTURE_BINDING_2D: GLenum = 0x8069;
+pub const GL_ACTIVE_TEXTURE: GLenum = 0x84E0;
+pub const GL_CURRENT_PROGRAM: GLenum = 0x8B8D;
+pub const GL_ARRAY_BUFFER_BINDING: GLenum = 0x8894;
+
+pub const GL_DONT_CARE: GLenum = 0x1100;
+pub const GL_FASTEST: GLenum = 0x1101;
+pub const GL_NICEST: GLenum = 0x1102;
+pub const GL_GENERATE_MIPMAP_HINT: GLenum = 0x8192;
+
+pub const GL_BYTE: GLenum = 0x1400;
+pub const GL_UNSIGNED_BYTE: GLenum = 0x1401;
+pub const GL_SHORT: GLenum = 0x1402;
+pub const GL_UNSIGNED_SHORT: GLenum = 0x1403;
+pub const GL_INT: GLenum = 0x1404;
+pub const GL_UNSIGNED_INT: GLenum = 0x1405;
+pub const GL_FLOAT: GLenum = 0x1406;
+
+pub const GL_ALPHA: GLenum = 0x1906;
+pub const GL_RGB: GLenum = 0x1907;
+pub const GL_RGBA: GLenum = 0x1908;
+pub const GL_LUMINANCE: GLenum = 0x1909;
+pub const GL_LUMINANCE_ALPHA: GLenum = 0x190A;
+
+pub const GL_FRAGMENT_SHADER: GLenum = 0x8B30;
+pub const GL_VERTEX_SHADER: GLenum = 0x8B31;
+pub const GL_DELETE_STATUS: GLenum = 0x8B80;
+pub const GL_COMPILE_STATUS: GLenum = 0x8B81;
+pub const GL_LINK_STATUS: GLenum = 0x8B82;
+pub const GL_VALIDATE_STATUS: GLenum = 0x8B83;
+pub const GL_INFO_LOG_LENGTH: GLenum = 0x8B84;
+pub const GL_ATTACHED_SHADERS: GLenum = 0x8B85;
+pub const GL_ACTIVE_UNIFORMS: GLenum = 0x8B86;
+pub const GL_ACTIVE_ATTRIBUTES: GLenum = 0x8B89;
+pub const GL_SHADER_SOURCE_LENGTH: GLenum = 0x8B88;
+pub const GL_SHADER_TYPE: GLenum = 0x8B4F;
+
+pub const GL_VENDOR: GLenum = 0x1F00;
+pub const GL_RENDERER: GLenum = 0x1F01;
+pub const GL_VERSION: GLenum = 0x1F02;
+pub const GL_EXTENSIONS: GLenum = 0x1F03;
+pub const GL_SHADING_LANGUAGE_VERSION: GLenum = 0x8B8C;
+
+pub const GL_TEXTURE_2D: GLenum = 0x0DE1;
+pub const GL_TEXTURE_MAG_FILTER: GLenum = 0x2800;
+pub const GL_TEXTURE_MIN_FILTER: GLenum = 0x2801;
+pub const GL_TEXTURE_WRAP_S: GLenum = 0x2802;
+pub const GL_TEXTURE_WRAP_T: GLenum = 0x2803;
+pub const GL_NEAREST: GLenum = 0x2600;
+pub const GL_LINEAR: GLenum = 0x2601;
+pub const GL_NEAREST_MIPMAP_NEAREST: GLenum = 0x2700;
+pub const GL_LINEAR_MIPMAP_NEAREST: GLenum = 0x2701;
+pub const GL_NEAREST_MIPMAP_LINEAR: GLenum = 0x2702;
+pub const GL_LINEAR_MIPMAP_LINEAR: GLenum = 0x2703;
+pub const GL_REPEAT: GLenum = 0x2901;
+pub const GL_CLAMP_TO_EDGE: GLenum = 0x812F;
+pub const GL_MIRRORED_REPEAT: GLenum = 0x8370;
+pub const GL_TEXTURE0: GLenum = 0x84C0;
+
+#[repr(C)]
+#[derive(Copy)]
+pub struct PPB_OpenGLES2 {
+    pub ActiveTexture: Option<extern "C" fn(context: PP_Resource, texture: GLenum)>,
+    pub AttachShader: Option<extern "C" fn(context: PP_Resource, program: GLuint, shader: GLuint)>,
+    pub BindAttribLocation: Option<extern "C" fn(context: PP_Resource, program: GLuint, index: GLuint, name: *const GLchar)>,
+    pub BindBuffer: Option<extern "C" fn(context: PP_Resource, target: GLenum, buffer: GLuint)>,
+    pub BindFramebuffer: Option<StubInterfaceFunc<()>>,
+    pub BindRenderbuffer: Option<StubInterfaceFunc<()>>,
+    pub BindTexture: Option<extern "C" fn(context: PP_Resource, target: GLenum, texture: GLuint)>,
+    pub BlendColor: Option<StubInterfaceFunc<()>>,
+    pub BlendEquation: Option<StubInterfaceFunc<()>>,
+    pub BlendEquationSeparate: Option<StubInterfaceFunc<()>>,
+    pub BlendFunc: Option<extern "C" fn(context: PP_Resource, sfactor: GLenum, dfactor: GLenum)>,
+    pub BlendFuncSeparate: Option<extern "C" fn(context: PP_Resource, src_rgb: GLenum, dst_rgb: GLenum, src_alpha: GLenum, dst_alpha: GLenum)>,
+    pub BufferData: Option<extern "C" fn(context: PP_Resource, target: GLenum, size: GLsizeiptr, data: *const GLvoid, usage: GLenum)>,
+    pub BufferSubData: Option<extern "C" fn(context: PP_Resource, target: GLenum, offset: GLintptr, size: GLsizeiptr, data: *const GLvoid)>,
+    pub CheckFramebufferStatus: Option<StubInterfaceFunc<()>>,
+    pub Clear: Option<extern "C" fn(context: PP_Resource, mask: GLbitfield)>,
+    pub ClearColor: Option<extern "C" fn(context: PP_Resource, red: GLclampf, green: GLclampf, blue: GLclampf, alpha: GLclampf)>,
+    pub ClearDepthf: Option<StubInterfaceFunc<()>>,
+    pub ClearStencil: Option<StubInterfaceFunc<()>>,
+    pub ColorMask: Option<StubInterfaceFunc<()>>,
+    pub CompileShader: Option<extern "C" fn(context: PP_Resource, shader: GLuint)>,
+    pub CompressedTexImage2D: Option<StubInterfaceFunc<()>>,
+    pub CompressedTexSubImage2D: Option<StubInterfaceFunc<()>>,
+    pub CopyTexImage2D: Option<StubInterfaceFunc<()>>,
+    pub CopyTexSubImage2D: Option<StubInterfaceFunc<()>>,
+    pub CreateProgram: Option<extern "C" fn(context: PP_Resource) -> GLuint>,
+    pub CreateShader: Option<extern "C" fn(context: PP_Resource, type_: GLenum) -> GLuint>,
+    pub CullFace: Option<StubInterfaceFunc<()>>,
+    pub DeleteBuffers: Option<extern "C" fn(context: PP_Resource, n: GLsizei, buffers: *const GLuint)>,
+    pub DeleteFramebuffers: Option<StubInterfaceFunc<()>>,
+    pub DeleteProgram: Option<extern "C" fn(context: PP_Resource, program: GLuint)>,
+    pub DeleteRenderbuffers: Option<StubInterfaceFunc<()>>,
+    pub DeleteShader: Option<extern "C" fn(context: PP_Resource, shader: GLuint)>,
+    pub DeleteTextures: Option<extern "C" fn(context: PP_Resource, n: GLsizei, textures: *const GLuint)>,
+    pub DepthFunc: Option<StubInterfaceFunc<()>>,
+    pub DepthMask: Option<StubInterfaceFunc<()>>,
+    pub DepthRangef: Option<StubInterfaceFunc<()>>,
+    pub DetachShader: Option<extern "C" fn(context: PP_Resource, program: GLuint, shader: GLuint)>,
+    pub Disable: Option<extern "C" fn(context: PP_Resource, cap: GLenum)>,
+    pub DisableVertexAttribArray: Option<extern "C" fn(context: PP_Resource, index: GLuint)>,
+    pub DrawArrays: Option<extern "C" fn(context: PP_Resource, mode: GLenum, first: GLint, count: GLsizei)>,
+    pub DrawElements: Option<extern "C" fn(context: PP_Resource, mode: GLenum, count: GLsizei, type_: GLenum, indices: *const GLvoid)>,
+    pub Enable: Option<extern "C" fn(context: PP_Resource, cap: GLenum)>,
+    pub EnableVertexAttribArray: Option<extern "C" fn(context: PP_Resource, index: GLuint)>,
+    pub Finish: Option<extern "C" fn(context: PP_Resource)>,
+    pub Flush: Option<extern "C" fn(context: PP_Resource)>,
+    pub FramebufferRenderbuffer: Option<StubInterfaceFunc<()>>,
+    pub FramebufferTexture2D: Option<StubInterfaceFunc<()>>,
+    pub FrontFace: Option<StubInterfaceFunc<()>>,
+    pub GenBuffers: Option<extern "C" fn(context: PP_Resource, n: GLsizei, buffers: *mut GLuint)>,
+    pub GenerateMipmap: Option<StubInterfaceFunc<()>>,
+    pub GenFramebuffers: Option<StubInterfaceFunc<()>>,
+    pub GenRenderbuffers: Option<StubInterfaceFunc<()>>,
+    pub GenTextures: Option<extern "C" fn(context: PP_Resource, n: GLsizei, textures: *mut GLuint)>,
+    pub GetActiveAttrib: Option<StubInterfaceFunc<()>>,
+    pub GetActiveUniform: Option<StubInterfaceFunc<()>>,
+    pub GetAttachedShaders: Option<StubInterfaceFunc<()>>,
+    pub GetAttribLocation: Option<extern "C" fn(context: PP_Resource, program: GLuint, name: *const GLchar) -> GLint>,
+    pub GetBooleanv: Option<StubInterfaceFunc<()>>,
+    pub GetBufferParameteriv: Option<StubInterfaceFunc<()>>,
+    pub GetError: Option<extern "C" fn(context: PP_Resource) -> GLenum>,
+    pub GetFloatv: Option<StubInterfaceFunc<()>>,
+    pub GetFramebufferAttachmentParameteriv: Option<StubInterfaceFunc<()>>,
+    pub GetIntegerv: Option<extern "C" fn(context: PP_Resource, pname: GLenum, params: *mut GLint)>,
+    pub GetProgramiv: Option<extern "C" fn(context: PP_Resource, program: GLuint, pname: GLenum, params: *mut GLint)>,
+    pub GetProgramInfoLog: Option<extern "C" fn(context: PP_Resource, program: GLuint, bufsize: GLsizei, length: *mut GLsizei, infolog: *mut GLchar)>,
+    pub GetRenderbufferParameteriv: Option<StubInterfaceFunc<()>>,
+    pub GetShaderiv: Option<extern "C" fn(context: PP_Resource, shader: GLuint, pname: GLenum, params: *mut GLint)>,
+    pub GetShaderInfoLog: Option<extern "C" fn(context: PP_Resource, shader: GLuint, bufsize: GLsizei, length: *mut GLsizei, infolog: *mut GLchar)>,
+    pub GetShaderPrecisionFormat: Option<StubInterfaceFunc<()>>,
+    pub GetShaderSource: Option<StubInterfaceFunc<()>>,
+    pub GetString: Option<extern "C" fn(context: PP_Resource, name: GLenum) -> *const GLubyte>,
+    pub GetTexParameterfv: Option<StubInterfaceFunc<()>>,
+    pub GetTexParameteriv: Option<StubInterfaceFunc<()>>,
+    pub GetUniformfv: Option<StubInterfaceFunc<()>>,
+    pub GetUniformiv: Option<StubInterfaceFunc<()>>,
+    pub GetUniformLocation: Option<extern "C" fn(context: PP_Resource, program: GLuint, name: *const GLchar) -> GLint>,
+    pub GetVertexAttribfv: Option<StubInterfaceFunc<()>>,
+    pub GetVertexAttribiv: Option<StubInterfaceFunc<()>>,
+    pub GetVertexAttribPointerv: Option<StubInterfaceFunc<()>>,
+    pub Hint: Option<extern "C" fn(context: PP_Resource, target: GLenum, mode: GLenum)>,
+    pub IsBuffer: Option<StubInterfaceFunc<()>>,
+    pub IsEnabled: Option<extern "C" fn(context: PP_Resource, cap: GLenum) -> GLboolean>,
+    pub IsFramebuffer: Option<StubInterfaceFunc<()>>,
+    pub IsProgram: Option<extern "C" fn(context: PP_Resource, program: GLuint) -> GLboolean>,
+    pub IsRenderbuffer: Option<StubInterfaceFunc<()>>,
+    pub IsShader: Option<extern "C" fn(context: PP_Resource, shader: GLuint) -> GLboolean>,
+    pub IsTexture: Option<extern "C" fn(context: PP_Resource, texture: GLuint) -> GLboolean>,
+    pub LineWidth: Option<StubInterfaceFunc<()>>,
+    pub LinkProgram: Option<extern "C" fn(context: PP_Resource, program: GLuint)>,
+    pub PixelStorei: Option<extern "C" fn(context: PP_Resource, pname: GLenum, param: GLint)>,
+    pub PolygonOffset: Option<StubInterfaceFunc<()>>,
+    pub ReadPixels: Option<extern "C" fn(context: PP_Resource, x: GLint, y: GLint, width: GLsizei, height: GLsizei, format: GLenum, type_: GLenum, pixels: *mut GLvoid)>,
+    pub ReleaseShaderCompiler: Option<StubInterfaceFunc<()>>,
+    pub RenderbufferStorage: Option<StubInterfaceFunc<()>>,
+    pub SampleCoverage: Option<StubInterfaceFunc<()>>,
+    pub Scissor: Option<extern "C" fn(context: PP_Resource, x: GLint, y: GLint, width: GLsizei, height: GLsizei)>,
+    pub ShaderBinary: Option<StubInterfaceFunc<()>>,
+    pub ShaderSource: Option<extern "C" fn(context: PP_Resource, shader: GLuint, count: GLsizei, str: *const *const GLchar, length: *const GLint)>,
+    pub StencilFunc: Option<StubInterfaceFunc<()>>,
+    pub StencilFuncSeparate: Option<StubInterfaceFunc<()>>,
+    pub StencilMask: Option<StubInterfaceFunc<()>>,
+    pub StencilMaskSeparate: Option<StubInterfaceFunc<()>>,
+    pub StencilOp: Option<StubInterfaceFunc<()>>,
+    pub StencilOpSeparate: Option<StubInterfaceFunc<()>>,
+    pub TexImage2D: Option<extern "C" fn(context: PP_Resource, target: GLenum, level: GLint, internalformat: GLint, width: GLsizei, height: GLsizei, border: GLint, format: GLenum, type_: GLenum, pixels: *const GLvoid)>,
+    pub TexParameterf: Option<extern "C" fn(context: PP_Resource, target: GLenum, pname: GLenum, param: GLfloat)>,
+    pub TexParameterfv: Option<StubInterfaceFunc<()>>,
+    pub TexParameteri: Option<extern "C" fn(context: PP_Resource, target: GLenum, pname: GLenum, param: GLint)>,
+    pub TexParameteriv: Option<StubInterfaceFunc<()>>,
+    pub TexSubImage2D: Option<extern "C" fn(context: PP_Resource, target: GLenum, level: GLint, xoffset: GLint, yoffset: GLint, width: GLsizei, height: GLsizei, format: GLenum, type_: GLenum, pixels: *const GLvoid)>,
+    pub Uniform1f: Option<extern "C" fn(context: PP_Resource, location: GLint, x: GLfloat)>,
+    pub Uniform1fv: Option<extern "C" fn(context: PP_Resource, location: GLint, count: GLsizei, v: *const GLfloat)>,
+    pub Uniform1i: Option<extern "C" fn(context: PP_Resource, location: GLint, x: GLint)>,
+    pub Uniform1iv: Option<extern "C" fn(context: PP_Resource, location: GLint, count: GLsizei, v: *const GLint)>,
+    pub Uniform2f: Option<extern "C" fn(context: PP_Resource, location: GLint, x: GLfloat, y: GLfloat)>,
+    pub Uniform2fv: Option<extern "C" fn(context: PP_Resource, location: GLint, count: GLsizei, v: *const GLfloat)>,
+    pub Uniform2i: Option<StubInterfaceFunc<()>>,
+    pub Uniform2iv: Option<StubInterfaceFunc<()>>,
+    pub Uniform3f: Option<extern "C" fn(context: PP_Resource, location: GLint, x: GLfloat, y: GLfloat, z: GLfloat)>,
+    pub Uniform3fv: Option<extern "C" fn(context: PP_Resource, location: GLint, count: GLsizei, v: *const GLfloat)>,
+    pub Uniform3i: Option<StubInterfaceFunc<()>>,
+    pub Uniform3iv: Option<StubInterfaceFunc<()>>,
+    pub Uniform4f: Option<extern "C" fn(context: PP_Resource, location: GLint, x: GLfloat, y: GLfloat, z: GLfloat, w: GLfloat)>,
+    pub Uniform4fv: Option<extern "C" fn(context: PP_Resource, location: GLint, count: GLsizei, v: *const GLfloat)>,
+    pub Uniform4i: Option<StubInterfaceFunc<()>>,
+    pub Uniform4iv: Option<StubInterfaceFunc<()>>,
+    pub UniformMatrix2fv: Option<extern "C" fn(context: PP_Resource, location: GLint, count: GLsizei, transpose: GLboolean, value: *const GLfloat)>,
+    pub UniformMatrix3fv: Option<extern "C" fn(context: PP_Resource, location: GLint, count: GLsizei, transpose: GLboolean, value: *const GLfloat)>,
+    pub UniformMatrix4fv: Option<extern "C" fn(context: PP_Resource, location: GLint, count: GLsizei, transpose: GLboolean, value: *const GLfloat)>,
+    pub UseProgram: Option<extern "C" fn(context: PP_Resource, program: GLuint)>,
+    pub ValidateProgram: Option<StubInterfaceFunc<()>>,
+    pub VertexAttrib1f: Option<StubInterfaceFunc<()>>,
+    pub VertexAttrib1fv: Option<StubInterfaceFunc<()>>,
+    pub VertexAttrib2f: Option<StubInterfaceFunc<()>>,
+    pub VertexAttrib2fv: Option<StubInterfaceFunc<()>>,
+    pub VertexAttrib3f: Option<StubInterfaceFunc<()>>,
+    pub VertexAttrib3fv: Option<StubInterfaceFunc<()>>,
+    pub VertexAttrib4f: Option<StubInterfaceFunc<()>>,
+    pub VertexAttrib4fv: Option<StubInterfaceFunc<()>>,
+    pub VertexAttribPointer: Option<extern "C" fn(context: PP_Resource, indx: GLuint, size: GLint, type_: GLenum, normalized: GLboolean, stride: GLsizei, ptr: *const GLvoid)>,
+    pub Viewport: Option<extern "C" fn(context: PP_Resource, x: GLint, y: GLint, width: GLsizei, height: GLsizei)>,
+}
+impl ::std::clone::Clone for PPB_OpenGLES2 {
+    fn clone(&self) -> Self { *self }
+}
+impl ::std::default::Default for PPB_OpenGLES2 {
+    fn default() -> Self { unsafe { ::std::mem::zeroed() } }
+}
+
+pub type PP_MouseCursor_Type = ::libc::c_int;
+pub const PP_MOUSECURSOR_TYPE_CUSTOM: PP_MouseCursor_Type = -1;
+pub const PP_MOUSECURSOR_TYPE_POINTER: PP_MouseCursor_Type = 0;
+pub const PP_MOUSECURSOR_TYPE_CROSS: PP_MouseCursor_Type = 1;
+pub const PP_MOUSECURSOR_TYPE_HAND: PP_MouseCursor_Type = 2;
+pub const PP_MOUSECURSOR_TYPE_IBEAM: PP_MouseCursor_Type = 3;
+pub const PP_MOUSECURSOR_TYPE_WAIT: PP_MouseCursor_Type = 4;
+pub const PP_MOUSECURSOR_TYPE_HELP: PP_MouseCursor_Type = 5;
+pub const PP_MOUSECURSOR_TYPE_EASTRESIZE: PP_MouseCursor_Type = 6;
+pub const PP_MOUSECURSOR_TYPE_NORTHRESIZE: PP_MouseCursor_Type = 7;
+pub const PP_MOUSECURSOR_TYPE_NORTHEASTRESIZE: PP_MouseCursor_Type = 8;
+pub const PP_MOUSECURSOR_TYPE_NORTHWESTRESIZE: PP_MouseCursor_Type = 9;
+pub const PP_MOUSECURSOR_TYPE_SOUTHRESIZE: PP_MouseCursor_Type = 10;
+pub const PP_MOUSECURSOR_TYPE_SOUTHEASTRESIZE: PP_MouseCursor_Type = 11;
+pub const PP_MOUSECURSOR_TYPE_SOUTHWESTRESIZE: PP_MouseCursor_Type = 12;
+pub const PP_MOUSECURSOR_TYPE_WESTRESIZE: PP_MouseCursor_Type = 13;
+pub const PP_MOUSECURSOR_TYPE_NORTHSOUTHRESIZE: PP_MouseCursor_Type = 14;
+pub const PP_MOUSECURSOR_TYPE_EASTWESTRESIZE: PP_MouseCursor_Type = 15;
+pub const PP_MOUSECURSOR_TYPE_NORTHEASTSOUTHWESTRESIZE: PP_MouseCursor_Type = 16;
+pub const PP_MOUSECURSOR_TYPE_NORTHWESTSOUTHEASTRESIZE: PP_MouseCursor_Type = 17;
+pub const PP_MOUSECURSOR_TYPE_COLUMNRESIZE: PP_MouseCursor_Type = 18;
+pub const PP_MOUSECURSOR_TYPE_ROWRESIZE: PP_MouseCursor_Type = 19;
+pub const PP_MOUSECURSOR_TYPE_MIDDLEPANNING: PP_MouseCursor_Type = 20;
+pub const PP_MOUSECURSOR_TYPE_EASTPANNING: PP_MouseCursor_Type = 21;
+pub const PP_MOUSECURSOR_TYPE_NORTHPANNING: PP_MouseCursor_Type = 22;
+pub const PP_MOUSECURSOR_TYPE_NORTHEASTPANNING: PP_MouseCursor_Type = 23;
+pub const PP_MOUSECURSOR_TYPE_NORTHWESTPANNING: PP_MouseCursor_Type = 24;
+pub const PP_MOUSECURSOR_TYPE_SOUTHPANNING: PP_MouseCursor_Type = 25;
+pub const PP_MOUSECURSOR_TYPE_SOUTHEASTPANNING: PP_MouseCursor_Type = 26;
+pub const PP_MOUSECURSOR_TYPE_SOUTHWESTPANNING: PP_MouseCursor_Type = 27;
+pub const PP_MOUSECURSOR_TYPE_WESTPANNING: PP_MouseCursor_Type = 28;
+pub const PP_MOUSECURSOR_TYPE_MOVE: PP_MouseCursor_Type = 29;
+pub const PP_MOUSECURSOR_TYPE_VERTICALTEXT: PP_MouseCursor_Type = 30;
+pub const PP_MOUSECURSOR_TYPE_CELL: PP_MouseCursor_Type = 31;
+pub const PP_MOUSECURSOR_TYPE_CONTEXTMENU: PP_MouseCursor_Type = 32;
+pub const PP_MOUSECURSOR_TYPE_ALIAS: PP_MouseCursor_Type = 33;
+pub const PP_MOUSECURSOR_TYPE_PROGRESS: PP_MouseCursor_Type = 34;
+pub const PP_MOUSECURSOR_TYPE_NODROP: PP_MouseCursor_Type = 35;
+pub const PP_MOUSECURSOR_TYPE_COPY: PP_MouseCursor_Type = 36;
+pub const PP_MOUSECURSOR_TYPE_NONE: PP_MouseCursor_Type = 37;
+pub const PP_MOUSECURSOR_TYPE_NOTALLOWED: PP_MouseCursor_Type = 38;
+pub const PP_MOUSECURSOR_TYPE_ZOOMIN: PP_MouseCursor_Type = 39;
+pub const PP_MOUSECURSOR_TYPE_ZOOMOUT: PP_MouseCursor_Type = 40;
+pub const PP_MOUSECURSOR_TYPE_GRAB: PP_MouseCursor_Type = 41;
+pub const PP_MOUSECURSOR_TYPE_GRABBING: PP_MouseCursor_Type = 42;
+
 #[repr(C)]
 #[derive(Copy)]
 pub struct PPB_MouseCursor_1_0 {
-    pub SetCursor: Option<StubInterfaceFunc<PP_Bool>>,
+    pub SetCursor: Option<extern "C" fn(instance: PP_Instance,
+                                        _type: PP_MouseCursor_Type,
+                                        image: PP_Resource,
+                                        hot_spot: *const PP_Point) -> PP_Bool>,
 }
 impl ::std::clone::Clone for PPB_MouseCursor_1_0 {
     fn clone(&self) -> Self { *self }
@@ -658,18 +1066,39 @@ impl ::std::clone::Clone for PPB_URLResponseInfo_1_0 {
 impl ::std::default::Default for PPB_URLResponseInfo_1_0 {
     fn default() -> Self { unsafe { ::std::mem::zeroed() } }
 }
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PP_Point {
+    pub x: int32_t,
+    pub y: int32_t,
+}
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PP_Size {
+    pub width: int32_t,
+    pub height: int32_t,
+}
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PP_Rect {
+    pub point: PP_Point,
+    pub size: PP_Size,
+}
+
 #[repr(C)]
 #[derive(Copy)]
 pub struct PPB_View_1_2 {
-    pub IsView: Option<StubInterfaceFunc<PP_Bool>>,
-    pub GetRect: Option<StubInterfaceFunc<PP_Bool>>,
-    pub IsFullscreen: Option<StubInterfaceFunc<PP_Bool>>,
-    pub IsVisible: Option<StubInterfaceFunc<PP_Bool>>,
-    pub IsPageVisible: Option<StubInterfaceFunc<PP_Bool>>,
-    pub GetClipRect: Option<StubInterfaceFunc<PP_Bool>>,
-    pub GetDeviceScale: Option<StubInterfaceFunc<::libc::c_float>>,
-    pub GetCSSScale: Option<StubInterfaceFunc<::libc::c_float>>,
-    pub GetScrollOffset: Option<StubInterfaceFunc<::libc::c_float>>,
+    pub IsView: Option<extern "C" fn(resource: PP_Resource) -> PP_Bool>,
+    pub GetRect: Option<extern "C" fn(resource: PP_Resource, rect: *mut PP_Rect) -> PP_Bool>,
+    pub IsFullscreen: Option<extern "C" fn(resource: PP_Resource) -> PP_Bool>,
+    pub IsVisible: Option<extern "C" fn(resource: PP_Resource) -> PP_Bool>,
+    pub IsPageVisible: Option<extern "C" fn(resource: PP_Resource) -> PP_Bool>,
+    pub GetClipRect: Option<extern "C" fn(resource: PP_Resource,
+                                          clip: *mut PP_Rect) -> PP_Bool>,
+    pub GetDeviceScale: Option<extern "C" fn(resource: PP_Resource) -> ::libc::c_float>,
+    pub GetCSSScale: Option<extern "C" fn(resource: PP_Resource) -> ::libc::c_float>,
+    pub GetScrollOffset: Option<extern "C" fn(resource: PP_Resource,
+                                              offset: *mut PP_Point) -> PP_Bool>,
 }
 impl ::std::clone::Clone for PPB_View_1_2 {
     fn clone(&self) -> Self { *self }
@@ -677,3 +1106,184 @@ impl ::std::clone::Clone for PPB_View_1_2 {
 impl ::std::default::Default for PPB_View_1_2 {
     fn default() -> Self { unsafe { ::std::mem::zeroed() } }
 }
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PP_FloatPoint {
+    pub x: ::libc::c_float,
+    pub y: ::libc::c_float,
+}
+
+pub type PP_InputEvent_Type = ::libc::c_int;
+pub const PP_INPUTEVENT_TYPE_UNDEFINED: PP_InputEvent_Type = -1;
+pub const PP_INPUTEVENT_TYPE_MOUSEDOWN: PP_InputEvent_Type = 0;
+pub const PP_INPUTEVENT_TYPE_MOUSEUP: PP_InputEvent_Type = 1;
+pub const PP_INPUTEVENT_TYPE_MOUSEMOVE: PP_InputEvent_Type = 2;
+pub const PP_INPUTEVENT_TYPE_MOUSEENTER: PP_InputEvent_Type = 3;
+pub const PP_INPUTEVENT_TYPE_MOUSELEAVE: PP_InputEvent_Type = 4;
+pub const PP_INPUTEVENT_TYPE_WHEEL: PP_InputEvent_Type = 5;
+pub const PP_INPUTEVENT_TYPE_RAWKEYDOWN: PP_InputEvent_Type = 6;
+pub const PP_INPUTEVENT_TYPE_KEYDOWN: PP_InputEvent_Type = 7;
+pub const PP_INPUTEVENT_TYPE_KEYUP: PP_InputEvent_Type = 8;
+pub const PP_INPUTEVENT_TYPE_CHAR: PP_InputEvent_Type = 9;
+pub const PP_INPUTEVENT_TYPE_CONTEXTMENU: PP_InputEvent_Type = 10;
+
+pub type PP_InputEvent_Modifier = uint32_t;
+pub const PP_INPUTEVENT_MODIFIER_SHIFTKEY: PP_InputEvent_Modifier = 1 << 0;
+pub const PP_INPUTEVENT_MODIFIER_CONTROLKEY: PP_InputEvent_Modifier = 1 << 1;
+pub const PP_INPUTEVENT_MODIFIER_ALTKEY: PP_InputEvent_Modifier = 1 << 2;
+pub const PP_INPUTEVENT_MODIFIER_METAKEY: PP_InputEvent_Modifier = 1 << 3;
+pub const PP_INPUTEVENT_MODIFIER_ISKEYPAD: PP_InputEvent_Modifier = 1 << 4;
+pub const PP_INPUTEVENT_MODIFIER_ISAUTOREPEAT: PP_InputEvent_Modifier = 1 << 5;
+pub const PP_INPUTEVENT_MODIFIER_LEFTBUTTONDOWN: PP_InputEvent_Modifier = 1 << 6;
+pub const PP_INPUTEVENT_MODIFIER_MIDDLEBUTTONDOWN: PP_InputEvent_Modifier = 1 << 7;
+pub const PP_INPUTEVENT_MODIFIER_RIGHTBUTTONDOWN: PP_InputEvent_Modifier = 1 << 8;
+pub const PP_INPUTEVENT_MODIFIER_CAPSLOCKKEY: PP_InputEvent_Modifier = 1 << 9;
+pub const PP_INPUTEVENT_MODIFIER_NUMLOCKKEY: PP_InputEvent_Modifier = 1 << 10;
+pub const PP_INPUTEVENT_MODIFIER_ISLEFT: PP_InputEvent_Modifier = 1 << 11;
+pub const PP_INPUTEVENT_MODIFIER_ISRIGHT: PP_InputEvent_Modifier = 1 << 12;
+
+pub type PP_InputEvent_MouseButton = ::libc::c_int;
+pub const PP_INPUTEVENT_MOUSEBUTTON_NONE: PP_InputEvent_MouseButton = -1;
+pub const PP_INPUTEVENT_MOUSEBUTTON_LEFT: PP_InputEvent_MouseButton = 0;
+pub const PP_INPUTEVENT_MOUSEBUTTON_MIDDLE: PP_InputEvent_MouseButton = 1;
+pub const PP_INPUTEVENT_MOUSEBUTTON_RIGHT: PP_InputEvent_MouseButton = 2;
+
+pub type PP_InputEvent_Class = uint32_t;
+pub const PP_INPUTEVENT_CLASS_MOUSE: PP_InputEvent_Class = 1 << 0;
+pub const PP_INPUTEVENT_CLASS_KEYBOARD: PP_InputEvent_Class = 1 << 1;
+pub const PP_INPUTEVENT_CLASS_WHEEL: PP_InputEvent_Class = 1 << 2;
+pub const PP_INPUTEVENT_CLASS_TOUCH: PP_InputEvent_Class = 1 << 3;
+pub const PP_INPUTEVENT_CLASS_IME: PP_InputEvent_Class = 1 << 4;
+
+#[repr(C)]
+#[derive(Copy)]
+pub struct PPB_InputEvent_1_0 {
+    pub RequestInputEvents: Option<extern "C" fn(instance: PP_Instance,
+                                                 event_classes: uint32_t) -> int32_t>,
+    pub RequestFilteringInputEvents: Option<extern "C" fn(instance: PP_Instance,
+                                                          event_classes: uint32_t) -> int32_t>,
+    pub ClearInputEventRequest: Option<extern "C" fn(instance: PP_Instance,
+                                                     event_classes: uint32_t)>,
+    pub IsInputEvent: Option<extern "C" fn(resource: PP_Resource) -> PP_Bool>,
+    pub GetType: Option<extern "C" fn(event: PP_Resource) -> PP_InputEvent_Type>,
+    pub GetTimeStamp: Option<extern "C" fn(event: PP_Resource) -> PP_TimeTicks>,
+    pub GetModifiers: Option<extern "C" fn(event: PP_Resource) -> uint32_t>,
+}
+impl ::std::clone::Clone for PPB_InputEvent_1_0 {
+    fn clone(&self) -> Self { *self }
+}
+impl ::std::default::Default for PPB_InputEvent_1_0 {
+    fn default() -> Self { unsafe { ::std::mem::zeroed() } }
+}
+
+#[repr(C)]
+#[derive(Copy)]
+pub struct PPB_MouseInputEvent_1_1 {
+    pub Create: Option<extern "C" fn(instance: PP_Instance,
+                                     _type: PP_InputEvent_Type,
+                                     time_stamp: PP_TimeTicks,
+                                     modifiers: uint32_t,
+                                     mouse_button: PP_InputEvent_MouseButton,
+                                     mouse_position: *const PP_Point,
+                                     click_count: int32_t,
+                                     mouse_movement: *const PP_Point) -> PP_Resource>,
+    pub IsMouseInputEvent: Option<extern "C" fn(resource: PP_Resource) -> PP_Bool>,
+    pub GetButton: Option<extern "C" fn(mouse_event: PP_Resource) -> PP_InputEvent_MouseButton>,
+    pub GetPosition: Option<extern "C" fn(mouse_event: PP_Resource) -> PP_Point>,
+    pub GetClickCount: Option<extern "C" fn(mouse_event: PP_Resource) -> int32_t>,
+    pub GetMovement: Option<extern "C" fn(mouse_event: PP_Resource) -> PP_Point>,
+}
+impl ::std::clone::Clone for PPB_MouseInputEvent_1_1 {
+    fn clone(&self) -> Self { *self }
+}
+impl ::std::default::Default for PPB_MouseInputEvent_1_1 {
+    fn default() -> Self { unsafe { ::std::mem::zeroed() } }
+}
+
+#[repr(C)]
+#[derive(Copy)]
+pub struct PPB_WheelInputEvent_1_0 {
+    pub Create: Option<extern "C" fn(instance: PP_Instance,
+                                     time_stamp: PP_TimeTicks,
+                                     modifiers: uint32_t,
+                                     wheel_delta: *const PP_FloatPoint,
+                                     wheel_ticks: *const PP_FloatPoint,
+                                     scroll_by_page: PP_Bool) -> PP_Resource>,
+    pub IsWheelInputEvent: Option<extern "C" fn(resource: PP_Resource) -> PP_Bool>,
+    pub GetDelta: Option<extern "C" fn(wheel_event: PP_Resource) -> PP_FloatPoint>,
+    pub GetTicks: Option<extern "C" fn(wheel_event: PP_Resource) -> PP_FloatPoint>,
+    pub GetScrollByPage: Option<extern "C" fn(wheel_event: PP_Resource) -> PP_Bool>,
+}
+impl ::std::clone::Clone for PPB_WheelInputEvent_1_0 {
+    fn clone(&self) -> Self { *self }
+}
+impl ::std::default::Default for PPB_WheelInputEvent_1_0 {
+    fn default() -> Self { unsafe { ::std::mem::zeroed() } }
+}
+
+#[repr(C)]
+#[derive(Copy)]
+pub struct PPB_KeyboardInputEvent_1_2 {
+    pub Create: Option<extern "C" fn(instance: PP_Instance,
+                                     _type: PP_InputEvent_Type,
+                                     time_stamp: PP_TimeTicks,
+                                     modifiers: uint32_t,
+                                     key_code: uint32_t,
+                                     character_text: PP_Var,
+                                     code: PP_Var) -> PP_Resource>,
+    pub IsKeyboardInputEvent: Option<extern "C" fn(resource: PP_Resource) -> PP_Bool>,
+    pub GetKeyCode: Option<extern "C" fn(key_event: PP_Resource) -> uint32_t>,
+    pub GetCharacterText: Option<extern "C" fn(character_event: PP_Resource) -> PP_Var>,
+    pub GetCode: Option<extern "C" fn(key_event: PP_Resource) -> PP_Var>,
+}
+impl ::std::clone::Clone for PPB_KeyboardInputEvent_1_2 {
+    fn clone(&self) -> Self { *self }
+}
+impl ::std::default::Default for PPB_KeyboardInputEvent_1_2 {
+    fn default() -> Self { unsafe { ::std::mem::zeroed() } }
+}
+
+#[repr(C)]
+#[derive(Copy)]
+pub struct PPP_InputEvent_0_1 {
+    pub HandleInputEvent: extern "C" fn(instance: PP_Instance,
+                                        input_event: PP_Resource) -> PP_Bool,
+}
+impl ::std::clone::Clone for PPP_InputEvent_0_1 {
+    fn clone(&self) -> Self { *self }
+}
+
+pub type PP_ImageDataFormat = ::libc::c_int;
+pub const PP_IMAGEDATAFORMAT_BGRA_PREMUL: PP_ImageDataFormat = 0;
+pub const PP_IMAGEDATAFORMAT_RGBA_PREMUL: PP_ImageDataFormat = 1;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PP_ImageDataDesc {
+    pub format: PP_ImageDataFormat,
+    pub size: PP_Size,
+    pub stride: int32_t,
+}
+
+#[repr(C)]
+#[derive(Copy)]
+pub struct PPB_ImageData_1_0 {
+    pub GetNativeImageDataFormat: Option<extern "C" fn() -> PP_ImageDataFormat>,
+    pub IsImageDataFormatSupported: Option<extern "C" fn(format: PP_ImageDataFormat) -> PP_Bool>,
+    pub Create: Option<extern "C" fn(instance: PP_Instance,
+                                     format: PP_ImageDataFormat,
+                                     size: *const PP_Size,
+                                     init_to_zero: PP_Bool) -> PP_Resource>,
+    pub IsImageData: Option<extern "C" fn(image_data: PP_Resource) -> PP_Bool>,
+    pub Describe: Option<extern "C" fn(image_data: PP_Resource,
+                                       desc: *mut PP_ImageDataDesc) -> PP_Bool>,
+    pub Map: Option<extern "C" fn(image_data: PP_Resource) -> *mut ::libc::c_void>,
+    pub Unmap: Option<extern "C" fn(image_data: PP_Resource)>,
+}
+impl ::std::clone::Clone for PPB_ImageData_1_0 {
+    fn clone(&self) -> Self { *self }
+}
+impl ::std::default::Default for PPB_ImageData_1_0 {
+    fn default() -> Self { unsafe { ::std::mem::zeroed() } }
+}
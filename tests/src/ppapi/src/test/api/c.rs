@@ -27,6 +27,10 @@ fn invalid_interface_not_present() {
     assert!(!check_get_interface("NOT AN INTERFACE, BRAH!"));
 }
 
+#[test]
+fn audio_1_0_interface_present() {
+    assert!(check_get_interface("PPB_Audio;1.0"));
+}
 #[test]
 fn audio_interface_present() {
     assert!(check_get_interface("PPB_Audio;1.1"));
@@ -66,7 +70,6 @@ fn graphics3d_interface_present() {
     assert!(check_get_interface("PPB_Graphics3D;1.0"));
 }
 
-#[ignore]
 #[test]
 fn image_data_interface_present() {
     assert!(check_get_interface("PPB_ImageData;1.0"));
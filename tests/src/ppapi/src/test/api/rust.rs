@@ -269,6 +269,21 @@ fn file_io_open_ref_count() {
     assert_eq!(io.ref_count(), 1);
 }
 
+#[test]
+fn file_ref_get_name_ref_count() {
+    let (i, fs) = _create_filesystem_resource();
+
+    let path = Path::new("/test-file").to_path_buf();
+    let fr = i.create_file_ref(fs.id(), path).unwrap();
+    let refs = fr.ref_count();
+
+    // Each call used to give back a ref it never took.
+    for _ in 0..2 {
+        assert!(i.get_name_file_ref(fr.id()).is_ok());
+    }
+    assert_eq!(fr.ref_count(), refs);
+}
+
 #[test]
 fn file_io_create() {
     let (i, fs) = _create_filesystem_resource();
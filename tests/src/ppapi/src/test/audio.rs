@@ -0,0 +1,287 @@
+/// Tests for the simulated audio device and the latency it reports, and that
+/// the delay ppapi-aout's `TimeGet` reports from that latency is the one the
+/// output model implies. The module isn't linked into the tests, so its
+/// arithmetic is ported exactly below.
+
+use libc;
+use std::sync::Mutex;
+use std::sync::mpsc::channel;
+use std::thread::{self, sleep};
+use std::time::Duration;
+
+use ppapi::audio::OutputBufferModel;
+use ppapi::callback::Callback;
+use ppapi::graphics::Graphics3DState;
+use ppapi::resource::{ResourceState, get_resource};
+use ppapi::sys::{self, PPB_Audio_1_0, PPB_Audio_1_1, PPB_AudioConfig_1_1,
+                 PPB_Graphics3D_1_0};
+use ppapi::view::ViewData;
+
+use super::{get_interface, new_test_instance};
+
+#[derive(Default)]
+struct Calls(Mutex<Vec<(u32, Option<f64>)>>);
+impl Calls {
+    fn user_data(&self) -> *mut libc::c_void {
+        self as *const Calls as *mut _
+    }
+    fn get(&self) -> Vec<(u32, Option<f64>)> { self.0.lock().unwrap().clone() }
+    fn wait_for(&self, count: usize) {
+        for _ in 0..100 {
+            if self.0.lock().unwrap().len() >= count { return; }
+            sleep(Duration::from_millis(10));
+        }
+        panic!("audio callback wasn't called");
+    }
+}
+extern "C" fn callback_1_0(_buffer: *mut libc::c_void, size: u32,
+                           user_data: *mut libc::c_void) {
+    let calls = unsafe { &*(user_data as *const Calls) };
+    calls.0.lock().unwrap().push((size, None));
+}
+extern "C" fn callback_1_1(_buffer: *mut libc::c_void, size: u32,
+                           latency: sys::PP_TimeDelta,
+                           user_data: *mut libc::c_void) {
+    let calls = unsafe { &*(user_data as *const Calls) };
+    calls.0.lock().unwrap().push((size, Some(latency)));
+}
+
+/// `TimeGet` in `modules/ppapi-aout.c`, in microseconds: the latency the
+/// last callback was given, plus what's left in `ppapi_stream`. `stream` is
+/// each block's `(i_nb_samples, i_buffer)`, with `offset` bytes of the first
+/// already read.
+fn aout_time_get(latency_s: f64, stream: &[(i64, usize)], offset: usize, rate: i64) -> i64 {
+    const CLOCK_FREQ: i64 = 1_000_000;
+    // `PPAPIAudioCallback` stores the latency as `sys->pts`.
+    let mut delay = (latency_s * CLOCK_FREQ as f64) as i64;
+
+    let mut samples_delay = 0;
+    for (n, &(nb_samples, buffer)) in stream.iter().enumerate() {
+        samples_delay += nb_samples;
+        if n == 0 {
+            let bytes_per_sample = buffer / nb_samples as usize;
+            let samples = (offset / bytes_per_sample) as i64;
+            assert!(samples <= samples_delay);
+            samples_delay -= samples;
+        }
+    }
+
+    samples_delay *= CLOCK_FREQ;
+    samples_delay /= rate;
+    delay += samples_delay;
+    delay
+}
+
+/// The latency the first callback of an audio resource playing with `model`
+/// is given.
+fn reported_latency(model: OutputBufferModel, rate: u32, frames: u32) -> f64 {
+    let i = new_test_instance(Default::default());
+    i.set_audio_output_model(model);
+
+    let iconfig: &PPB_AudioConfig_1_1 = get_interface("PPB_AudioConfig;1.1");
+    let iaudio: &PPB_Audio_1_1 = get_interface("PPB_Audio;1.1");
+    let config = (iconfig.CreateStereo16Bit)(i.id(), rate, frames);
+    let calls: Calls = Default::default();
+    let audio = (iaudio.create)(i.id(), config, callback_1_1, calls.user_data());
+    assert_eq!((iaudio.start_playback)(audio), sys::PP_TRUE);
+    calls.wait_for(1);
+    assert_eq!((iaudio.stop_playback)(audio), sys::PP_TRUE);
+
+    calls.get()[0].1.unwrap()
+}
+
+const RATE: u32 = sys::PP_AUDIOSAMPLERATE_48000;
+// `BUFFER_SAMPLES` in ppapi-aout.c
+const FRAMES: u32 = 386;
+
+#[test]
+fn latency_matches_output_model() {
+    let i = new_test_instance(Default::default());
+    i.set_audio_output_model(OutputBufferModel {
+        queued_buffers: 3,
+        hardware_latency: 0.01,
+    });
+
+    let iconfig: &PPB_AudioConfig_1_1 = get_interface("PPB_AudioConfig;1.1");
+    let iaudio: &PPB_Audio_1_1 = get_interface("PPB_Audio;1.1");
+
+    let config = (iconfig.CreateStereo16Bit)(i.id(), RATE, FRAMES);
+    assert!(config != 0);
+
+    let calls: Calls = Default::default();
+    let audio = (iaudio.create)(i.id(), config, callback_1_1, calls.user_data());
+    assert!(audio != 0);
+    assert_eq!((iaudio.get_config)(audio), config);
+
+    assert_eq!((iaudio.start_playback)(audio), sys::PP_TRUE);
+    calls.wait_for(2);
+    assert_eq!((iaudio.stop_playback)(audio), sys::PP_TRUE);
+
+    // 3 buffers of 386 frames at 48kHz is 24.125ms, plus the hardware's 10ms.
+    for (size, latency) in calls.get() {
+        assert_eq!(size, FRAMES * 4);
+        let latency = latency.unwrap();
+        assert!((latency - 0.034125).abs() < 1e-9, "latency `{}`s != 34.125ms", latency);
+    }
+}
+
+#[test]
+fn aout_delay_matches_output_model() {
+    // aout truncates to whole microseconds, and the latency may be a hair
+    // under its exact value.
+    fn assert_near(delay: i64, expected: i64) {
+        assert!((delay - expected).abs() <= 1, "aout delay `{}`us != `{}`us", delay, expected);
+    }
+
+    // 3 buffers of 386 frames at 48kHz is 24.125ms, plus 10ms. With one
+    // block of 386 samples queued, unread: 8041.67us more.
+    let latency = reported_latency(OutputBufferModel {
+        queued_buffers: 3,
+        hardware_latency: 0.01,
+    }, RATE, FRAMES);
+    assert_near(aout_time_get(latency, &[(386, 386 * 4)], 0, RATE as i64), 42_166);
+
+    // 5 buffers of 441 frames at 44.1kHz is 50ms, plus 12.5ms. Two blocks of
+    // 1024 samples queued, 250 of them read: 1798 samples, or 40770.98us.
+    let rate = sys::PP_AUDIOSAMPLERATE_44100;
+    let latency = reported_latency(OutputBufferModel {
+        queued_buffers: 5,
+        hardware_latency: 0.0125,
+    }, rate, 441);
+    let stream = [(1024, 1024 * 4), (1024, 1024 * 4)];
+    assert_near(aout_time_get(latency, &stream, 250 * 4, rate as i64), 103_270);
+
+    // Nothing queued anywhere.
+    let latency = reported_latency(OutputBufferModel {
+        queued_buffers: 0,
+        hardware_latency: 0.0,
+    }, rate, 441);
+    assert_eq!(aout_time_get(latency, &[], 0, rate as i64), 0);
+}
+
+#[test]
+fn audio_1_0_has_no_latency() {
+    let i = new_test_instance(Default::default());
+
+    let iconfig: &PPB_AudioConfig_1_1 = get_interface("PPB_AudioConfig;1.1");
+    let iaudio: &PPB_Audio_1_0 = get_interface("PPB_Audio;1.0");
+
+    let config = (iconfig.CreateStereo16Bit)(i.id(), RATE, FRAMES);
+    let calls: Calls = Default::default();
+    let audio = (iaudio.create)(i.id(), config, callback_1_0, calls.user_data());
+    assert!(audio != 0);
+
+    assert_eq!((iaudio.start_playback)(audio), sys::PP_TRUE);
+    calls.wait_for(1);
+    assert_eq!((iaudio.stop_playback)(audio), sys::PP_TRUE);
+
+    assert_eq!(calls.get()[0], (FRAMES * 4, None));
+}
+
+#[test]
+fn no_callbacks_after_stop_playback() {
+    let i = new_test_instance(Default::default());
+
+    let iconfig: &PPB_AudioConfig_1_1 = get_interface("PPB_AudioConfig;1.1");
+    let iaudio: &PPB_Audio_1_1 = get_interface("PPB_Audio;1.1");
+
+    let config = (iconfig.CreateStereo16Bit)(i.id(), RATE, FRAMES);
+    let calls: Calls = Default::default();
+    let audio = (iaudio.create)(i.id(), config, callback_1_1, calls.user_data());
+
+    assert_eq!((iaudio.start_playback)(audio), sys::PP_TRUE);
+    calls.wait_for(1);
+    assert_eq!((iaudio.stop_playback)(audio), sys::PP_TRUE);
+
+    let count = calls.get().len();
+    sleep(Duration::from_millis(50));
+    assert_eq!(calls.get().len(), count);
+}
+
+#[test]
+fn audio_config_rejects_bad_rates() {
+    let i = new_test_instance(Default::default());
+
+    let iconfig: &PPB_AudioConfig_1_1 = get_interface("PPB_AudioConfig;1.1");
+    assert_eq!((iconfig.CreateStereo16Bit)(i.id(), 22050, FRAMES), 0);
+    assert_eq!((iconfig.CreateStereo16Bit)(i.id(), RATE, 1), 0);
+
+    let config = (iconfig.CreateStereo16Bit)(i.id(), RATE, FRAMES);
+    assert_eq!((iconfig.IsAudioConfig)(config), sys::PP_TRUE);
+    assert_eq!((iconfig.GetSampleRate)(config), RATE);
+    assert_eq!((iconfig.GetSampleFrameCount)(config), FRAMES);
+}
+
+#[test]
+fn audio_callbacks_recorded_on_timeline() {
+    use ppapi::timeline::TimelineEvent;
+
+    let i = new_test_instance(Default::default());
+
+    let iconfig: &PPB_AudioConfig_1_1 = get_interface("PPB_AudioConfig;1.1");
+    let iaudio: &PPB_Audio_1_1 = get_interface("PPB_Audio;1.1");
+
+    let config = (iconfig.CreateStereo16Bit)(i.id(), RATE, FRAMES);
+    let calls: Calls = Default::default();
+    let audio = (iaudio.create)(i.id(), config, callback_1_1, calls.user_data());
+
+    i.start_timeline();
+    assert_eq!((iaudio.start_playback)(audio), sys::PP_TRUE);
+    calls.wait_for(3);
+    assert_eq!((iaudio.stop_playback)(audio), sys::PP_TRUE);
+
+    let timeline = i.take_timeline().unwrap();
+    let callbacks = timeline.audio_callbacks();
+    assert_eq!(callbacks.len(), calls.get().len());
+    for entry in callbacks.iter() {
+        assert_eq!(entry.event, TimelineEvent::AudioCallback {
+            audio: audio,
+            sample_rate: RATE,
+            frames: FRAMES,
+            latency: calls.get()[0].1.unwrap(),
+        });
+    }
+    for pair in callbacks.windows(2) {
+        assert!(pair[0].ts <= pair[1].ts);
+    }
+
+    // Recording stopped with `take_timeline`.
+    assert!(i.take_timeline().unwrap().is_empty());
+}
+
+#[test]
+fn audio_plays_while_video_is_held() {
+    let i = new_test_instance(Default::default());
+    let view = ViewData::on_screen(320, 240);
+    i.set_view(view.page_hidden()).unwrap();
+
+    let g3d: &PPB_Graphics3D_1_0 = get_interface("PPB_Graphics3D;1.0");
+    let attribs = [
+        sys::PP_GRAPHICS3DATTRIB_WIDTH, 320,
+        sys::PP_GRAPHICS3DATTRIB_HEIGHT, 240,
+        sys::PP_GRAPHICS3DATTRIB_NONE,
+    ];
+    let context = (g3d.Create.unwrap())(i.id(), 0, attribs.as_ptr());
+    let (swapped_tx, swapped) = channel();
+    let swapper = thread::spawn(move || {
+        let res = get_resource::<Graphics3DState>(context).unwrap();
+        let state = Graphics3DState::state_from_resstate(res.get_rc()).unwrap();
+        Graphics3DState::swap_buffers(state, Callback::Sync).unwrap();
+        swapped_tx.send(()).unwrap();
+    });
+
+    let iconfig: &PPB_AudioConfig_1_1 = get_interface("PPB_AudioConfig;1.1");
+    let iaudio: &PPB_Audio_1_1 = get_interface("PPB_Audio;1.1");
+    let config = (iconfig.CreateStereo16Bit)(i.id(), RATE, FRAMES);
+    let calls: Calls = Default::default();
+    let audio = (iaudio.create)(i.id(), config, callback_1_1, calls.user_data());
+    assert_eq!((iaudio.start_playback)(audio), sys::PP_TRUE);
+
+    calls.wait_for(3);
+    assert!(swapped.try_recv().is_err());
+
+    i.set_view(view).unwrap();
+    swapper.join().unwrap();
+    assert!(swapped.try_recv().is_ok());
+    assert_eq!((iaudio.stop_playback)(audio), sys::PP_TRUE);
+}
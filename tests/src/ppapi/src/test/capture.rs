@@ -0,0 +1,156 @@
+/// Tests for reading back frames on `SwapBuffers`.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Read;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use ppapi::callback::Callback;
+use ppapi::capture::{self, FrameSink, ImageFormat};
+use ppapi::graphics::Graphics3DState;
+use ppapi::resource::{ResourceState, get_resource};
+use ppapi::sys::{self, PP_Resource, PPB_Graphics3D_1_0, PPB_OpenGLES2};
+
+use super::{get_interface, new_test_instance, TestInstance};
+
+fn new_context(i: &TestInstance, width: i32, height: i32) -> PP_Resource {
+    let g3d: &PPB_Graphics3D_1_0 = get_interface("PPB_Graphics3D;1.0");
+    let attribs = [
+        sys::PP_GRAPHICS3DATTRIB_WIDTH, width,
+        sys::PP_GRAPHICS3DATTRIB_HEIGHT, height,
+        sys::PP_GRAPHICS3DATTRIB_NONE,
+    ];
+    (g3d.Create.unwrap())(i.id(), 0, attribs.as_ptr())
+}
+/// Clear the bottom row to `bottom` and the rest to `top`, then swap.
+fn draw_and_swap(context: PP_Resource, top: [f32; 3], bottom: [f32; 3]) {
+    let gl: &PPB_OpenGLES2 = get_interface("PPB_OpenGLES2;1.0");
+    (gl.Disable.unwrap())(context, sys::GL_SCISSOR_TEST);
+    (gl.ClearColor.unwrap())(context, top[0], top[1], top[2], 1.0);
+    (gl.Clear.unwrap())(context, sys::GL_COLOR_BUFFER_BIT);
+    (gl.Enable.unwrap())(context, sys::GL_SCISSOR_TEST);
+    (gl.Scissor.unwrap())(context, 0, 0, 16, 1);
+    (gl.ClearColor.unwrap())(context, bottom[0], bottom[1], bottom[2], 1.0);
+    (gl.Clear.unwrap())(context, sys::GL_COLOR_BUFFER_BIT);
+
+    let res = get_resource::<Graphics3DState>(context).unwrap();
+    let state = Graphics3DState::state_from_resstate(res.get_rc()).unwrap();
+    Graphics3DState::swap_buffers(state, Callback::Sync).unwrap();
+}
+
+#[test]
+fn fnv1a_is_stable() {
+    assert_eq!(capture::fnv1a(b""), 0xcbf29ce484222325);
+    assert_eq!(capture::fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+}
+
+#[test]
+fn image_encodings() {
+    let rgba = [1, 2, 3, 255, 4, 5, 6, 128];
+
+    let mut ppm = Vec::new();
+    capture::write_ppm(&mut ppm, 2, 1, &rgba).unwrap();
+    assert_eq!(&ppm[..], &b"P6\n2 1\n255\n\x01\x02\x03\x04\x05\x06"[..]);
+
+    let mut png = Vec::new();
+    capture::write_png(&mut png, 2, 1, &rgba).unwrap();
+    assert_eq!(&png[..8], &b"\x89PNG\r\n\x1a\n"[..]);
+    assert_eq!(&png[12..16], b"IHDR");
+    // IEND always has the same CRC.
+    assert_eq!(&png[png.len() - 12..],
+               &b"\0\0\0\0IEND\xae\x42\x60\x82"[..]);
+    // One stored block: the filter byte and the row.
+    let idat = 8 + 25;
+    assert_eq!(&png[idat + 4..idat + 8], b"IDAT");
+    assert_eq!(&png[idat + 8..idat + 15], &[0x78, 0x01, 1, 9, 0, !9, 0xff][..]);
+    assert_eq!(&png[idat + 15..idat + 24], &[0, 1, 2, 3, 255, 4, 5, 6, 128][..]);
+}
+
+#[test]
+fn swaps_are_captured_top_row_first() {
+    let i = new_test_instance(Default::default());
+    let context = new_context(&i, 2, 2);
+
+    draw_and_swap(context, [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]);
+    i.start_frame_capture(FrameSink::Keep);
+    draw_and_swap(context, [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]);
+    draw_and_swap(context, [0.0, 1.0, 0.0], [0.0, 1.0, 0.0]);
+
+    let frames = i.take_frames().unwrap();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames.iter().map(|f| f.index ).collect::<Vec<_>>(), vec![1, 2]);
+    assert!(frames[0].ts <= frames[1].ts);
+
+    let expected = [
+        255, 0, 0, 255, 255, 0, 0, 255,
+        0, 0, 255, 255, 0, 0, 255, 255,
+    ];
+    let first = &frames[0];
+    assert_eq!((first.context, first.width, first.height), (context, 2, 2));
+    assert_eq!(first.pixels.as_ref().map(|p| &p[..] ), Some(&expected[..]));
+    assert_eq!(first.hash, capture::fnv1a(&expected[..]));
+    assert!(first.hash != frames[1].hash);
+
+    // Capturing stopped.
+    draw_and_swap(context, [0.0, 0.0, 0.0], [0.0, 0.0, 0.0]);
+    assert!(i.take_frames().unwrap().is_empty());
+}
+
+#[test]
+fn frames_saved_to_dir() {
+    let i = new_test_instance(Default::default());
+    let context = new_context(&i, 3, 2);
+    let dir = env::temp_dir().join(format!("ppapi-capture-{}", i.id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    i.start_frame_capture(FrameSink::Save {
+        dir: dir.clone(),
+        format: ImageFormat::Ppm,
+    });
+    draw_and_swap(context, [1.0, 1.0, 1.0], [0.0, 0.0, 0.0]);
+    let frames = i.take_frames().unwrap();
+
+    let path = frames[0].path.clone().unwrap();
+    assert_eq!(path, dir.join(format!("{}-0.ppm", context)));
+    let mut contents = Vec::new();
+    File::open(&path).unwrap().read_to_end(&mut contents).unwrap();
+    let mut expected = b"P6\n3 2\n255\n".to_vec();
+    expected.extend_from_slice(&[255; 9]);
+    expected.extend_from_slice(&[0; 9]);
+    assert_eq!(contents, expected);
+    assert!(frames[0].pixels.is_none());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn restarted_captures_only_hold_their_own_frames() {
+    let i = new_test_instance(Default::default());
+    // Big enough that reading back a frame takes a while.
+    let context = new_context(&i, 512, 512);
+
+    let done = Arc::new(AtomicBool::new(false));
+    let swapper = {
+        let done = done.clone();
+        thread::spawn(move || {
+            while !done.load(Ordering::SeqCst) {
+                draw_and_swap(context, [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]);
+            }
+        })
+    };
+
+    for _ in 0..200 {
+        i.start_frame_capture(FrameSink::Keep);
+        thread::sleep(Duration::from_millis(1));
+        i.start_frame_capture(FrameSink::Hash);
+        let frames = i.take_frames().unwrap();
+        assert!(frames.iter().all(|f| f.pixels.is_none() ));
+    }
+
+    done.store(true, Ordering::SeqCst);
+    swapper.join().unwrap();
+}
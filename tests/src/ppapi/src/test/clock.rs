@@ -0,0 +1,130 @@
+/// Tests for the module's clock, and the timers and loads that go by it.
+
+use libc;
+use std::sync::Mutex;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
+
+use ppapi::clock::ClockMode;
+use ppapi::resource::get_resource;
+use ppapi::sys::{self, PPB_Core_1_0};
+use ppapi::url_loader::{UrlInfo, UrlLoaderState, url_manager};
+
+use super::{exclusive_module, get_interface, new_test_instance};
+
+fn assert_close(a: f64, b: f64) {
+    assert!((a - b).abs() < 0.001, "{} isn't {}", a, b);
+}
+
+/// `user` points to a `Mutex<Sender<i32>>`.
+extern "C" fn send_result(user: *mut libc::c_void, result: i32) {
+    let tx = unsafe { &*(user as *const Mutex<Sender<i32>>) };
+    let _ = tx.lock().unwrap().send(result);
+}
+/// Posts to the main loop, to be sent the result after `delay_ms`.
+fn call_on_main_thread(delay_ms: i32) -> Receiver<i32> {
+    let core: &PPB_Core_1_0 = get_interface("PPB_Core;1.0");
+    let (tx, rx) = channel();
+    // Leaked; the callback may outlive the test if it fails.
+    let user = Box::into_raw(Box::new(Mutex::new(tx)));
+    (core.call_on_main_thread)(delay_ms, sys::PP_CompletionCallback {
+        func: send_result,
+        user_data: user as *mut _,
+        flags: 0,
+    }, sys::PP_OK);
+    rx
+}
+
+#[test]
+fn frozen_clock_stops_module_time() {
+    let module = exclusive_module();
+    let clock = module.clock();
+    let core: &PPB_Core_1_0 = get_interface("PPB_Core;1.0");
+
+    clock.set_mode(ClockMode::Frozen);
+    let (ticks, time) = ((core.get_time_ticks)(), (core.get_time)());
+    sleep(Duration::from_millis(20));
+    assert_eq!((core.get_time_ticks)(), ticks);
+    assert_eq!((core.get_time)(), time);
+
+    clock.advance(Duration::from_secs(3));
+    assert_close((core.get_time_ticks)() - ticks, 3.0);
+    assert_close((core.get_time)() - time, 3.0);
+
+    // Back to real time from there, not from the system clocks.
+    clock.set_mode(ClockMode::Real);
+    let since = (core.get_time_ticks)() - ticks;
+    assert!(since >= 3.0 && since < 4.0, "{}s passed", since);
+}
+
+#[test]
+fn delayed_work_waits_for_the_clock() {
+    let module = exclusive_module();
+    let clock = module.clock();
+    clock.set_mode(ClockMode::Frozen);
+
+    let rx = call_on_main_thread(3000);
+    assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+    clock.advance(Duration::from_secs(2));
+    assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(sys::PP_OK));
+}
+
+#[test]
+fn scaled_clock_runs_fast() {
+    let module = exclusive_module();
+    let clock = module.clock();
+    let core: &PPB_Core_1_0 = get_interface("PPB_Core;1.0");
+    clock.set_mode(ClockMode::Scaled(100.0));
+
+    let ticks = (core.get_time_ticks)();
+    sleep(Duration::from_millis(20));
+    assert!((core.get_time_ticks)() - ticks >= 2.0);
+
+    let start = Instant::now();
+    let rx = call_on_main_thread(3000);
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(sys::PP_OK));
+    assert!(Instant::now() - start < Duration::from_secs(1));
+}
+
+#[test]
+fn throttled_loads_arrive_with_the_clock() {
+    const URL: &'static str = "http://fixtures.test/throttled.webm";
+    let body: Vec<u8> = (0..20).collect();
+    url_manager().add_fixture(URL, UrlInfo::new(body.clone(), "video/webm").throttled(10))
+        .unwrap();
+
+    let module = exclusive_module();
+    let clock = module.clock();
+    clock.set_mode(ClockMode::Frozen);
+    let i = new_test_instance(Default::default());
+    let loader = UrlLoaderState::open_document(&i, URL).unwrap();
+
+    assert_eq!(loader.download_progress(), Ok((0, 20)));
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(loader.download_progress(), Ok((10, 20)));
+    let mut buffer = [0u8; 16];
+    assert_eq!(loader.read_response_body(&mut buffer), Ok(10));
+    assert_eq!(&buffer[..10], &body[..10]);
+
+    // Reads wait for the rest to arrive.
+    let (tx, rx) = channel();
+    let id = loader.id();
+    spawn(move || {
+        let loader = get_resource::<UrlLoaderState>(id).unwrap();
+        let mut buffer = [0u8; 16];
+        for _ in 0..3 {
+            let read = loader.read_response_body(&mut buffer).unwrap();
+            tx.send(buffer[..read].to_vec()).unwrap();
+        }
+    });
+    assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+    clock.advance(Duration::from_millis(500));
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(body[10..15].to_vec()));
+    assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(body[15..].to_vec()));
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(vec![]));
+}
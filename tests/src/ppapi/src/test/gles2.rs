@@ -0,0 +1,349 @@
+/// Tests for the software `PPB_OpenGLES2;1.0`, using the shaders and calls
+/// `vout_display_opengl` makes.
+
+use libc;
+use std::ffi::CString;
+use std::ptr;
+
+use ppapi::gles2;
+use ppapi::sys::{self, PP_Resource, PPB_Graphics3D_1_0, PPB_OpenGLES2};
+use ppapi::sys::{GLenum, GLint, GLuint};
+
+use super::{get_interface, new_test_instance, TestInstance};
+
+const VERTEX_SHADER: &'static str = "
+#version 100
+varying vec4 TexCoord0,TexCoord1,TexCoord2;
+attribute vec4 MultiTexCoord0,MultiTexCoord1,MultiTexCoord2;
+attribute vec4 VertexPosition;
+void main() {
+ TexCoord0 = MultiTexCoord0;
+ TexCoord1 = MultiTexCoord1;
+ TexCoord2 = MultiTexCoord2;
+ gl_Position = VertexPosition;
+}";
+const RGBA_SHADER: &'static str = "
+#version 100
+precision highp float;
+uniform sampler2D Texture0;
+uniform vec4 FillColor;
+varying vec4 TexCoord0;
+void main() {
+ gl_FragColor = texture2D(Texture0, TexCoord0.st) * FillColor;
+}";
+const YUV_SHADER: &'static str = "
+#version 100
+precision highp float;
+uniform sampler2D Texture0;
+uniform sampler2D Texture1;
+uniform sampler2D Texture2;
+uniform vec4      Coefficient[4];
+varying vec4      TexCoord0,TexCoord1,TexCoord2;
+void main(void) {
+ vec4 x,y,z,result;
+ x  = texture2D(Texture0, TexCoord0.st);
+ y  = texture2D(Texture1, TexCoord1.st);
+ z  = texture2D(Texture2, TexCoord2.st);
+ result = x * Coefficient[0] + Coefficient[3];
+ result = (y * Coefficient[1]) + result;
+ result = (z * Coefficient[2]) + result;
+ gl_FragColor = result;
+}";
+
+/// A full surface quad, as a triangle strip.
+const QUAD: [f32; 8] = [-1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0];
+const QUAD_TEX: [f32; 8] = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+
+fn gl() -> &'static PPB_OpenGLES2 { get_interface("PPB_OpenGLES2;1.0") }
+
+fn new_context(i: &TestInstance, width: i32, height: i32) -> PP_Resource {
+    let g3d: &PPB_Graphics3D_1_0 = get_interface("PPB_Graphics3D;1.0");
+    let attribs = [
+        sys::PP_GRAPHICS3DATTRIB_WIDTH, width,
+        sys::PP_GRAPHICS3DATTRIB_HEIGHT, height,
+        sys::PP_GRAPHICS3DATTRIB_NONE,
+    ];
+    let context = (g3d.Create.unwrap())(i.id(), 0, attribs.as_ptr());
+    assert!(context != 0);
+    context
+}
+
+fn read_pixels(c: PP_Resource, width: i32, height: i32) -> Vec<[u8; 4]> {
+    let gl = gl();
+    let mut out = vec![[0u8; 4]; (width * height) as usize];
+    (gl.ReadPixels.unwrap())(c, 0, 0, width, height, sys::GL_RGBA,
+                             sys::GL_UNSIGNED_BYTE, out.as_mut_ptr() as *mut _);
+    assert_eq!((gl.GetError.unwrap())(c), sys::GL_NO_ERROR);
+    out
+}
+
+fn compile(c: PP_Resource, kind: GLenum, source: &str) -> GLuint {
+    let gl = gl();
+    let shader = (gl.CreateShader.unwrap())(c, kind);
+    let source = CString::new(source).unwrap();
+    let sources = [source.as_ptr()];
+    (gl.ShaderSource.unwrap())(c, shader, 1, sources.as_ptr(), ptr::null());
+    (gl.CompileShader.unwrap())(c, shader);
+
+    let mut status = 0;
+    (gl.GetShaderiv.unwrap())(c, shader, sys::GL_COMPILE_STATUS, &mut status);
+    assert_eq!(status, sys::GL_TRUE as GLint);
+    shader
+}
+fn link(c: PP_Resource, fragment: &str) -> Result<GLuint, String> {
+    let gl = gl();
+    let program = (gl.CreateProgram.unwrap())(c);
+    (gl.AttachShader.unwrap())(c, program, compile(c, sys::GL_VERTEX_SHADER, VERTEX_SHADER));
+    (gl.AttachShader.unwrap())(c, program, compile(c, sys::GL_FRAGMENT_SHADER, fragment));
+    (gl.LinkProgram.unwrap())(c, program);
+
+    let mut status = 0;
+    (gl.GetProgramiv.unwrap())(c, program, sys::GL_LINK_STATUS, &mut status);
+    if status == sys::GL_TRUE as GLint {
+        return Ok(program);
+    }
+
+    let mut log = [0 as libc::c_char; 256];
+    let mut len = 0;
+    (gl.GetProgramInfoLog.unwrap())(c, program, log.len() as i32, &mut len, log.as_mut_ptr());
+    let log: Vec<u8> = log[..len as usize].iter().map(|&b| b as u8 ).collect();
+    Err(String::from_utf8(log).unwrap())
+}
+fn location(c: PP_Resource, program: GLuint, name: &str) -> GLint {
+    let name = CString::new(name).unwrap();
+    (gl().GetUniformLocation.unwrap())(c, program, name.as_ptr())
+}
+
+fn texture(c: PP_Resource, unit: GLuint, format: GLenum, width: i32, height: i32,
+           pixels: &[u8]) -> GLuint {
+    let gl = gl();
+    let mut texture = 0;
+    (gl.GenTextures.unwrap())(c, 1, &mut texture);
+    (gl.ActiveTexture.unwrap())(c, sys::GL_TEXTURE0 + unit);
+    (gl.BindTexture.unwrap())(c, sys::GL_TEXTURE_2D, texture);
+    (gl.PixelStorei.unwrap())(c, sys::GL_UNPACK_ALIGNMENT, 1);
+    (gl.TexParameteri.unwrap())(c, sys::GL_TEXTURE_2D, sys::GL_TEXTURE_MIN_FILTER,
+                                sys::GL_NEAREST as GLint);
+    (gl.TexParameteri.unwrap())(c, sys::GL_TEXTURE_2D, sys::GL_TEXTURE_MAG_FILTER,
+                                sys::GL_NEAREST as GLint);
+    (gl.TexParameteri.unwrap())(c, sys::GL_TEXTURE_2D, sys::GL_TEXTURE_WRAP_S,
+                                sys::GL_CLAMP_TO_EDGE as GLint);
+    (gl.TexParameteri.unwrap())(c, sys::GL_TEXTURE_2D, sys::GL_TEXTURE_WRAP_T,
+                                sys::GL_CLAMP_TO_EDGE as GLint);
+    (gl.TexImage2D.unwrap())(c, sys::GL_TEXTURE_2D, 0, format as GLint, width, height,
+                             0, format, sys::GL_UNSIGNED_BYTE,
+                             pixels.as_ptr() as *const _);
+    assert_eq!((gl.GetError.unwrap())(c), sys::GL_NO_ERROR);
+    texture
+}
+
+/// Like `DrawWithShaders`: client side arrays, a triangle strip.
+fn draw_quad(c: PP_Resource, program: GLuint, textures: u32) {
+    let gl = gl();
+    let attrib = |name: &str| {
+        let name = CString::new(name).unwrap();
+        (gl.GetAttribLocation.unwrap())(c, program, name.as_ptr()) as GLuint
+    };
+    for j in 0..textures {
+        let loc = attrib(&format!("MultiTexCoord{}", j));
+        (gl.EnableVertexAttribArray.unwrap())(c, loc);
+        (gl.VertexAttribPointer.unwrap())(c, loc, 2, sys::GL_FLOAT, 0, 0,
+                                          QUAD_TEX.as_ptr() as *const _);
+    }
+    let loc = attrib("VertexPosition");
+    (gl.EnableVertexAttribArray.unwrap())(c, loc);
+    (gl.VertexAttribPointer.unwrap())(c, loc, 2, sys::GL_FLOAT, 0, 0,
+                                      QUAD.as_ptr() as *const _);
+    (gl.DrawArrays.unwrap())(c, sys::GL_TRIANGLE_STRIP, 0, 4);
+    assert_eq!((gl.GetError.unwrap())(c), sys::GL_NO_ERROR);
+}
+
+#[test]
+fn clear_respects_scissor() {
+    let i = new_test_instance(Default::default());
+    let c = new_context(&i, 4, 2);
+    let gl = gl();
+
+    (gl.ClearColor.unwrap())(c, 1.0, 0.0, 0.0, 1.0);
+    (gl.Clear.unwrap())(c, sys::GL_COLOR_BUFFER_BIT);
+    assert!(read_pixels(c, 4, 2).iter().all(|&px| px == [255, 0, 0, 255] ));
+
+    (gl.Enable.unwrap())(c, sys::GL_SCISSOR_TEST);
+    (gl.Scissor.unwrap())(c, 1, 1, 2, 1);
+    (gl.ClearColor.unwrap())(c, 0.0, 1.0, 0.0, 0.5);
+    (gl.Clear.unwrap())(c, sys::GL_COLOR_BUFFER_BIT | sys::GL_DEPTH_BUFFER_BIT);
+
+    let red = [255, 0, 0, 255];
+    let green = [0, 255, 0, 128];
+    assert_eq!(read_pixels(c, 4, 2), vec![red, red, red, red,
+                                          red, green, green, red]);
+}
+
+#[test]
+fn rgba_quad_samples_texels() {
+    let i = new_test_instance(Default::default());
+    let c = new_context(&i, 2, 2);
+    let gl = gl();
+
+    let program = link(c, RGBA_SHADER).unwrap();
+    (gl.UseProgram.unwrap())(c, program);
+    let texels = [
+        [10, 20, 30, 255], [40, 50, 60, 255],
+        [70, 80, 90, 255], [100, 110, 120, 255],
+    ];
+    let flat: Vec<u8> = texels.iter().flat_map(|px| px.iter().cloned() ).collect();
+    texture(c, 0, sys::GL_RGBA, 2, 2, &flat[..]);
+    (gl.Uniform1i.unwrap())(c, location(c, program, "Texture0"), 0);
+    (gl.Uniform4f.unwrap())(c, location(c, program, "FillColor"), 1.0, 1.0, 1.0, 1.0);
+    draw_quad(c, program, 1);
+    assert_eq!(read_pixels(c, 2, 2), texels.to_vec());
+
+    // Half alpha FillColor, blended over black like subpictures are.
+    (gl.ClearColor.unwrap())(c, 0.0, 0.0, 0.0, 1.0);
+    (gl.Clear.unwrap())(c, sys::GL_COLOR_BUFFER_BIT);
+    (gl.Enable.unwrap())(c, sys::GL_BLEND);
+    (gl.BlendFunc.unwrap())(c, sys::GL_SRC_ALPHA, sys::GL_ONE_MINUS_SRC_ALPHA);
+    (gl.Uniform4f.unwrap())(c, location(c, program, "FillColor"), 1.0, 1.0, 1.0, 0.5);
+    draw_quad(c, program, 1);
+    let px = read_pixels(c, 2, 2)[3];
+    assert!((px[0] as i32 - 50).abs() <= 1, "{:?}", px);
+}
+
+#[test]
+fn yuv_program_applies_coefficients() {
+    let i = new_test_instance(Default::default());
+    let c = new_context(&i, 2, 2);
+    let gl = gl();
+
+    let program = link(c, YUV_SHADER).unwrap();
+    (gl.UseProgram.unwrap())(c, program);
+    let planes = [200u8, 100, 50];
+    for (unit, &plane) in planes.iter().enumerate() {
+        texture(c, unit as GLuint, sys::GL_LUMINANCE, 1, 1, &[plane]);
+        (gl.Uniform1i.unwrap())(c, location(c, program, &format!("Texture{}", unit)),
+                                unit as GLint);
+    }
+    // R = Y, G = U, B = V.
+    let coefficients: [f32; 16] = [
+        1.0, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0,
+        0.0, 0.0, 0.0, 1.0,
+    ];
+    (gl.Uniform4fv.unwrap())(c, location(c, program, "Coefficient"), 4,
+                             coefficients.as_ptr());
+    draw_quad(c, program, 3);
+
+    assert!(read_pixels(c, 2, 2).iter().all(|&px| px == [200, 100, 50, 255] ));
+}
+
+#[test]
+fn unrecognized_program_fails_to_link() {
+    let i = new_test_instance(Default::default());
+    let c = new_context(&i, 2, 2);
+    let gl = gl();
+
+    let log = link(c, "void main() { gl_FragColor = vec4(1.0); }").unwrap_err();
+    assert!(log.starts_with("ERROR:"), "{}", log);
+
+    // Drawing without a linked program.
+    (gl.DrawArrays.unwrap())(c, sys::GL_TRIANGLES, 0, 3);
+    assert_eq!((gl.GetError.unwrap())(c), sys::GL_INVALID_OPERATION);
+}
+
+#[test]
+fn errors_are_sticky_until_read() {
+    let i = new_test_instance(Default::default());
+    let c = new_context(&i, 2, 2);
+    let gl = gl();
+
+    (gl.Enable.unwrap())(c, 0x1234);
+    (gl.Viewport.unwrap())(c, 0, 0, -1, -1);
+    assert_eq!((gl.GetError.unwrap())(c), sys::GL_INVALID_ENUM);
+    assert_eq!((gl.GetError.unwrap())(c), sys::GL_NO_ERROR);
+
+    let program = link(c, RGBA_SHADER).unwrap();
+    (gl.UseProgram.unwrap())(c, program);
+    // Texture0 is a sampler; FillColor isn't an int.
+    (gl.Uniform4f.unwrap())(c, location(c, program, "Texture0"), 0.0, 0.0, 0.0, 0.0);
+    assert_eq!((gl.GetError.unwrap())(c), sys::GL_INVALID_OPERATION);
+    (gl.Uniform1i.unwrap())(c, location(c, program, "FillColor"), 1);
+    assert_eq!((gl.GetError.unwrap())(c), sys::GL_INVALID_OPERATION);
+    (gl.Uniform1i.unwrap())(c, -1, 1);
+    assert_eq!((gl.GetError.unwrap())(c), sys::GL_NO_ERROR);
+}
+
+#[test]
+fn out_of_range_draws_and_reads_are_errors() {
+    let i = new_test_instance(Default::default());
+    let c = new_context(&i, 2, 2);
+    let gl = gl();
+    let max = i32::max_value();
+
+    let program = link(c, RGBA_SHADER).unwrap();
+    (gl.UseProgram.unwrap())(c, program);
+    (gl.DrawArrays.unwrap())(c, sys::GL_TRIANGLES, max, 1);
+    assert_eq!((gl.GetError.unwrap())(c), sys::GL_INVALID_VALUE);
+
+    // The quad, from a buffer: 4 vertices and no more.
+    let mut buffer = 0;
+    (gl.GenBuffers.unwrap())(c, 1, &mut buffer);
+    (gl.BindBuffer.unwrap())(c, sys::GL_ARRAY_BUFFER, buffer);
+    (gl.BufferData.unwrap())(c, sys::GL_ARRAY_BUFFER, 32, QUAD.as_ptr() as *const _,
+                             sys::GL_STATIC_DRAW);
+    let name = CString::new("VertexPosition").unwrap();
+    let loc = (gl.GetAttribLocation.unwrap())(c, program, name.as_ptr()) as GLuint;
+    (gl.EnableVertexAttribArray.unwrap())(c, loc);
+    (gl.VertexAttribPointer.unwrap())(c, loc, 2, sys::GL_FLOAT, 0, 0, ptr::null());
+    (gl.DrawArrays.unwrap())(c, sys::GL_TRIANGLE_STRIP, 0, max);
+    assert_eq!((gl.GetError.unwrap())(c), sys::GL_INVALID_OPERATION);
+    (gl.DrawArrays.unwrap())(c, sys::GL_TRIANGLE_STRIP, 1, 4);
+    assert_eq!((gl.GetError.unwrap())(c), sys::GL_INVALID_OPERATION);
+    (gl.DrawArrays.unwrap())(c, sys::GL_TRIANGLE_STRIP, 0, 4);
+    assert_eq!((gl.GetError.unwrap())(c), sys::GL_NO_ERROR);
+
+    // Indices past the end of the element buffer, and an offset that wraps.
+    let elements = [0u8, 1, 2, 3];
+    let mut element_buffer = 0;
+    (gl.GenBuffers.unwrap())(c, 1, &mut element_buffer);
+    (gl.BindBuffer.unwrap())(c, sys::GL_ELEMENT_ARRAY_BUFFER, element_buffer);
+    (gl.BufferData.unwrap())(c, sys::GL_ELEMENT_ARRAY_BUFFER, 4,
+                             elements.as_ptr() as *const _, sys::GL_STATIC_DRAW);
+    (gl.DrawElements.unwrap())(c, sys::GL_TRIANGLE_STRIP, 4, sys::GL_UNSIGNED_BYTE,
+                               1 as *const _);
+    assert_eq!((gl.GetError.unwrap())(c), sys::GL_INVALID_OPERATION);
+    (gl.DrawElements.unwrap())(c, sys::GL_TRIANGLE_STRIP, 2, sys::GL_UNSIGNED_SHORT,
+                               !0usize as *const _);
+    assert_eq!((gl.GetError.unwrap())(c), sys::GL_INVALID_OPERATION);
+    (gl.DrawElements.unwrap())(c, sys::GL_TRIANGLE_STRIP, 4, sys::GL_UNSIGNED_BYTE,
+                               ptr::null());
+    assert_eq!((gl.GetError.unwrap())(c), sys::GL_NO_ERROR);
+
+    // Nothing of the framebuffer is there to read.
+    let mut px = [7u8; 4];
+    (gl.ReadPixels.unwrap())(c, max, max, 1, 1, sys::GL_RGBA, sys::GL_UNSIGNED_BYTE,
+                             px.as_mut_ptr() as *mut _);
+    assert_eq!((gl.GetError.unwrap())(c), sys::GL_NO_ERROR);
+    assert_eq!(px, [7; 4]);
+}
+
+#[test]
+fn exports_use_current_context() {
+    let i = new_test_instance(Default::default());
+    let c = new_context(&i, 1, 1);
+
+    assert_eq!(gles2::glInitializePPAPI(::ppapi::get_interface), sys::PP_TRUE);
+    gles2::glSetCurrentContextPPAPI(c);
+    assert_eq!(gles2::glGetCurrentContextPPAPI(), c);
+
+    gles2::glClearColor(0.0, 0.0, 1.0, 1.0);
+    gles2::glClear(sys::GL_COLOR_BUFFER_BIT);
+    let mut px = [0u8; 4];
+    gles2::glReadPixels(0, 0, 1, 1, sys::GL_RGBA, sys::GL_UNSIGNED_BYTE,
+                        px.as_mut_ptr() as *mut _);
+    assert_eq!(px, [0, 0, 255, 255]);
+
+    gles2::glSetCurrentContextPPAPI(0);
+    assert_eq!(gles2::glGetError(), sys::GL_NO_ERROR);
+    assert!(gles2::glGetString(sys::GL_VERSION).is_null());
+}
@@ -0,0 +1,508 @@
+/// Tests for `PPB_Graphics3D;1.0` contexts.
+
+use libc;
+use std::cell::Cell;
+use std::ptr;
+use std::sync::Mutex;
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ppapi::callback::{Callback, current_message_loop};
+use ppapi::graphics::{Graphics3DState, MAX_SURFACE_SIZE, SwapThrottling, Throttle};
+use ppapi::resource::{ResourceState, get_resource};
+use ppapi::sys::{self, PPB_Core_1_0, PPB_Graphics3D_1_0, PPB_Instance_1_0,
+                 PPB_MessageLoop_1_0};
+use ppapi::view::ViewData;
+
+use super::{get_interface, new_test_instance};
+
+/// The list `ppapi-vout-graphics3d` creates its context with.
+fn vout_attribs(width: i32, height: i32) -> Vec<i32> {
+    vec![
+        sys::PP_GRAPHICS3DATTRIB_ALPHA_SIZE, 0,
+        sys::PP_GRAPHICS3DATTRIB_BLUE_SIZE, 8,
+        sys::PP_GRAPHICS3DATTRIB_GREEN_SIZE, 8,
+        sys::PP_GRAPHICS3DATTRIB_RED_SIZE, 8,
+        sys::PP_GRAPHICS3DATTRIB_DEPTH_SIZE, 0,
+        sys::PP_GRAPHICS3DATTRIB_STENCIL_SIZE, 0,
+        sys::PP_GRAPHICS3DATTRIB_WIDTH, width,
+        sys::PP_GRAPHICS3DATTRIB_HEIGHT, height,
+        sys::PP_GRAPHICS3DATTRIB_GPU_PREFERENCE,
+        sys::PP_GRAPHICS3DATTRIB_GPU_PREFERENCE_LOW_POWER,
+        sys::PP_GRAPHICS3DATTRIB_NONE,
+    ]
+}
+
+fn g3d() -> &'static PPB_Graphics3D_1_0 { get_interface("PPB_Graphics3D;1.0") }
+
+#[test]
+fn create_parses_attribs() {
+    let i = new_test_instance(Default::default());
+    let g3d = g3d();
+
+    let context = (g3d.Create.unwrap())(i.id(), 0, vout_attribs(320, 240).as_ptr());
+    assert!(context != 0);
+    assert_eq!((g3d.IsGraphics3D.unwrap())(context), sys::PP_TRUE);
+
+    let mut query = vec![
+        sys::PP_GRAPHICS3DATTRIB_WIDTH, 0,
+        sys::PP_GRAPHICS3DATTRIB_HEIGHT, 0,
+        sys::PP_GRAPHICS3DATTRIB_ALPHA_SIZE, -1,
+        sys::PP_GRAPHICS3DATTRIB_GPU_PREFERENCE, 0,
+        sys::PP_GRAPHICS3DATTRIB_NONE,
+    ];
+    assert_eq!((g3d.GetAttribs.unwrap())(context, query.as_mut_ptr()), sys::PP_OK);
+    assert_eq!(&query[..8], &[
+        sys::PP_GRAPHICS3DATTRIB_WIDTH, 320,
+        sys::PP_GRAPHICS3DATTRIB_HEIGHT, 240,
+        sys::PP_GRAPHICS3DATTRIB_ALPHA_SIZE, 0,
+        sys::PP_GRAPHICS3DATTRIB_GPU_PREFERENCE,
+        sys::PP_GRAPHICS3DATTRIB_GPU_PREFERENCE_LOW_POWER,
+    ]);
+
+    let mut bad_query = vec![
+        sys::PP_GRAPHICS3DATTRIB_WIDTH, 0,
+        0x1234, 0,
+        sys::PP_GRAPHICS3DATTRIB_NONE,
+    ];
+    assert_eq!((g3d.GetAttribs.unwrap())(context, bad_query.as_mut_ptr()),
+               sys::PP_ERROR_BADARGUMENT);
+    assert_eq!(bad_query[1], 0);
+}
+
+#[test]
+fn create_rejects_bad_attribs() {
+    let i = new_test_instance(Default::default());
+    let g3d = g3d();
+    let create = g3d.Create.unwrap();
+
+    let unknown = [0x1234, 1, sys::PP_GRAPHICS3DATTRIB_NONE];
+    assert_eq!(create(i.id(), 0, unknown.as_ptr()), 0);
+    assert_eq!(create(i.id(), 0, vout_attribs(-1, 240).as_ptr()), 0);
+    assert_eq!(create(i.id(), 0, vout_attribs(MAX_SURFACE_SIZE + 1, 240).as_ptr()), 0);
+    let bad_pref = [
+        sys::PP_GRAPHICS3DATTRIB_GPU_PREFERENCE, 7,
+        sys::PP_GRAPHICS3DATTRIB_NONE,
+    ];
+    assert_eq!(create(i.id(), 0, bad_pref.as_ptr()), 0);
+
+    // Sharing with something that isn't a context.
+    assert_eq!(create(i.id(), i.id() + 1000, ptr::null()), 0);
+    let context = create(i.id(), 0, ptr::null());
+    assert!(context != 0);
+    assert!(create(i.id(), context, ptr::null()) != 0);
+
+    let mut max = 0;
+    assert_eq!((g3d.GetAttribMaxValue.unwrap())(i.id(), sys::PP_GRAPHICS3DATTRIB_WIDTH,
+                                                &mut max),
+               sys::PP_OK);
+    assert_eq!(max, MAX_SURFACE_SIZE);
+    assert_eq!((g3d.GetAttribMaxValue.unwrap())(i.id(), 0x1234, &mut max),
+               sys::PP_ERROR_BADARGUMENT);
+}
+
+#[test]
+fn set_attribs_only_swap_behavior() {
+    let i = new_test_instance(Default::default());
+    let g3d = g3d();
+    let context = (g3d.Create.unwrap())(i.id(), 0, ptr::null());
+
+    let preserved = [
+        sys::PP_GRAPHICS3DATTRIB_SWAP_BEHAVIOR,
+        sys::PP_GRAPHICS3DATTRIB_BUFFER_PRESERVED,
+        sys::PP_GRAPHICS3DATTRIB_NONE,
+    ];
+    assert_eq!((g3d.SetAttribs.unwrap())(context, preserved.as_ptr()), sys::PP_OK);
+
+    let resize = [
+        sys::PP_GRAPHICS3DATTRIB_SWAP_BEHAVIOR,
+        sys::PP_GRAPHICS3DATTRIB_BUFFER_DESTROYED,
+        sys::PP_GRAPHICS3DATTRIB_WIDTH, 100,
+        sys::PP_GRAPHICS3DATTRIB_NONE,
+    ];
+    assert_eq!((g3d.SetAttribs.unwrap())(context, resize.as_ptr()),
+               sys::PP_ERROR_BADARGUMENT);
+
+    let state = get_resource::<Graphics3DState>(context).unwrap();
+    assert_eq!(state.attribs().swap_behavior, sys::PP_GRAPHICS3DATTRIB_BUFFER_PRESERVED);
+    assert_eq!(state.size(), (0, 0));
+}
+
+#[test]
+fn resize_buffers_tracks_size() {
+    let i = new_test_instance(Default::default());
+    let g3d = g3d();
+    let context = (g3d.Create.unwrap())(i.id(), 0, vout_attribs(320, 240).as_ptr());
+    let state = get_resource::<Graphics3DState>(context).unwrap();
+    assert_eq!(state.size(), (320, 240));
+
+    assert_eq!((g3d.ResizeBuffers.unwrap())(context, 640, 480), sys::PP_OK);
+    assert_eq!(state.size(), (640, 480));
+
+    assert_eq!((g3d.ResizeBuffers.unwrap())(context, -1, 480), sys::PP_ERROR_BADARGUMENT);
+    assert_eq!(state.size(), (640, 480));
+
+    assert_eq!((g3d.ResizeBuffers.unwrap())(i.id() + 1000, 640, 480),
+               sys::PP_ERROR_BADRESOURCE);
+    assert_eq!((g3d.GetError.unwrap())(context), sys::PP_OK);
+}
+
+thread_local!(static ON_SWAPPER: Cell<bool> = Cell::new(false));
+
+struct Swapper {
+    message_loop: sys::PP_Resource,
+    results: Mutex<Vec<(i32, bool)>>,
+}
+extern "C" fn swap_done(user: *mut libc::c_void, result: i32) {
+    let swapper = unsafe { &*(user as *const Swapper) };
+    let on_swapper = ON_SWAPPER.with(|s| s.get() );
+    swapper.results.lock().unwrap().push((result, on_swapper));
+
+    let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+    (iml.PostQuit.unwrap())(swapper.message_loop, sys::PP_FALSE);
+}
+
+#[test]
+fn swap_buffers_completes_on_callers_loop() {
+    let i = new_test_instance(Default::default());
+    let instance = i.id();
+    let g3d = g3d();
+    let context = (g3d.Create.unwrap())(instance, 0, vout_attribs(320, 240).as_ptr());
+    let state = get_resource::<Graphics3DState>(context).unwrap();
+
+    let results = thread::spawn(move || {
+        let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+        ON_SWAPPER.with(|s| s.set(true) );
+
+        let ml = (iml.Create.unwrap())(instance);
+        assert_eq!((iml.AttachToCurrentThread.unwrap())(ml), sys::PP_OK);
+
+        let swapper = Swapper {
+            message_loop: ml,
+            results: Mutex::new(Vec::new()),
+        };
+        let cb = sys::PP_CompletionCallback {
+            func: swap_done,
+            user_data: &swapper as *const Swapper as *mut _,
+            flags: 0,
+        };
+
+        let swap = g3d.SwapBuffers.unwrap();
+        assert_eq!(swap(context, cb), sys::PP_OK_COMPLETIONPENDING);
+        // Only one swap may be in flight.
+        assert_eq!(swap(context, cb), sys::PP_ERROR_INPROGRESS);
+
+        assert_eq!((iml.Run.unwrap())(ml), sys::PP_OK);
+        let results = swapper.results.lock().unwrap().clone();
+        results
+    }).join().unwrap();
+
+    assert_eq!(results, vec![(sys::PP_OK, true)]);
+    assert_eq!(state.frames(), 1);
+    assert!(!state.swap_pending());
+}
+
+#[test]
+fn optional_swap_callbacks_only_run_if_held() {
+    let i = new_test_instance(Default::default());
+    let instance = i.id();
+    let g3d = g3d();
+    let context = (g3d.Create.unwrap())(instance, 0, vout_attribs(320, 240).as_ptr());
+    let view = ViewData::on_screen(320, 240);
+
+    let (swapped_tx, swapped_rx) = ::std::sync::mpsc::channel();
+    let (hidden_tx, hidden_rx) = ::std::sync::mpsc::channel::<()>();
+    let swapper = thread::spawn(move || {
+        let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+        let ml = (iml.Create.unwrap())(instance);
+        assert_eq!((iml.AttachToCurrentThread.unwrap())(ml), sys::PP_OK);
+
+        let swapper = Swapper {
+            message_loop: ml,
+            results: Mutex::new(Vec::new()),
+        };
+        let cb = sys::PP_CompletionCallback {
+            func: swap_done,
+            user_data: &swapper as *const Swapper as *mut _,
+            flags: sys::PP_COMPLETIONCALLBACK_FLAG_OPTIONAL as i32,
+        };
+        let swap = g3d.SwapBuffers.unwrap();
+        assert_eq!(swap(context, cb), sys::PP_OK);
+        assert_eq!(swap(context, cb), sys::PP_OK);
+
+        swapped_tx.send(()).unwrap();
+        hidden_rx.recv().unwrap();
+        assert_eq!(swap(context, cb), sys::PP_OK_COMPLETIONPENDING);
+        assert_eq!((iml.Run.unwrap())(ml), sys::PP_OK);
+        let results = swapper.results.lock().unwrap().clone();
+        results
+    });
+
+    swapped_rx.recv().unwrap();
+    i.set_view(view.page_hidden()).unwrap();
+    hidden_tx.send(()).unwrap();
+    let state = get_resource::<Graphics3DState>(context).unwrap();
+    while state.frames() < 3 {
+        thread::sleep(Duration::from_millis(5));
+    }
+    i.set_view(view).unwrap();
+
+    assert_eq!(swapper.join().unwrap(), vec![(sys::PP_OK, false)]);
+    assert!(!state.swap_pending());
+}
+
+#[test]
+fn lost_contexts_fail_swaps() {
+    use super::ppp::{PPPInstanceCall, ppp_instance_calls};
+
+    let i = new_test_instance(Default::default());
+    let instance = i.id();
+    let g3d = g3d();
+    let first = (g3d.Create.unwrap())(instance, 0, vout_attribs(320, 240).as_ptr());
+    let second = (g3d.Create.unwrap())(instance, first, vout_attribs(320, 240).as_ptr());
+
+    assert_eq!(i.lose_graphics_3d_contexts(), Ok(2));
+    // Already lost; the module isn't told twice.
+    assert_eq!(i.lose_graphics_3d_contexts(), Ok(0));
+    let lost_calls = ppp_instance_calls()
+        .take_instance_calls(instance)
+        .into_iter()
+        .filter(|call| *call == PPPInstanceCall::Graphics3DContextLost )
+        .count();
+    assert_eq!(lost_calls, 1);
+
+    let results = thread::spawn(move || {
+        let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+        let ml = (iml.Create.unwrap())(instance);
+        assert_eq!((iml.AttachToCurrentThread.unwrap())(ml), sys::PP_OK);
+
+        let swapper = Swapper {
+            message_loop: ml,
+            results: Mutex::new(Vec::new()),
+        };
+        let cb = sys::PP_CompletionCallback {
+            func: swap_done,
+            user_data: &swapper as *const Swapper as *mut _,
+            flags: 0,
+        };
+        let swap = g3d.SwapBuffers.unwrap();
+        vec![swap(first, cb), swap(second, cb)]
+    }).join().unwrap();
+    assert_eq!(results, vec![sys::PP_ERROR_CONTEXT_LOST, sys::PP_ERROR_CONTEXT_LOST]);
+
+    let state = get_resource::<Graphics3DState>(first).unwrap();
+    assert!(state.is_lost());
+    assert_eq!(state.frames(), 0);
+}
+
+#[test]
+fn hidden_pages_hold_swaps() {
+    let i = new_test_instance(Default::default());
+    let instance = i.id();
+    let g3d = g3d();
+    let context = (g3d.Create.unwrap())(instance, 0, vout_attribs(320, 240).as_ptr());
+    let view = ViewData::on_screen(320, 240);
+    i.set_view(view.page_hidden()).unwrap();
+
+    let swapper = thread::spawn(move || {
+        let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+        let ml = (iml.Create.unwrap())(instance);
+        assert_eq!((iml.AttachToCurrentThread.unwrap())(ml), sys::PP_OK);
+
+        let swapper = Swapper {
+            message_loop: ml,
+            results: Mutex::new(Vec::new()),
+        };
+        let cb = sys::PP_CompletionCallback {
+            func: swap_done,
+            user_data: &swapper as *const Swapper as *mut _,
+            flags: 0,
+        };
+        // The frame is presented, only the callback is held.
+        assert_eq!((g3d.SwapBuffers.unwrap())(context, cb), sys::PP_OK_COMPLETIONPENDING);
+        assert_eq!((iml.Run.unwrap())(ml), sys::PP_OK);
+        let results = swapper.results.lock().unwrap().clone();
+        (results, Instant::now())
+    });
+
+    thread::sleep(Duration::from_millis(50));
+    let state = get_resource::<Graphics3DState>(context).unwrap();
+    assert_eq!(state.frames(), 1);
+    assert!(state.swap_pending());
+
+    // Only the page's visibility matters by default.
+    i.set_view(view.page_hidden().scrolled_off()).unwrap();
+    thread::sleep(Duration::from_millis(20));
+    assert!(state.swap_pending());
+
+    let shown = Instant::now();
+    i.set_view(view).unwrap();
+    let (results, done) = swapper.join().unwrap();
+    assert_eq!(results, vec![(sys::PP_OK, false)]);
+    assert!(done >= shown);
+    assert!(!state.swap_pending());
+}
+
+#[test]
+fn losing_the_context_fails_held_swaps() {
+    let i = new_test_instance(Default::default());
+    let instance = i.id();
+    let g3d = g3d();
+    let context = (g3d.Create.unwrap())(instance, 0, vout_attribs(320, 240).as_ptr());
+    let blocking = (g3d.Create.unwrap())(instance, 0, vout_attribs(320, 240).as_ptr());
+    i.set_view(ViewData::on_screen(320, 240).page_hidden()).unwrap();
+
+    let swapper = thread::spawn(move || {
+        let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+        let ml = (iml.Create.unwrap())(instance);
+        assert_eq!((iml.AttachToCurrentThread.unwrap())(ml), sys::PP_OK);
+
+        let swapper = Swapper {
+            message_loop: ml,
+            results: Mutex::new(Vec::new()),
+        };
+        let cb = sys::PP_CompletionCallback {
+            func: swap_done,
+            user_data: &swapper as *const Swapper as *mut _,
+            flags: 0,
+        };
+        assert_eq!((g3d.SwapBuffers.unwrap())(context, cb), sys::PP_OK_COMPLETIONPENDING);
+        assert_eq!((iml.Run.unwrap())(ml), sys::PP_OK);
+        let results = swapper.results.lock().unwrap().clone();
+        results
+    });
+    let blocked = thread::spawn(move || {
+        let res = get_resource::<Graphics3DState>(blocking).unwrap();
+        let state = Graphics3DState::state_from_resstate(res.get_rc()).unwrap();
+        Graphics3DState::swap_buffers(state, Callback::Sync)
+    });
+
+    let state = get_resource::<Graphics3DState>(context).unwrap();
+    let blocking = get_resource::<Graphics3DState>(blocking).unwrap();
+    while state.frames() < 1 || blocking.frames() < 1 {
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!((g3d.GetError.unwrap())(context), sys::PP_OK);
+    // Still hidden; the swaps fail without waiting to be shown.
+    assert_eq!(i.lose_graphics_3d_contexts(), Ok(2));
+    assert_eq!((g3d.GetError.unwrap())(context), sys::PP_ERROR_CONTEXT_LOST);
+    assert_eq!(swapper.join().unwrap(), vec![(sys::PP_ERROR_CONTEXT_LOST, false)]);
+    assert_eq!(blocked.join().unwrap(), Err(::ppapi::Error::ContextLost));
+    assert!(!state.swap_pending());
+    assert!(!blocking.swap_pending());
+}
+
+#[test]
+fn swaps_after_the_instance_is_destroyed_are_aborted() {
+    let i = new_test_instance(Default::default());
+    let instance = i.id();
+    let context = (g3d().Create.unwrap())(instance, 0, vout_attribs(320, 240).as_ptr());
+    let res = get_resource::<Graphics3DState>(context).unwrap();
+    let state = Graphics3DState::state_from_resstate(res.get_rc()).unwrap().clone();
+
+    let (destroyed_tx, destroyed_rx) = channel::<()>();
+    let swapper = thread::spawn(move || {
+        let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+        let ml = (iml.Create.unwrap())(instance);
+        assert_eq!((iml.AttachToCurrentThread.unwrap())(ml), sys::PP_OK);
+        destroyed_rx.recv().unwrap();
+
+        let swapper = Swapper {
+            message_loop: ml,
+            results: Mutex::new(Vec::new()),
+        };
+        let callback = Callback::Async {
+            f: swap_done,
+            user: &swapper as *const Swapper as *mut _,
+            message_loop: current_message_loop().unwrap(),
+            optional: false,
+        };
+        assert_eq!(Graphics3DState::swap_buffers(&state, callback),
+                   Err(::ppapi::Error::CompletionPending));
+        assert_eq!((iml.Run.unwrap())(ml), sys::PP_OK);
+        let results = swapper.results.lock().unwrap().clone();
+        results
+    });
+
+    drop(res);
+    drop(i);
+    destroyed_tx.send(()).unwrap();
+    assert_eq!(swapper.join().unwrap(), vec![(sys::PP_ERROR_ABORTED, false)]);
+}
+
+#[test]
+fn offscreen_swaps_are_paced() {
+    let i = new_test_instance(Default::default());
+    let context = (g3d().Create.unwrap())(i.id(), 0, vout_attribs(320, 240).as_ptr());
+    let res = get_resource::<Graphics3DState>(context).unwrap();
+    let swap = || {
+        let state = Graphics3DState::state_from_resstate(res.get_rc()).unwrap();
+        Graphics3DState::swap_buffers(state, Callback::Sync).unwrap();
+    };
+
+    let interval = Duration::from_millis(30);
+    i.set_swap_throttling(SwapThrottling {
+        offscreen: Throttle::Interval(interval),
+        .. Default::default()
+    });
+    i.set_view(ViewData::on_screen(320, 240).scrolled_off()).unwrap();
+
+    let start = Instant::now();
+    for _ in 0..3 { swap(); }
+    assert!(start.elapsed() >= interval * 2);
+
+    i.set_view(ViewData::on_screen(320, 240)).unwrap();
+    let start = Instant::now();
+    for _ in 0..3 { swap(); }
+    assert!(start.elapsed() < interval);
+    assert_eq!(res.frames(), 6);
+}
+
+#[test]
+fn bind_graphics_tracks_the_bound_context() {
+    let i = new_test_instance(Default::default());
+    let g3d = g3d();
+    let iinstance: &PPB_Instance_1_0 = get_interface("PPB_Instance;1.0");
+    let bind = iinstance.BindGraphics.unwrap();
+
+    let first = (g3d.Create.unwrap())(i.id(), 0, vout_attribs(320, 240).as_ptr());
+    let second = (g3d.Create.unwrap())(i.id(), 0, vout_attribs(320, 240).as_ptr());
+    assert_eq!(i.bound_graphics(), Ok(None));
+
+    assert_eq!(bind(i.id(), first), sys::PP_TRUE);
+    assert_eq!(i.bound_graphics(), Ok(Some(first)));
+    assert_eq!(bind(i.id(), second), sys::PP_TRUE);
+    assert_eq!(i.bound_graphics(), Ok(Some(second)));
+
+    // Not ours, or not graphics at all; the binding is kept.
+    let instance = i.id();
+    thread::spawn(move || {
+        let other = new_test_instance(Default::default());
+        let others = (g3d.Create.unwrap())(other.id(), 0, vout_attribs(320, 240).as_ptr());
+        assert_eq!(bind(instance, others), sys::PP_FALSE);
+        assert_eq!(bind(other.id(), second), sys::PP_FALSE);
+        assert_eq!(other.bound_graphics(), Ok(None));
+    }).join().unwrap();
+    let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+    let ml = (iml.Create.unwrap())(i.id());
+    assert_eq!(bind(i.id(), ml), sys::PP_FALSE);
+    assert_eq!(i.bound_graphics(), Ok(Some(second)));
+
+    assert_eq!(bind(i.id(), 0), sys::PP_TRUE);
+    assert_eq!(i.bound_graphics(), Ok(None));
+}
+
+#[test]
+fn bound_contexts_outlive_the_modules_reference() {
+    let i = new_test_instance(Default::default());
+    let context = (g3d().Create.unwrap())(i.id(), 0, vout_attribs(320, 240).as_ptr());
+    assert_eq!(i.bind_graphics(context), Ok(()));
+
+    let core: &PPB_Core_1_0 = get_interface("PPB_Core;1.0");
+    (core.down_ref_resource)(context);
+    assert_eq!((g3d().IsGraphics3D.unwrap())(context), sys::PP_TRUE);
+
+    i.bind_graphics(0).unwrap();
+    assert_eq!((g3d().IsGraphics3D.unwrap())(context), sys::PP_FALSE);
+}
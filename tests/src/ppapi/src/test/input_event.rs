@@ -0,0 +1,165 @@
+/// Tests for input event requests and dispatch to `PPP_InputEvent`.
+
+use ppapi::Lifecycle;
+use ppapi::input_event::{Event, EventData, InputEventRequests};
+use ppapi::sys::{self, PP_Instance, PP_Point, PPB_Core_1_0, PPB_InputEvent_1_0,
+                 PPB_KeyboardInputEvent_1_2, PPB_MouseInputEvent_1_1,
+                 PPB_Var_1_2};
+
+use super::{get_interface, new_test_instance_with};
+use super::ppp::{PPPInstanceCall, input_event_results, ppp_instance_calls};
+
+fn handled_events(instance: PP_Instance) -> Vec<(sys::PP_TimeTicks, Event)> {
+    ppp_instance_calls().take_instance_calls(instance)
+        .into_iter()
+        .filter_map(|call| match call {
+            PPPInstanceCall::HandleInputEvent { time_stamp, event, } => Some((time_stamp, event)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn input_event_requests_are_tracked() {
+    let i = new_test_instance_with(Default::default(), Lifecycle::Raw);
+    let iinput: &PPB_InputEvent_1_0 = get_interface("PPB_InputEvent;1.0");
+
+    assert_eq!((iinput.RequestInputEvents.unwrap())(i.id(), sys::PP_INPUTEVENT_CLASS_MOUSE),
+               sys::PP_OK);
+    assert_eq!((iinput.RequestFilteringInputEvents.unwrap())(i.id(),
+                                                             sys::PP_INPUTEVENT_CLASS_KEYBOARD |
+                                                             sys::PP_INPUTEVENT_CLASS_WHEEL),
+               sys::PP_OK);
+    assert_eq!(i.input_event_requests().unwrap(), InputEventRequests {
+        requested: sys::PP_INPUTEVENT_CLASS_MOUSE,
+        filtering: sys::PP_INPUTEVENT_CLASS_KEYBOARD | sys::PP_INPUTEVENT_CLASS_WHEEL,
+    });
+
+    // Requesting unfiltered drops the filtering request.
+    (iinput.RequestInputEvents.unwrap())(i.id(), sys::PP_INPUTEVENT_CLASS_WHEEL);
+    (iinput.ClearInputEventRequest.unwrap())(i.id(), sys::PP_INPUTEVENT_CLASS_MOUSE);
+    assert_eq!(i.input_event_requests().unwrap(), InputEventRequests {
+        requested: sys::PP_INPUTEVENT_CLASS_WHEEL,
+        filtering: sys::PP_INPUTEVENT_CLASS_KEYBOARD,
+    });
+
+    assert_eq!((iinput.RequestInputEvents.unwrap())(i.id(), sys::PP_INPUTEVENT_CLASS_TOUCH |
+                                                    sys::PP_INPUTEVENT_CLASS_MOUSE),
+               sys::PP_ERROR_NOTSUPPORTED);
+    assert_eq!(i.input_event_requests().unwrap().requested, sys::PP_INPUTEVENT_CLASS_WHEEL);
+}
+
+#[test]
+fn unrequested_events_dont_reach_the_module() {
+    let i = new_test_instance_with(Default::default(), Lifecycle::Raw);
+    i.request_input_events(sys::PP_INPUTEVENT_CLASS_KEYBOARD, false).unwrap();
+
+    let click = Event::mouse(sys::PP_INPUTEVENT_TYPE_MOUSEDOWN,
+                             sys::PP_INPUTEVENT_MOUSEBUTTON_LEFT, 10, 20);
+    assert_eq!(i.send_input_event(click), Ok(false));
+    assert_eq!(i.send_input_event(Event::wheel(0.0, 100.0)), Ok(false));
+    assert!(handled_events(i.id()).is_empty());
+
+    // Unfiltered events are consumed whatever the module returns.
+    input_event_results().set_handled(i.id(), false);
+    let key = Event::key(sys::PP_INPUTEVENT_TYPE_KEYDOWN, 65);
+    assert_eq!(i.send_input_event(key.clone()), Ok(true));
+    let events: Vec<_> = handled_events(i.id()).into_iter().map(|(_, e)| e ).collect();
+    assert_eq!(events, vec![key]);
+}
+
+#[test]
+fn filtered_events_respect_the_handler() {
+    let i = new_test_instance_with(Default::default(), Lifecycle::Raw);
+    i.request_input_events(sys::PP_INPUTEVENT_CLASS_MOUSE, true).unwrap();
+
+    let moved = Event::mouse(sys::PP_INPUTEVENT_TYPE_MOUSEMOVE,
+                             sys::PP_INPUTEVENT_MOUSEBUTTON_NONE, 5, 5);
+    assert_eq!(i.send_input_event(moved.clone()), Ok(true));
+    input_event_results().set_handled(i.id(), false);
+    assert_eq!(i.send_input_event(moved), Ok(false));
+    assert_eq!(handled_events(i.id()).len(), 2);
+}
+
+#[test]
+fn injected_events_round_trip() {
+    let i = new_test_instance_with(Default::default(), Lifecycle::Raw);
+    i.request_input_events(sys::PP_INPUTEVENT_CLASS_MOUSE |
+                           sys::PP_INPUTEVENT_CLASS_KEYBOARD |
+                           sys::PP_INPUTEVENT_CLASS_WHEEL, false).unwrap();
+
+    let mut sent = vec![
+        Event::mouse(sys::PP_INPUTEVENT_TYPE_MOUSEDOWN,
+                     sys::PP_INPUTEVENT_MOUSEBUTTON_RIGHT, 320, 240)
+            .with_modifiers(sys::PP_INPUTEVENT_MODIFIER_RIGHTBUTTONDOWN),
+        Event::wheel(-50.0, 200.0)
+            .with_modifiers(sys::PP_INPUTEVENT_MODIFIER_CONTROLKEY),
+    ];
+    sent.extend(Event::key_press(65, Some("a")));
+    for event in sent.iter() {
+        assert_eq!(i.send_input_event(event.clone()), Ok(true));
+    }
+
+    let handled = handled_events(i.id());
+    let events: Vec<_> = handled.iter().map(|&(_, ref e)| e.clone() ).collect();
+    assert_eq!(events, sent);
+    assert_eq!(events[3].kind, sys::PP_INPUTEVENT_TYPE_CHAR);
+    if let EventData::Wheel { ticks, .. } = events[1].data {
+        assert_eq!((ticks.x, ticks.y), (-0.5, 2.0));
+    } else {
+        panic!("expected a wheel event");
+    }
+    for pair in handled.windows(2) {
+        assert!(pair[0].0 <= pair[1].0);
+    }
+}
+
+#[test]
+fn modules_can_create_events() {
+    let i = new_test_instance_with(Default::default(), Lifecycle::Raw);
+    let core: &PPB_Core_1_0 = get_interface("PPB_Core;1.0");
+    let iinput: &PPB_InputEvent_1_0 = get_interface("PPB_InputEvent;1.0");
+    let imouse: &PPB_MouseInputEvent_1_1 = get_interface("PPB_MouseInputEvent;1.1");
+    let ikey: &PPB_KeyboardInputEvent_1_2 = get_interface("PPB_KeyboardInputEvent;1.2");
+    let ivar: &PPB_Var_1_2 = get_interface("PPB_Var;1.2");
+
+    let position = PP_Point { x: 7, y: 9, };
+    let mouse = (imouse.Create.unwrap())(i.id(), sys::PP_INPUTEVENT_TYPE_MOUSEUP, 1.5,
+                                         sys::PP_INPUTEVENT_MODIFIER_SHIFTKEY,
+                                         sys::PP_INPUTEVENT_MOUSEBUTTON_MIDDLE,
+                                         &position, 2, ::std::ptr::null());
+    assert!(mouse != 0);
+    assert_eq!((iinput.IsInputEvent.unwrap())(mouse), sys::PP_TRUE);
+    assert_eq!((iinput.GetType.unwrap())(mouse), sys::PP_INPUTEVENT_TYPE_MOUSEUP);
+    assert_eq!((iinput.GetTimeStamp.unwrap())(mouse), 1.5);
+    assert_eq!((iinput.GetModifiers.unwrap())(mouse), sys::PP_INPUTEVENT_MODIFIER_SHIFTKEY);
+    assert_eq!((imouse.GetButton.unwrap())(mouse), sys::PP_INPUTEVENT_MOUSEBUTTON_MIDDLE);
+    assert_eq!((imouse.GetPosition.unwrap())(mouse), position);
+    assert_eq!((imouse.GetClickCount.unwrap())(mouse), 2);
+    assert_eq!((imouse.GetMovement.unwrap())(mouse), PP_Point::default());
+    assert_eq!((ikey.IsKeyboardInputEvent.unwrap())(mouse), sys::PP_FALSE);
+
+    // Keyboard types only.
+    let undefined = (ikey.GetCode.unwrap())(0);
+    assert_eq!((ikey.Create.unwrap())(i.id(), sys::PP_INPUTEVENT_TYPE_MOUSEUP, 0.0, 0, 65,
+                                      undefined, undefined),
+               0);
+
+    let text = "b";
+    let text = (ivar.VarFromUtf8.unwrap())(text.as_ptr() as *const _, text.len() as u32);
+    let key = (ikey.Create.unwrap())(i.id(), sys::PP_INPUTEVENT_TYPE_CHAR, 2.0, 0, 66,
+                                     text, undefined);
+    (ivar.Release.unwrap())(text);
+    assert!(key != 0);
+    assert_eq!((ikey.GetKeyCode.unwrap())(key), 66);
+    let text = (ikey.GetCharacterText.unwrap())(key);
+    let mut len = 0;
+    let ptr = (ivar.VarToUtf8.unwrap())(text, &mut len);
+    assert_eq!(unsafe { ::std::slice::from_raw_parts(ptr as *const u8, len as usize) }, b"b");
+    (ivar.Release.unwrap())(text);
+    assert_eq!((ikey.GetCode.unwrap())(key)._type, sys::PP_VARTYPE_UNDEFINED);
+
+    (core.down_ref_resource)(mouse);
+    (core.down_ref_resource)(key);
+    assert_eq!((iinput.IsInputEvent.unwrap())(mouse), sys::PP_FALSE);
+}
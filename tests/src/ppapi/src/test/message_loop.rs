@@ -0,0 +1,649 @@
+/// Tests for message loops, the main thread's included, and the completion
+/// callbacks run on them.
+
+use libc;
+use std::sync::Mutex;
+use std::sync::mpsc::{Sender, channel};
+use std::time::{Duration, Instant};
+
+use ppapi::callback::{LeakedCallback, ThreadChecks, leaked_callbacks};
+use ppapi::completion::{Completion, Rng};
+
+use ppapi::sys::{self, PP_Resource, PPB_Core_1_0, PPB_FileIO_1_1, PPB_MessageLoop_1_0};
+
+use super::{TestInstance, get_interface, new_test_instance, shared_module};
+
+/// What a callback saw when it ran.
+#[derive(Clone, Debug, PartialEq)]
+struct Ran {
+    tag: u32,
+    result: i32,
+    on_main_thread: bool,
+    current_loop: PP_Resource,
+}
+
+struct Recorder(Mutex<Sender<(Ran, Instant)>>);
+impl Recorder {
+    fn new() -> (Box<Recorder>, ::std::sync::mpsc::Receiver<(Ran, Instant)>) {
+        let (tx, rx) = channel();
+        (Box::new(Recorder(Mutex::new(tx))), rx)
+    }
+}
+
+/// `user` points to a `(tag, &Recorder)`.
+extern "C" fn record(user: *mut libc::c_void, result: i32) {
+    let &(tag, recorder) = unsafe { &*(user as *const (u32, &Recorder)) };
+    let core: &PPB_Core_1_0 = get_interface("PPB_Core;1.0");
+    let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+
+    let current_loop = (iml.GetCurrent.unwrap())();
+    (core.down_ref_resource)(current_loop);
+    let ran = Ran {
+        tag: tag,
+        result: result,
+        on_main_thread: (core.on_main_thread)() != sys::PP_FALSE,
+        current_loop: current_loop,
+    };
+    // Not sent under the lock: the test may free the recorder as soon as
+    // it's received.
+    let tx = recorder.0.lock().unwrap().clone();
+    let _ = tx.send((ran, Instant::now()));
+}
+
+fn callback(user: &(u32, &Recorder)) -> sys::PP_CompletionCallback {
+    sys::PP_CompletionCallback {
+        func: record,
+        user_data: user as *const (u32, &Recorder) as *mut _,
+        flags: 0,
+    }
+}
+
+fn main_loop() -> PP_Resource {
+    let core: &PPB_Core_1_0 = get_interface("PPB_Core;1.0");
+    let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+    let main_loop = (iml.GetForMainThread.unwrap())();
+    (core.down_ref_resource)(main_loop);
+    main_loop
+}
+
+#[test]
+fn call_on_main_thread_runs_on_the_main_loop() {
+    let _module = shared_module();
+    let core: &PPB_Core_1_0 = get_interface("PPB_Core;1.0");
+    let (recorder, rx) = Recorder::new();
+    let user = (1, &*recorder);
+
+    assert_eq!((core.on_main_thread)(), sys::PP_FALSE);
+    (core.call_on_main_thread)(0, callback(&user), sys::PP_ERROR_ABORTED);
+
+    let (ran, _) = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    let main_loop = main_loop();
+    assert!(main_loop != 0);
+    assert_eq!(ran, Ran {
+        tag: 1,
+        result: sys::PP_ERROR_ABORTED,
+        on_main_thread: true,
+        current_loop: main_loop,
+    });
+}
+
+#[test]
+fn call_on_main_thread_delays() {
+    let _module = shared_module();
+    let core: &PPB_Core_1_0 = get_interface("PPB_Core;1.0");
+    let (recorder, rx) = Recorder::new();
+    let users = [(0, &*recorder), (1, &*recorder), (2, &*recorder)];
+
+    let start = Instant::now();
+    (core.call_on_main_thread)(60, callback(&users[0]), sys::PP_OK);
+    (core.call_on_main_thread)(0, callback(&users[1]), sys::PP_OK);
+    (core.call_on_main_thread)(30, callback(&users[2]), sys::PP_OK);
+
+    let ran: Vec<_> = (0..3)
+        .map(|_| rx.recv_timeout(Duration::from_secs(5)).unwrap() )
+        .collect();
+    let order: Vec<_> = ran.iter().map(|&(ref r, _)| r.tag ).collect();
+    assert_eq!(order, vec![1, 2, 0]);
+    assert!(ran[1].1 - start >= Duration::from_millis(30));
+    assert!(ran[2].1 - start >= Duration::from_millis(60));
+    assert!(ran.iter().all(|&(ref r, _)| r.on_main_thread ));
+}
+
+#[test]
+fn main_loop_belongs_to_the_module_thread() {
+    let _module = shared_module();
+    let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+    let main_loop = main_loop();
+    assert_eq!(main_loop, self::main_loop());
+
+    assert_eq!((iml.AttachToCurrentThread.unwrap())(main_loop), sys::PP_ERROR_INPROGRESS);
+    assert_eq!((iml.Run.unwrap())(main_loop), sys::PP_ERROR_INPROGRESS);
+    assert_eq!((iml.PostQuit.unwrap())(main_loop, sys::PP_FALSE), sys::PP_ERROR_WRONG_THREAD);
+
+    // Any thread can post work to it.
+    let (recorder, rx) = Recorder::new();
+    let user = (7, &*recorder);
+    assert_eq!((iml.PostWork.unwrap())(main_loop, callback(&user), 0), sys::PP_OK);
+    let (ran, _) = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!((ran.tag, ran.result, ran.on_main_thread), (7, sys::PP_OK, true));
+}
+
+/// `user` is the loop to quit.
+extern "C" fn quit(user: *mut libc::c_void, _result: i32) {
+    let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+    (iml.PostQuit.unwrap())(user as PP_Resource, sys::PP_FALSE);
+}
+fn quit_callback(ml: PP_Resource) -> sys::PP_CompletionCallback {
+    sys::PP_CompletionCallback {
+        func: quit,
+        user_data: ml as usize as *mut _,
+        flags: 0,
+    }
+}
+
+/// Creates a loop for `instance` on a new thread, then runs `f` there with it.
+fn on_new_loop<F, U>(instance: sys::PP_Instance, f: F) -> U
+    where F: FnOnce(&PPB_MessageLoop_1_0, PP_Resource) -> U + Send + 'static,
+          U: Send + 'static,
+{
+    ::std::thread::spawn(move || {
+        let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+        let ml = (iml.Create.unwrap())(instance);
+        assert_eq!((iml.AttachToCurrentThread.unwrap())(ml), sys::PP_OK);
+        f(iml, ml)
+    }).join().unwrap()
+}
+
+#[test]
+fn delayed_work_runs_in_due_order() {
+    let i = new_test_instance(Default::default());
+    let ran = on_new_loop(i.id(), |iml, ml| {
+        let (recorder, rx) = Recorder::new();
+        let users = [(0, &*recorder), (1, &*recorder), (2, &*recorder), (3, &*recorder)];
+        let post = iml.PostWork.unwrap();
+
+        let start = Instant::now();
+        assert_eq!(post(ml, callback(&users[0]), 40), sys::PP_OK);
+        assert_eq!(post(ml, callback(&users[1]), 0), sys::PP_OK);
+        assert_eq!(post(ml, callback(&users[2]), 20), sys::PP_OK);
+        assert_eq!(post(ml, callback(&users[3]), 20), sys::PP_OK);
+        assert_eq!(post(ml, quit_callback(ml), 60), sys::PP_OK);
+        assert_eq!((iml.Run.unwrap())(ml), sys::PP_OK);
+
+        rx.try_iter()
+            .map(|(ran, at)| (ran.tag, at - start) )
+            .collect::<Vec<_>>()
+    });
+
+    let order: Vec<_> = ran.iter().map(|&(tag, _)| tag ).collect();
+    assert_eq!(order, vec![1, 2, 3, 0]);
+    assert!(ran[1].1 >= Duration::from_millis(20));
+    assert!(ran[3].1 >= Duration::from_millis(40));
+}
+
+#[test]
+fn quit_leaves_work_that_isnt_due() {
+    let i = new_test_instance(Default::default());
+    let (first_run, second_run) = on_new_loop(i.id(), |iml, ml| {
+        let (recorder, rx) = Recorder::new();
+        let user = (0, &*recorder);
+        let post = iml.PostWork.unwrap();
+
+        let start = Instant::now();
+        assert_eq!(post(ml, callback(&user), 50), sys::PP_OK);
+        assert_eq!((iml.PostQuit.unwrap())(ml, sys::PP_FALSE), sys::PP_OK);
+        assert_eq!((iml.Run.unwrap())(ml), sys::PP_OK);
+        let first_run = rx.try_iter().count();
+
+        assert_eq!(post(ml, quit_callback(ml), 80), sys::PP_OK);
+        assert_eq!((iml.Run.unwrap())(ml), sys::PP_OK);
+        let second_run: Vec<_> = rx.try_iter().map(|(_, at)| at - start ).collect();
+        (first_run, second_run)
+    });
+
+    assert_eq!(first_run, 0);
+    assert_eq!(second_run.len(), 1);
+    assert!(second_run[0] >= Duration::from_millis(50));
+}
+
+#[test]
+fn due_work_runs_before_later_work_and_quit() {
+    let i = new_test_instance(Default::default());
+    let order = on_new_loop(i.id(), |iml, ml| {
+        let (recorder, rx) = Recorder::new();
+        let users = [(0, &*recorder), (1, &*recorder)];
+        let post = iml.PostWork.unwrap();
+
+        assert_eq!(post(ml, callback(&users[0]), 10), sys::PP_OK);
+        ::std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(post(ml, callback(&users[1]), 0), sys::PP_OK);
+        assert_eq!((iml.PostQuit.unwrap())(ml, sys::PP_FALSE), sys::PP_OK);
+        assert_eq!((iml.Run.unwrap())(ml), sys::PP_OK);
+
+        rx.try_iter().map(|(ran, _)| ran.tag ).collect::<Vec<_>>()
+    });
+
+    assert_eq!(order, vec![0, 1]);
+}
+
+#[test]
+fn destroyed_loops_refuse_work() {
+    let i = new_test_instance(Default::default());
+    on_new_loop(i.id(), |iml, ml| {
+        let (recorder, _rx) = Recorder::new();
+        let user = (0, &*recorder);
+
+        assert_eq!((iml.PostQuit.unwrap())(ml, sys::PP_TRUE), sys::PP_OK);
+        assert_eq!((iml.Run.unwrap())(ml), sys::PP_OK);
+        assert_eq!((iml.PostWork.unwrap())(ml, callback(&user), 0), sys::PP_ERROR_FAILED);
+        assert_eq!((iml.PostWork.unwrap())(ml, callback(&user), 10), sys::PP_ERROR_FAILED);
+    });
+}
+
+#[test]
+fn async_callbacks_get_the_sync_result() {
+    let i = new_test_instance(Default::default());
+    let fs = i.create_file_system().unwrap();
+    i.open_file_system(fs.id(), Default::default()).unwrap();
+    let fr = i.create_file_ref(fs.id(), "/async-file".into()).unwrap();
+    let io = i.create_file_io().unwrap();
+    let (fr, io) = (fr.id(), io.id());
+
+    let instance = (*i).clone();
+    let results = on_new_loop(i.id(), move |iml, ml| {
+        let iio: &PPB_FileIO_1_1 = get_interface("PPB_FileIO;1.1");
+        let (recorder, rx) = Recorder::new();
+        let users = [(0, &*recorder), (1, &*recorder), (2, &*recorder), (3, &*recorder)];
+
+        let flags = sys::PP_FILEOPENFLAG_READ | sys::PP_FILEOPENFLAG_WRITE |
+            sys::PP_FILEOPENFLAG_CREATE;
+        assert_eq!((iio.open)(io, fr, flags as i32, callback(&users[0])),
+                   sys::PP_OK_COMPLETIONPENDING);
+        let data = b"0123456789";
+        assert_eq!((iio.write)(io, 0, data.as_ptr() as *const _, 10, callback(&users[1])),
+                   sys::PP_OK_COMPLETIONPENDING);
+        let mut buffer = [0u8; 16];
+        assert_eq!((iio.read)(io, 4, buffer.as_mut_ptr() as *mut _, 16, callback(&users[2])),
+                   sys::PP_OK_COMPLETIONPENDING);
+        // Errors found when the operation runs go to the callback too.
+        assert_eq!((iio.open)(io, 0, flags as i32, callback(&users[3])),
+                   sys::PP_OK_COMPLETIONPENDING);
+        // The instance posts the callbacks.
+        instance.ping().unwrap();
+        assert_eq!((iml.PostQuit.unwrap())(ml, sys::PP_FALSE), sys::PP_OK);
+        assert_eq!((iml.Run.unwrap())(ml), sys::PP_OK);
+
+        assert_eq!(&buffer[..6], b"456789");
+        rx.try_iter().map(|(ran, _)| (ran.tag, ran.result) ).collect::<Vec<_>>()
+    });
+
+    assert_eq!(results[..3], [(0, sys::PP_OK), (1, 10), (2, 6)]);
+    assert_eq!(results[3].0, 3);
+    assert!(results[3].1 < 0);
+}
+
+/// Opened, empty files, referenced as the module would.
+pub fn open_files(i: &TestInstance, paths: &[&str]) -> Vec<PP_Resource> {
+    let fs = i.create_file_system().unwrap();
+    i.open_file_system(fs.id(), Default::default()).unwrap();
+    paths.iter()
+        .map(|&path| {
+            let fr = i.create_file_ref(fs.id(), path.into()).unwrap();
+            let io = i.create_file_io().unwrap();
+            let flags = sys::PP_FILEOPENFLAG_READ | sys::PP_FILEOPENFLAG_WRITE |
+                sys::PP_FILEOPENFLAG_CREATE;
+            i.open_file_io(io.id(), fr.id(), flags, Default::default()).unwrap();
+            io.move_into_id()
+        })
+        .collect()
+}
+fn open_file(i: &TestInstance, path: &str) -> PP_Resource { open_files(i, &[path])[0] }
+
+/// Starts `count` one byte writes to `io`, tagged by offset, then runs the
+/// loop until `quit_after` has passed. Returns the tags in the order their
+/// callbacks ran.
+fn write_bytes(i: &TestInstance, io: PP_Resource, count: u32,
+               quit_after: i64) -> Vec<u32> {
+    let instance = (**i).clone();
+    on_new_loop(i.id(), move |iml, ml| {
+        let iio: &PPB_FileIO_1_1 = get_interface("PPB_FileIO;1.1");
+        let (recorder, rx) = Recorder::new();
+        let users: Vec<_> = (0..count).map(|tag| (tag, &*recorder) ).collect();
+
+        for user in users.iter() {
+            let offset = user.0 as i64;
+            assert_eq!((iio.write)(io, offset, b"x".as_ptr() as *const _, 1, callback(user)),
+                       sys::PP_OK_COMPLETIONPENDING);
+        }
+        instance.ping().unwrap();
+        assert_eq!((iml.PostWork.unwrap())(ml, quit_callback(ml), quit_after), sys::PP_OK);
+        assert_eq!((iml.Run.unwrap())(ml), sys::PP_OK);
+
+        rx.try_iter()
+            .map(|(ran, _)| {
+                assert_eq!(ran.result, 1);
+                ran.tag
+            })
+            .collect()
+    })
+}
+
+#[test]
+fn delayed_completions() {
+    let i = new_test_instance(Default::default());
+    let io = open_file(&i, "/delayed");
+    i.set_completion(Completion::Delayed(Duration::from_millis(50)));
+
+    // Quit before the callbacks are due.
+    assert_eq!(write_bytes(&i, io, 3, 20), vec![]);
+    i.set_completion(Completion::Immediate);
+    assert_eq!(write_bytes(&i, io, 3, 20), vec![0, 1, 2]);
+}
+
+#[test]
+fn manual_completions_wait_for_release() {
+    let i = new_test_instance(Default::default());
+    let io = open_file(&i, "/manual");
+    i.set_completion(Completion::Manual);
+
+    assert_eq!(write_bytes(&i, io, 3, 0), vec![]);
+    assert_eq!(i.held_completions(), Ok(3));
+    // Their loop is gone, so they go nowhere, and the writes never happen.
+    assert_eq!(i.release_completions(10), Ok(3));
+
+    let instance = (*i).clone();
+    let ran = on_new_loop(i.id(), move |iml, ml| {
+        let iio: &PPB_FileIO_1_1 = get_interface("PPB_FileIO;1.1");
+        let (recorder, rx) = Recorder::new();
+        let users = [(0, &*recorder), (1, &*recorder)];
+        let mut buffer = [0u8; 8];
+        for user in users.iter() {
+            assert_eq!((iio.read)(io, 0, buffer.as_mut_ptr() as *mut _, 8, callback(user)),
+                       sys::PP_OK_COMPLETIONPENDING);
+        }
+
+        assert_eq!(instance.release_completions(1), Ok(1));
+        assert_eq!((iml.PostQuit.unwrap())(ml, sys::PP_FALSE), sys::PP_OK);
+        assert_eq!((iml.Run.unwrap())(ml), sys::PP_OK);
+        let first: Vec<_> = rx.try_iter().map(|(ran, _)| (ran.tag, ran.result) ).collect();
+        assert_eq!(instance.held_completions(), Ok(1));
+
+        assert_eq!(instance.release_completions(10), Ok(1));
+        assert_eq!((iml.PostQuit.unwrap())(ml, sys::PP_FALSE), sys::PP_OK);
+        assert_eq!((iml.Run.unwrap())(ml), sys::PP_OK);
+        let second: Vec<_> = rx.try_iter().map(|(ran, _)| (ran.tag, ran.result) ).collect();
+        (first, second)
+    });
+
+    assert_eq!(i.held_completions(), Ok(0));
+    assert_eq!(ran, (vec![(0, 0)], vec![(1, 0)]));
+}
+
+#[test]
+fn manual_completions_hold_back_the_operation() {
+    let i = new_test_instance(Default::default());
+    let io = open_file(&i, "/held-read");
+    assert_eq!(write_bytes(&i, io, 3, 20), vec![0, 1, 2]);
+    i.set_completion(Completion::Manual);
+
+    let instance = (*i).clone();
+    let (buffer, ran) = on_new_loop(i.id(), move |iml, ml| {
+        let iio: &PPB_FileIO_1_1 = get_interface("PPB_FileIO;1.1");
+        let (recorder, rx) = Recorder::new();
+        let user = (0, &*recorder);
+        let mut buffer = [0u8; 8];
+        assert_eq!((iio.read)(io, 0, buffer.as_mut_ptr() as *mut _, 8, callback(&user)),
+                   sys::PP_OK_COMPLETIONPENDING);
+        instance.ping().unwrap();
+        // Nothing's been read yet.
+        assert_eq!(buffer, [0; 8]);
+
+        assert_eq!(instance.release_completions(1), Ok(1));
+        assert_eq!((iml.PostQuit.unwrap())(ml, sys::PP_FALSE), sys::PP_OK);
+        assert_eq!((iml.Run.unwrap())(ml), sys::PP_OK);
+        let ran: Vec<_> = rx.try_iter().map(|(ran, _)| ran.result ).collect();
+        (buffer, ran)
+    });
+    assert_eq!(ran, vec![3]);
+    assert_eq!(&buffer[..3], b"xxx");
+}
+
+#[test]
+fn random_completions_all_run() {
+    let i = new_test_instance(Default::default());
+    let io = open_file(&i, "/random");
+    i.set_completion(Completion::Random {
+        seed: 45,
+        max_delay: Duration::from_millis(30),
+    });
+
+    let mut ran = write_bytes(&i, io, 8, 60);
+    ran.sort();
+    assert_eq!(ran, (0..8).collect::<Vec<_>>());
+}
+
+#[test]
+fn rng_is_seeded() {
+    let max = Duration::from_millis(30);
+    let delays = |seed| {
+        let mut rng = Rng::new(seed);
+        (0..8).map(|_| rng.duration(max) ).collect::<Vec<_>>()
+    };
+    assert_eq!(delays(45), delays(45));
+    assert!(delays(45) != delays(46));
+    assert!(delays(0).iter().all(|&d| d <= max ));
+}
+
+struct MainThreadWrite {
+    io: PP_Resource,
+    result: Mutex<Sender<i32>>,
+}
+/// `PP_BlockUntilComplete()`; `sys::PP_CompletionCallback` can't hold the
+/// null `func` in Rust.
+#[repr(C)]
+struct BlockUntilComplete {
+    func: Option<sys::PP_CompletionCallback_Func>,
+    user_data: *mut libc::c_void,
+    flags: i32,
+}
+/// Writes a byte to `io`, blocking.
+fn blocking_write(io: PP_Resource) -> i32 {
+    type Write = extern "C" fn(PP_Resource, i64, *const libc::c_char, i32,
+                               BlockUntilComplete) -> i32;
+    let iio: &PPB_FileIO_1_1 = get_interface("PPB_FileIO;1.1");
+    let write: Write = unsafe { ::std::mem::transmute(iio.write) };
+    write(io, 0, b"x".as_ptr() as *const _, 1, BlockUntilComplete {
+        func: None,
+        user_data: ::std::ptr::null_mut(),
+        flags: 0,
+    })
+}
+extern "C" fn write_on_main_thread(user: *mut libc::c_void, _result: i32) {
+    let write = unsafe { &*(user as *const MainThreadWrite) };
+    let result = blocking_write(write.io);
+    let tx = write.result.lock().unwrap().clone();
+    let _ = tx.send(result);
+}
+
+#[test]
+fn blocking_calls_on_the_main_thread() {
+    let i = new_test_instance(Default::default());
+    let core: &PPB_Core_1_0 = get_interface("PPB_Core;1.0");
+    let (tx, rx) = channel();
+    let write = MainThreadWrite {
+        io: open_file(&i, "/main-thread"),
+        result: Mutex::new(tx),
+    };
+    let cb = sys::PP_CompletionCallback {
+        func: write_on_main_thread,
+        user_data: &write as *const MainThreadWrite as *mut _,
+        flags: 0,
+    };
+
+    let results: Vec<_> = [ThreadChecks::Enforce, ThreadChecks::Warn, ThreadChecks::Off]
+        .iter()
+        .map(|&checks| {
+            i.set_thread_checks(checks);
+            (core.call_on_main_thread)(0, cb, sys::PP_OK);
+            rx.recv_timeout(Duration::from_secs(5)).unwrap()
+        })
+        .collect();
+    assert_eq!(results, vec![sys::PP_ERROR_BLOCKS_MAIN_THREAD, 1, 1]);
+
+    // Fine anywhere else.
+    i.set_thread_checks(ThreadChecks::Enforce);
+    assert_eq!(blocking_write(write.io), 1);
+}
+
+/// Starts writing a byte to `io`.
+fn write_byte(io: PP_Resource, cb: sys::PP_CompletionCallback) -> i32 {
+    let iio: &PPB_FileIO_1_1 = get_interface("PPB_FileIO;1.1");
+    (iio.write)(io, 0, b"x".as_ptr() as *const _, 1, cb)
+}
+
+#[test]
+fn callbacks_on_another_instances_loop() {
+    let i = new_test_instance(Default::default());
+    let io = open_file(&i, "/other-loop");
+    let (recorder, rx) = Recorder::new();
+    let user = (0, &*recorder);
+    let ffi = callback(&user);
+
+    let instance = (*i).clone();
+    let (results, ml) = ::std::thread::spawn(move || {
+        let other = new_test_instance(Default::default());
+        let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+        let ml = (iml.Create.unwrap())(other.id());
+        assert_eq!((iml.AttachToCurrentThread.unwrap())(ml), sys::PP_OK);
+
+        instance.set_thread_checks(ThreadChecks::Enforce);
+        let enforced = write_byte(io, ffi);
+        instance.set_thread_checks(ThreadChecks::Warn);
+        let warned = write_byte(io, ffi);
+
+        instance.ping().unwrap();
+        assert_eq!((iml.PostWork.unwrap())(ml, quit_callback(ml), 0), sys::PP_OK);
+        assert_eq!((iml.Run.unwrap())(ml), sys::PP_OK);
+        ((enforced, warned), ml)
+    }).join().unwrap();
+
+    assert_eq!(results, (sys::PP_ERROR_WRONG_THREAD, sys::PP_OK_COMPLETIONPENDING));
+    // Only the warned write ran, with its callback on the loop it was given.
+    let (ran, _) = rx.try_recv().unwrap();
+    assert_eq!((ran.result, ran.current_loop), (1, ml));
+    assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn callbacks_without_a_loop() {
+    let i = new_test_instance(Default::default());
+    let io = open_file(&i, "/no-loop");
+    let (recorder, rx) = Recorder::new();
+    let user = (0, &*recorder);
+    let ffi = callback(&user);
+
+    let instance = (*i).clone();
+    let results = ::std::thread::spawn(move || {
+        instance.set_thread_checks(ThreadChecks::Enforce);
+        let enforced = write_byte(io, ffi);
+        instance.set_thread_checks(ThreadChecks::Off);
+        (enforced, write_byte(io, ffi))
+    }).join().unwrap();
+
+    assert_eq!(results, (sys::PP_ERROR_NO_MESSAGE_LOOP, sys::PP_OK_COMPLETIONPENDING));
+    // The thread can't run it, so the main thread does.
+    let (ran, _) = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!((ran.result, ran.on_main_thread), (1, true));
+}
+
+#[test]
+fn closing_a_file_aborts_its_callbacks() {
+    let i = new_test_instance(Default::default());
+    let files = open_files(&i, &["/closed", "/still-open"]);
+    let (first, second) = (files[0], files[1]);
+    i.set_completion(Completion::Manual);
+
+    let instance = (*i).clone();
+    let ran = on_new_loop(i.id(), move |iml, ml| {
+        let iio: &PPB_FileIO_1_1 = get_interface("PPB_FileIO;1.1");
+        let (recorder, rx) = Recorder::new();
+        let users = [(0, &*recorder), (1, &*recorder)];
+
+        for (&io, user) in [first, second].iter().zip(users.iter()) {
+            assert_eq!((iio.write)(io, 0, b"x".as_ptr() as *const _, 1, callback(user)),
+                       sys::PP_OK_COMPLETIONPENDING);
+        }
+        (iio.close)(first);
+        assert_eq!(instance.held_completions(), Ok(1));
+        assert_eq!(instance.release_completions(1), Ok(1));
+
+        assert_eq!((iml.PostQuit.unwrap())(ml, sys::PP_FALSE), sys::PP_OK);
+        assert_eq!((iml.Run.unwrap())(ml), sys::PP_OK);
+        rx.try_iter().map(|(ran, _)| (ran.tag, ran.result) ).collect::<Vec<_>>()
+    });
+
+    assert_eq!(ran, vec![(0, sys::PP_ERROR_ABORTED), (1, 1)]);
+}
+
+#[test]
+fn destroying_the_instance_aborts_callbacks() {
+    let i = new_test_instance(Default::default());
+    let io = open_file(&i, "/destroyed");
+    i.set_completion(Completion::Delayed(Duration::from_secs(60)));
+
+    let (started_tx, started_rx) = channel();
+    let (destroyed_tx, destroyed_rx) = channel::<()>();
+    let instance = i.id();
+    let loop_thread = ::std::thread::spawn(move || {
+        let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+        let iio: &PPB_FileIO_1_1 = get_interface("PPB_FileIO;1.1");
+        let ml = (iml.Create.unwrap())(instance);
+        assert_eq!((iml.AttachToCurrentThread.unwrap())(ml), sys::PP_OK);
+        let (recorder, rx) = Recorder::new();
+        let user = (0, &*recorder);
+
+        assert_eq!((iio.write)(io, 0, b"x".as_ptr() as *const _, 1, callback(&user)),
+                   sys::PP_OK_COMPLETIONPENDING);
+        started_tx.send(()).unwrap();
+        destroyed_rx.recv().unwrap();
+
+        assert_eq!((iml.PostQuit.unwrap())(ml, sys::PP_FALSE), sys::PP_OK);
+        assert_eq!((iml.Run.unwrap())(ml), sys::PP_OK);
+        rx.try_iter().map(|(ran, _)| ran.result ).collect::<Vec<_>>()
+    });
+
+    started_rx.recv().unwrap();
+    drop(i);
+    destroyed_tx.send(()).unwrap();
+    assert_eq!(loop_thread.join().unwrap(), vec![sys::PP_ERROR_ABORTED]);
+}
+
+#[test]
+fn callbacks_left_on_destroyed_loops_are_reported() {
+    let i = new_test_instance(Default::default());
+    let io = open_file(&i, "/leaked");
+    i.set_completion(Completion::Manual);
+
+    let ml = on_new_loop(i.id(), move |iml, ml| {
+        let iio: &PPB_FileIO_1_1 = get_interface("PPB_FileIO;1.1");
+        let (recorder, _rx) = Recorder::new();
+        let user = (0, &*recorder);
+
+        assert_eq!((iio.write)(io, 0, b"x".as_ptr() as *const _, 1, callback(&user)),
+                   sys::PP_OK_COMPLETIONPENDING);
+        assert_eq!((iml.PostWork.unwrap())(ml, callback(&user), 1000), sys::PP_OK);
+        assert_eq!((iml.PostQuit.unwrap())(ml, sys::PP_TRUE), sys::PP_OK);
+        assert_eq!((iml.Run.unwrap())(ml), sys::PP_OK);
+        ml
+    });
+    assert_eq!(i.release_completions(1), Ok(1));
+
+    let leak = |result| LeakedCallback {
+        instance: i.id(),
+        message_loop: ml,
+        result: result,
+    };
+    // The write hadn't run either.
+    assert_eq!(leaked_callbacks().take_instance(i.id()),
+               vec![leak(sys::PP_OK), leak(sys::PP_OK)]);
+}
@@ -1,14 +1,36 @@
 
 /// This module holds the code for testing the PPAPI testing backend.
 
+use std::cell::RefCell;
+use std::env;
+use std::mem::replace;
+use std::panic::{AssertUnwindSafe, catch_unwind, resume_unwind};
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Duration;
+
 use super::ppapi::*;
+use super::ppapi::clock::ClockMode;
+use super::ppapi::scheduler::Scheduling;
+use super::ppapi::support::global_singleton_default;
 
 use self::ppp::{PPPInstanceCall, ppp_instance_calls};
 
 pub mod ppp;
 mod api;
+mod audio;
+mod capture;
+mod clock;
+mod gles2;
+mod graphics;
+mod input_event;
+mod message_loop;
+mod mouse;
+mod scheduler;
+mod timeline;
+mod url_loader;
+mod view;
 
-pub struct TestInstance(ModuleInterface, Instance);
+pub struct TestInstance(ModuleInterface, Instance, SharedModule);
 impl Drop for TestInstance {
     fn drop(&mut self) {
         var::clear_var_instance();
@@ -23,11 +45,125 @@ impl ::std::ops::Deref for TestInstance {
 }
 
 pub fn new_test_instance(args: Vec<(String, String)>) -> TestInstance {
+    new_test_instance_with(args, Default::default())
+}
+pub fn new_test_instance_with(args: Vec<(String, String)>,
+                              lifecycle: Lifecycle) -> TestInstance {
+    let shared = shared_module();
     let module = global_module();
-    let instance = module.create_instance(args);
+    let instance = module.create_instance_with(args, lifecycle);
 
     let instance = instance.expect("failed to create testing instance");
-    TestInstance(module, instance)
+    TestInstance(module, instance, shared)
+}
+
+/// Tests share the module, and so its clock and scheduling. Tests that go by
+/// them share this; those that change them hold it exclusively, so they
+/// don't run alongside.
+#[derive(Default)]
+struct ModuleUsers(RwLock<()>);
+fn module_users() -> &'static ModuleUsers { global_singleton_default() }
+
+/// What this test's thread holds. It's only locked once, however many test
+/// instances the thread has: a second lock could wait behind a test waiting
+/// for `exclusive_clock`.
+enum Held {
+    Nothing,
+    Shared(RwLockReadGuard<'static, ()>, usize),
+    Exclusive,
+}
+thread_local!(static HELD: RefCell<Held> = RefCell::new(Held::Nothing));
+
+/// Held by every test instance, so only needed by tests that time things or
+/// use the main loop without one.
+pub struct SharedModule(());
+pub fn shared_module() -> SharedModule {
+    HELD.with(|held| {
+        let mut held = held.borrow_mut();
+        let next = match replace(&mut *held, Held::Nothing) {
+            Held::Nothing => {
+                Held::Shared(module_users().0.read().unwrap_or_else(PoisonError::into_inner), 1)
+            },
+            Held::Shared(lock, count) => Held::Shared(lock, count + 1),
+            Held::Exclusive => Held::Exclusive,
+        };
+        *held = next;
+    });
+    SharedModule(())
+}
+impl Drop for SharedModule {
+    fn drop(&mut self) {
+        HELD.with(|held| {
+            let mut held = held.borrow_mut();
+            let next = match replace(&mut *held, Held::Nothing) {
+                Held::Shared(_, 1) | Held::Nothing => Held::Nothing,
+                Held::Shared(lock, count) => Held::Shared(lock, count - 1),
+                Held::Exclusive => Held::Exclusive,
+            };
+            *held = next;
+        });
+    }
+}
+
+/// The module, to change the clock and scheduling of as the test likes.
+/// They're set back to the defaults when dropped. Take it before creating
+/// any test instances.
+pub struct ExclusiveModule(ModuleInterface, RwLockWriteGuard<'static, ()>);
+pub fn exclusive_module() -> ExclusiveModule {
+    HELD.with(|held| {
+        if let Held::Nothing = *held.borrow() { } else {
+            panic!("this test already holds the module");
+        }
+    });
+    let lock = module_users().0.write().unwrap_or_else(PoisonError::into_inner);
+    HELD.with(|held| *held.borrow_mut() = Held::Exclusive );
+    ExclusiveModule(global_module(), lock)
+}
+impl Drop for ExclusiveModule {
+    fn drop(&mut self) {
+        self.0.clock().set_mode(ClockMode::Real);
+        self.0.scheduler().set_scheduling(Scheduling::Threaded);
+        HELD.with(|held| *held.borrow_mut() = Held::Nothing );
+    }
+}
+impl ::std::ops::Deref for ExclusiveModule {
+    type Target = ModuleInterface;
+    fn deref(&self) -> &ModuleInterface { &self.0 }
+}
+
+/// Runs `f` `iterations` times, each under `Scheduling::Chaos` with the next
+/// seed, and says which seed it was if `f` panics. `PPAPI_CHAOS_ITERATIONS`
+/// overrides `iterations`; `PPAPI_CHAOS_SEED` runs just that seed.
+pub fn chaos_iterations<F>(iterations: u64, max_delay: Duration, f: F)
+    where F: Fn(),
+{
+    fn var(name: &str) -> Option<u64> {
+        env::var(name).ok().and_then(|v| v.parse().ok() )
+    }
+    let seeds: Vec<u64> = match var("PPAPI_CHAOS_SEED") {
+        Some(seed) => vec![seed],
+        None => (1..var("PPAPI_CHAOS_ITERATIONS").unwrap_or(iterations) + 1).collect(),
+    };
+
+    let module = exclusive_module();
+    for seed in seeds.into_iter() {
+        module.scheduler().set_scheduling(Scheduling::Chaos {
+            seed: seed,
+            max_delay: max_delay,
+        });
+        if let Err(err) = catch_unwind(AssertUnwindSafe(&f)) {
+            println!("failed with chaos seed {}; rerun it with PPAPI_CHAOS_SEED={}", seed, seed);
+            resume_unwind(err);
+        }
+    }
+}
+
+/// Get a `PPB_*` interface the way the modules do.
+pub fn get_interface<T>(name: &str) -> &'static T {
+    let c_name = format!("{}\0", name);
+    let ptr = ::ppapi::get_interface(c_name.as_ptr() as *const _);
+    unsafe { (ptr as *const T).as_ref() }
+        .expect("missing interface")
 }
 
 #[test]
@@ -55,3 +191,48 @@ fn ppp_did_destroy_called() {
     let calls = ppp_instance_calls().take_instance_calls(id);
     assert_eq!(calls.last(), Some(&PPPInstanceCall::DestroyInstance));
 }
+
+#[test]
+fn ppp_did_change_focus_called() {
+    let instance = new_test_instance_with(Default::default(), Lifecycle::Raw);
+    instance.set_focus(true).unwrap();
+    instance.set_focus(false).unwrap();
+    let calls: Vec<_> = ppp_instance_calls()
+        .take_instance_calls(instance.id())
+        .into_iter()
+        .filter(|call| match call {
+            &PPPInstanceCall::DidChangeFocus { .. } => true,
+            _ => false,
+        })
+        .collect();
+    assert_eq!(calls, vec![
+        PPPInstanceCall::DidChangeFocus { has_focus: true, },
+        PPPInstanceCall::DidChangeFocus { has_focus: false, },
+    ]);
+}
+
+#[test]
+fn chrome_lifecycle_sequence() {
+    let view = ::ppapi::view::ViewData::on_screen(640, 360);
+    let instance = new_test_instance_with(Default::default(), Lifecycle::Chrome {
+        view: view,
+        has_focus: true,
+        document: None,
+    });
+    let calls = ppp_instance_calls().take_instance_calls(instance.id());
+    assert_eq!(calls.len(), 3);
+    assert_eq!(calls[0], PPPInstanceCall::CreateInstance { args: Default::default(), });
+    match calls[1] {
+        PPPInstanceCall::DidChangeView { data, .. } => assert_eq!(data, view),
+        ref call => panic!("expected DidChangeView, got {:?}", call),
+    }
+    assert_eq!(calls[2], PPPInstanceCall::DidChangeFocus { has_focus: true, });
+}
+
+#[test]
+fn raw_lifecycle_only_creates() {
+    let instance = new_test_instance_with(Default::default(), Lifecycle::Raw);
+    instance.ping().unwrap();
+    let calls = ppp_instance_calls().take_instance_calls(instance.id());
+    assert_eq!(calls, vec![PPPInstanceCall::CreateInstance { args: Default::default(), }]);
+}
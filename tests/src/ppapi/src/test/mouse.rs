@@ -0,0 +1,137 @@
+/// Tests for `PPB_MouseCursor` and the cursor state it leaves on instances.
+
+use std::ptr::null;
+use std::slice::from_raw_parts_mut;
+use std::thread::sleep;
+use std::time::Duration;
+
+use ppapi::Lifecycle;
+use ppapi::input_event::Event;
+use ppapi::mouse::{Cursor, hidden_since};
+use ppapi::sys::{self, PP_Point, PP_Size, PPB_Core_1_0, PPB_ImageData_1_0,
+                 PPB_MouseCursor_1_0};
+
+use super::{get_interface, new_test_instance, new_test_instance_with};
+use super::ppp::{PPPInstanceCall, ppp_instance_calls};
+
+#[test]
+fn standard_cursors_are_recorded() {
+    let i = new_test_instance(Default::default());
+    let icursor: &PPB_MouseCursor_1_0 = get_interface("PPB_MouseCursor;1.0");
+    let set_cursor = icursor.SetCursor.unwrap();
+
+    assert_eq!(i.cursor(), Ok(Cursor::Standard(sys::PP_MOUSECURSOR_TYPE_POINTER)));
+
+    assert_eq!(set_cursor(i.id(), sys::PP_MOUSECURSOR_TYPE_HAND, 0, null()), sys::PP_TRUE);
+    assert_eq!(set_cursor(i.id(), sys::PP_MOUSECURSOR_TYPE_NONE, 0, null()), sys::PP_TRUE);
+    assert!(i.cursor().unwrap().is_hidden());
+
+    let history = i.cursor_history().unwrap();
+    let cursors: Vec<_> = history.iter().map(|c| c.cursor.clone() ).collect();
+    assert_eq!(cursors, vec![
+        Cursor::Standard(sys::PP_MOUSECURSOR_TYPE_HAND),
+        Cursor::Standard(sys::PP_MOUSECURSOR_TYPE_NONE),
+    ]);
+    assert!(history[0].ts <= history[1].ts);
+    assert_eq!(hidden_since(&history), Some(history[1].ts));
+}
+
+#[test]
+fn bad_cursors_are_refused() {
+    let i = new_test_instance(Default::default());
+    let core: &PPB_Core_1_0 = get_interface("PPB_Core;1.0");
+    let iimage: &PPB_ImageData_1_0 = get_interface("PPB_ImageData;1.0");
+    let icursor: &PPB_MouseCursor_1_0 = get_interface("PPB_MouseCursor;1.0");
+    let set_cursor = icursor.SetCursor.unwrap();
+    let format = (iimage.GetNativeImageDataFormat.unwrap())();
+
+    let small = (iimage.Create.unwrap())(i.id(), format, &PP_Size { width: 16, height: 16, },
+                                         sys::PP_TRUE);
+    let big = (iimage.Create.unwrap())(i.id(), format, &PP_Size { width: 64, height: 64, },
+                                       sys::PP_TRUE);
+    assert!(small != 0 && big != 0);
+
+    let origin = PP_Point { x: 0, y: 0, };
+    let outside = PP_Point { x: 16, y: 0, };
+    assert_eq!(set_cursor(i.id(), sys::PP_MOUSECURSOR_TYPE_GRABBING + 1, 0, null()),
+               sys::PP_FALSE);
+    assert_eq!(set_cursor(i.id(), sys::PP_MOUSECURSOR_TYPE_CUSTOM, small, null()),
+               sys::PP_FALSE);
+    assert_eq!(set_cursor(i.id(), sys::PP_MOUSECURSOR_TYPE_CUSTOM, 0, &origin),
+               sys::PP_FALSE);
+    assert_eq!(set_cursor(i.id(), sys::PP_MOUSECURSOR_TYPE_CUSTOM, big, &origin),
+               sys::PP_FALSE);
+    assert_eq!(set_cursor(i.id(), sys::PP_MOUSECURSOR_TYPE_CUSTOM, small, &outside),
+               sys::PP_FALSE);
+    assert!(i.cursor_history().unwrap().is_empty());
+
+    (core.down_ref_resource)(small);
+    (core.down_ref_resource)(big);
+}
+
+#[test]
+fn custom_cursors_copy_the_image() {
+    let i = new_test_instance(Default::default());
+    let core: &PPB_Core_1_0 = get_interface("PPB_Core;1.0");
+    let iimage: &PPB_ImageData_1_0 = get_interface("PPB_ImageData;1.0");
+    let icursor: &PPB_MouseCursor_1_0 = get_interface("PPB_MouseCursor;1.0");
+
+    let size = PP_Size { width: 2, height: 2, };
+    let image = (iimage.Create.unwrap())(i.id(), sys::PP_IMAGEDATAFORMAT_BGRA_PREMUL, &size,
+                                         sys::PP_TRUE);
+    let mut desc = Default::default();
+    assert_eq!((iimage.Describe.unwrap())(image, &mut desc), sys::PP_TRUE);
+    assert_eq!(desc.stride, 8);
+
+    let pixels = (iimage.Map.unwrap())(image) as *mut u8;
+    let pixels = unsafe { from_raw_parts_mut(pixels, 16) };
+    for (idx, p) in pixels.iter_mut().enumerate() { *p = idx as u8; }
+
+    let hot_spot = PP_Point { x: 1, y: 1, };
+    assert_eq!((icursor.SetCursor.unwrap())(i.id(), sys::PP_MOUSECURSOR_TYPE_CUSTOM, image,
+                                            &hot_spot),
+               sys::PP_TRUE);
+    pixels[0] = 0xff;
+    (iimage.Unmap.unwrap())(image);
+    (core.down_ref_resource)(image);
+
+    assert_eq!(i.cursor(), Ok(Cursor::Custom {
+        size: size,
+        hot_spot: hot_spot,
+        pixels: (0..16).collect(),
+    }));
+}
+
+#[test]
+fn cursor_hidden_after_inactivity() {
+    const INACTIVITY: f64 = 0.05;
+
+    let i = new_test_instance_with(Default::default(), Lifecycle::Raw);
+    let icursor: &PPB_MouseCursor_1_0 = get_interface("PPB_MouseCursor;1.0");
+    i.request_input_events(sys::PP_INPUTEVENT_CLASS_MOUSE, false).unwrap();
+
+    let moved = Event::mouse(sys::PP_INPUTEVENT_TYPE_MOUSEMOVE,
+                             sys::PP_INPUTEVENT_MOUSEBUTTON_NONE, 100, 100);
+    assert_eq!(i.send_input_event(moved.clone()), Ok(true));
+    let moved_at = ppp_instance_calls().take_instance_calls(i.id())
+        .into_iter()
+        .filter_map(|call| match call {
+            PPPInstanceCall::HandleInputEvent { time_stamp, .. } => Some(time_stamp),
+            _ => None,
+        })
+        .last()
+        .unwrap();
+
+    // What the vout does once the mouse has been still for a while.
+    sleep(Duration::from_millis((INACTIVITY * 1000.0) as u64));
+    (icursor.SetCursor.unwrap())(i.id(), sys::PP_MOUSECURSOR_TYPE_NONE, 0, null());
+
+    let hidden_at = hidden_since(&i.cursor_history().unwrap()).unwrap();
+    assert!(hidden_at - moved_at >= INACTIVITY,
+            "hidden {}s after the last move", hidden_at - moved_at);
+
+    // And shows it again when the mouse moves.
+    assert_eq!(i.send_input_event(moved), Ok(true));
+    (icursor.SetCursor.unwrap())(i.id(), sys::PP_MOUSECURSOR_TYPE_POINTER, 0, null());
+    assert_eq!(hidden_since(&i.cursor_history().unwrap()), None);
+}
@@ -9,7 +9,10 @@ use std::sync::{Mutex, RwLock};
 use std::slice::from_raw_parts;
 use std::str::from_utf8_unchecked;
 
+use ppapi::input_event::{Event, EventData};
 use ppapi::sys::{self, PP_Instance, PP_Resource};
+use ppapi::var::Var;
+use ppapi::view::ViewData;
 use super::super::support::*;
 
 pub static mut MODULE: Option<sys::PP_Module> = None;
@@ -58,6 +61,12 @@ pub extern "C" fn PPP_GetInterface(name: *const libc::c_char) -> *const libc::c_
         "PPP_Instance;1.1" => {
             (&PPP_INSTANCE as *const sys::PPP_Instance_1_1) as *const libc::c_void
         },
+        "PPP_Graphics3D;1.0" => {
+            (&PPP_GRAPHICS_3D as *const sys::PPP_Graphics3D_1_0) as *const libc::c_void
+        },
+        "PPP_InputEvent;0.1" => {
+            (&PPP_INPUT_EVENT as *const sys::PPP_InputEvent_0_1) as *const libc::c_void
+        },
         _ => 0 as _,
     }
 }
@@ -69,6 +78,32 @@ static PPP_INSTANCE: sys::PPP_Instance_1_1 = sys::PPP_Instance_1_1 {
     change_focus: change_focus,
     handle_document_load: handle_document_load,
 };
+static PPP_GRAPHICS_3D: sys::PPP_Graphics3D_1_0 = sys::PPP_Graphics3D_1_0 {
+    Graphics3DContextLost: graphics_3d_context_lost,
+};
+static PPP_INPUT_EVENT: sys::PPP_InputEvent_0_1 = sys::PPP_InputEvent_0_1 {
+    HandleInputEvent: handle_input_event,
+};
+
+/// What `HandleInputEvent` returns, per instance. Instances handle every
+/// event unless told otherwise.
+#[derive(Default)]
+pub struct InputEventResults(Mutex<HashMap<PP_Instance, bool>>);
+impl InputEventResults {
+    pub fn set_handled(&self, instance: PP_Instance, handled: bool) {
+        self.0.lock().unwrap().insert(instance, handled);
+    }
+    fn handled(&self, instance: PP_Instance) -> bool {
+        self.0.lock().unwrap()
+            .get(&instance)
+            .cloned()
+            .unwrap_or(true)
+    }
+}
+pub fn input_event_results() -> &'static InputEventResults {
+    global_singleton_default()
+}
+
 #[derive(Default)]
 pub struct PPPInstanceCalls(Mutex<HashMap<PP_Instance, Vec<PPPInstanceCall>>>);
 impl PPPInstanceCalls {
@@ -100,12 +135,30 @@ pub fn ppp_instance_calls() -> &'static PPPInstanceCalls {
     global_singleton_default()
 }
 
-#[derive(Clone, Eq, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum PPPInstanceCall {
     CreateInstance {
         args: Vec<(String, String)>,
     },
     DestroyInstance,
+    /// `data` is read back through `PPB_View`, like a module would.
+    DidChangeView {
+        view: PP_Resource,
+        data: ViewData,
+    },
+    DidChangeFocus {
+        has_focus: bool,
+    },
+    /// The loader is AddRef'd so tests can read it; they should release it.
+    HandleDocumentLoad {
+        loader: PP_Resource,
+    },
+    Graphics3DContextLost,
+    /// `event` is read back through the `PPB_*InputEvent` interfaces.
+    HandleInputEvent {
+        time_stamp: sys::PP_TimeTicks,
+        event: Event,
+    },
 }
 
 extern "C" fn create_instance(instance: PP_Instance,
@@ -151,13 +204,96 @@ extern "C" fn destroy_instance(instance: PP_Instance) {
     let call = PPPInstanceCall::DestroyInstance;
     ppp_instance_calls().add_call(instance, call);
 }
-extern "C" fn change_view(_instance: PP_Instance, _view: PP_Resource) {
-    unimplemented!()
+extern "C" fn graphics_3d_context_lost(instance: PP_Instance) {
+    let call = PPPInstanceCall::Graphics3DContextLost;
+    ppp_instance_calls().add_call(instance, call);
+}
+extern "C" fn change_view(instance: PP_Instance, view: PP_Resource) {
+    let iview: &sys::PPB_View_1_2 = super::get_interface("PPB_View;1.2");
+    let mut data = ViewData {
+        is_fullscreen: (iview.IsFullscreen.unwrap())(view) != sys::PP_FALSE,
+        is_visible: (iview.IsVisible.unwrap())(view) != sys::PP_FALSE,
+        is_page_visible: (iview.IsPageVisible.unwrap())(view) != sys::PP_FALSE,
+        device_scale: (iview.GetDeviceScale.unwrap())(view),
+        css_scale: (iview.GetCSSScale.unwrap())(view),
+        .. Default::default()
+    };
+    (iview.GetRect.unwrap())(view, &mut data.rect);
+    (iview.GetClipRect.unwrap())(view, &mut data.clip_rect);
+    (iview.GetScrollOffset.unwrap())(view, &mut data.scroll_offset);
+
+    let call = PPPInstanceCall::DidChangeView {
+        view: view,
+        data: data,
+    };
+    ppp_instance_calls().add_call(instance, call);
+}
+extern "C" fn change_focus(instance: PP_Instance, has_focus: sys::PP_Bool) {
+    let call = PPPInstanceCall::DidChangeFocus {
+        has_focus: has_focus != sys::PP_FALSE,
+    };
+    ppp_instance_calls().add_call(instance, call);
 }
-extern "C" fn change_focus(_instance: PP_Instance, _has_focus: sys::PP_Bool) {
-    unimplemented!()
+extern "C" fn handle_document_load(instance: PP_Instance,
+                                   url_loader: PP_Resource) -> sys::PP_Bool {
+    let core: &sys::PPB_Core_1_0 = super::get_interface("PPB_Core;1.0");
+    (core.up_ref_resource)(url_loader);
+
+    let call = PPPInstanceCall::HandleDocumentLoad {
+        loader: url_loader,
+    };
+    ppp_instance_calls().add_call(instance, call);
+    sys::PP_TRUE
 }
-extern "C" fn handle_document_load(_instance: PP_Instance,
-                                   _url_loader: PP_Resource) -> sys::PP_Bool {
-    unimplemented!()
+extern "C" fn handle_input_event(instance: PP_Instance,
+                                 event: PP_Resource) -> sys::PP_Bool {
+    let ievent: &sys::PPB_InputEvent_1_0 = super::get_interface("PPB_InputEvent;1.0");
+    let imouse: &sys::PPB_MouseInputEvent_1_1 = super::get_interface("PPB_MouseInputEvent;1.1");
+    let iwheel: &sys::PPB_WheelInputEvent_1_0 = super::get_interface("PPB_WheelInputEvent;1.0");
+    let ikey: &sys::PPB_KeyboardInputEvent_1_2 =
+        super::get_interface("PPB_KeyboardInputEvent;1.2");
+    let ivar: &sys::PPB_Var_1_2 = super::get_interface("PPB_Var;1.2");
+
+    let take_string = |var: sys::PP_Var| {
+        let s = match Var::from(var) {
+            Ok(Var::String(s)) => Some(s.to_string()),
+            _ => None,
+        };
+        (ivar.Release.unwrap())(var);
+        s
+    };
+
+    let data = if (imouse.IsMouseInputEvent.unwrap())(event) != sys::PP_FALSE {
+        EventData::Mouse {
+            button: (imouse.GetButton.unwrap())(event),
+            position: (imouse.GetPosition.unwrap())(event),
+            click_count: (imouse.GetClickCount.unwrap())(event),
+            movement: (imouse.GetMovement.unwrap())(event),
+        }
+    } else if (iwheel.IsWheelInputEvent.unwrap())(event) != sys::PP_FALSE {
+        EventData::Wheel {
+            delta: (iwheel.GetDelta.unwrap())(event),
+            ticks: (iwheel.GetTicks.unwrap())(event),
+            scroll_by_page: (iwheel.GetScrollByPage.unwrap())(event) != sys::PP_FALSE,
+        }
+    } else {
+        assert!((ikey.IsKeyboardInputEvent.unwrap())(event) != sys::PP_FALSE);
+        EventData::Keyboard {
+            key_code: (ikey.GetKeyCode.unwrap())(event),
+            text: take_string((ikey.GetCharacterText.unwrap())(event)),
+            code: take_string((ikey.GetCode.unwrap())(event)),
+        }
+    };
+
+    let call = PPPInstanceCall::HandleInputEvent {
+        time_stamp: (ievent.GetTimeStamp.unwrap())(event),
+        event: Event {
+            kind: (ievent.GetType.unwrap())(event),
+            modifiers: (ievent.GetModifiers.unwrap())(event),
+            data: data,
+        },
+    };
+    ppp_instance_calls().add_call(instance, call);
+
+    if input_event_results().handled(instance) { sys::PP_TRUE } else { sys::PP_FALSE }
 }
@@ -0,0 +1,284 @@
+/// Tests for seeded and chaotic scheduling of message loops.
+
+use libc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{Sender, channel};
+use std::thread::{JoinHandle, spawn};
+use std::time::Duration;
+
+use ppapi::scheduler::Scheduling;
+use ppapi::sys::{self, PP_Resource, PPB_FileIO_1_1, PPB_MessageLoop_1_0};
+
+use super::{TestInstance, chaos_iterations, exclusive_module, get_interface, new_test_instance};
+use super::message_loop::open_files;
+
+/// The tags of the work run so far, in the order it ran.
+struct Log(Mutex<Vec<u32>>);
+
+/// `user` points to a `(tag, &Log)`.
+extern "C" fn log_tag(user: *mut libc::c_void, _result: i32) {
+    let &(tag, log) = unsafe { &*(user as *const (u32, &Log)) };
+    log.0.lock().unwrap().push(tag);
+}
+/// `user` is the loop to destroy.
+extern "C" fn destroy(user: *mut libc::c_void, _result: i32) {
+    let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+    (iml.PostQuit.unwrap())(user as PP_Resource, sys::PP_TRUE);
+}
+
+/// Loop `l`'s chain of writes to `io`, each started by the last one's
+/// callback, until `left` have finished and it quits `ml`.
+struct Writes<'a> {
+    l: u32,
+    io: PP_Resource,
+    ml: PP_Resource,
+    count: usize,
+    left: AtomicUsize,
+    log: &'a Log,
+}
+impl<'a> Writes<'a> {
+    /// Writes byte `n` of the file.
+    fn start(&self, n: usize) {
+        let iio: &PPB_FileIO_1_1 = get_interface("PPB_FileIO;1.1");
+        let cb = sys::PP_CompletionCallback {
+            func: wrote,
+            user_data: self as *const Writes as *mut _,
+            flags: 0,
+        };
+        (iio.write)(self.io, n as i64, b"x".as_ptr() as *const _, 1, cb);
+    }
+}
+/// `user` points to a `Writes`.
+extern "C" fn start_writes(user: *mut libc::c_void, _result: i32) {
+    let writes = unsafe { &*(user as *const Writes) };
+    writes.start(0);
+}
+/// Logs `l * 100 + n` for write `n`, if it wrote its byte, then starts the
+/// next one.
+extern "C" fn wrote(user: *mut libc::c_void, result: i32) {
+    let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+    let writes = unsafe { &*(user as *const Writes) };
+    let left = writes.left.fetch_sub(1, Ordering::SeqCst) - 1;
+    let n = writes.count - left - 1;
+    if result == 1 {
+        writes.log.0.lock().unwrap().push(writes.l * 100 + n as u32);
+    }
+    if left == 0 {
+        (iml.PostQuit.unwrap())(writes.ml, sys::PP_TRUE);
+    } else {
+        writes.start(n + 1);
+    }
+}
+
+/// Loops created here have the same ids, and so the same places in the
+/// draw, each time.
+fn create_loops(instance: sys::PP_Instance, loops: u32) -> Vec<PP_Resource> {
+    let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+    (0..loops)
+        .map(|_| (iml.Create.unwrap())(instance) )
+        .collect()
+}
+
+/// Attaches each of `mls` to a thread of its own, which runs it once told
+/// to go.
+fn attach_loops(mls: &[PP_Resource]) -> Vec<(Sender<()>, JoinHandle<()>)> {
+    let (attached_tx, attached_rx) = channel();
+    let threads: Vec<_> = mls.iter()
+        .map(|&ml| {
+            let (go_tx, go_rx) = channel();
+            let attached_tx = attached_tx.clone();
+            let thread = spawn(move || {
+                let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+                assert_eq!((iml.AttachToCurrentThread.unwrap())(ml), sys::PP_OK);
+                attached_tx.send(()).unwrap();
+                go_rx.recv().unwrap();
+                assert_eq!((iml.Run.unwrap())(ml), sys::PP_OK);
+            });
+            (go_tx, thread)
+        })
+        .collect();
+    for _ in mls.iter() { attached_rx.recv().unwrap(); }
+    threads
+}
+
+/// Lets the loops run, and waits for them to quit.
+fn run_attached(threads: Vec<(Sender<()>, JoinHandle<()>)>) {
+    for &(ref go, _) in threads.iter() {
+        go.send(()).unwrap();
+    }
+    for (_, thread) in threads.into_iter() {
+        thread.join().unwrap();
+    }
+}
+
+/// Posts `count` pieces of work to each of `loops` new loops, then runs them
+/// all at once, on their own threads, until they're destroyed after
+/// `quit_delay_ms`. Work on loop `l` is tagged `l * 100 + n`, `n` counting
+/// up from 0.
+fn run_loops(instance: sys::PP_Instance, loops: u32, count: u32,
+             quit_delay_ms: i64) -> Vec<u32> {
+    let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+    let log = Log(Mutex::new(Vec::new()));
+
+    let mls = create_loops(instance, loops);
+    let threads = attach_loops(&mls);
+
+    let users: Vec<(u32, &Log)> = (0..loops)
+        .flat_map(|l| (0..count).map(move |n| l * 100 + n ) )
+        .map(|tag| (tag, &log) )
+        .collect();
+    for n in 0..count {
+        for (l, &ml) in mls.iter().enumerate() {
+            let user = &users[l * count as usize + n as usize];
+            let cb = sys::PP_CompletionCallback {
+                func: log_tag,
+                user_data: user as *const (u32, &Log) as *mut _,
+                flags: 0,
+            };
+            assert_eq!((iml.PostWork.unwrap())(ml, cb, 0), sys::PP_OK);
+        }
+    }
+    for &ml in mls.iter() {
+        let cb = sys::PP_CompletionCallback {
+            func: destroy,
+            user_data: ml as usize as *mut _,
+            flags: 0,
+        };
+        assert_eq!((iml.PostWork.unwrap())(ml, cb, quit_delay_ms), sys::PP_OK);
+    }
+
+    run_attached(threads);
+    let ran = log.0.lock().unwrap().clone();
+    ran
+}
+
+/// Like `run_loops`, but each loop's one piece of work starts a chain of
+/// `count` one byte writes to its own file in `ios`, so the rest of its
+/// work is the completions the instance thread posts back. Returns the log,
+/// and the loop of each turn the scheduler gave since it was set, by its
+/// number.
+fn run_writes(i: &TestInstance, ios: &[PP_Resource],
+              count: usize) -> (Vec<u32>, Vec<Option<usize>>) {
+    let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+    let log = Log(Mutex::new(Vec::new()));
+
+    let mls = create_loops(i.id(), ios.len() as u32);
+    let threads = attach_loops(&mls);
+
+    let writes: Vec<Writes> = ios.iter()
+        .zip(mls.iter())
+        .enumerate()
+        .map(|(l, (&io, &ml))| Writes {
+            l: l as u32,
+            io: io,
+            ml: ml,
+            count: count,
+            left: AtomicUsize::new(count),
+            log: &log,
+        })
+        .collect();
+    for writes in writes.iter() {
+        let cb = sys::PP_CompletionCallback {
+            func: start_writes,
+            user_data: writes as *const Writes as *mut _,
+            flags: 0,
+        };
+        assert_eq!((iml.PostWork.unwrap())(writes.ml, cb, 0), sys::PP_OK);
+    }
+
+    run_attached(threads);
+    let turns = i.scheduler().turns()
+        .into_iter()
+        .map(|id| mls.iter().position(|&ml| ml == id ) )
+        .collect();
+    let ran = log.0.lock().unwrap().clone();
+    (ran, turns)
+}
+
+#[test]
+fn same_seed_same_order() {
+    let module = exclusive_module();
+    let i = new_test_instance(Default::default());
+
+    module.scheduler().set_scheduling(Scheduling::Seeded(7));
+    let first = run_loops(i.id(), 3, 4, 0);
+    // The work, then the quits.
+    assert_eq!(module.scheduler().turns().len(), 15);
+    module.scheduler().set_scheduling(Scheduling::Seeded(7));
+    let second = run_loops(i.id(), 3, 4, 0);
+    assert_eq!(first, second);
+
+    // Each loop still runs its own work in order.
+    assert_eq!(first.len(), 12);
+    for l in 0..3 {
+        let own: Vec<_> = first.iter().filter(|&&tag| tag / 100 == l ).collect();
+        assert_eq!(own, vec![&(l * 100), &(l * 100 + 1), &(l * 100 + 2), &(l * 100 + 3)]);
+    }
+}
+
+#[test]
+fn same_seed_same_turns_with_completions() {
+    let module = exclusive_module();
+    let i = new_test_instance(Default::default());
+    let ios = open_files(&i, &["/0", "/1", "/2", "/3"]);
+
+    module.scheduler().set_scheduling(Scheduling::Seeded(11));
+    let (first, first_turns) = run_writes(&i, &ios, 5);
+    module.scheduler().set_scheduling(Scheduling::Seeded(11));
+    let (second, second_turns) = run_writes(&i, &ios, 5);
+
+    // The writes' completions would race the other loops' work if the
+    // instance thread's posts weren't waited for.
+    assert_eq!(first_turns, second_turns);
+    assert_eq!(first, second);
+
+    assert_eq!(first.len(), 20);
+    // The work, then the completions, all on the loops.
+    assert_eq!(first_turns.len(), 24);
+    assert!(first_turns.iter().all(|turn| turn.is_some() ));
+}
+
+#[test]
+fn seeds_pick_different_orders() {
+    let module = exclusive_module();
+    let i = new_test_instance(Default::default());
+
+    let mut orders: Vec<Vec<u32>> = (1..9)
+        .map(|seed| {
+            module.scheduler().set_scheduling(Scheduling::Seeded(seed));
+            run_loops(i.id(), 3, 4, 0)
+        })
+        .collect();
+    orders.sort();
+    orders.dedup();
+    assert!(orders.len() > 1);
+}
+
+#[test]
+fn chaos_runs_all_work() {
+    let mut all: Vec<u32> = (0..3).flat_map(|l| (0..4).map(move |n| l * 100 + n ) ).collect();
+    all.sort();
+    chaos_iterations(20, Duration::from_millis(2), || {
+        let i = new_test_instance(Default::default());
+        // Quit once everything's due.
+        let mut ran = run_loops(i.id(), 3, 4, 20);
+        ran.sort();
+        assert_eq!(ran, all);
+        i.ping().unwrap();
+    });
+}
+
+#[test]
+fn chaos_reorders_posted_work() {
+    let reordered = AtomicBool::new(false);
+    chaos_iterations(10, Duration::from_millis(5), || {
+        let i = new_test_instance(Default::default());
+        let ran = run_loops(i.id(), 1, 10, 50);
+        assert_eq!(ran.len(), 10);
+        if ran.windows(2).any(|w| w[0] > w[1] ) {
+            reordered.store(true, Ordering::SeqCst);
+        }
+    });
+    assert!(reordered.load(Ordering::SeqCst));
+}
@@ -0,0 +1,89 @@
+/// Tests for the A/V timeline math.
+
+use ppapi::sys;
+use ppapi::timeline::{Timeline, TimelineEntry, TimelineEvent};
+
+const RATE: u32 = sys::PP_AUDIOSAMPLERATE_48000;
+const FRAMES: u32 = 480;
+
+fn audio_at(ts: f64) -> TimelineEntry { audio_of(ts, FRAMES) }
+fn audio_of(ts: f64, frames: u32) -> TimelineEntry {
+    TimelineEntry {
+        ts: ts,
+        event: TimelineEvent::AudioCallback {
+            audio: 1,
+            sample_rate: RATE,
+            frames: frames,
+            latency: 0.02,
+        },
+    }
+}
+fn swap_at(ts: f64) -> TimelineEntry {
+    TimelineEntry {
+        ts: ts,
+        event: TimelineEvent::SwapBuffers { context: 2, },
+    }
+}
+
+#[test]
+fn entries_sorted_by_timestamp() {
+    let mut timeline = Timeline::new();
+    timeline.push(audio_at(0.02));
+    timeline.push(swap_at(0.03));
+    timeline.push(audio_at(0.01));
+
+    let ts: Vec<_> = timeline.entries().iter().map(|e| e.ts ).collect();
+    assert_eq!(ts, vec![0.01, 0.02, 0.03]);
+    assert_eq!(timeline.audio_callbacks().len(), 2);
+    assert_eq!(timeline.swaps().len(), 1);
+}
+
+#[test]
+fn audio_position_accounts_for_latency() {
+    let mut timeline = Timeline::new();
+    // 10ms of audio every 10ms, with 20ms queued ahead.
+    for n in 0..5 {
+        timeline.push(audio_at(n as f64 * 0.01));
+    }
+    timeline.push(swap_at(0.045));
+
+    assert_eq!(timeline.audio_position_at(-1.0), None);
+    // Only 10ms written so far, and it's behind 20ms of latency.
+    assert_eq!(timeline.audio_position_at(0.0), Some(0.0));
+
+    let pairs = timeline.av_pairs();
+    assert_eq!(pairs.len(), 1);
+    let (ts, position) = pairs[0];
+    assert_eq!(ts, 0.045);
+    // The last callback's 10ms is heard 20ms after it, so of the 40ms
+    // before it, 20ms has played by then, plus the 5ms since.
+    let position = position.unwrap();
+    assert!((position - 0.025).abs() < 1e-9, "position `{}`", position);
+}
+
+#[test]
+fn audio_position_uses_the_last_buffer_not_the_interval() {
+    let mut timeline = Timeline::new();
+    // 20ms of audio every 10ms.
+    timeline.push(audio_of(0.0, 2 * FRAMES));
+    timeline.push(audio_of(0.01, 2 * FRAMES));
+
+    // 20ms before the last callback, less its 20ms of latency, plus the 15ms
+    // since. Taking off the 10ms interval instead would give 25ms.
+    let position = timeline.audio_position_at(0.025).unwrap();
+    assert!((position - 0.015).abs() < 1e-9, "position `{}`", position);
+}
+
+#[test]
+fn csv_export() {
+    let mut timeline = Timeline::new();
+    timeline.push(audio_at(0.5));
+    timeline.push(swap_at(1.0));
+
+    let mut out = Vec::new();
+    timeline.write_csv(&mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "ts,kind,resource,sample_rate,frames,latency\n\
+                0.500000,audio,1,48000,480,0.020000\n\
+                1.000000,swap,2,,,\n");
+}
@@ -0,0 +1,158 @@
+/// Tests for full-frame instances and the loaders they're given.
+
+use std::slice::from_raw_parts;
+use std::str::from_utf8;
+
+use ppapi::Lifecycle;
+use ppapi::resource::get_resource;
+use ppapi::sys::{self, PP_Resource, PPB_Core_1_0, PPB_Instance_1_0,
+                 PPB_URLLoader_1_0, PPB_URLResponseInfo_1_0, PPB_Var_1_2};
+use ppapi::url_loader::{UrlInfo, UrlLoaderState, url_manager};
+use ppapi::view::ViewData;
+
+use super::{get_interface, new_test_instance, new_test_instance_with};
+use super::ppp::{PPPInstanceCall, ppp_instance_calls};
+
+fn response_string(response: PP_Resource, property: sys::PP_URLResponseProperty) -> String {
+    let iresponse: &PPB_URLResponseInfo_1_0 = get_interface("PPB_URLResponseInfo;1.0");
+    let ivar: &PPB_Var_1_2 = get_interface("PPB_Var;1.2");
+
+    let var = (iresponse.GetProperty.unwrap())(response, property);
+    let mut len = 0;
+    let ptr = (ivar.VarToUtf8.unwrap())(var, &mut len);
+    assert!(!ptr.is_null());
+    let s = from_utf8(unsafe { from_raw_parts(ptr as *const u8, len as usize) })
+        .unwrap()
+        .to_string();
+    (ivar.Release.unwrap())(var);
+    s
+}
+
+#[test]
+fn full_frame_instances_load_the_document() {
+    const URL: &'static str = "http://fixtures.test/full-frame.webm";
+    let body: Vec<u8> = (0..10).collect();
+    url_manager().add_fixture(URL, UrlInfo::new(body.clone(), "video/webm")).unwrap();
+
+    let view = ViewData::on_screen(1280, 720);
+    let i = new_test_instance_with(Default::default(), Lifecycle::full_frame(URL, view));
+
+    let iinstance: &PPB_Instance_1_0 = get_interface("PPB_Instance;1.0");
+    assert_eq!((iinstance.IsFullFrame.unwrap())(i.id()), sys::PP_TRUE);
+
+    let calls = ppp_instance_calls().take_instance_calls(i.id());
+    assert_eq!(calls.len(), 4);
+    assert_eq!(calls[2], PPPInstanceCall::DidChangeFocus { has_focus: true, });
+    let loader = match calls[3] {
+        PPPInstanceCall::HandleDocumentLoad { loader, } => loader,
+        ref call => panic!("expected HandleDocumentLoad, got {:?}", call),
+    };
+
+    let iloader: &PPB_URLLoader_1_0 = get_interface("PPB_URLLoader;1.0");
+    assert_eq!((iloader.IsURLLoader.unwrap())(loader), sys::PP_TRUE);
+
+    let response = (iloader.GetResponseInfo.unwrap())(loader);
+    assert_eq!(response_string(response, sys::PP_URLRESPONSEPROPERTY_URL), URL);
+    assert_eq!(response_string(response, sys::PP_URLRESPONSEPROPERTY_HEADERS),
+               "Content-Type: video/webm\nContent-Length: 10");
+    let iresponse: &PPB_URLResponseInfo_1_0 = get_interface("PPB_URLResponseInfo;1.0");
+    let mut status = (iresponse.GetProperty.unwrap())(response, sys::PP_URLRESPONSEPROPERTY_STATUSCODE);
+    assert_eq!(status._type, sys::PP_VARTYPE_INT32);
+    assert_eq!(unsafe { *status.value.as_int() }, 200);
+
+    let (mut received, mut total) = (0, 0);
+    assert_eq!((iloader.GetDownloadProgress.unwrap())(loader, &mut received, &mut total),
+               sys::PP_TRUE);
+    assert_eq!((received, total), (10, 10));
+
+    let state = get_resource::<UrlLoaderState>(loader).unwrap();
+    let mut read = Vec::new();
+    let mut buffer = [0u8; 4];
+    loop {
+        let n = state.read_response_body(&mut buffer).unwrap();
+        if n == 0 { break; }
+        read.extend_from_slice(&buffer[..n]);
+    }
+    assert_eq!(read, body);
+
+    (iloader.Close.unwrap())(loader);
+    assert!(state.read_response_body(&mut buffer).is_err());
+
+    let core: &PPB_Core_1_0 = get_interface("PPB_Core;1.0");
+    (core.down_ref_resource)(response);
+    (core.down_ref_resource)(loader);
+}
+
+#[test]
+fn embedded_instances_are_not_full_frame() {
+    let i = new_test_instance(Default::default());
+    let iinstance: &PPB_Instance_1_0 = get_interface("PPB_Instance;1.0");
+    assert_eq!((iinstance.IsFullFrame.unwrap())(i.id()), sys::PP_FALSE);
+    assert!(ppp_instance_calls()
+            .take_instance_calls(i.id())
+            .iter()
+            .all(|call| match call {
+                &PPPInstanceCall::HandleDocumentLoad { .. } => false,
+                _ => true,
+            }));
+}
+
+#[test]
+fn documents_need_a_fixture() {
+    let lifecycle = Lifecycle::full_frame("http://fixtures.test/missing.webm",
+                                          Default::default());
+    let instance = ::ppapi::global_module()
+        .create_instance_with(Default::default(), lifecycle);
+    assert_eq!(instance.err(), Some(::ppapi::Error::BadArgument));
+}
+
+#[test]
+fn optional_callbacks_are_skipped_for_available_data() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use ppapi::sys::PPB_MessageLoop_1_0;
+
+    static CALLED: AtomicUsize = AtomicUsize::new(0);
+    extern "C" fn called(_user: *mut ::libc::c_void, _result: i32) {
+        CALLED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    const URL: &'static str = "http://fixtures.test/optional-read.webm";
+    url_manager().add_fixture(URL, UrlInfo::new((0..10).collect(), "video/webm")).unwrap();
+    let i = new_test_instance_with(Default::default(),
+                                   Lifecycle::full_frame(URL, ViewData::on_screen(320, 240)));
+    let loader = ppp_instance_calls().take_instance_calls(i.id())
+        .into_iter()
+        .filter_map(|call| match call {
+            PPPInstanceCall::HandleDocumentLoad { loader, } => Some(loader),
+            _ => None,
+        })
+        .next()
+        .unwrap();
+
+    let instance = i.id();
+    let (direct, pending) = ::std::thread::spawn(move || {
+        let iml: &PPB_MessageLoop_1_0 = get_interface("PPB_MessageLoop;1.0");
+        let iloader: &PPB_URLLoader_1_0 = get_interface("PPB_URLLoader;1.0");
+        let read = iloader.ReadResponseBody.unwrap();
+        let ml = (iml.Create.unwrap())(instance);
+        assert_eq!((iml.AttachToCurrentThread.unwrap())(ml), sys::PP_OK);
+
+        let mut buffer = [0u8; 4];
+        let mut cb = sys::PP_CompletionCallback {
+            func: called,
+            user_data: ::std::ptr::null_mut(),
+            flags: sys::PP_COMPLETIONCALLBACK_FLAG_OPTIONAL as i32,
+        };
+        let direct = read(loader, buffer.as_mut_ptr() as *mut _, 4, cb);
+        cb.flags = 0;
+        let pending = read(loader, buffer.as_mut_ptr() as *mut _, 4, cb);
+        (direct, pending)
+    }).join().unwrap();
+
+    assert_eq!(direct, 4);
+    assert_eq!(pending, sys::PP_OK_COMPLETIONPENDING);
+    // The second read's callback was posted, but the loop never ran.
+    assert_eq!(CALLED.load(Ordering::SeqCst), 0);
+    let core: &PPB_Core_1_0 = get_interface("PPB_Core;1.0");
+    (core.down_ref_resource)(loader);
+}
@@ -0,0 +1,123 @@
+/// Tests for `PPB_View;1.2` and `Instance::set_view`.
+
+use ppapi::sys::{self, PP_Point, PP_Rect, PP_Size, PPB_View_1_2};
+use ppapi::view::{Page, ViewData};
+
+use ppapi::Lifecycle;
+
+use super::{get_interface, new_test_instance, new_test_instance_with};
+use super::ppp::{PPPInstanceCall, ppp_instance_calls};
+
+fn rect(x: i32, y: i32, width: i32, height: i32) -> PP_Rect {
+    PP_Rect {
+        point: PP_Point { x: x, y: y, },
+        size: PP_Size { width: width, height: height, },
+    }
+}
+
+fn view_calls(instance: sys::PP_Instance) -> Vec<(sys::PP_Resource, ViewData)> {
+    ppp_instance_calls()
+        .take_instance_calls(instance)
+        .into_iter()
+        .filter_map(|call| match call {
+            PPPInstanceCall::DidChangeView { view, data, } => Some((view, data)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn set_view_calls_did_change_view() {
+    let i = new_test_instance_with(Default::default(), Lifecycle::Raw);
+
+    let view = ViewData {
+        rect: rect(10, 20, 640, 360),
+        clip_rect: rect(0, 0, 640, 300),
+        is_fullscreen: true,
+        is_page_visible: false,
+        device_scale: 2.0,
+        css_scale: 1.25,
+        scroll_offset: PP_Point { x: 0, y: 60, },
+        .. Default::default()
+    };
+    i.set_view(Default::default()).unwrap();
+    i.set_view(view).unwrap();
+
+    let calls = view_calls(i.id());
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0].1, Default::default());
+    assert_eq!(calls[1].1, view);
+    assert!(calls[0].0 != calls[1].0);
+}
+
+#[test]
+fn views_released_after_the_call() {
+    let i = new_test_instance(Default::default());
+    i.set_view(Default::default()).unwrap();
+    let (view, _) = view_calls(i.id())[0];
+
+    let iview: &PPB_View_1_2 = get_interface("PPB_View;1.2");
+    assert_eq!((iview.IsView.unwrap())(view), sys::PP_FALSE);
+    let mut r = rect(1, 2, 3, 4);
+    assert_eq!((iview.GetRect.unwrap())(view, &mut r), sys::PP_FALSE);
+    assert_eq!(r, rect(1, 2, 3, 4));
+    assert_eq!((iview.GetDeviceScale.unwrap())(view), 0.0);
+}
+
+#[test]
+fn device_scale_and_zoom_are_independent() {
+    let element = rect(0, 0, 640, 360);
+
+    let hidpi = Page { device_scale: 2.0, .. Page::new(1280, 720) }.view(element);
+    assert_eq!(hidpi.rect, element);
+    assert_eq!((hidpi.device_scale, hidpi.css_scale), (2.0, 1.0));
+    assert_eq!(hidpi.device_size(), PP_Size { width: 1280, height: 720, });
+
+    let zoomed = Page { zoom: 1.5, .. Page::new(1280, 720) }.view(element);
+    assert_eq!(zoomed.rect, rect(0, 0, 960, 540));
+    assert_eq!((zoomed.device_scale, zoomed.css_scale), (1.0, 1.5));
+    assert_eq!(zoomed.device_size(), PP_Size { width: 960, height: 540, });
+
+    let both = Page {
+        device_scale: 2.0,
+        zoom: 1.5,
+        .. Page::new(1280, 720)
+    }.view(element);
+    assert_eq!(both.rect, rect(0, 0, 960, 540));
+    assert_eq!(both.device_size(), PP_Size { width: 1920, height: 1080, });
+}
+
+#[test]
+fn page_views_round_out_and_clip() {
+    // 110% zoom doesn't land on whole DIPs.
+    let page = Page {
+        zoom: 1.1,
+        scroll_offset: PP_Point { x: 0, y: 15, },
+        .. Page::new(800, 600)
+    };
+    let view = page.view(rect(5, 10, 101, 600));
+    // (5, -5) * 1.1 = (5.5, -5.5), 101 * 1.1 = 111.1, 600 * 1.1 = 660.
+    assert_eq!(view.rect, rect(5, -6, 112, 661));
+    assert_eq!(view.clip_rect, rect(0, 6, 112, 600));
+    assert_eq!(view.scroll_offset, PP_Point { x: 0, y: 15, });
+    assert!(view.is_visible);
+
+    let view = Page { device_scale: 1.5, .. page }.view(rect(0, 0, 33, 33));
+    assert_eq!(view.device_size(), PP_Size { width: 56, height: 56, });
+
+    let below = page.view(rect(0, 700, 100, 100));
+    assert_eq!(below.clip_rect, Default::default());
+    assert!(!below.is_visible && below.is_page_visible);
+}
+
+#[test]
+fn scales_reach_the_module() {
+    let i = new_test_instance(Default::default());
+    let page = Page { device_scale: 2.0, zoom: 1.25, .. Page::new(1280, 720) };
+    i.set_view(page.view(rect(0, 0, 320, 240))).unwrap();
+
+    let (_, data) = *view_calls(i.id()).last().unwrap();
+    assert_eq!((data.device_scale, data.css_scale), (2.0, 1.25));
+    assert_eq!(data.rect, rect(0, 0, 400, 300));
+    assert_eq!(data.device_size(), PP_Size { width: 800, height: 600, });
+}
@@ -0,0 +1,140 @@
+//! A per-instance record of when audio was requested and when frames were
+//! presented, so tests can check how far apart ppapi-aout and the vout drift.
+
+use std::io::{self, Write};
+
+use super::sys::{PP_Resource, PP_TimeDelta, PP_TimeTicks, PP_AudioSampleRate};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimelineEvent {
+    /// The audio thread ran the module's callback. The samples written are
+    /// heard `latency` seconds later.
+    AudioCallback {
+        audio: PP_Resource,
+        sample_rate: PP_AudioSampleRate,
+        frames: u32,
+        latency: PP_TimeDelta,
+    },
+    /// A Graphics3D context presented a frame, ie `SwapBuffers`.
+    SwapBuffers {
+        context: PP_Resource,
+    },
+}
+impl TimelineEvent {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            &TimelineEvent::AudioCallback { .. } => "audio",
+            &TimelineEvent::SwapBuffers { .. } => "swap",
+        }
+    }
+    pub fn resource(&self) -> PP_Resource {
+        match self {
+            &TimelineEvent::AudioCallback { audio, .. } => audio,
+            &TimelineEvent::SwapBuffers { context } => context,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimelineEntry {
+    /// Module ticks, ie what `PPB_Core::GetTimeTicks` returned at the time.
+    pub ts: PP_TimeTicks,
+    pub event: TimelineEvent,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Timeline {
+    entries: Vec<TimelineEntry>,
+}
+impl Timeline {
+    pub fn new() -> Timeline { Default::default() }
+
+    /// Events from different threads can arrive slightly out of order, so
+    /// keep the entries sorted by timestamp.
+    pub fn push(&mut self, entry: TimelineEntry) {
+        let pos = self.entries
+            .iter()
+            .rposition(|e| e.ts <= entry.ts )
+            .map(|p| p + 1 )
+            .unwrap_or(0);
+        self.entries.insert(pos, entry);
+    }
+    pub fn entries(&self) -> &[TimelineEntry] { &self.entries[..] }
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+    pub fn audio_callbacks(&self) -> Vec<TimelineEntry> {
+        self.entries
+            .iter()
+            .filter(|e| match e.event {
+                TimelineEvent::AudioCallback { .. } => true,
+                _ => false,
+            })
+            .cloned()
+            .collect()
+    }
+    pub fn swaps(&self) -> Vec<TimelineEntry> {
+        self.entries
+            .iter()
+            .filter(|e| match e.event {
+                TimelineEvent::SwapBuffers { .. } => true,
+                _ => false,
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// How much audio (in seconds) the output device has played at `ts`,
+    /// computed like ppapi-aout's `TimeGet`: the latest callback's samples
+    /// are heard `latency` after it, so by then everything written before
+    /// them has played. `None` before the first callback.
+    pub fn audio_position_at(&self, ts: PP_TimeTicks) -> Option<PP_TimeDelta> {
+        let mut written = 0.0f64;
+        let mut last = None;
+        for entry in self.entries.iter() {
+            if entry.ts > ts { break; }
+            if let TimelineEvent::AudioCallback { sample_rate, frames, latency, .. } = entry.event {
+                let buffer = frames as f64 / sample_rate as f64;
+                last = Some((entry.ts, written, buffer, latency));
+                written += buffer;
+            }
+        }
+
+        last.map(|(cb_ts, before, buffer, latency)| {
+            // The device keeps playing between callbacks, but never past what
+            // was written.
+            let written = before + buffer;
+            let played = before - latency + (ts - cb_ts);
+            if played < 0.0 { 0.0 }
+            else if played > written { written }
+            else { played }
+        })
+    }
+
+    /// For each presented frame, the (timestamp, audio position) pair. The
+    /// difference between consecutive pairs' components is the A/V drift.
+    pub fn av_pairs(&self) -> Vec<(PP_TimeTicks, Option<PP_TimeDelta>)> {
+        self.swaps()
+            .into_iter()
+            .map(|e| (e.ts, self.audio_position_at(e.ts)) )
+            .collect()
+    }
+
+    /// One line per event: `ts,kind,resource,sample_rate,frames,latency`.
+    /// The audio-only columns are empty for swaps.
+    pub fn write_csv<W: Write>(&self, mut out: W) -> io::Result<()> {
+        try!(writeln!(out, "ts,kind,resource,sample_rate,frames,latency"));
+        for entry in self.entries.iter() {
+            match entry.event {
+                TimelineEvent::AudioCallback { audio, sample_rate, frames, latency, } => {
+                    try!(writeln!(out, "{:.6},audio,{},{},{},{:.6}", entry.ts,
+                                  audio, sample_rate, frames, latency));
+                },
+                TimelineEvent::SwapBuffers { context, } => {
+                    try!(writeln!(out, "{:.6},swap,{},,,", entry.ts, context));
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
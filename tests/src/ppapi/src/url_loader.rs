@@ -2,22 +2,27 @@
 #![allow(unused_variables)]
 
 use libc::{int32_t, int64_t, uint32_t};
+use std::cmp::min;
+use std::slice::from_raw_parts_mut;
 use std::sync::{Arc, RwLock};
-use std::time::{Instant};
+use std::time::{Duration, Instant};
 
 use std::collections::{VecDeque, HashMap};
 use std::ops::{Range};
 use url::Url;
 
 use super::prelude::*;
+use super::callback::Callback;
 use super::interface::*;
 use super::instance::Instance;
-use super::resource::{ResState, ResourceRc};
-use super::sys::{PP_Bool, PP_CompletionCallback, PP_Var,
+use super::resource::{ResState, ResourceRc, get_resource, get_resource_arc,
+                      take_resource_id};
+use super::support::global_singleton_default;
+use super::sys::{self, PP_Bool, PP_CompletionCallback, PP_Var,
                  PPB_URLLoader_1_0, PPB_URLRequestInfo_1_0,
                  PPB_URLResponseInfo_1_0,
                  PP_URLRequestProperty, PP_URLResponseProperty,
-                 PP_Time, PP_FALSE};
+                 PP_Time, PP_TRUE, PP_FALSE};
 
 pub type UrlLoader = Resource<UrlLoaderState>;
 pub type UrlRequestInfo = Resource<UrlRequestInfoState>;
@@ -42,9 +47,103 @@ pub struct UrlLoaderState {
     reader: RwLock<Option<Reader>>,
 }
 
+impl Reader {
+    /// `now` is on the module's clock, as are the others.
+    fn new(info: Arc<UrlInfo>, now: Instant) -> Reader {
+        let len = info.data.len();
+        Reader {
+            info: info,
+            parts: vec![0..len].into_iter().collect(),
+            opened: now,
+            cursor: 0,
+        }
+    }
+    /// How many bytes have arrived by `now`.
+    fn received(&self, now: Instant) -> usize {
+        let available = self.parts.back().map(|r| r.end ).unwrap_or(0);
+        match self.info.rate {
+            Some(rate) => {
+                let elapsed = now - self.opened;
+                let secs = elapsed.as_secs() as f64 +
+                    elapsed.subsec_nanos() as f64 / 1_000_000_000f64;
+                min(available, (secs * rate as f64) as usize)
+            },
+            None => available,
+        }
+    }
+    /// When a read has to wait for the next byte to arrive; `None` if it
+    /// doesn't, ie there's something to read, or the body has all been read.
+    fn next_arrival(&self, now: Instant) -> Option<Instant> {
+        if self.cursor < self.received(now) || self.cursor == self.info.data.len() {
+            return None;
+        }
+        self.info.rate.map(|rate| {
+            let nanos = (self.cursor as u64 + 1) * 1_000_000_000 / rate as u64;
+            self.opened + Duration::new(nanos / 1_000_000_000, (nanos % 1_000_000_000) as u32)
+        })
+    }
+    fn read(&mut self, buffer: &mut [u8], now: Instant) -> usize {
+        let end = min(self.cursor + buffer.len(), self.received(now));
+        let read = end - self.cursor;
+        buffer[..read].copy_from_slice(&self.info.data[self.cursor..end]);
+        self.cursor = end;
+        read
+    }
+}
+
 impl UrlLoaderState {
+    /// A loader that has already been opened on `url`'s fixture, as given to
+    /// `PPP_Instance::HandleDocumentLoad` for full-frame instances.
+    pub fn open_document(i: &Instance, url: &str) -> Code<UrlLoader> {
+        let url = try!(Url::parse(url).map_err(|_| Error::BadArgument ));
+        let info = try!(url_manager().get(&url).ok_or(Error::BadArgument));
+        let response = UrlResponseInfoState::create(i, &url, &*info);
+
+        let inner = UrlLoaderState {
+            id: take_resource_id(),
+            instance: i.clone(),
+            request: RwLock::new(None),
+            response: RwLock::new(Some(response)),
+            reader: RwLock::new(Some(Reader::new(info, i.clock().now()))),
+        };
+        Ok(Resource::create(i, Arc::new(inner)))
+    }
+
     pub fn get_request(&self) -> Code<Option<UrlRequestInfo>> { Ok(try!(self.request.read()).clone()) }
     pub fn get_response(&self) -> Code<Option<UrlResponseInfo>> { Ok(try!(self.response.read()).clone()) }
+
+    /// Returns 0 at the end of the body. Fails once closed. For throttled
+    /// fixtures, waits on the module's clock until at least a byte has
+    /// arrived.
+    pub fn read_response_body(&self, buffer: &mut [u8]) -> Code<usize> {
+        let clock = self.instance.clock();
+        loop {
+            let due = {
+                let mut reader = try!(self.reader.write());
+                let reader = try!(reader.as_mut().ok_or(Error::Failed));
+                let now = clock.now();
+                match reader.next_arrival(now) {
+                    Some(due) if !buffer.is_empty() => due,
+                    _ => { return Ok(reader.read(buffer, now)); },
+                }
+            };
+            // Not holding the reader, so progress can still be checked.
+            clock.wait_until(due);
+        }
+    }
+    /// `(received, total)`.
+    pub fn download_progress(&self) -> Code<(usize, usize)> {
+        let reader = try!(self.reader.read());
+        let now = self.instance.clock().now();
+        reader.as_ref()
+            .map(|reader| (reader.received(now), reader.info.data.len()) )
+            .ok_or(Error::Failed)
+    }
+    pub fn close(&self) {
+        if let Ok(mut reader) = self.reader.write() {
+            reader.take();
+        }
+    }
 }
 impl ResourceState for UrlLoaderState {
     fn into_resstate(this: Arc<UrlLoaderState>) -> ResState {
@@ -126,14 +225,44 @@ pub struct UrlResponseInfoState {
     instance: Instance,
 
     url: StringVar,
-    redirect_url: StringVar,
-    redirect_method: StringVar,
+    redirect_url: Option<StringVar>,
+    redirect_method: Option<StringVar>,
     status: i32,
     status_line: StringVar,
     headers: StringVar,
 }
 impl UrlResponseInfoState {
+    /// A `200 OK` for `info`.
+    fn create(i: &Instance, url: &Url, info: &UrlInfo) -> UrlResponseInfo {
+        let headers = format!("Content-Type: {}\nContent-Length: {}",
+                              info.content_type, info.data.len());
+        let inner = UrlResponseInfoState {
+            id: take_resource_id(),
+            instance: i.clone(),
+            url: StringVar::new(url.to_string()),
+            redirect_url: None,
+            redirect_method: None,
+            status: 200,
+            status_line: StringVar::new("OK".to_string()),
+            headers: StringVar::new(headers),
+        };
+        Resource::create(i, Arc::new(inner))
+    }
 
+    pub fn property(&self, property: PP_URLResponseProperty) -> Var {
+        fn string(v: &StringVar) -> Var { v.clone().into() }
+        match property {
+            sys::PP_URLRESPONSEPROPERTY_URL => string(&self.url),
+            sys::PP_URLRESPONSEPROPERTY_REDIRECTURL =>
+                self.redirect_url.as_ref().map(string).unwrap_or(Var::Undefined),
+            sys::PP_URLRESPONSEPROPERTY_REDIRECTMETHOD =>
+                self.redirect_method.as_ref().map(string).unwrap_or(Var::Undefined),
+            sys::PP_URLRESPONSEPROPERTY_STATUSCODE => Var::Int(self.status),
+            sys::PP_URLRESPONSEPROPERTY_STATUSLINE => string(&self.status_line),
+            sys::PP_URLRESPONSEPROPERTY_HEADERS => string(&self.headers),
+            _ => Var::Undefined,
+        }
+    }
 }
 impl ResourceState for UrlResponseInfoState {
     fn into_resstate(this: Arc<UrlResponseInfoState>) -> ResState {
@@ -159,17 +288,60 @@ impl ResourceState for UrlResponseInfoState {
 pub struct UrlInfo {
     data: Vec<u8>,
     content_type: String,
+    /// Bytes per second the body arrives at; all at once if `None`.
+    rate: Option<usize>,
+}
+impl UrlInfo {
+    pub fn new(data: Vec<u8>, content_type: &str) -> UrlInfo {
+        UrlInfo {
+            data: data,
+            content_type: content_type.to_string(),
+            rate: None,
+        }
+    }
+    /// Has the body arrive at `bytes_per_sec` from when it's opened, by the
+    /// module's clock. Must not be 0.
+    pub fn throttled(mut self, bytes_per_sec: usize) -> UrlInfo {
+        self.rate = Some(bytes_per_sec);
+        self
+    }
+    pub fn data(&self) -> &[u8] { &self.data[..] }
+    pub fn content_type(&self) -> &str { &self.content_type[..] }
 }
 
+/// The fixtures loaders are served from, shared by every instance.
+#[derive(Default)]
 pub struct UrlManager {
-    urls: HashMap<Url, Arc<UrlInfo>>,
+    urls: RwLock<HashMap<Url, Arc<UrlInfo>>>,
+}
+impl UrlManager {
+    /// Replaces any fixture already at `url`.
+    pub fn add_fixture(&self, url: &str, info: UrlInfo) -> Code<()> {
+        let url = try!(Url::parse(url).map_err(|_| Error::BadArgument ));
+        let mut urls = try!(self.urls.write());
+        urls.insert(url, Arc::new(info));
+        Ok(())
+    }
+    pub fn get(&self, url: &Url) -> Option<Arc<UrlInfo>> {
+        self.urls.read().ok()
+            .and_then(|urls| urls.get(url).cloned() )
+    }
 }
+pub fn url_manager() -> &'static UrlManager { global_singleton_default() }
+
+fn get_loader(loader: PP_Resource) -> Code<UrlLoader> { get_resource(loader) }
 
 extern "C" fn ppb_url_loader_create(instance: PP_Instance) -> PP_Resource {
     unimplemented!()
 }
 extern "C" fn ppb_url_loader_is(resource: PP_Resource) -> PP_Bool {
-    unimplemented!()
+    match unsafe { get_resource_arc(resource) } {
+        Some(rc) => match rc.state() {
+            &ResState::UrlLoader(_) => PP_TRUE,
+            _ => PP_FALSE,
+        },
+        None => PP_FALSE,
+    }
 }
 extern "C" fn ppb_url_loader_open(loader: PP_Resource,
                                   request_info: PP_Resource,
@@ -188,23 +360,58 @@ extern "C" fn ppb_url_loader_get_upload_progress(loader: PP_Resource,
 extern "C" fn ppb_url_loader_get_download_progress(loader: PP_Resource,
                                                    bytes_received: *mut int64_t,
                                                    total_bytes_to_be_received: *mut int64_t) -> PP_Bool {
-    unimplemented!()
+    let progress = get_loader(loader)
+        .and_then(|loader| loader.download_progress() );
+    let out = unsafe { (bytes_received.as_mut(), total_bytes_to_be_received.as_mut()) };
+    match (progress, out) {
+        (Ok((received, total)), (Some(received_out), Some(total_out))) => {
+            *received_out = received as int64_t;
+            *total_out = total as int64_t;
+            PP_TRUE
+        },
+        _ => PP_FALSE,
+    }
 }
 extern "C" fn ppb_url_loader_get_response_info(loader: PP_Resource) -> PP_Resource {
-    unimplemented!()
+    get_loader(loader)
+        .and_then(|loader| loader.get_response() )
+        .ok()
+        .and_then(|response| response )
+        .map(|response| response.move_into_id() )
+        .unwrap_or(0)
 }
 extern "C" fn ppb_url_loader_read_response_body(loader: PP_Resource,
                                                 buffer: *mut ::libc::c_void,
                                                 bytes_to_read: int32_t,
                                                 callback: PP_CompletionCallback) -> int32_t {
-    unimplemented!()
+    if buffer.is_null() || bytes_to_read < 0 {
+        return Error::BadArgument.into();
+    }
+    let buffer = unsafe {
+        from_raw_parts_mut(buffer as *mut u8, bytes_to_read as usize)
+    };
+
+    let loader = match get_loader(loader) {
+        Ok(loader) => loader,
+        Err(err) => { return err.into(); },
+    };
+    let callback = match Callback::from_ffi(callback, &loader.instance) {
+        Ok(cb) => cb,
+        Err(err) => { return err.into(); },
+    };
+    let instance = loader.instance.clone();
+    let id = loader.id();
+    callback.complete(&instance, id, move || loader.read_response_body(buffer) ).into_code()
 }
 extern "C" fn ppb_url_loader_finish_streaming_to_file(loader: PP_Resource,
                                                       callback: PP_CompletionCallback) -> int32_t {
     unimplemented!()
 }
 extern "C" fn ppb_url_loader_close(loader: PP_Resource) {
-    unimplemented!()
+    if let Ok(loader) = get_loader(loader) {
+        loader.close();
+        loader.instance.abort_completions(loader.id());
+    }
 }
 
 static URL_LOADER_INTERFACE: PPB_URLLoader_1_0 = PPB_URLLoader_1_0 {
@@ -253,11 +460,20 @@ static URL_REQUEST_INFO_INTERFACE: PPB_URLRequestInfo_1_0 = PPB_URLRequestInfo_1
 };
 
 extern "C" fn ppb_url_response_info_is(resource: PP_Resource) -> PP_Bool {
-    PP_FALSE
+    match unsafe { get_resource_arc(resource) } {
+        Some(rc) => match rc.state() {
+            &ResState::UrlResponseInfo(_) => PP_TRUE,
+            _ => PP_FALSE,
+        },
+        None => PP_FALSE,
+    }
 }
 extern "C" fn ppb_url_response_info_get_property(response: PP_Resource,
                                                  property: PP_URLResponseProperty) -> PP_Var {
-    unimplemented!()
+    get_resource::<UrlResponseInfoState>(response)
+        .map(|response| response.property(property) )
+        .unwrap_or(Var::Undefined)
+        .into()
 }
 extern "C" fn ppb_url_response_info_get_body_as_file_ref(response: PP_Resource) -> PP_Resource {
     unimplemented!()
@@ -1,17 +1,300 @@
 
+use libc;
+
+use std::sync::Arc;
+
+use super::instance::Instance;
 use super::interface::*;
-use super::sys::PPB_View_1_2;
+use super::prelude::*;
+use super::resource::{ResState, ResourceRc, get_resource, get_resource_arc,
+                      take_resource_id};
+use super::sys::{PP_Bool, PP_TRUE, PP_FALSE, PP_Point, PP_Rect, PP_Size,
+                 PPB_View_1_2};
+
+pub type View = Resource<ViewState>;
+
+/// What a `PPB_View` resource reports. The default is what Chrome gives an
+/// instance before layout: an empty, visible rect at 1x. Use `Page::view` to
+/// lay out an instance on a scaled or zoomed page.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ViewData {
+    /// In DIPs, relative to the page.
+    pub rect: PP_Rect,
+    /// The visible part of `rect`, relative to `rect`.
+    pub clip_rect: PP_Rect,
+    pub is_fullscreen: bool,
+    pub is_visible: bool,
+    pub is_page_visible: bool,
+    pub device_scale: f32,
+    pub css_scale: f32,
+    pub scroll_offset: PP_Point,
+}
+impl Default for ViewData {
+    fn default() -> ViewData {
+        ViewData {
+            rect: Default::default(),
+            clip_rect: Default::default(),
+            is_fullscreen: false,
+            is_visible: true,
+            is_page_visible: true,
+            device_scale: 1.0,
+            css_scale: 1.0,
+            scroll_offset: Default::default(),
+        }
+    }
+}
+
+impl ViewData {
+    /// A `width`x`height` instance at the top left of a visible page, fully
+    /// on screen.
+    pub fn on_screen(width: i32, height: i32) -> ViewData {
+        let rect = PP_Rect {
+            point: PP_Point { x: 0, y: 0, },
+            size: PP_Size { width: width, height: height, },
+        };
+        ViewData {
+            rect: rect,
+            clip_rect: rect,
+            .. Default::default()
+        }
+    }
+    /// The tab was hidden or minimized. Chrome reports the instance as not
+    /// visible too, whatever its clip rect.
+    pub fn page_hidden(mut self) -> ViewData {
+        self.is_page_visible = false;
+        self.is_visible = false;
+        self
+    }
+    pub fn page_shown(mut self) -> ViewData {
+        self.is_page_visible = true;
+        self.is_visible = !rect_is_empty(&self.clip_rect);
+        self
+    }
+    /// Only `clip` (relative to `rect`) is on screen; the instance is visible
+    /// only if that isn't empty.
+    pub fn clipped_to(mut self, clip: PP_Rect) -> ViewData {
+        self.clip_rect = clip;
+        self.is_visible = self.is_page_visible && !rect_is_empty(&clip);
+        self
+    }
+    /// The element was scrolled out of the viewport.
+    pub fn scrolled_off(self) -> ViewData {
+        self.clipped_to(Default::default())
+    }
+
+    /// The backbuffer size, in device pixels, a HiDPI-aware module should
+    /// use: `rect` scaled by `device_scale`, rounded up the way Chrome sizes
+    /// the plugin's layer.
+    pub fn device_size(&self) -> PP_Size {
+        let scale = self.device_scale as f64;
+        PP_Size {
+            width: ceil(self.rect.size.width as f64 * scale),
+            height: ceil(self.rect.size.height as f64 * scale),
+        }
+    }
+
+    pub fn visibility(&self) -> Visibility {
+        if !self.is_page_visible {
+            Visibility::PageHidden
+        } else if !self.is_visible || rect_is_empty(&self.clip_rect) {
+            Visibility::Offscreen
+        } else {
+            Visibility::Visible
+        }
+    }
+}
+fn rect_is_empty(r: &PP_Rect) -> bool { r.size.width <= 0 || r.size.height <= 0 }
+
+/// Scaling by zoom factors like 1.1 leaves float error, which Chrome ignores
+/// when rounding (`gfx::ToEnclosingRectIgnoringError`).
+const ROUNDING_ERROR: f64 = 0.001;
+fn floor(v: f64) -> i32 { (v + ROUNDING_ERROR).floor() as i32 }
+fn ceil(v: f64) -> i32 { (v - ROUNDING_ERROR).ceil() as i32 }
+
+/// The page an instance is laid out in, for building views with Chrome's
+/// math. The device pixel ratio and the zoom are independent: a 2x display
+/// at 100% reports `device_scale` 2 and `css_scale` 1, a 1x display at 150%
+/// reports 1 and 1.5.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Page {
+    /// The browser's viewport, in DIPs.
+    pub viewport: PP_Size,
+    /// Device pixels per DIP.
+    pub device_scale: f32,
+    /// Page zoom times pinch zoom: DIPs per CSS pixel.
+    pub zoom: f32,
+    /// How far the page is scrolled, in CSS pixels.
+    pub scroll_offset: PP_Point,
+    pub is_visible: bool,
+}
+impl Page {
+    /// A visible, unscrolled `width`x`height` DIP viewport at 1x and 100%.
+    pub fn new(width: i32, height: i32) -> Page {
+        Page {
+            viewport: PP_Size { width: width, height: height, },
+            device_scale: 1.0,
+            zoom: 1.0,
+            scroll_offset: Default::default(),
+            is_visible: true,
+        }
+    }
+
+    /// The view Chrome gives an instance whose element is at `element` (in
+    /// CSS pixels, relative to the document). `rect` is the element's box in
+    /// viewport DIPs, rounded out to whole DIPs; `clip_rect` is the part of
+    /// it inside the viewport, relative to `rect`.
+    pub fn view(&self, element: PP_Rect) -> ViewData {
+        let zoom = self.zoom as f64;
+        let left = (element.point.x - self.scroll_offset.x) as f64 * zoom;
+        let top = (element.point.y - self.scroll_offset.y) as f64 * zoom;
+        let right = left + element.size.width as f64 * zoom;
+        let bottom = top + element.size.height as f64 * zoom;
+        let (left, top) = (floor(left), floor(top));
+        let (right, bottom) = (ceil(right), ceil(bottom));
+        let rect = PP_Rect {
+            point: PP_Point { x: left, y: top, },
+            size: PP_Size { width: right - left, height: bottom - top, },
+        };
+
+        let clip_left = ::std::cmp::max(left, 0);
+        let clip_top = ::std::cmp::max(top, 0);
+        let clip_right = ::std::cmp::min(right, self.viewport.width);
+        let clip_bottom = ::std::cmp::min(bottom, self.viewport.height);
+        let clip_rect = if clip_right > clip_left && clip_bottom > clip_top {
+            PP_Rect {
+                point: PP_Point { x: clip_left - left, y: clip_top - top, },
+                size: PP_Size {
+                    width: clip_right - clip_left,
+                    height: clip_bottom - clip_top,
+                },
+            }
+        } else {
+            Default::default()
+        };
+
+        ViewData {
+            rect: rect,
+            clip_rect: clip_rect,
+            is_fullscreen: false,
+            is_visible: self.is_visible && !rect_is_empty(&clip_rect),
+            is_page_visible: self.is_visible,
+            device_scale: self.device_scale,
+            css_scale: self.zoom,
+            scroll_offset: self.scroll_offset,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Visibility {
+    Visible,
+    /// On a visible page, but clipped away or scrolled out of view.
+    Offscreen,
+    /// The whole page (tab) is hidden.
+    PageHidden,
+}
+
+#[derive(Debug)]
+pub struct ViewState {
+    id: PP_Resource,
+    instance: Instance,
+
+    data: ViewData,
+}
+impl ViewState {
+    pub fn create(i: &Instance, data: ViewData) -> View {
+        let inner = ViewState {
+            id: take_resource_id(),
+            instance: i.clone(),
+            data: data,
+        };
+        Resource::create(i, Arc::new(inner))
+    }
+
+    pub fn data(&self) -> &ViewData { &self.data }
+}
+impl ResourceState for ViewState {
+    fn into_resstate(this: Arc<Self>) -> ResState {
+        ResState::View(this)
+    }
+    fn state_from_resstate(rs: &Arc<ResourceRc>) -> Code<&Arc<Self>> {
+        match rs.state() {
+            &ResState::View(ref v) => Ok(v),
+            _ => Err(Error::BadArgument),
+        }
+    }
+    fn resource_id(this: &Arc<Self>) -> PP_Resource { this.id }
+    fn resource_instance(this: &Arc<Self>) -> Instance { this.instance.clone() }
+}
+
+fn to_bool(b: bool) -> PP_Bool { if b { PP_TRUE } else { PP_FALSE } }
+fn get_data(view: PP_Resource) -> Code<ViewData> {
+    get_resource::<ViewState>(view)
+        .map(|view| *view.data() )
+}
+
+extern "C" fn is_view(res: PP_Resource) -> PP_Bool {
+    match unsafe { get_resource_arc(res) } {
+        Some(rc) => match rc.state() {
+            &ResState::View(_) => PP_TRUE,
+            _ => PP_FALSE,
+        },
+        None => PP_FALSE,
+    }
+}
+extern "C" fn get_rect(view: PP_Resource, rect: *mut PP_Rect) -> PP_Bool {
+    match (get_data(view), unsafe { rect.as_mut() }) {
+        (Ok(data), Some(rect)) => {
+            *rect = data.rect;
+            PP_TRUE
+        },
+        _ => PP_FALSE,
+    }
+}
+extern "C" fn is_fullscreen(view: PP_Resource) -> PP_Bool {
+    to_bool(get_data(view).map(|d| d.is_fullscreen ).unwrap_or(false))
+}
+extern "C" fn is_visible(view: PP_Resource) -> PP_Bool {
+    to_bool(get_data(view).map(|d| d.is_visible ).unwrap_or(false))
+}
+extern "C" fn is_page_visible(view: PP_Resource) -> PP_Bool {
+    to_bool(get_data(view).map(|d| d.is_page_visible ).unwrap_or(false))
+}
+extern "C" fn get_clip_rect(view: PP_Resource, clip: *mut PP_Rect) -> PP_Bool {
+    match (get_data(view), unsafe { clip.as_mut() }) {
+        (Ok(data), Some(clip)) => {
+            *clip = data.clip_rect;
+            PP_TRUE
+        },
+        _ => PP_FALSE,
+    }
+}
+extern "C" fn get_device_scale(view: PP_Resource) -> libc::c_float {
+    get_data(view).map(|d| d.device_scale ).unwrap_or(0.0)
+}
+extern "C" fn get_css_scale(view: PP_Resource) -> libc::c_float {
+    get_data(view).map(|d| d.css_scale ).unwrap_or(0.0)
+}
+extern "C" fn get_scroll_offset(view: PP_Resource, offset: *mut PP_Point) -> PP_Bool {
+    match (get_data(view), unsafe { offset.as_mut() }) {
+        (Ok(data), Some(offset)) => {
+            *offset = data.scroll_offset;
+            PP_TRUE
+        },
+        _ => PP_FALSE,
+    }
+}
 
 static VIEW_INTERFACE: PPB_View_1_2 = PPB_View_1_2 {
-    IsView: Some(ret_false_stub),
-    GetRect: Some(ret_false_stub),
-    IsFullscreen: Some(ret_false_stub),
-    IsVisible: Some(ret_false_stub),
-    IsPageVisible: Some(ret_false_stub),
-    GetClipRect: Some(ret_false_stub),
-    GetDeviceScale: Some(ret_default_stub),
-    GetCSSScale: Some(ret_default_stub),
-    GetScrollOffset: Some(ret_default_stub),
+    IsView: Some(is_view),
+    GetRect: Some(get_rect),
+    IsFullscreen: Some(is_fullscreen),
+    IsVisible: Some(is_visible),
+    IsPageVisible: Some(is_page_visible),
+    GetClipRect: Some(get_clip_rect),
+    GetDeviceScale: Some(get_device_scale),
+    GetCSSScale: Some(get_css_scale),
+    GetScrollOffset: Some(get_scroll_offset),
 };
 pub static INTERFACES: Interfaces = &[
     ("PPB_View;1.2", interface_ptr(&VIEW_INTERFACE)),